@@ -0,0 +1,98 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Primitive types shared by the Core Text bindings and the pure-Rust font backend.
+//!
+//! On macOS these are the Core Foundation and Core Graphics types themselves. Elsewhere they
+//! are layout-compatible stand-ins, so code written against one backend compiles against both.
+
+#![allow(non_upper_case_globals)]
+
+#[cfg(target_os = "macos")]
+pub use core_foundation::string::UniChar;
+#[cfg(target_os = "macos")]
+pub use core_graphics::base::CGFloat;
+#[cfg(target_os = "macos")]
+pub use core_graphics::font::CGGlyph;
+#[cfg(target_os = "macos")]
+pub use core_graphics::geometry::{CGPoint, CGRect, CGSize};
+
+#[cfg(not(target_os = "macos"))]
+pub type UniChar = u16;
+
+#[cfg(not(target_os = "macos"))]
+pub type CGFloat = f64;
+
+#[cfg(not(target_os = "macos"))]
+pub type CGGlyph = u16;
+
+#[cfg(not(target_os = "macos"))]
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CGSize {
+    pub width: CGFloat,
+    pub height: CGFloat,
+}
+
+#[cfg(not(target_os = "macos"))]
+impl CGSize {
+    #[inline]
+    pub fn new(width: CGFloat, height: CGFloat) -> CGSize {
+        CGSize {
+            width,
+            height,
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CGPoint {
+    pub x: CGFloat,
+    pub y: CGFloat,
+}
+
+#[cfg(not(target_os = "macos"))]
+impl CGPoint {
+    #[inline]
+    pub fn new(x: CGFloat, y: CGFloat) -> CGPoint {
+        CGPoint {
+            x,
+            y,
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CGRect {
+    pub origin: CGPoint,
+    pub size: CGSize,
+}
+
+#[cfg(not(target_os = "macos"))]
+impl CGRect {
+    #[inline]
+    pub fn new(origin: &CGPoint, size: &CGSize) -> CGRect {
+        CGRect {
+            origin: *origin,
+            size: *size,
+        }
+    }
+}
+
+/*
+* CTFontDescriptor.h
+*/
+pub type CTFontOrientation = u32;
+pub const kCTFontDefaultOrientation: CTFontOrientation = 0;
+pub const kCTFontHorizontalOrientation: CTFontOrientation = 1;
+pub const kCTFontVerticalOrientation: CTFontOrientation = 2;
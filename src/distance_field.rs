@@ -0,0 +1,573 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Signed distance fields of glyph outlines, which can be scaled and thresholded by a shader
+//! to draw text sharply at any size.
+//!
+//! Multi-channel fields follow Viktor Chlumský's msdfgen: each contour's edges are colored so
+//! that adjacent edges meeting at a corner never share all their channels, and the median of
+//! the three channels then reproduces the sharp corner.
+
+use base::{CGFloat, CGPoint, CGRect, CGSize};
+use outline::{self, PathElement};
+
+use std::mem;
+
+/// How far, in pixels, the line segments that approximate a curve may stray from it.
+const FLATTENING_TOLERANCE: f64 = 0.01;
+/// The sine of the smallest change of direction between two edges that counts as a corner.
+const CORNER_THRESHOLD: f64 = 0.141_120_008_059_867_2; // sin(3)
+
+const BLACK: u8 = 0;
+const RED: u8 = 1;
+const GREEN: u8 = 2;
+const BLUE: u8 = 4;
+const CYAN: u8 = GREEN | BLUE;
+const WHITE: u8 = RED | GREEN | BLUE;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DistanceFieldKind {
+    /// One channel holding the true distance to the outline. Corners come out rounded when
+    /// magnified.
+    SingleChannel,
+    /// Red, green and blue channels whose median gives the distance, keeping corners sharp.
+    MultiChannel,
+}
+
+/// A glyph's distance field, as returned by `FontBackend::glyph_distance_field`.
+#[derive(Clone, Debug)]
+pub struct DistanceField {
+    pub width: usize,
+    pub height: usize,
+    /// The position of the field's left edge, in pixels right of the glyph origin.
+    pub left: i32,
+    /// The position of the field's top edge, in pixels above the glyph origin.
+    pub top: i32,
+    /// The number of bytes per pixel: 1 for single-channel fields, or 3, in red, green, blue
+    /// order, for multi-channel ones.
+    pub channels: usize,
+    /// Each pixel's distance from the outline, row by row from the top. 128 is on the outline,
+    /// and values grow towards 255 inside it and fall towards 0 outside, saturating at the
+    /// spread.
+    pub data: Vec<u8>,
+    /// The outline's bounding box in points, relative to the glyph origin, as
+    /// `get_bounding_rects_for_glyphs` returns it. Glyphs with no outline have an empty box
+    /// at the origin.
+    pub bounds: CGRect,
+}
+
+/// One edge of a contour: a line or curve of the outline, as line segments.
+#[derive(Clone, Debug)]
+struct Edge {
+    /// The flattened edge, from its start to its end. There are always at least two points.
+    points: Vec<CGPoint>,
+    /// The unit tangents at the edge's ends, which the distance is measured from beyond them.
+    start_direction: CGPoint,
+    end_direction: CGPoint,
+    color: u8,
+}
+
+/// The nearest point of an edge to a pixel.
+#[derive(Clone, Copy, Debug)]
+struct Nearest {
+    distance: f64,
+    point: CGPoint,
+    /// The direction of the edge at `point`.
+    direction: CGPoint,
+    at_start: bool,
+    at_end: bool,
+}
+
+impl Edge {
+    /// Returns the edge through `points`, or `None` if it has no length.
+    fn new(points: Vec<CGPoint>, start_tangents: &[CGPoint], end_tangents: &[CGPoint])
+           -> Option<Edge> {
+        if !points.windows(2).any(|segment| segment[0].x != segment[1].x ||
+                                             segment[0].y != segment[1].y) {
+            return None;
+        }
+        Some(Edge {
+            start_direction: first_direction(start_tangents)?,
+            end_direction: first_direction(end_tangents)?,
+            points,
+            color: WHITE,
+        })
+    }
+
+    fn start(&self) -> CGPoint {
+        self.points[0]
+    }
+
+    fn end(&self) -> CGPoint {
+        self.points[self.points.len() - 1]
+    }
+
+    /// Splits the edge into two halves, for contours that need more edges to color.
+    fn split(mut self) -> (Edge, Edge) {
+        if self.points.len() == 2 {
+            let (start, end) = (self.start(), self.end());
+            self.points.insert(1, CGPoint::new(0.5 * (start.x + end.x), 0.5 * (start.y + end.y)));
+        }
+        let middle = self.points.len() / 2;
+        let direction = first_direction(&[subtract(self.points[middle], self.points[middle - 1])])
+            .unwrap_or(self.start_direction);
+        let second = Edge {
+            points: self.points[middle..].to_vec(),
+            start_direction: direction,
+            end_direction: self.end_direction,
+            color: self.color,
+        };
+        self.points.truncate(middle + 1);
+        self.end_direction = direction;
+        (self, second)
+    }
+
+    fn nearest(&self, pixel: CGPoint) -> Nearest {
+        let last = self.points.len() - 2;
+        let mut best: Option<Nearest> = None;
+        for (index, segment) in self.points.windows(2).enumerate() {
+            let (from, to) = (segment[0], segment[1]);
+            let chord = subtract(to, from);
+            let length = dot(chord, chord);
+            if length == 0.0 {
+                continue;
+            }
+            let t = (dot(subtract(pixel, from), chord) / length).clamp(0.0, 1.0);
+            let point = CGPoint::new(from.x + chord.x * t, from.y + chord.y * t);
+            let offset = subtract(pixel, point);
+            let distance = dot(offset, offset).sqrt();
+            if best.is_some_and(|best| best.distance <= distance) {
+                continue;
+            }
+            let length = length.sqrt();
+            best = Some(Nearest {
+                distance,
+                point,
+                direction: CGPoint::new(chord.x / length, chord.y / length),
+                at_start: index == 0 && t == 0.0,
+                at_end: index == last && t == 1.0,
+            });
+        }
+        // `Edge::new` rejects edges without a segment of any length.
+        best.unwrap()
+    }
+
+    /// Returns the distance from the edge, extended along its tangents past its ends, positive
+    /// on the left.
+    fn signed_pseudo_distance(&self, pixel: CGPoint, nearest: &Nearest) -> f64 {
+        if nearest.at_start {
+            let offset = subtract(pixel, self.start());
+            if dot(offset, self.start_direction) < 0.0 {
+                return cross(self.start_direction, offset);
+            }
+        }
+        if nearest.at_end {
+            let offset = subtract(pixel, self.end());
+            if dot(offset, self.end_direction) > 0.0 {
+                return cross(self.end_direction, offset);
+            }
+        }
+        let side = cross(nearest.direction, subtract(pixel, nearest.point));
+        if side < 0.0 { -nearest.distance } else { nearest.distance }
+    }
+
+    /// Returns how squarely the pixel faces the edge at its nearest point, from 0 to 1, which
+    /// decides between edges at the same distance where they meet.
+    fn orthogonality(&self, pixel: CGPoint, nearest: &Nearest) -> f64 {
+        let direction = if nearest.at_start {
+            self.start_direction
+        } else if nearest.at_end {
+            self.end_direction
+        } else {
+            nearest.direction
+        };
+        match first_direction(&[subtract(pixel, nearest.point)]) {
+            Some(offset) => cross(direction, offset).abs(),
+            None => 1.0,
+        }
+    }
+}
+
+/// Splits an outline, scaled by `scale`, into closed contours of edges.
+fn contours(elements: &[PathElement], scale: f64) -> Vec<Vec<Edge>> {
+    let mut contours = vec![];
+    let mut edges = vec![];
+    let mut start = CGPoint::new(0.0, 0.0);
+    let mut current = start;
+    for element in elements {
+        let element = element.map(|point| CGPoint::new(point.x * scale, point.y * scale));
+        match element {
+            PathElement::MoveTo(to) => {
+                close_contour(&mut contours, &mut edges, current, start);
+                start = to;
+                current = to;
+            }
+            PathElement::LineTo(to) => {
+                let direction = subtract(to, current);
+                edges.extend(Edge::new(vec![current, to], &[direction], &[direction]));
+                current = to;
+            }
+            PathElement::QuadTo(control, to) => {
+                let mut points = vec![current];
+                points.extend(outline::flatten_quadratic(current, control, to,
+                                                         FLATTENING_TOLERANCE));
+                let chord = subtract(to, current);
+                edges.extend(Edge::new(points,
+                                       &[subtract(control, current), chord],
+                                       &[subtract(to, control), chord]));
+                current = to;
+            }
+            PathElement::CubicTo(control1, control2, to) => {
+                let mut points = vec![current];
+                points.extend(outline::flatten_cubic(current, control1, control2, to,
+                                                     FLATTENING_TOLERANCE));
+                let chord = subtract(to, current);
+                edges.extend(Edge::new(points,
+                                       &[subtract(control1, current),
+                                         subtract(control2, current),
+                                         chord],
+                                       &[subtract(to, control2), subtract(to, control1), chord]));
+                current = to;
+            }
+            PathElement::Close => {
+                close_contour(&mut contours, &mut edges, current, start);
+                current = start;
+            }
+        }
+    }
+    close_contour(&mut contours, &mut edges, current, start);
+    contours
+}
+
+fn close_contour(contours: &mut Vec<Vec<Edge>>, edges: &mut Vec<Edge>, current: CGPoint,
+                 start: CGPoint) {
+    let direction = subtract(start, current);
+    edges.extend(Edge::new(vec![current, start], &[direction], &[direction]));
+    if !edges.is_empty() {
+        contours.push(mem::take(edges));
+    }
+}
+
+fn is_corner(incoming: CGPoint, outgoing: CGPoint) -> bool {
+    dot(incoming, outgoing) <= 0.0 || cross(incoming, outgoing).abs() > CORNER_THRESHOLD
+}
+
+/// Returns the next color in the cycle of two-channel colors, avoiding the single channel
+/// `color` shares with `banned`, if any.
+fn switch_color(color: u8, banned: u8) -> u8 {
+    let shared = color & banned;
+    if shared == RED || shared == GREEN || shared == BLUE {
+        return shared ^ WHITE;
+    }
+    if color == BLACK || color == WHITE {
+        return CYAN;
+    }
+    let shifted = color << 1;
+    (shifted | shifted >> 3) & WHITE
+}
+
+/// Returns -1, 0 or 1 for the first, middle or last third of `count` positions.
+fn trichotomy(position: usize, count: usize) -> i32 {
+    (3.0 + 2.875 * position as f64 / (count - 1) as f64 - 1.4375 + 0.5) as i32 - 3
+}
+
+/// Colors the edges of a contour so that edges meeting at a corner differ in two channels.
+fn color_edges(edges: &mut Vec<Edge>) {
+    let corners: Vec<usize> = (0..edges.len()).filter(|&index| {
+        let previous = &edges[(index + edges.len() - 1) % edges.len()];
+        is_corner(previous.end_direction, edges[index].start_direction)
+    }).collect();
+
+    match corners.len() {
+        // A smooth contour has no corners to keep sharp.
+        0 => {}
+        1 => {
+            // A teardrop: spread three colors around the contour from its corner, splitting
+            // edges if there are too few.
+            edges.rotate_left(corners[0]);
+            while edges.len() < 3 {
+                let longest = (0..edges.len()).max_by_key(|&index| edges[index].points.len())
+                                              .unwrap();
+                let (first, second) = edges.remove(longest).split();
+                edges.insert(longest, second);
+                edges.insert(longest, first);
+            }
+            let first = switch_color(WHITE, BLACK);
+            let colors = [first, WHITE, switch_color(first, BLACK)];
+            let count = edges.len();
+            for (index, edge) in edges.iter_mut().enumerate() {
+                edge.color = colors[(1 + trichotomy(index, count)) as usize];
+            }
+        }
+        _ => {
+            let count = edges.len();
+            let mut color = switch_color(WHITE, BLACK);
+            let initial = color;
+            let mut spline = 0;
+            for offset in 0..count {
+                let index = (corners[0] + offset) % count;
+                if spline + 1 < corners.len() && corners[spline + 1] == index {
+                    spline += 1;
+                    // The last run mustn't match the first, which it meets at the first corner.
+                    let banned = if spline == corners.len() - 1 { initial } else { BLACK };
+                    color = switch_color(color, banned);
+                }
+                edges[index].color = color;
+            }
+        }
+    }
+}
+
+/// Returns whether a point is inside the outline by the nonzero rule.
+fn is_inside(contours: &[Vec<Edge>], pixel: CGPoint) -> bool {
+    let mut winding = 0;
+    for segment in contours.iter().flat_map(|edges| edges.iter()).flat_map(|edge| {
+        edge.points.windows(2)
+    }) {
+        let (from, to) = (segment[0], segment[1]);
+        let side = cross(subtract(to, from), subtract(pixel, from));
+        if from.y <= pixel.y && to.y > pixel.y && side > 0.0 {
+            winding += 1;
+        } else if to.y <= pixel.y && from.y > pixel.y && side < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding != 0
+}
+
+/// Returns 1 if the outline's contours mostly run counterclockwise, so that their insides are
+/// on their left, or -1 if they mostly run clockwise.
+fn orientation(contours: &[Vec<Edge>]) -> f64 {
+    let area: f64 = contours.iter().flat_map(|edges| edges.iter()).flat_map(|edge| {
+        edge.points.windows(2)
+    }).map(|segment| cross(segment[0], segment[1])).sum();
+    if area < 0.0 { -1.0 } else { 1.0 }
+}
+
+/// Renders the distance field of an outline in points, at `pixels_per_point`, with distances
+/// saturating `spread` pixels from the outline. The field extends past the outline's bounds by
+/// the spread rounded up, so that the saturated edge is included.
+pub fn distance_field(elements: &[PathElement],
+                      kind: DistanceFieldKind,
+                      pixels_per_point: CGFloat,
+                      spread: CGFloat)
+                      -> DistanceField {
+    let channels = match kind {
+        DistanceFieldKind::SingleChannel => 1,
+        DistanceFieldKind::MultiChannel => 3,
+    };
+    let bounds = match outline::bounding_box(elements) {
+        Some(bounds) => bounds,
+        None => {
+            return DistanceField {
+                width: 0,
+                height: 0,
+                left: 0,
+                top: 0,
+                channels,
+                data: vec![],
+                bounds: CGRect::new(&CGPoint::new(0.0, 0.0), &CGSize::new(0.0, 0.0)),
+            }
+        }
+    };
+
+    let padding = spread.ceil();
+    let left = (bounds.origin.x * pixels_per_point).floor() - padding;
+    let right = ((bounds.origin.x + bounds.size.width) * pixels_per_point).ceil() + padding;
+    let bottom = (bounds.origin.y * pixels_per_point).floor() - padding;
+    let top = ((bounds.origin.y + bounds.size.height) * pixels_per_point).ceil() + padding;
+    let (width, height) = ((right - left) as usize, (top - bottom) as usize);
+
+    let mut contours = contours(elements, pixels_per_point);
+    if kind == DistanceFieldKind::MultiChannel {
+        for edges in &mut contours {
+            color_edges(edges);
+        }
+    }
+    let orientation = orientation(&contours);
+    let edges: Vec<&Edge> = contours.iter().flat_map(|edges| edges.iter()).collect();
+
+    let mut data = Vec::with_capacity(width * height * channels);
+    let mut nearest = Vec::with_capacity(edges.len());
+    for row in 0..height {
+        for column in 0..width {
+            let pixel = CGPoint::new(left + column as f64 + 0.5, top - row as f64 - 0.5);
+            nearest.clear();
+            nearest.extend(edges.iter().map(|edge| edge.nearest(pixel)));
+            let distance = nearest.iter().map(|nearest| nearest.distance).fold(f64::MAX, f64::min);
+            let inside = is_inside(&contours, pixel);
+            let true_distance = if inside { distance } else { -distance };
+
+            if kind == DistanceFieldKind::SingleChannel {
+                data.push(encode(true_distance, spread));
+                continue;
+            }
+
+            let mut values = [0.0; 3];
+            for (value, &channel) in values.iter_mut().zip(&[RED, GREEN, BLUE]) {
+                let mut best: Option<(usize, f64)> = None;
+                for (index, edge) in edges.iter().enumerate() {
+                    if edge.color & channel == 0 {
+                        continue;
+                    }
+                    let distance = nearest[index].distance;
+                    let better = match best {
+                        None => true,
+                        Some((best, best_distance)) => {
+                            distance < best_distance - 1e-9 ||
+                            (distance <= best_distance + 1e-9 &&
+                             edge.orthogonality(pixel, &nearest[index]) >
+                             edges[best].orthogonality(pixel, &nearest[best]))
+                        }
+                    };
+                    if better {
+                        best = Some((index, distance));
+                    }
+                }
+                *value = match best {
+                    Some((index, _)) => {
+                        orientation * edges[index].signed_pseudo_distance(pixel, &nearest[index])
+                    }
+                    None => true_distance,
+                };
+            }
+
+            // Where the channels disagree with the outline about which side the pixel is on,
+            // edges of different contours have clashed, so fall back to the true distance.
+            let median = values[0].min(values[1]).max(values[0].max(values[1]).min(values[2]));
+            if (median > 0.0) != inside {
+                values = [true_distance; 3];
+            }
+            data.extend(values.iter().map(|&value| encode(value, spread)));
+        }
+    }
+
+    DistanceField {
+        width,
+        height,
+        left: left as i32,
+        top: top as i32,
+        channels,
+        data,
+        bounds,
+    }
+}
+
+fn encode(distance: f64, spread: f64) -> u8 {
+    ((0.5 + 0.5 * distance / spread).clamp(0.0, 1.0) * 255.0 + 0.5) as u8
+}
+
+/// Returns the first of `vectors` with any length, scaled to unit length.
+fn first_direction(vectors: &[CGPoint]) -> Option<CGPoint> {
+    vectors.iter().find(|vector| vector.x != 0.0 || vector.y != 0.0).map(|vector| {
+        let length = vector.x.hypot(vector.y);
+        CGPoint::new(vector.x / length, vector.y / length)
+    })
+}
+
+fn subtract(a: CGPoint, b: CGPoint) -> CGPoint {
+    CGPoint::new(a.x - b.x, a.y - b.y)
+}
+
+fn dot(a: CGPoint, b: CGPoint) -> f64 {
+    a.x * b.x + a.y * b.y
+}
+
+fn cross(a: CGPoint, b: CGPoint) -> f64 {
+    a.x * b.y - a.y * b.x
+}
+
+#[cfg(test)]
+mod tests {
+    use base::CGPoint;
+    use outline::PathElement;
+    use super::{DistanceField, DistanceFieldKind, color_edges, contours, distance_field};
+
+    /// A `size`-point square with its bottom-left corner at `(x, y)`.
+    fn square(x: f64, y: f64, size: f64) -> Vec<PathElement> {
+        vec![PathElement::MoveTo(CGPoint::new(x, y)),
+             PathElement::LineTo(CGPoint::new(x + size, y)),
+             PathElement::LineTo(CGPoint::new(x + size, y + size)),
+             PathElement::LineTo(CGPoint::new(x, y + size)),
+             PathElement::Close]
+    }
+
+    /// Returns the pixel's value, the median of its channels for a multi-channel field.
+    fn value(field: &DistanceField, column: usize, row: usize) -> u8 {
+        let start = (row * field.width + column) * field.channels;
+        let mut channels = field.data[start..start + field.channels].to_vec();
+        channels.sort();
+        channels[channels.len() / 2]
+    }
+
+    #[test]
+    fn measures_distances_inside_and_outside_a_square() {
+        let elements = square(0.0, 0.0, 10.0);
+        let single = distance_field(&elements, DistanceFieldKind::SingleChannel, 1.0, 2.5);
+        let multi = distance_field(&elements, DistanceFieldKind::MultiChannel, 1.0, 2.5);
+        assert_eq!((single.left, single.top), (-3, 13));
+        assert_eq!((single.width, single.height, single.channels), (16, 16, 1));
+        assert_eq!((multi.width, multi.height, multi.channels), (16, 16, 3));
+
+        // Pixel centers are at x = column - 2.5 and y = 12.5 - row.
+        assert!(value(&single, 8, 8) > 128);
+        assert!(value(&multi, 8, 8) > 128);
+        assert_eq!(value(&single, 0, 8), 0);
+        assert_eq!(value(&multi, 0, 8), 0);
+
+        // Away from the corners, the median of the channels is the true distance.
+        for row in 0..16 {
+            for column in 0..16 {
+                let (x, y) = (column as f64 - 2.5, 12.5 - row as f64);
+                let near_corner = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]
+                    .iter()
+                    .any(|&(corner_x, corner_y): &(f64, f64)| {
+                        (x - corner_x).hypot(y - corner_y) < 3.0
+                    });
+                let outside_both_axes = !(0.0..=10.0).contains(&x) && !(0.0..=10.0).contains(&y);
+                if near_corner || outside_both_axes {
+                    continue;
+                }
+                let (single, multi) = (value(&single, column, row), value(&multi, column, row));
+                assert!((single as i32 - multi as i32).abs() <= 1,
+                        "({}, {}): {} and {}", x, y, single, multi);
+            }
+        }
+    }
+
+    #[test]
+    fn places_the_field_around_the_outline_with_padding() {
+        let elements = square(1.25, -0.5, 3.0);
+        let field = distance_field(&elements, DistanceFieldKind::SingleChannel, 2.0, 1.5);
+        // The outline covers pixels 2.5 to 8.5 across and -1 to 5 up, padded by 2.
+        assert_eq!((field.left, field.top), (0, 7));
+        assert_eq!((field.width, field.height), (11, 10));
+        assert_eq!((field.bounds.origin.x, field.bounds.origin.y), (1.25, -0.5));
+        assert_eq!((field.bounds.size.width, field.bounds.size.height), (3.0, 3.0));
+        assert_eq!(field.data.len(), 110);
+    }
+
+    #[test]
+    fn colors_the_edges_at_corners_differently() {
+        let mut contours = contours(&square(0.0, 0.0, 10.0), 1.0);
+        assert_eq!(contours.len(), 1);
+        let edges = &mut contours[0];
+        assert_eq!(edges.len(), 4);
+        color_edges(edges);
+
+        let mut colors: Vec<u8> = edges.iter().map(|edge| edge.color).collect();
+        for (index, &color) in colors.iter().enumerate() {
+            let next = colors[(index + 1) % colors.len()];
+            assert!((color & next).count_ones() <= 1, "{:?}", colors);
+        }
+        colors.sort();
+        colors.dedup();
+        assert!(colors.len() >= 2);
+    }
+}
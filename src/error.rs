@@ -0,0 +1,51 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use tag::Tag;
+
+use std::error;
+use std::fmt;
+use std::result;
+
+/// Why a font, or something read from one, couldn't be produced.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// No font matched the given description, such as a name or a collection index.
+    FontNotFound(String),
+    /// The font has no value for the named attribute, trait or name key.
+    MissingAttribute(&'static str),
+    /// The named attribute exists but holds a value of an unexpected type.
+    WrongAttributeType(&'static str),
+    /// The font has no table with this tag.
+    MissingTable(Tag),
+    /// The table with this tag is truncated or internally inconsistent.
+    MalformedTable(Tag),
+    /// The string isn't a valid table tag of one to four printable ASCII characters.
+    InvalidTag(String),
+    /// The data isn't in a font format this crate can read.
+    UnsupportedFormat,
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::FontNotFound(ref description) => write!(f, "font not found: {}", description),
+            Error::MissingAttribute(name) => write!(f, "font has no {}", name),
+            Error::WrongAttributeType(name) => write!(f, "font's {} has the wrong type", name),
+            Error::MissingTable(tag) => write!(f, "font has no '{}' table", tag),
+            Error::MalformedTable(tag) => write!(f, "font's '{}' table is malformed", tag),
+            Error::InvalidTag(ref string) => write!(f, "invalid table tag: {:?}", string),
+            Error::UnsupportedFormat => f.write_str("unsupported font format"),
+        }
+    }
+}
+
+impl error::Error for Error {}
@@ -9,12 +9,20 @@
 
 #![allow(non_upper_case_globals)]
 
-use font_descriptor::{CTFontDescriptor, CTFontDescriptorRef, CTFontOrientation};
+use error::{Error, Result};
+use font_backend::{CharacterGlyphs, FontBackend, VariationAxis};
+use font_descriptor::{self, CTFontDescriptor, CTFontDescriptorRef, CTFontOrientation};
+use font_descriptor::kCTFontVariationAttribute;
 use font_descriptor::{CTFontSymbolicTraits, CTFontTraits, SymbolicTraitAccessors, TraitAccessors};
+use outline::OutlineSink;
+use tables::name::{LocalizedName, Name, NameId};
 
-use core_foundation::base::{CFIndex, CFOptionFlags, CFTypeID, CFRelease, CFRetain, CFTypeRef, TCFType};
+use core_foundation::array::{CFArray, CFArrayRef};
+use core_foundation::base::{CFIndex, CFOptionFlags, CFType, CFTypeID, CFRelease, CFRetain, CFTypeRef, TCFType};
+use core_foundation::boolean::kCFBooleanTrue;
 use core_foundation::data::{CFData, CFDataRef};
-use core_foundation::dictionary::CFDictionaryRef;
+use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+use core_foundation::number::{CFNumber, CFNumberRef};
 use core_foundation::string::{CFString, CFStringRef, UniChar};
 use core_foundation::url::{CFURL, CFURLRef};
 use core_graphics::base::{CGAffineTransform, CGFloat};
@@ -57,7 +65,7 @@ pub const kCTFontToolTipFontType: CTFontUIFontType = 25;
 pub const kCTFontControlContentFontType: CTFontUIFontType = 26;
 
 pub type CTFontTableTag = u32;
-// TODO: create bindings for enum with 'chars' values
+pub use tag::*;
 
 pub type CTFontTableOptions = u32;
 pub const kCTFontTableOptionsNoOptions: CTFontTableOptions = 0;
@@ -69,7 +77,29 @@ pub const kCTFontOptionsPreventAutoActivation: CTFontOptions = (1 << 0);
 pub const kCTFontOptionsPreferSystemFont: CTFontOptions = (1 << 2);
 
 #[repr(C)]
-struct __CTFont;
+pub struct __CTFont;
+
+/// A Core Graphics path, as returned by `CTFontCreatePathForGlyph`.
+#[repr(C)]
+pub struct __CGPath;
+
+pub type CGPathRef = *const __CGPath;
+
+pub type CGPathElementType = i32;
+pub const kCGPathElementMoveToPoint: CGPathElementType = 0;
+pub const kCGPathElementAddLineToPoint: CGPathElementType = 1;
+pub const kCGPathElementAddQuadCurveToPoint: CGPathElementType = 2;
+pub const kCGPathElementAddCurveToPoint: CGPathElementType = 3;
+pub const kCGPathElementCloseSubpath: CGPathElementType = 4;
+
+#[repr(C)]
+pub struct CGPathElement {
+    pub element_type: CGPathElementType,
+    pub points: *mut CGPoint,
+}
+
+pub type CGPathApplierFunction = extern "C" fn(info: *mut libc::c_void,
+                                               element: *const CGPathElement);
 
 pub type CTFontRef = *const __CTFont;
 
@@ -148,14 +178,14 @@ pub fn new_from_descriptor(desc: &CTFontDescriptor, pt_size: f64) -> CTFont {
     }
 }
 
-pub fn new_from_name(name: &str, pt_size: f64) -> Result<CTFont, ()> {
+pub fn new_from_name(name: &str, pt_size: f64) -> Result<CTFont> {
     unsafe {
-        let name: CFString = name.parse().unwrap();
-        let font_ref = CTFontCreateWithName(name.as_concrete_TypeRef(),
+        let cfname: CFString = name.parse().unwrap();
+        let font_ref = CTFontCreateWithName(cfname.as_concrete_TypeRef(),
                                             pt_size as CGFloat,
                                             ptr::null());
         if font_ref.is_null() {
-            Err(())
+            Err(Error::FontNotFound(name.to_owned()))
         } else {
             Ok(TCFType::wrap_under_create_rule(font_ref))
         }
@@ -190,25 +220,118 @@ impl CTFont {
         }
     }
 
+    /// Returns a copy of the font at a position on its variation axes, such as
+    /// `[(Tag::new(b"wght"), 650.0)]`, keeping its size.
+    ///
+    /// Values are in each axis's user units. Axes without a value stay at their defaults.
+    pub fn with_variations(&self, variations: &[(Tag, f32)]) -> CTFont {
+        let variation: Vec<(CFNumber, CFNumber)> = variations.iter().map(|&(tag, value)| {
+            (CFNumber::from_i64(tag.0 as i64), CFNumber::from_f64(value as f64))
+        }).collect();
+        let variation = CFDictionary::from_CFType_pairs(&variation);
+        unsafe {
+            let key: CFString = TCFType::wrap_under_get_rule(kCTFontVariationAttribute);
+            let attributes = CFDictionary::from_CFType_pairs(&[(key, variation)]);
+            let descriptor = font_descriptor::new_from_attributes(&attributes);
+            let font_ref = CTFontCreateCopyWithAttributes(self.obj,
+                                                          0.0,
+                                                          ptr::null(),
+                                                          descriptor.as_concrete_TypeRef());
+            TCFType::wrap_under_create_rule(font_ref)
+        }
+    }
+
     // Names
     pub fn family_name(&self) -> String {
-        let value = get_string_by_name_key(self, kCTFontFamilyNameKey);
-        value.expect("Fonts should always have a family name.")
+        self.try_family_name().expect("Fonts should always have a family name.")
+    }
+
+    pub fn try_family_name(&self) -> Result<String> {
+        let value = unsafe { get_string_by_name_key(self, kCTFontFamilyNameKey) };
+        value.ok_or(Error::MissingAttribute("family name"))
     }
 
     pub fn face_name(&self) -> String {
-        let value = get_string_by_name_key(self, kCTFontSubFamilyNameKey);
-        value.expect("Fonts should always have a face name.")
+        self.try_face_name().expect("Fonts should always have a face name.")
+    }
+
+    /// Returns the style name. Core Text gives every instance of a variable font the default
+    /// instance's style name, so fonts away from their defaults are named for their position,
+    /// as `names_for_variation` names it.
+    pub fn try_face_name(&self) -> Result<String> {
+        let axes = self.variation_axes();
+        let variation = self.variation();
+        if axes.iter().zip(&variation).any(|(axis, &(_, value))| value != axis.default_value) {
+            return self.names_for_variation(&variation).map(|names| names.style_name);
+        }
+        let value = unsafe { get_string_by_name_key(self, kCTFontSubFamilyNameKey) };
+        value.ok_or(Error::MissingAttribute("face name"))
     }
 
     pub fn unique_name(&self) -> String {
-        let value = get_string_by_name_key(self, kCTFontUniqueNameKey);
-        value.expect("Fonts should always have a unique name.")
+        self.try_unique_name().expect("Fonts should always have a unique name.")
+    }
+
+    pub fn try_unique_name(&self) -> Result<String> {
+        let value = unsafe { get_string_by_name_key(self, kCTFontUniqueNameKey) };
+        value.ok_or(Error::MissingAttribute("unique name"))
     }
 
     pub fn postscript_name(&self) -> String {
-        let value = get_string_by_name_key(self, kCTFontPostScriptNameKey);
-        value.expect("Fonts should always have a PostScript name.")
+        self.try_postscript_name().expect("Fonts should always have a PostScript name.")
+    }
+
+    pub fn try_postscript_name(&self) -> Result<String> {
+        let value = unsafe { get_string_by_name_key(self, kCTFontPostScriptNameKey) };
+        value.ok_or(Error::MissingAttribute("PostScript name"))
+    }
+
+    /// Returns the `name_id` string in `language`, a BCP 47 tag such as `ja`, or in US English
+    /// if `language` is `None`.
+    ///
+    /// Unlocalized names Core Text has a key for come from `CTFontCopyName`, so they match
+    /// what Core Text reports elsewhere. Everything else is read from the `name` table.
+    pub fn name(&self, name_id: NameId, language: Option<&str>) -> Option<String> {
+        if language.is_none() {
+            if let Some(name) = name_key(name_id).and_then(|key| get_string_by_name_key(self, key)) {
+                return Some(name);
+            }
+        }
+        let table = self.get_font_table(kCTFontTableName)?;
+        Name::parse(&table).ok()?.get(name_id, language)
+    }
+
+    /// Returns every name in the font's `name` table, one per name ID and language.
+    pub fn localized_names(&self) -> Vec<LocalizedName> {
+        self.get_font_table(kCTFontTableName)
+            .and_then(|table| Name::parse(&table).ok().map(|name| name.localized_names()))
+            .unwrap_or_default()
+    }
+
+    /// Returns the `name_id` string in the user's most preferred language that the font has,
+    /// as chosen by `CTFontCopyLocalizedName`. Only IDs with a Core Text name key are
+    /// supported.
+    pub fn preferred_localized_name(&self, name_id: NameId) -> Option<LocalizedName> {
+        let key = name_key(name_id)?;
+        unsafe {
+            let mut language: CFStringRef = ptr::null();
+            let result = CTFontCopyLocalizedName(self.obj, key, &mut language);
+            let language = if language.is_null() {
+                None
+            } else {
+                let language: CFString = TCFType::wrap_under_create_rule(language);
+                Some(language.to_string())
+            };
+            if result.is_null() {
+                return None;
+            }
+            let value: CFString = TCFType::wrap_under_create_rule(result);
+            Some(LocalizedName {
+                name_id,
+                language,
+                value: value.to_string(),
+            })
+        }
     }
 
     pub fn all_traits(&self) -> CTFontTraits {
@@ -272,6 +395,8 @@ impl CTFont {
         }
     }
 
+    /// The caller must pass valid buffers of `count` elements. Prefer the bounds-checked
+    /// `glyphs_for_characters`.
     pub fn get_glyphs_for_characters(&self, characters: *const UniChar, glyphs: *mut CGGlyph, count: CFIndex)
                                      -> bool {
         unsafe {
@@ -279,6 +404,8 @@ impl CTFont {
         }
     }
 
+    /// The caller must pass valid buffers of `count` elements. Prefer the bounds-checked
+    /// `advances_for_glyphs`.
     pub fn get_advances_for_glyphs(&self,
                                    orientation: CTFontOrientation,
                                    glyphs: *const CGGlyph,
@@ -290,10 +417,37 @@ impl CTFont {
         }
     }
 
-    pub fn get_font_table(&self, tag: u32) -> Option<CFData> {
+    /// Maps UTF-16 code units to glyphs, reporting which characters the font can't map. The
+    /// glyph for a surrogate pair is at the high surrogate and the low surrogate's slot gets 0.
+    pub fn glyphs_for_characters(&self, characters: &[UniChar]) -> CharacterGlyphs {
+        let mut glyphs = vec![0; characters.len()];
+        unsafe {
+            CTFontGetGlyphsForCharacters(self.obj,
+                                         characters.as_ptr(),
+                                         glyphs.as_mut_ptr(),
+                                         characters.len() as CFIndex);
+        }
+        CharacterGlyphs::new(characters, glyphs)
+    }
+
+    /// Returns the advance of each glyph for `orientation`.
+    pub fn advances_for_glyphs(&self, orientation: CTFontOrientation, glyphs: &[CGGlyph])
+                               -> Vec<CGSize> {
+        let mut advances = vec![CGSize::new(0.0, 0.0); glyphs.len()];
+        unsafe {
+            CTFontGetAdvancesForGlyphs(self.obj,
+                                       orientation,
+                                       glyphs.as_ptr(),
+                                       advances.as_mut_ptr(),
+                                       glyphs.len() as CFIndex);
+        }
+        advances
+    }
+
+    pub fn get_font_table<T: Into<Tag>>(&self, tag: T) -> Option<CFData> {
         unsafe {
             let result = CTFontCopyTable(self.obj,
-                                         tag as CTFontTableTag,
+                                         tag.into().0 as CTFontTableTag,
                                          kCTFontTableOptionsExcludeSynthetic);
             if result.is_null() {
                 None
@@ -303,6 +457,18 @@ impl CTFont {
         }
     }
 
+    pub fn available_tables(&self) -> Vec<Tag> {
+        unsafe {
+            let result = CTFontCopyAvailableTables(self.obj, kCTFontTableOptionsExcludeSynthetic);
+            if result.is_null() {
+                return vec![];
+            }
+            // The array holds the tags themselves cast to pointers, not CFNumbers.
+            let tags: CFArray = TCFType::wrap_under_create_rule(result);
+            tags.get_all_values().into_iter().map(|value| Tag(value as usize as u32)).collect()
+        }
+    }
+
     pub fn get_bounding_rects_for_glyphs(&self, orientation: CTFontOrientation, glyphs: &[CGGlyph])
                                          -> CGRect {
         unsafe {
@@ -315,6 +481,116 @@ impl CTFont {
         }
     }
 
+    /// Returns the bounding rect of each glyph along with their union, which is all
+    /// `get_bounding_rects_for_glyphs` returns.
+    pub fn bounding_rects_for_glyphs(&self, orientation: CTFontOrientation, glyphs: &[CGGlyph])
+                                     -> (CGRect, Vec<CGRect>) {
+        let mut rects = vec![CGRect::new(&CGPoint::new(0.0, 0.0), &CGSize::new(0.0, 0.0));
+                             glyphs.len()];
+        unsafe {
+            let union = CTFontGetBoundingRectsForGlyphs(self.as_concrete_TypeRef(),
+                                                        orientation,
+                                                        glyphs.as_ptr(),
+                                                        rects.as_mut_ptr(),
+                                                        glyphs.len() as CFIndex);
+            (union, rects)
+        }
+    }
+
+    // Variations
+    /// Returns the design axes of a variable font, in the font's order, or nothing for other
+    /// fonts.
+    pub fn variation_axes(&self) -> Vec<VariationAxis> {
+        unsafe {
+            let result = CTFontCopyVariationAxes(self.obj);
+            if result.is_null() {
+                return vec![];
+            }
+            let axes: CFArray = TCFType::wrap_under_create_rule(result);
+            axes.get_all_values().into_iter().filter_map(|axis| {
+                let axis: CFDictionary = TCFType::wrap_under_get_rule(axis as CFDictionaryRef);
+                let name = axis.find(kCTFontVariationAxisNameKey as *const libc::c_void)
+                               .map(|name| {
+                    let name: CFString = TCFType::wrap_under_get_rule(name as CFStringRef);
+                    name.to_string()
+                });
+                let hidden = axis.find(kCTFontVariationAxisHiddenKey as *const libc::c_void);
+                Some(VariationAxis {
+                    tag: Tag(number_for_key(&axis, kCTFontVariationAxisIdentifierKey)? as u32),
+                    name: name.unwrap_or_default(),
+                    min_value: number_for_key(&axis, kCTFontVariationAxisMinimumValueKey)?,
+                    default_value: number_for_key(&axis, kCTFontVariationAxisDefaultValueKey)?,
+                    max_value: number_for_key(&axis, kCTFontVariationAxisMaximumValueKey)?,
+                    hidden: hidden == Some(kCFBooleanTrue as *const libc::c_void),
+                })
+            }).collect()
+        }
+    }
+
+    /// Returns the font's position on each of its variation axes, in user units and in
+    /// `variation_axes` order. Axes Core Text doesn't report a value for are at their defaults.
+    pub fn variation(&self) -> Vec<(Tag, f64)> {
+        let axes = self.variation_axes();
+        let variation: Option<CFDictionary> = unsafe {
+            let result = CTFontCopyVariation(self.obj);
+            if result.is_null() {
+                None
+            } else {
+                Some(TCFType::wrap_under_create_rule(result))
+            }
+        };
+        axes.into_iter().map(|axis| {
+            let value = variation.as_ref().and_then(|variation| {
+                let key = CFNumber::from_i64(axis.tag.0 as i64);
+                let value = variation.find(key.as_CFTypeRef())?;
+                let value: CFType = unsafe { TCFType::wrap_under_get_rule(value as CFTypeRef) };
+                if !value.instance_of::<CFNumberRef, CFNumber>() {
+                    return None;
+                }
+                let value: CFNumber = unsafe {
+                    TCFType::wrap_under_get_rule(value.as_CFTypeRef() as CFNumberRef)
+                };
+                value.to_f64()
+            });
+            (axis.tag, value.unwrap_or(axis.default_value))
+        }).collect()
+    }
+
+    /// Sends the glyph's outline to `sink`, in points relative to the glyph's origin. Returns
+    /// false, without calling `sink`, if Core Text has no path for the glyph, as for bitmap
+    /// glyphs, or an empty one, as for spaces.
+    pub fn draw_glyph_outline(&self, glyph: CGGlyph, sink: &mut dyn OutlineSink) -> bool {
+        unsafe {
+            let path = CTFontCreatePathForGlyph(self.obj, glyph, ptr::null());
+            if path.is_null() {
+                return false;
+            }
+            let is_empty = CGPathIsEmpty(path);
+            if !is_empty {
+                let mut sink = sink;
+                CGPathApply(path, &mut sink as *mut &mut dyn OutlineSink as *mut libc::c_void,
+                            apply_path_element);
+            }
+            CFRelease(path as CFTypeRef);
+            !is_empty
+        }
+    }
+
+    /// Returns the positions of the carets inside a ligature glyph, in points along the
+    /// baseline from its origin. Glyphs that aren't ligatures have none.
+    pub fn ligature_caret_positions(&self, glyph: CGGlyph) -> Vec<CGFloat> {
+        unsafe {
+            let count = CTFontGetLigatureCaretPositions(self.obj, glyph, ptr::null_mut(), 0);
+            let mut positions = vec![0.0; count.max(0) as usize];
+            let count = CTFontGetLigatureCaretPositions(self.obj,
+                                                        glyph,
+                                                        positions.as_mut_ptr(),
+                                                        positions.len() as CFIndex);
+            positions.truncate(count.max(0) as usize);
+            positions
+        }
+    }
+
     pub fn draw_glyphs(&self, glyphs: &[CGGlyph], positions: &[CGPoint], context: CGContext) {
         assert!(glyphs.len() == positions.len());
         unsafe {
@@ -338,7 +614,170 @@ impl CTFont {
     }
 }
 
+impl FontBackend for CTFont {
+    type FontTable = CFData;
+
+    fn try_family_name(&self) -> Result<String> {
+        CTFont::try_family_name(self)
+    }
+
+    fn try_face_name(&self) -> Result<String> {
+        CTFont::try_face_name(self)
+    }
+
+    fn try_unique_name(&self) -> Result<String> {
+        CTFont::try_unique_name(self)
+    }
+
+    fn try_postscript_name(&self) -> Result<String> {
+        CTFont::try_postscript_name(self)
+    }
+
+    fn name(&self, name_id: NameId, language: Option<&str>) -> Option<String> {
+        CTFont::name(self, name_id, language)
+    }
+
+    fn localized_names(&self) -> Vec<LocalizedName> {
+        CTFont::localized_names(self)
+    }
+
+    fn pt_size(&self) -> CGFloat {
+        CTFont::pt_size(self)
+    }
+
+    fn ascent(&self) -> CGFloat {
+        CTFont::ascent(self)
+    }
+
+    fn descent(&self) -> CGFloat {
+        CTFont::descent(self)
+    }
+
+    fn leading(&self) -> CGFloat {
+        CTFont::leading(self)
+    }
+
+    fn units_per_em(&self) -> u32 {
+        CTFont::units_per_em(self)
+    }
+
+    fn x_height(&self) -> CGFloat {
+        CTFont::x_height(self)
+    }
+
+    fn underline_position(&self) -> CGFloat {
+        CTFont::underline_position(self)
+    }
+
+    fn underline_thickness(&self) -> CGFloat {
+        CTFont::underline_thickness(self)
+    }
+
+    fn bounding_box(&self) -> CGRect {
+        CTFont::bounding_box(self)
+    }
+
+    fn glyphs_for_characters(&self, characters: &[UniChar]) -> CharacterGlyphs {
+        CTFont::glyphs_for_characters(self, characters)
+    }
+
+    fn advances_for_glyphs(&self, orientation: CTFontOrientation, glyphs: &[CGGlyph])
+                           -> Vec<CGSize> {
+        CTFont::advances_for_glyphs(self, orientation, glyphs)
+    }
+
+    fn get_bounding_rects_for_glyphs(&self, orientation: CTFontOrientation, glyphs: &[CGGlyph])
+                                     -> CGRect {
+        CTFont::get_bounding_rects_for_glyphs(self, orientation, glyphs)
+    }
+
+    fn bounding_rects_for_glyphs(&self, orientation: CTFontOrientation, glyphs: &[CGGlyph])
+                                 -> (CGRect, Vec<CGRect>) {
+        CTFont::bounding_rects_for_glyphs(self, orientation, glyphs)
+    }
+
+    fn get_font_table(&self, tag: Tag) -> Option<CFData> {
+        CTFont::get_font_table(self, tag)
+    }
+
+    fn available_tables(&self) -> Vec<Tag> {
+        CTFont::available_tables(self)
+    }
+
+    fn variation_axes(&self) -> Vec<VariationAxis> {
+        CTFont::variation_axes(self)
+    }
+
+    fn variation(&self) -> Vec<(Tag, f64)> {
+        CTFont::variation(self)
+    }
+
+    fn draw_glyph_outline(&self, glyph: CGGlyph, sink: &mut dyn OutlineSink) -> bool {
+        CTFont::draw_glyph_outline(self, glyph, sink)
+    }
+
+    fn ligature_caret_positions(&self, glyph: CGGlyph) -> Vec<CGFloat> {
+        CTFont::ligature_caret_positions(self, glyph)
+    }
+}
+
+/// Forwards one element of a glyph path to the `&mut dyn OutlineSink` that `info` points to.
+extern "C" fn apply_path_element(info: *mut libc::c_void, element: *const CGPathElement) {
+    unsafe {
+        let sink = &mut **(info as *mut &mut dyn OutlineSink);
+        let element = &*element;
+        let point = |index: isize| *element.points.offset(index);
+        match element.element_type {
+            kCGPathElementMoveToPoint => sink.move_to(point(0)),
+            kCGPathElementAddLineToPoint => sink.line_to(point(0)),
+            kCGPathElementAddQuadCurveToPoint => sink.quad_to(point(0), point(1)),
+            kCGPathElementAddCurveToPoint => sink.cubic_to(point(0), point(1), point(2)),
+            kCGPathElementCloseSubpath => sink.close(),
+            _ => {}
+        }
+    }
+}
+
 // Helper methods
+/// Returns Core Text's name key for `name_id`, if it has one.
+fn name_key(name_id: NameId) -> Option<CFStringRef> {
+    unsafe {
+        Some(match name_id {
+            NameId::Copyright => kCTFontCopyrightNameKey,
+            NameId::Family => kCTFontFamilyNameKey,
+            NameId::Subfamily => kCTFontSubFamilyNameKey,
+            NameId::UniqueId => kCTFontUniqueNameKey,
+            NameId::FullName => kCTFontFullNameKey,
+            NameId::Version => kCTFontVersionNameKey,
+            NameId::PostScriptName => kCTFontPostScriptNameKey,
+            NameId::Trademark => kCTFontTrademarkNameKey,
+            NameId::Manufacturer => kCTFontManufacturerNameKey,
+            NameId::Designer => kCTFontDesignerNameKey,
+            NameId::Description => kCTFontDescriptionNameKey,
+            NameId::VendorUrl => kCTFontVendorURLNameKey,
+            NameId::DesignerUrl => kCTFontDesignerURLNameKey,
+            NameId::License => kCTFontLicenseNameKey,
+            NameId::LicenseUrl => kCTFontLicenseURLNameKey,
+            NameId::SampleText => kCTFontSampleTextNameKey,
+            NameId::PostScriptCidName => kCTFontPostScriptCIDNameKey,
+            _ => return None,
+        })
+    }
+}
+
+/// Returns the number that `key` maps to in a Core Text dictionary, if it is one.
+fn number_for_key(dictionary: &CFDictionary, key: CFStringRef) -> Option<f64> {
+    unsafe {
+        let value = dictionary.find(key as *const libc::c_void)?;
+        let value: CFType = TCFType::wrap_under_get_rule(value as CFTypeRef);
+        if !value.instance_of::<CFNumberRef, CFNumber>() {
+            return None;
+        }
+        let number: CFNumber = TCFType::wrap_under_get_rule(value.as_CFTypeRef() as CFNumberRef);
+        number.to_f64()
+    }
+}
+
 fn get_string_by_name_key(font: &CTFont, name_key: CFStringRef) -> Option<String> {
     unsafe {
         let result = CTFontCopyName(font.as_concrete_TypeRef(), name_key);
@@ -356,12 +795,12 @@ pub fn debug_font_names(font: &CTFont) {
         get_string_by_name_key(font, key).unwrap()
     }
 
-    println!("kCTFontFamilyNameKey: {}", get_key(font, kCTFontFamilyNameKey));
-    println!("kCTFontSubFamilyNameKey: {}", get_key(font, kCTFontSubFamilyNameKey));
-    println!("kCTFontStyleNameKey: {}", get_key(font, kCTFontStyleNameKey));
-    println!("kCTFontUniqueNameKey: {}", get_key(font, kCTFontUniqueNameKey));
-    println!("kCTFontFullNameKey: {}", get_key(font, kCTFontFullNameKey));
-    println!("kCTFontPostScriptNameKey: {}", get_key(font, kCTFontPostScriptNameKey));
+    println!("kCTFontFamilyNameKey: {}", get_key(font, unsafe { kCTFontFamilyNameKey }));
+    println!("kCTFontSubFamilyNameKey: {}", get_key(font, unsafe { kCTFontSubFamilyNameKey }));
+    println!("kCTFontStyleNameKey: {}", get_key(font, unsafe { kCTFontStyleNameKey }));
+    println!("kCTFontUniqueNameKey: {}", get_key(font, unsafe { kCTFontUniqueNameKey }));
+    println!("kCTFontFullNameKey: {}", get_key(font, unsafe { kCTFontFullNameKey }));
+    println!("kCTFontPostScriptNameKey: {}", get_key(font, unsafe { kCTFontPostScriptNameKey }));
 }
 
 pub fn debug_font_traits(font: &CTFont) {
@@ -385,30 +824,31 @@ extern {
      */
 
     /* Name Specifier Constants */
-    //static kCTFontCopyrightNameKey: CFStringRef;
+    static kCTFontCopyrightNameKey: CFStringRef;
     static kCTFontFamilyNameKey: CFStringRef;
     static kCTFontSubFamilyNameKey: CFStringRef;
     static kCTFontStyleNameKey: CFStringRef;
     static kCTFontUniqueNameKey: CFStringRef;
     static kCTFontFullNameKey: CFStringRef;
-    //static kCTFontVersionNameKey: CFStringRef;
+    static kCTFontVersionNameKey: CFStringRef;
     static kCTFontPostScriptNameKey: CFStringRef;
-    //static kCTFontTrademarkNameKey: CFStringRef;
-    //static kCTFontManufacturerNameKey: CFStringRef;
-    //static kCTFontDesignerNameKey: CFStringRef;
-    //static kCTFontDescriptionNameKey: CFStringRef;
-    //static kCTFontVendorURLNameKey: CFStringRef;
-    //static kCTFontDesignerURLNameKey: CFStringRef;
-    //static kCTFontLicenseNameKey: CFStringRef;
-    //static kCTFontLicenseURLNameKey: CFStringRef;
-    //static kCTFontSampleTextNameKey: CFStringRef;
-    //static kCTFontPostScriptCIDNameKey: CFStringRef;
-
-    //static kCTFontVariationAxisIdentifierKey: CFStringRef;
-    //static kCTFontVariationAxisMinimumValueKey: CFStringRef;
-    //static kCTFontVariationAxisMaximumValueKey: CFStringRef;
-    //static kCTFontVariationAxisDefaultValueKey: CFStringRef;
-    //static kCTFontVariationAxisNameKey: CFStringRef;
+    static kCTFontTrademarkNameKey: CFStringRef;
+    static kCTFontManufacturerNameKey: CFStringRef;
+    static kCTFontDesignerNameKey: CFStringRef;
+    static kCTFontDescriptionNameKey: CFStringRef;
+    static kCTFontVendorURLNameKey: CFStringRef;
+    static kCTFontDesignerURLNameKey: CFStringRef;
+    static kCTFontLicenseNameKey: CFStringRef;
+    static kCTFontLicenseURLNameKey: CFStringRef;
+    static kCTFontSampleTextNameKey: CFStringRef;
+    static kCTFontPostScriptCIDNameKey: CFStringRef;
+
+    static kCTFontVariationAxisIdentifierKey: CFStringRef;
+    static kCTFontVariationAxisMinimumValueKey: CFStringRef;
+    static kCTFontVariationAxisMaximumValueKey: CFStringRef;
+    static kCTFontVariationAxisDefaultValueKey: CFStringRef;
+    static kCTFontVariationAxisNameKey: CFStringRef;
+    static kCTFontVariationAxisHiddenKey: CFStringRef;
 
     //static kCTFontFeatureTypeIdentifierKey: CFStringRef;
     //static kCTFontFeatureTypeNameKey: CFStringRef;
@@ -451,8 +891,8 @@ extern {
     //fn CTFontCopyFullName(font: CTFontRef) -> CFStringRef;
     //fn CTFontCopyDisplayName(font: CTFontRef) -> CFStringRef;
     fn CTFontCopyName(font: CTFontRef, nameKey: CFStringRef) -> CFStringRef;
-    //fn CTFontCopyLocalizedName(font: CTFontRef, nameKey: CFStringRef, 
-    //                           language: *CFStringRef) -> CFStringRef;
+    fn CTFontCopyLocalizedName(font: CTFontRef, nameKey: CFStringRef,
+                               language: *mut CFStringRef) -> CFStringRef;
 
     /* Working With Encoding */
     //fn CTFontCopyCharacterSet
@@ -473,7 +913,10 @@ extern {
     fn CTFontGetXHeight(font: CTFontRef) -> CGFloat;
 
     /* Getting Glyph Data */
-    //fn CTFontCreatePathForGlyph
+    fn CTFontCreatePathForGlyph(font: CTFontRef,
+                                glyph: CGGlyph,
+                                matrix: *const CGAffineTransform)
+                                -> CGPathRef;
     //fn CTFontGetGlyphWithName
     fn CTFontGetBoundingRectsForGlyphs(font: CTFontRef,
                                        orientation: CTFontOrientation,
@@ -485,8 +928,8 @@ extern {
     //fn CTFontGetVerticalTranslationsForGlyphs
 
     /* Working With Font Variations */
-    //fn CTFontCopyVariationAxes
-    //fn CTFontCopyVariation
+    fn CTFontCopyVariationAxes(font: CTFontRef) -> CFArrayRef;
+    fn CTFontCopyVariation(font: CTFontRef) -> CFDictionaryRef;
 
     /* Getting Font Features */
     //fn CTFontCopyFeatures
@@ -499,7 +942,11 @@ extern {
                         positions: *const CGPoint,
                         count: size_t,
                         context: CGContextRef);
-    //fn CTFontGetLigatureCaretPositions
+    fn CTFontGetLigatureCaretPositions(font: CTFontRef,
+                                       glyph: CGGlyph,
+                                       positions: *mut CGFloat,
+                                       maxPositions: CFIndex)
+                                       -> CFIndex;
 
     /* Converting Fonts */
     fn CTFontCopyGraphicsFont(font: CTFontRef, attributes: *mut CTFontDescriptorRef) -> CGFontRef;
@@ -511,9 +958,15 @@ extern {
     //fn CTFontCreateWithQuickdrawInstance
 
     /* Getting Font Table Data */
-    //fn CTFontCopyAvailableTables(font: CTFontRef, options: CTFontTableOptions) -> CFArrayRef;
+    fn CTFontCopyAvailableTables(font: CTFontRef, options: CTFontTableOptions) -> CFArrayRef;
     fn CTFontCopyTable(font: CTFontRef, table: CTFontTableTag, options: CTFontTableOptions) -> CFDataRef;
 
     fn CTFontGetTypeID() -> CFTypeID;
+
+    /*
+     * CGPath.h
+     */
+    fn CGPathApply(path: CGPathRef, info: *mut libc::c_void, function: CGPathApplierFunction);
+    fn CGPathIsEmpty(path: CGPathRef) -> bool;
 }
 
@@ -0,0 +1,784 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use base::{CGFloat, CGGlyph, CGPoint, CGRect, CGSize, CTFontOrientation, UniChar};
+use base::kCTFontHorizontalOrientation;
+use distance_field::{self, DistanceField, DistanceFieldKind};
+use error::{Error, Result};
+use outline::{OutlineSink, PathElement};
+use rasterizer::{self, GlyphBitmap};
+use shaping::{aat, positioning, substitution};
+use shaping::{Direction, Feature, GlyphBuffer, GlyphPosition, MARK_GLYPH, ShapedGlyph};
+use tables::ankr::Ankr;
+use tables::feat::Feat;
+use tables::fvar::Fvar;
+use tables::gdef::Gdef;
+use tables::gpos::Gpos;
+use tables::kern::Kern;
+use tables::kerx::Kerx;
+use tables::gsub::Gsub;
+use tables::morx::Morx;
+use tables::name::{LocalizedName, NameId};
+use tables::stat::Stat;
+use tables::trak::Trak;
+use tag::{Tag, kCTFontTableFvar, kCTFontTableGPOS, kCTFontTableGSUB, kCTFontTableKern};
+use tag::{kCTFontTableAnkr, kCTFontTableFeat, kCTFontTableKerx, kCTFontTableMorx};
+use tag::{kCTFontTableGDEF, kCTFontTableSTAT, kCTFontTableTrak};
+
+use std::borrow::Cow;
+use std::iter;
+use std::ops::Deref;
+
+/// Variation values closer than this are the same 16.16 fixed-point number.
+const FIXED_PRECISION: f64 = 1.0 / 65536.0;
+
+/// The features `shape` turns on unless it's told otherwise: those that every script needs
+/// for its glyphs to be correct, and the usual ligatures and kerning.
+const DEFAULT_FEATURES: [Tag; 13] = [
+    Tag::new(b"rvrn"), Tag::new(b"ccmp"), Tag::new(b"locl"), Tag::new(b"rlig"),
+    Tag::new(b"calt"), Tag::new(b"clig"), Tag::new(b"liga"), Tag::new(b"rclt"),
+    Tag::new(b"curs"), Tag::new(b"dist"), Tag::new(b"kern"), Tag::new(b"mark"),
+    Tag::new(b"mkmk"),
+];
+
+/// Brackets and other characters that right-to-left text draws mirrored, in pairs of each
+/// other's mirror images.
+const MIRRORED_PAIRS: [(UniChar, UniChar); 14] = [
+    (0x0028, 0x0029), (0x003C, 0x003E), (0x005B, 0x005D), (0x007B, 0x007D),
+    (0x00AB, 0x00BB), (0x2039, 0x203A), (0x2045, 0x2046), (0x207D, 0x207E),
+    (0x208D, 0x208E), (0x2264, 0x2265), (0x3008, 0x3009), (0x300A, 0x300B),
+    (0xFF08, 0xFF09), (0xFF3B, 0xFF3D),
+];
+
+/// The questions a sized font can answer, independent of what answers them.
+///
+/// `font::CTFont` implements this on macOS by calling into Core Text, and
+/// `memory_font::MemoryFont` implements it on every platform by reading the font's tables
+/// directly. All metrics are in points at the font's `pt_size`, with the same sign
+/// conventions as Core Text: `descent` is positive below the baseline and `underline_position`
+/// is negative below it.
+pub trait FontBackend {
+    /// The raw bytes of a font table, as returned by `get_font_table`.
+    type FontTable: Deref<Target = [u8]>;
+
+    // Names
+    fn try_family_name(&self) -> Result<String>;
+    fn try_face_name(&self) -> Result<String>;
+    fn try_unique_name(&self) -> Result<String>;
+    fn try_postscript_name(&self) -> Result<String>;
+
+    fn family_name(&self) -> String {
+        self.try_family_name().expect("Fonts should always have a family name.")
+    }
+
+    fn face_name(&self) -> String {
+        self.try_face_name().expect("Fonts should always have a face name.")
+    }
+
+    fn unique_name(&self) -> String {
+        self.try_unique_name().expect("Fonts should always have a unique name.")
+    }
+
+    fn postscript_name(&self) -> String {
+        self.try_postscript_name().expect("Fonts should always have a PostScript name.")
+    }
+
+    /// Returns the `name_id` string in `language`, a BCP 47 tag such as `ja`, or in US English
+    /// if `language` is `None`. See `tables::name::Name::get` for how languages are matched.
+    fn name(&self, name_id: NameId, language: Option<&str>) -> Option<String>;
+
+    /// Returns every name the font has, one per name ID and language.
+    fn localized_names(&self) -> Vec<LocalizedName>;
+
+    // Font metrics
+    fn pt_size(&self) -> CGFloat;
+    fn ascent(&self) -> CGFloat;
+    fn descent(&self) -> CGFloat;
+    fn leading(&self) -> CGFloat;
+    fn units_per_em(&self) -> u32;
+    fn x_height(&self) -> CGFloat;
+    fn underline_position(&self) -> CGFloat;
+    fn underline_thickness(&self) -> CGFloat;
+    fn bounding_box(&self) -> CGRect;
+
+    /// Converts a value in font units, as stored in the font's tables, to points at `pt_size`.
+    fn font_units_to_points(&self, value: f64) -> CGFloat {
+        value * self.pt_size() / self.units_per_em() as f64
+    }
+
+    /// Maps UTF-16 code units to glyphs, reporting which characters the font can't map.
+    ///
+    /// As in Core Text, the glyph for a surrogate pair is at the high surrogate and the low
+    /// surrogate's slot gets 0.
+    fn glyphs_for_characters(&self, characters: &[UniChar]) -> CharacterGlyphs;
+
+    /// Returns the advance of each glyph for `orientation`.
+    fn advances_for_glyphs(&self, orientation: CTFontOrientation, glyphs: &[CGGlyph])
+                           -> Vec<CGSize>;
+
+    /// Returns the ink bounds of each glyph for `orientation`, in points relative to the glyph's
+    /// origin, along with their union.
+    fn bounding_rects_for_glyphs(&self, orientation: CTFontOrientation, glyphs: &[CGGlyph])
+                                 -> (CGRect, Vec<CGRect>);
+
+    /// Returns the union of the glyphs' ink bounds for `orientation`.
+    fn get_bounding_rects_for_glyphs(&self, orientation: CTFontOrientation, glyphs: &[CGGlyph])
+                                     -> CGRect {
+        self.bounding_rects_for_glyphs(orientation, glyphs).0
+    }
+
+    // Variations
+    /// Returns the design axes of a variable font, in the font's order, or nothing for other
+    /// fonts.
+    fn variation_axes(&self) -> Vec<VariationAxis>;
+
+    /// Returns the font's position on each of its variation axes, in user units and in
+    /// `variation_axes` order.
+    fn variation(&self) -> Vec<(Tag, f64)>;
+
+    /// Returns the named instances of a variable font, such as "Bold", in the font's order.
+    fn named_instances(&self) -> Vec<NamedInstance> {
+        let table = match self.get_font_table(kCTFontTableFvar) {
+            Some(table) => table,
+            None => return vec![],
+        };
+        let fvar = match Fvar::parse(&table) {
+            Ok(fvar) => fvar,
+            Err(_) => return vec![],
+        };
+        let axes = fvar.axes();
+        fvar.instances().into_iter().filter_map(|instance| {
+            Some(NamedInstance {
+                style_name: self.name(NameId::from(instance.subfamily_name_id), None)?,
+                postscript_name: instance.postscript_name_id.and_then(|name_id| {
+                    self.name(NameId::from(name_id), None)
+                }),
+                variation: axes.iter().map(|axis| axis.tag).zip(instance.coordinates).collect(),
+            })
+        }).collect()
+    }
+
+    /// Returns the names the font's `STAT` table gives to positions on its family's design
+    /// axes, in the table's order.
+    fn style_attributes(&self) -> Vec<StyleAttribute> {
+        let table = match self.get_font_table(kCTFontTableSTAT) {
+            Some(table) => table,
+            None => return vec![],
+        };
+        let stat = match Stat::parse(&table) {
+            Ok(stat) => stat,
+            Err(_) => return vec![],
+        };
+        let axes = stat.design_axes();
+        stat.axis_values().into_iter().filter_map(|value| {
+            let values = value.values.iter().map(|&(axis, value)| {
+                axes.get(axis as usize).map(|axis| (axis.tag, value))
+            }).collect::<Option<Vec<_>>>()?;
+            Some(StyleAttribute {
+                name: self.name(NameId::from(value.name_id), None)?,
+                values,
+                range: value.range,
+                linked_value: value.linked_value,
+                elidable: value.is_elidable(),
+                older_sibling: value.is_older_sibling(),
+            })
+        }).collect()
+    }
+
+    /// Returns the family and style names of the font at `variation`, a position in user units
+    /// on any of its axes, with other axes at their defaults.
+    ///
+    /// A named instance at `variation` gives its own name. Otherwise the style name is composed
+    /// from the `STAT` names of each axis's value, leaving out axes whose value has no name.
+    /// Fonts with neither use their default style name.
+    fn names_for_variation(&self, variation: &[(Tag, f64)]) -> Result<StyleNames> {
+        let family_name = self.name(NameId::TypographicFamily, None)
+                              .or_else(|| self.name(NameId::Family, None))
+                              .ok_or(Error::MissingAttribute("family name"))?;
+        let default_style_name = || {
+            self.name(NameId::TypographicSubfamily, None)
+                .or_else(|| self.name(NameId::Subfamily, None))
+                .ok_or(Error::MissingAttribute("face name"))
+        };
+
+        let position: Vec<(Tag, f64)> = self.variation_axes().into_iter().map(|axis| {
+            let value = variation.iter().rev().find(|&&(tag, _)| tag == axis.tag);
+            let value = value.map_or(axis.default_value, |&(_, value)| value);
+            (axis.tag, value.max(axis.min_value).min(axis.max_value))
+        }).collect();
+        let instance = self.named_instances().into_iter().find(|instance| {
+            instance.variation.len() == position.len() &&
+            instance.variation.iter().zip(&position).all(|(&(_, a), &(_, b))| {
+                (a - b).abs() < FIXED_PRECISION
+            })
+        });
+        if let Some(instance) = instance {
+            return Ok(StyleNames {
+                family_name,
+                style_name: instance.style_name,
+            });
+        }
+
+        let stat = self.get_font_table(kCTFontTableSTAT);
+        let stat = match stat.as_ref().and_then(|table| Stat::parse(table).ok()) {
+            Some(stat) => stat,
+            None => {
+                return Ok(StyleNames {
+                    family_name,
+                    style_name: default_style_name()?,
+                })
+            }
+        };
+        // Design axes the font doesn't vary along sit at the one value it names for them.
+        let axis_values = stat.axis_values();
+        let stat_position: Vec<f64> = stat.design_axes().iter().enumerate().map(|(index, axis)| {
+            match position.iter().find(|&&(tag, _)| tag == axis.tag) {
+                Some(&(_, value)) => value,
+                None => {
+                    axis_values.iter()
+                               .flat_map(|value| &value.values)
+                               .find(|&&(axis, _)| axis as usize == index)
+                               .map_or(0.0, |&(_, value)| value)
+                }
+            }
+        }).collect();
+        let names = stat.style_name_ids(&stat_position)
+                        .into_iter()
+                        .map(|name_id| self.name(NameId::from(name_id), None))
+                        .collect::<Option<Vec<String>>>();
+        Ok(StyleNames {
+            family_name,
+            style_name: match names {
+                Some(names) => names.join(" "),
+                None => default_style_name()?,
+            },
+        })
+    }
+
+    /// Returns the table with the given tag, if the font has one.
+    fn get_font_table(&self, tag: Tag) -> Option<Self::FontTable>;
+
+    /// Returns the tags of every table in the font.
+    fn available_tables(&self) -> Vec<Tag>;
+
+    /// Sends the glyph's outline to `sink`, in points relative to the glyph's origin with y
+    /// pointing up. Returns false, without calling `sink`, if the glyph has no outline or an
+    /// empty one, as spaces do.
+    fn draw_glyph_outline(&self, glyph: CGGlyph, sink: &mut dyn OutlineSink) -> bool;
+
+    /// Returns the positions of the carets between the components of a ligature glyph, such
+    /// as the one between f and i in fi, in points along the baseline from the glyph's origin.
+    /// Glyphs that aren't ligatures have none.
+    fn ligature_caret_positions(&self, glyph: CGGlyph) -> Vec<CGFloat>;
+
+    /// Renders the glyph's outline at `pt_size`, one pixel per point, with antialiasing.
+    ///
+    /// The glyph origin is moved right and up by `subpixel_offset`, normally a fraction of a
+    /// pixel, before rendering. Returns `None` if the glyph has no outline or an empty one, as
+    /// spaces do.
+    fn rasterize_glyph(&self, glyph: CGGlyph, subpixel_offset: CGPoint) -> Option<GlyphBitmap> {
+        let mut elements: Vec<PathElement> = vec![];
+        if !self.draw_glyph_outline(glyph, &mut elements) {
+            return None;
+        }
+        Some(rasterizer::rasterize_outline(&elements, subpixel_offset))
+    }
+
+    /// Renders a distance field of the glyph's outline, at `pixels_per_point` pixels per point
+    /// of `pt_size`, with distances saturating `spread` pixels from the outline.
+    ///
+    /// Returns `None` if the glyph has no outline or an empty one.
+    fn glyph_distance_field(&self,
+                            glyph: CGGlyph,
+                            kind: DistanceFieldKind,
+                            pixels_per_point: CGFloat,
+                            spread: CGFloat)
+                            -> Option<DistanceField> {
+        let mut elements: Vec<PathElement> = vec![];
+        if !self.draw_glyph_outline(glyph, &mut elements) {
+            return None;
+        }
+        Some(distance_field::distance_field(&elements, kind, pixels_per_point, spread))
+    }
+
+    /// Maps a string to glyphs. The result is laid out by UTF-16 code unit, exactly as for
+    /// `glyphs_for_characters` on the string's UTF-16 encoding.
+    fn glyphs_for_str(&self, string: &str) -> CharacterGlyphs {
+        let characters: Vec<UniChar> = string.encode_utf16().collect();
+        self.glyphs_for_characters(&characters)
+    }
+
+    /// Applies the `GSUB` lookups of `features` for `script` and `language`, and the required
+    /// feature of that language system, to `buffer`, with glyph classes from `GDEF`. Returns
+    /// false, leaving `buffer` alone, if the font has no usable `GSUB` table.
+    fn apply_substitutions(&self,
+                           buffer: &mut GlyphBuffer,
+                           script: Tag,
+                           language: Option<Tag>,
+                           features: &[Feature])
+                           -> bool {
+        let table = match self.get_font_table(kCTFontTableGSUB) {
+            Some(table) => table,
+            None => return false,
+        };
+        let gdef_table = self.get_font_table(kCTFontTableGDEF);
+        let gdef = gdef_table.as_ref().and_then(|table| Gdef::parse(table).ok());
+        match Gsub::parse(&table) {
+            Ok(gsub) => {
+                let gsub = gdef.map_or(gsub, |gdef| gsub.with_gdef(gdef));
+                substitution::substitute(&gsub, buffer, script, language, features);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Applies the `morx` chains to `buffer`, with the settings `features` select on top of the
+    /// font's defaults. OpenType features such as `liga` select the equivalent AAT settings.
+    /// Returns false, leaving `buffer` alone, if the font has no usable `morx` table.
+    fn apply_morphs(&self, buffer: &mut GlyphBuffer, features: &[Feature]) -> bool {
+        let table = match self.get_font_table(kCTFontTableMorx) {
+            Some(table) => table,
+            None => return false,
+        };
+        let gdef_table = self.get_font_table(kCTFontTableGDEF);
+        let gdef = gdef_table.as_ref().and_then(|table| Gdef::parse(table).ok());
+        match Morx::parse(&table) {
+            Ok(morx) => {
+                aat::morph(&morx, gdef.as_ref(), buffer, features);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the AAT features the font's `feat` table lists, with their names, in the table's
+    /// order. Features and settings without names are left out.
+    fn feature_types(&self) -> Vec<FeatureType> {
+        let table = match self.get_font_table(kCTFontTableFeat) {
+            Some(table) => table,
+            None => return vec![],
+        };
+        let feat = match Feat::parse(&table) {
+            Ok(feat) => feat,
+            Err(_) => return vec![],
+        };
+        feat.features().into_iter().filter_map(|feature| {
+            let default_setting = if feature.exclusive {
+                feature.settings.get(feature.default_setting_index)
+            } else {
+                None
+            };
+            Some(FeatureType {
+                identifier: feature.feature_type,
+                name: self.name(NameId::from(feature.name_id), None)?,
+                exclusive: feature.exclusive,
+                default_selector: default_setting.map(|&(setting, _)| setting),
+                selectors: feature.settings.iter().filter_map(|&(setting, name_id)| {
+                    Some((setting, self.name(NameId::from(name_id), None)?))
+                }).collect(),
+            })
+        }).collect()
+    }
+
+    /// Returns the legacy `kern` table's horizontal kerning between `left` and `right`, in
+    /// points. This is 0 if the font has no `kern` table, even if it kerns with `GPOS`.
+    fn kerning_for_glyph_pair(&self, left: CGGlyph, right: CGGlyph) -> CGFloat {
+        let table = match self.get_font_table(kCTFontTableKern) {
+            Some(table) => table,
+            None => return 0.0,
+        };
+        match Kern::parse(&table) {
+            Ok(kern) => {
+                kern.kerning(left, right) as CGFloat * self.pt_size() /
+                self.units_per_em() as CGFloat
+            }
+            Err(_) => 0.0,
+        }
+    }
+
+    /// Returns where each of `buffer`'s glyphs goes when laid out horizontally: the glyph's
+    /// advance from `advances_for_glyphs`, adjusted by the `GPOS` lookups of `features`
+    /// for `script` and `language`, and the offset it is drawn at. Positions are in the
+    /// buffer's logical order.
+    ///
+    /// If `GPOS` doesn't kern `script` and `language`, the `kern` feature uses the `kerx`
+    /// table instead, or failing that the legacy `kern` table. A `trak` table's tracking for
+    /// the font's size is added last. Glyph classes, which decide the marks that kerning
+    /// passes over, come from `GDEF`.
+    fn glyph_positions(&self,
+                       buffer: &GlyphBuffer,
+                       script: Tag,
+                       language: Option<Tag>,
+                       features: &[Feature])
+                       -> Vec<GlyphPosition> {
+        let advances = self.advances_for_glyphs(kCTFontHorizontalOrientation, &buffer.glyph_ids());
+        let mut positions: Vec<GlyphPosition> = advances.iter().map(|advance| {
+            GlyphPosition::new(advance.width, advance.height)
+        }).collect();
+        let scale = self.pt_size() / self.units_per_em() as CGFloat;
+        let gdef_table = self.get_font_table(kCTFontTableGDEF);
+        let gdef = gdef_table.as_ref().and_then(|table| Gdef::parse(table).ok());
+        let gpos_table = self.get_font_table(kCTFontTableGPOS);
+        let gpos = gpos_table.as_ref().and_then(|table| Gpos::parse(table).ok()).map(|gpos| {
+            gdef.map_or(gpos, |gdef| gpos.with_gdef(gdef))
+        });
+        let mut classified = Cow::Borrowed(buffer);
+        if let Some(ref gdef) = gdef {
+            classified.to_mut().set_glyph_classes(gdef);
+        }
+        let buffer = &*classified;
+
+        // `kerx` or legacy kerning goes first, so that marks attach with the kerned advances.
+        if !gpos.is_some_and(|gpos| positioning::has_kerning(&gpos, script, language)) {
+            let kerx_table = self.get_font_table(kCTFontTableKerx);
+            match kerx_table.as_ref().and_then(|table| Kerx::parse(table).ok()) {
+                Some(kerx) => {
+                    let ankr_table = self.get_font_table(kCTFontTableAnkr);
+                    let ankr = ankr_table.as_ref().and_then(|table| Ankr::parse(table).ok());
+                    aat::kern(&kerx, ankr.as_ref(), buffer, features, scale, &mut positions);
+                }
+                None => {
+                    let kern_table = self.get_font_table(kCTFontTableKern);
+                    if let Some(kern) = kern_table.as_ref().and_then(|table| {
+                        Kern::parse(table).ok()
+                    }) {
+                        positioning::apply_kern_table(&kern, buffer, features, scale,
+                                                      &mut positions);
+                    }
+                }
+            }
+        }
+        if let Some(gpos) = gpos {
+            positioning::position(&gpos, buffer, script, language, features, scale, &mut positions);
+        }
+        let trak_table = self.get_font_table(kCTFontTableTrak);
+        if let Some(trak) = trak_table.as_ref().and_then(|table| Trak::parse(table).ok()) {
+            aat::track(&trak, buffer, self.pt_size(), scale, &mut positions);
+        }
+        positions
+    }
+
+    /// Shapes a run of `text` in `script`, `language` and `direction`: maps its characters to
+    /// glyphs, applies `GSUB` or failing that `morx`, and positions the glyphs as
+    /// `glyph_positions` does.
+    ///
+    /// The features the run needs, such as `ccmp`, `liga` and `kern`, are on by default;
+    /// `features` turns others on or these off. Their ranges, like the glyphs' clusters, are
+    /// byte indices into `text`. Glyphs are returned in visual order, left to right, so a
+    /// right-to-left run's first glyph is its last, and brackets in right-to-left runs are
+    /// mirrored.
+    fn shape(&self,
+             text: &str,
+             features: &[Feature],
+             script: Tag,
+             language: Option<Tag>,
+             direction: Direction)
+             -> Vec<ShapedGlyph> {
+        let characters: Vec<UniChar> = text.encode_utf16().collect();
+        let mut buffer = GlyphBuffer::new(self, &characters);
+        buffer.direction = direction;
+        if direction == Direction::RightToLeft {
+            for info in &mut buffer.glyphs {
+                let glyph = mirror(characters[info.cluster]).map_or(0, |mirrored| {
+                    self.glyphs_for_characters(&[mirrored]).glyphs[0]
+                });
+                if glyph != 0 {
+                    info.glyph = glyph;
+                }
+            }
+        }
+
+        // The byte index of each UTF-16 code unit, and of the end of the text.
+        let mut byte_indices: Vec<usize> = text.char_indices().flat_map(|(index, character)| {
+            iter::repeat_n(index, character.len_utf16())
+        }).collect();
+        byte_indices.push(text.len());
+        let utf16_index = |index: usize| byte_indices.partition_point(|&byte| byte < index);
+
+        let direction_features: [Tag; 2] = match direction {
+            Direction::LeftToRight => [Tag::new(b"ltra"), Tag::new(b"ltrm")],
+            Direction::RightToLeft => [Tag::new(b"rtla"), Tag::new(b"rtlm")],
+        };
+        let features: Vec<Feature> = DEFAULT_FEATURES.iter()
+            .chain(&direction_features)
+            .map(|&tag| Feature::new(tag))
+            .chain(features.iter().map(|feature| {
+                feature.with_range(utf16_index(feature.start), utf16_index(feature.end))
+            }))
+            .collect();
+
+        if !self.apply_substitutions(&mut buffer, script, language, &features) {
+            self.apply_morphs(&mut buffer, &features);
+        }
+        let positions = self.glyph_positions(&buffer, script, language, &features);
+
+        // Marks join the cluster of the glyph they follow, so that a base and its marks are
+        // edited as one.
+        let mut cluster = 0;
+        let glyphs: Vec<ShapedGlyph> = buffer.glyphs.iter().zip(positions).map(|(info, position)| {
+            if info.glyph_class != MARK_GLYPH {
+                cluster = info.cluster;
+            }
+            ShapedGlyph {
+                glyph: info.glyph,
+                cluster: byte_indices[cluster],
+                position,
+            }
+        }).collect();
+        match direction {
+            Direction::LeftToRight => glyphs,
+            Direction::RightToLeft => glyphs.into_iter().rev().collect(),
+        }
+    }
+}
+
+/// A design axis of a variable font, as returned by `FontBackend::variation_axes`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariationAxis {
+    pub tag: Tag,
+    /// The axis's name, such as "Weight", in the font's default language.
+    pub name: String,
+    pub min_value: f64,
+    pub default_value: f64,
+    pub max_value: f64,
+    /// Whether the font asks for the axis to be left out of user interfaces.
+    pub hidden: bool,
+}
+
+/// A named instance of a variable font, as returned by `FontBackend::named_instances`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NamedInstance {
+    /// The instance's style name, such as "Semibold Condensed".
+    pub style_name: String,
+    pub postscript_name: Option<String>,
+    /// The instance's position on each axis, in user units and `variation_axes` order.
+    pub variation: Vec<(Tag, f64)>,
+}
+
+/// A name for a position on one or more of a font family's design axes, as returned by
+/// `FontBackend::style_attributes`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StyleAttribute {
+    /// The name, such as "Bold".
+    pub name: String,
+    /// The value on each axis the name applies to. Only combinations of values, such as
+    /// "Caption" for a size and weight, name more than one axis.
+    pub values: Vec<(Tag, f64)>,
+    /// The range of values the name covers on its axis, if it covers more than one.
+    pub range: Option<(f64, f64)>,
+    /// The value that style-linking pairs this one with, such as Bold's 700 for Regular.
+    pub linked_value: Option<f64>,
+    /// Whether the name is left out of composed style names, as "Regular" usually is.
+    pub elidable: bool,
+    /// Whether the name is only there for compatibility with older fonts in the family.
+    pub older_sibling: bool,
+}
+
+/// The names of a font at some variation, as returned by `FontBackend::names_for_variation`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StyleNames {
+    /// The typographic family name, such as "Source Sans 3".
+    pub family_name: String,
+    /// The style name within the family, such as "Semibold Italic".
+    pub style_name: String,
+}
+
+/// An AAT feature of a font, as returned by `FontBackend::feature_types`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeatureType {
+    /// The feature type, such as 1 for ligatures.
+    pub identifier: u16,
+    /// The feature's name, such as "Ligatures", in the font's default language.
+    pub name: String,
+    /// Whether exactly one of the selectors is on at a time, rather than each turning
+    /// something on or off.
+    pub exclusive: bool,
+    /// The selector that is on by default, for exclusive features.
+    pub default_selector: Option<u16>,
+    /// The feature's selectors, such as 2 for common ligatures on, and their names.
+    pub selectors: Vec<(u16, String)>,
+}
+
+/// The glyphs for a run of UTF-16 text, as returned by `FontBackend::glyphs_for_characters`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CharacterGlyphs {
+    /// One glyph per code unit. A surrogate pair's glyph is at its high surrogate, followed by
+    /// a 0 for the low surrogate.
+    pub glyphs: Vec<CGGlyph>,
+    /// The code unit indices of characters with no glyph in the font, in ascending order.
+    pub missing: Vec<usize>,
+}
+
+impl CharacterGlyphs {
+    /// Collects the glyphs that a backend wrote for `characters`, one per code unit, noting
+    /// the characters that got glyph 0.
+    pub fn new(characters: &[UniChar], glyphs: Vec<CGGlyph>) -> CharacterGlyphs {
+        let mut missing = vec![];
+        let mut index = 0;
+        while index < characters.len() {
+            if glyphs[index] == 0 {
+                missing.push(index);
+            }
+            index += if is_surrogate_pair(&characters[index..]) { 2 } else { 1 };
+        }
+        CharacterGlyphs {
+            glyphs,
+            missing,
+        }
+    }
+
+    /// Returns true if the font had a glyph for every character.
+    pub fn all_found(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    /// Returns true if the character starting at code unit `index` has no glyph in the font.
+    pub fn is_missing(&self, index: usize) -> bool {
+        self.missing.binary_search(&index).is_ok()
+    }
+}
+
+/// Returns the mirror image of a bracket or other mirrored character, for right-to-left text.
+fn mirror(character: UniChar) -> Option<UniChar> {
+    MIRRORED_PAIRS.iter().find_map(|&(left, right)| {
+        if character == left {
+            Some(right)
+        } else if character == right {
+            Some(left)
+        } else {
+            None
+        }
+    })
+}
+
+/// Returns true if `characters` starts with a high surrogate followed by a low surrogate.
+pub fn is_surrogate_pair(characters: &[UniChar]) -> bool {
+    match characters {
+        [high, low, ..] => (0xD800..0xDC00).contains(high) && (0xDC00..0xE000).contains(low),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base::CGFloat;
+    use shaping::{Direction, Feature, GlyphBuffer};
+    use tag::{Tag, kCTFontTableFvar, kCTFontTableGPOS, kCTFontTableKern, kCTFontTableName};
+    use tag::kCTFontTableSTAT;
+    use test_font::{self, A, FI, GRAVE_COMB_CAP, GRINNING_FACE, LOWER_A, LOWER_F, LOWER_I, T};
+    use test_font::UNITS_PER_EM;
+    use super::FontBackend;
+
+    const LATIN: Tag = Tag::new(b"latn");
+    const WEIGHT: Tag = Tag::new(b"wght");
+    const WIDTH: Tag = Tag::new(b"wdth");
+
+    /// Returns each glyph's advance for "TaAT", kerned with the `kern` feature.
+    fn kerned_advances<F: FontBackend>(font: &F) -> Vec<CGFloat> {
+        let buffer = GlyphBuffer::from_str(font, "TaAT");
+        let features = [Feature::new(Tag::new(b"kern"))];
+        let positions = font.glyph_positions(&buffer, LATIN, None, &features);
+        positions.iter().map(|position| position.x_advance).collect()
+    }
+
+    /// Shapes `text` as Latin, and returns its glyphs and their clusters.
+    fn shape_str(text: &str, features: &[Feature], direction: Direction) -> Vec<(u16, usize)> {
+        let font = test_font::dejavu_sans(12.0);
+        font.shape(text, features, LATIN, None, direction)
+            .iter()
+            .map(|glyph| (glyph.glyph, glyph.cluster))
+            .collect()
+    }
+
+    #[test]
+    fn shapes_ligatures_into_one_cluster() {
+        assert_eq!(shape_str("fi", &[], Direction::LeftToRight), vec![(FI, 0)]);
+    }
+
+    #[test]
+    fn gives_clusters_as_byte_indices() {
+        assert_eq!(shape_str("\u{1F600}A", &[], Direction::LeftToRight),
+                   vec![(GRINNING_FACE, 0), (A, 4)]);
+    }
+
+    #[test]
+    fn puts_marks_in_the_cluster_of_their_base() {
+        assert_eq!(shape_str("T\u{300}", &[], Direction::LeftToRight),
+                   vec![(T, 0), (GRAVE_COMB_CAP, 0)]);
+    }
+
+    #[test]
+    fn reverses_and_mirrors_right_to_left_runs() {
+        let font = test_font::dejavu_sans(12.0);
+        let characters: Vec<u16> = "()".encode_utf16().collect();
+        let brackets = font.glyphs_for_characters(&characters).glyphs;
+        assert_eq!(shape_str("(A", &[], Direction::RightToLeft),
+                   vec![(A, 1), (brackets[1], 0)]);
+    }
+
+    #[test]
+    fn applies_features_to_byte_ranges() {
+        // The second "fi" is at bytes 6 to 8, but UTF-16 indices 4 to 6.
+        let features = [Feature::new(Tag::new(b"liga")).with_value(0).with_range(6, 8)];
+        assert_eq!(shape_str("\u{1F600}fifi", &features, Direction::LeftToRight),
+                   vec![(GRINNING_FACE, 0), (FI, 4), (LOWER_F, 6), (LOWER_I, 7)]);
+    }
+
+    #[test]
+    fn uses_the_kern_table_only_without_gpos_kerning() {
+        // A Microsoft format 0 table kerning "AT" by -200 and "Ta" by -100.
+        let kern = test_font::words(&[0, 1, 0, 26, 0x0001, 2, 0, 0, 0,
+                                      A as i32, T as i32, -200, T as i32, LOWER_A as i32, -100]);
+        let tables = [(kCTFontTableKern, kern)];
+
+        let font = test_font::dejavu_sans(UNITS_PER_EM);
+        let gpos_kerned = kerned_advances(&font);
+        assert_eq!(gpos_kerned[0], 912.0);
+        let font = test_font::dejavu_sans_with_tables(&tables, &[], UNITS_PER_EM);
+        assert_eq!(kerned_advances(&font), gpos_kerned);
+
+        let font = test_font::dejavu_sans_with_tables(&[], &[kCTFontTableGPOS], UNITS_PER_EM);
+        let unkerned = kerned_advances(&font);
+        assert_eq!(unkerned[0], 1251.0);
+        let font = test_font::dejavu_sans_with_tables(&tables, &[kCTFontTableGPOS], UNITS_PER_EM);
+        assert_eq!(kerned_advances(&font),
+                   vec![unkerned[0] - 100.0, unkerned[1], unkerned[2] - 200.0, unkerned[3]]);
+    }
+
+    #[test]
+    fn names_variations_by_named_instance_then_stat() {
+        // Weights from 100 to 900 and widths from 75 to 100, with a named instance at weight
+        // 700 and width 100.
+        let fvar = test_font::words(&[1, 0, 16, 2, 2, 20, 1, 12,
+                                      0x7767, 0x6874, 100, 0, 400, 0, 900, 0, 0, 256,
+                                      0x7764, 0x7468, 75, 0, 100, 0, 100, 0, 0, 257,
+                                      400, 0, 700, 0, 100, 0]);
+        let names = [(1, "Test Sans"), (2, "Regular"), (256, "Weight"), (257, "Width"),
+                     (300, "Bold"), (301, "Regular"), (302, "Heavy"), (303, "Condensed"),
+                     (304, "Normal"), (305, "Caption"), (310, "Roman"), (400, "Instance Bold")];
+        let tables = [(kCTFontTableFvar, fvar),
+                      (kCTFontTableName, test_font::name_table(&names)),
+                      (kCTFontTableSTAT, test_font::stat_table(1))];
+        let font = test_font::dejavu_sans_with_tables(&tables, &[], 12.0);
+        let style_name = |variation: &[(Tag, f64)]| {
+            let names = font.names_for_variation(variation).unwrap();
+            assert_eq!(names.family_name, "Test Sans");
+            names.style_name
+        };
+
+        assert_eq!(style_name(&[(WEIGHT, 700.0)]), "Instance Bold");
+        assert_eq!(style_name(&[(WEIGHT, 700.00001), (WIDTH, 100.0)]), "Instance Bold");
+        assert_eq!(style_name(&[(WEIGHT, 700.001)]), "Heavy");
+        assert_eq!(style_name(&[(WEIGHT, 700.0), (WIDTH, 75.0)]), "Caption");
+        assert_eq!(style_name(&[(WEIGHT, 800.0), (WIDTH, 75.0)]), "Condensed Heavy");
+        assert_eq!(style_name(&[(WIDTH, 75.0)]), "Condensed");
+        assert_eq!(style_name(&[]), "Roman");
+    }
+}
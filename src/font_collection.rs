@@ -22,7 +22,7 @@ use std::mem;
 use std::ptr;
 
 #[repr(C)]
-struct __CTFontCollection;
+pub struct __CTFontCollection;
 
 pub type CTFontCollectionRef = *const __CTFontCollection;
 
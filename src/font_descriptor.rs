@@ -17,8 +17,11 @@ use core_foundation::set::CFSetRef;
 use core_foundation::string::{CFString, CFStringRef};
 use core_foundation::url::{CFURL, CFURLRef};
 use core_graphics::base::CGFloat;
+use error::{Error, Result};
+use font;
 
 use std::mem;
+use std::ptr;
 
 /*
 * CTFontTraits.h
@@ -119,47 +122,77 @@ pub trait TraitAccessors {
     fn normalized_weight(&self) -> f64;
     fn normalized_width(&self) -> f64;
     fn normalized_slant(&self) -> f64;
+
+    fn try_symbolic_traits(&self) -> Result<CTFontSymbolicTraits>;
+    fn try_normalized_weight(&self) -> Result<f64>;
+    fn try_normalized_width(&self) -> Result<f64>;
+    fn try_normalized_slant(&self) -> Result<f64>;
 }
 
 trait TraitAccessorPrivate {
-    unsafe fn extract_number_for_key(&self, key: CFStringRef) -> CFNumber;
+    unsafe fn extract_number_for_key(&self, key: CFStringRef, name: &'static str)
+                                     -> Result<CFNumber>;
 }
 
 impl TraitAccessorPrivate for CTFontTraits {
-    unsafe fn extract_number_for_key(&self, key: CFStringRef) -> CFNumber {
-        let cftype = self.get_CFType(mem::transmute(key));
-        assert!(cftype.instance_of::<CFNumberRef,CFNumber>());
-        TCFType::wrap_under_get_rule(mem::transmute(cftype.as_CFTypeRef()))
+    unsafe fn extract_number_for_key(&self, key: CFStringRef, name: &'static str)
+                                     -> Result<CFNumber> {
+        let value = match self.find(mem::transmute(key)) {
+            Some(value) => value,
+            None => return Err(Error::MissingAttribute(name)),
+        };
+        let cftype: CFType = TCFType::wrap_under_get_rule(mem::transmute(value));
+        if !cftype.instance_of::<CFNumberRef,CFNumber>() {
+            return Err(Error::WrongAttributeType(name));
+        }
+        Ok(TCFType::wrap_under_get_rule(mem::transmute(cftype.as_CFTypeRef())))
     }
 
 }
 
 impl TraitAccessors for CTFontTraits {
     fn symbolic_traits(&self) -> CTFontSymbolicTraits {
+        self.try_symbolic_traits().unwrap()
+    }
+
+    fn normalized_weight(&self) -> f64 {
+        self.try_normalized_weight().unwrap()
+    }
+
+    fn normalized_width(&self) -> f64 {
+        self.try_normalized_width().unwrap()
+    }
+
+    fn normalized_slant(&self) -> f64 {
+        self.try_normalized_slant().unwrap()
+    }
+
+    fn try_symbolic_traits(&self) -> Result<CTFontSymbolicTraits> {
         unsafe {
-            let number = self.extract_number_for_key(kCTFontSymbolicTrait);
-            number.to_i64().unwrap() as u32
+            let number = self.extract_number_for_key(kCTFontSymbolicTrait, "symbolic traits")?;
+            number.to_i64().map(|traits| traits as u32)
+                           .ok_or(Error::WrongAttributeType("symbolic traits"))
         }
     }
 
-    fn normalized_weight(&self) -> f64 {
+    fn try_normalized_weight(&self) -> Result<f64> {
         unsafe {
-            let number = self.extract_number_for_key(kCTFontWeightTrait);
-            number.to_f64().unwrap()
+            let number = self.extract_number_for_key(kCTFontWeightTrait, "weight trait")?;
+            number.to_f64().ok_or(Error::WrongAttributeType("weight trait"))
         }
     }
 
-    fn normalized_width(&self) -> f64 {
+    fn try_normalized_width(&self) -> Result<f64> {
         unsafe {
-            let number = self.extract_number_for_key(kCTFontWidthTrait);
-            number.to_f64().unwrap()
+            let number = self.extract_number_for_key(kCTFontWidthTrait, "width trait")?;
+            number.to_f64().ok_or(Error::WrongAttributeType("width trait"))
         }
     }
 
-    fn normalized_slant(&self) -> f64 {
+    fn try_normalized_slant(&self) -> Result<f64> {
         unsafe {
-            let number = self.extract_number_for_key(kCTFontSlantTrait);
-            number.to_f64().unwrap()
+            let number = self.extract_number_for_key(kCTFontSlantTrait, "slant trait")?;
+            number.to_f64().ok_or(Error::WrongAttributeType("slant trait"))
         }
     }
 }
@@ -167,10 +200,8 @@ impl TraitAccessors for CTFontTraits {
 /*
 * CTFontDescriptor.h
 */
-pub type CTFontOrientation = u32;
-pub const kCTFontDefaultOrientation: CTFontOrientation = 0;
-pub const kCTFontHorizontalOrientation: CTFontOrientation = 1;
-pub const kCTFontVerticalOrientation: CTFontOrientation = 2;
+pub use base::{CTFontOrientation, kCTFontDefaultOrientation};
+pub use base::{kCTFontHorizontalOrientation, kCTFontVerticalOrientation};
 
 pub type CTFontPriority = u32;
 pub const kCTFontPrioritySystem: CTFontPriority = 10000;
@@ -181,7 +212,7 @@ pub const kCTFontPriorityDynamic: CTFontPriority = 50000;
 pub const kCTFontPriorityProcess: CTFontPriority = 60000;
 
 #[repr(C)]
-struct __CTFontDescriptor;
+pub struct __CTFontDescriptor;
 
 pub type CTFontDescriptorRef = *const __CTFontDescriptor;
 
@@ -233,52 +264,140 @@ impl TCFType<CTFontDescriptorRef> for CTFontDescriptor {
 }
 
 impl CTFontDescriptor {
-    fn get_string_attribute(&self, attribute: CFStringRef) -> Option<String> {
+    fn has_attribute(&self, attribute: CFStringRef) -> bool {
+        unsafe {
+            let value = CTFontDescriptorCopyAttribute(self.obj, attribute);
+            if value.is_null() {
+                return false;
+            }
+            CFRelease(value);
+            true
+        }
+    }
+
+    fn get_string_attribute(&self, attribute: CFStringRef, name: &'static str)
+                            -> Result<String> {
         unsafe {
             let value = CTFontDescriptorCopyAttribute(self.obj, attribute);
             if value.is_null() {
-                return None
+                return Err(Error::MissingAttribute(name))
+            }
+
+            let value: CFType = TCFType::wrap_under_create_rule(value);
+            if !value.instance_of::<CFStringRef,CFString>() {
+                return Err(Error::WrongAttributeType(name))
+            }
+            let s: CFString = TCFType::wrap_under_get_rule(mem::transmute(value.as_CFTypeRef()));
+            Ok(s.to_string())
+        }
+    }
+
+    fn get_localized_string_attribute(&self, attribute: CFStringRef, name: &'static str)
+                                      -> Result<LocalizedString> {
+        unsafe {
+            let mut language: CFStringRef = ptr::null();
+            let value = CTFontDescriptorCopyLocalizedAttribute(self.obj, attribute, &mut language);
+            let language = if language.is_null() {
+                None
+            } else {
+                let language: CFString = TCFType::wrap_under_create_rule(language);
+                Some(language.to_string())
+            };
+            if value.is_null() {
+                return Err(Error::MissingAttribute(name))
             }
 
-            let value: CFType = TCFType::wrap_under_get_rule(value);
-            assert!(value.instance_of::<CFStringRef,CFString>());
+            let value: CFType = TCFType::wrap_under_create_rule(value);
+            if !value.instance_of::<CFStringRef,CFString>() {
+                return Err(Error::WrongAttributeType(name))
+            }
             let s: CFString = TCFType::wrap_under_get_rule(mem::transmute(value.as_CFTypeRef()));
-            Some(s.to_string())
+            Ok(LocalizedString {
+                value: s.to_string(),
+                language,
+            })
         }
     }
+}
 
+/// An attribute localized into the user's most preferred language the font supports.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LocalizedString {
+    pub value: String,
+    /// The language Core Text chose, such as `ja`, if it says.
+    pub language: Option<String>,
 }
 
 impl CTFontDescriptor {
     pub fn family_name(&self) -> String {
-        let value = self.get_string_attribute(kCTFontDisplayNameAttribute);
-        value.expect("A font2 must have a non-null font family name.")
+        self.try_family_name().expect("A font2 must have a non-null font family name.")
     }
 
     pub fn font_name(&self) -> String {
-        let value = self.get_string_attribute(kCTFontNameAttribute);
-        value.expect("A font must have a non-null name.")
+        self.try_font_name().expect("A font must have a non-null name.")
     }
 
     pub fn style_name(&self) -> String {
-        let value = self.get_string_attribute(kCTFontStyleNameAttribute);
-        value.expect("A font must have a non-null style name.")
+        self.try_style_name().expect("A font must have a non-null style name.")
     }
 
     pub fn display_name(&self) -> String {
-        let value = self.get_string_attribute(kCTFontDisplayNameAttribute);
-        value.expect("A font must have a non-null display name.")
+        self.try_display_name().expect("A font must have a non-null display name.")
     }
 
     pub fn font_path(&self) -> String {
+        self.try_font_path().expect("A font must have a non-null URL.")
+    }
+
+    pub fn try_family_name(&self) -> Result<String> {
+        unsafe { self.get_string_attribute(kCTFontDisplayNameAttribute, "family name") }
+    }
+
+    pub fn try_font_name(&self) -> Result<String> {
+        unsafe { self.get_string_attribute(kCTFontNameAttribute, "name") }
+    }
+
+    /// Returns the style name. Descriptors with a variation are named for their position on
+    /// the font's axes, as `CTFont::face_name` names it, rather than after the default
+    /// instance.
+    pub fn try_style_name(&self) -> Result<String> {
+        if unsafe { self.has_attribute(kCTFontVariationAttribute) } {
+            return font::new_from_descriptor(self, 0.0).try_face_name();
+        }
+        unsafe { self.get_string_attribute(kCTFontStyleNameAttribute, "style name") }
+    }
+
+    pub fn try_display_name(&self) -> Result<String> {
+        unsafe { self.get_string_attribute(kCTFontDisplayNameAttribute, "display name") }
+    }
+
+    pub fn try_localized_family_name(&self) -> Result<LocalizedString> {
+        unsafe { self.get_localized_string_attribute(kCTFontFamilyNameAttribute, "family name") }
+    }
+
+    pub fn try_localized_style_name(&self) -> Result<LocalizedString> {
+        unsafe { self.get_localized_string_attribute(kCTFontStyleNameAttribute, "style name") }
+    }
+
+    pub fn try_localized_display_name(&self) -> Result<LocalizedString> {
+        unsafe {
+            self.get_localized_string_attribute(kCTFontDisplayNameAttribute, "display name")
+        }
+    }
+
+    pub fn try_font_path(&self) -> Result<String> {
         unsafe {
             let value = CTFontDescriptorCopyAttribute(self.obj, kCTFontURLAttribute);
-            assert!(!value.is_null());
+            if value.is_null() {
+                return Err(Error::MissingAttribute("URL"))
+            }
 
-            let value: CFType = TCFType::wrap_under_get_rule(value);
-            assert!(value.instance_of::<CFURLRef,CFURL>());
+            let value: CFType = TCFType::wrap_under_create_rule(value);
+            if !value.instance_of::<CFURLRef,CFURL>() {
+                return Err(Error::WrongAttributeType("URL"))
+            }
             let url: CFURL = TCFType::wrap_under_get_rule(mem::transmute(value.as_CFTypeRef()));
-            format!("{:?}", url)
+            Ok(format!("{:?}", url))
         }
     }
 }
@@ -0,0 +1,493 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A cache of rasterized glyphs packed into fixed-size atlas pages, ready for uploading to
+//! GPU textures.
+
+use base::{CGFloat, CGGlyph, CGPoint};
+use font_backend::FontBackend;
+
+use std::collections::HashMap;
+
+/// Empty pixels left around each glyph so that filtering doesn't pick up its neighbors.
+const GLYPH_PADDING: u32 = 1;
+
+/// A rectangle of an atlas page, in pixels from its top-left corner.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtlasRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl AtlasRect {
+    fn union(&self, other: &AtlasRect) -> AtlasRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        AtlasRect {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+}
+
+/// Where a cached glyph's bitmap is, and where to draw it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GlyphPlacement {
+    /// The page holding the glyph's coverage, or `None` if it has no pixels, as spaces don't.
+    pub page: Option<usize>,
+    /// The glyph's coverage in the page, or an empty rectangle if it's on no page.
+    pub rect: AtlasRect,
+    /// Where the bitmap's top-left corner goes, in the same y-down pixel space as the position
+    /// passed to `GlyphCache::glyph`.
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Part of a page that changed since the last `GlyphCache::take_dirty_rects`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DirtyRect {
+    pub page: usize,
+    pub rect: AtlasRect,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    /// The caller's id for the font's data.
+    font_id: u64,
+    /// The caller's id for the font's variation.
+    instance_id: u64,
+    pt_size: u64,
+    glyph: CGGlyph,
+    subpixel_x: u8,
+    subpixel_y: u8,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CachedGlyph {
+    /// `None` for glyphs without pixels, which take no room.
+    page: Option<usize>,
+    rect: AtlasRect,
+    left: i32,
+    top: i32,
+}
+
+/// A skyline packer: the used part of a page is tracked as the height of its top edge at
+/// each span of x, and glyphs are placed as high up as they fit.
+#[derive(Clone, Debug)]
+struct Skyline {
+    width: u32,
+    height: u32,
+    /// `(x, y, width)` spans, left to right, each used from the top of the page down to `y`.
+    spans: Vec<(u32, u32, u32)>,
+}
+
+impl Skyline {
+    fn new(width: u32, height: u32) -> Skyline {
+        Skyline {
+            width,
+            height,
+            spans: vec![(0, 0, width)],
+        }
+    }
+
+    /// Returns the lowest y at which a `width`-wide rectangle starting at span `index` clears
+    /// the skyline, if it fits on the page.
+    fn fit(&self, index: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.spans[index].0;
+        if x + width > self.width {
+            return None;
+        }
+        let mut y = 0;
+        let mut covered = 0;
+        for &(_, span_y, span_width) in &self.spans[index..] {
+            y = y.max(span_y);
+            covered += span_width;
+            if covered >= width {
+                break;
+            }
+        }
+        if y + height > self.height { None } else { Some(y) }
+    }
+
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        // Prefer the highest position, then the narrowest span to waste the least space.
+        let (index, y) = (0..self.spans.len())
+            .filter_map(|index| self.fit(index, width, height).map(|y| (index, y)))
+            .min_by_key(|&(index, y)| (y, self.spans[index].2))?;
+        let x = self.spans[index].0;
+
+        // Replace the spans under the rectangle with its bottom edge.
+        let mut spans = Vec::with_capacity(self.spans.len() + 1);
+        spans.extend_from_slice(&self.spans[..index]);
+        spans.push((x, y + height, width));
+        for &(span_x, span_y, span_width) in &self.spans[index..] {
+            let end = span_x + span_width;
+            if end <= x + width {
+                continue;
+            }
+            let start = span_x.max(x + width);
+            spans.push((start, span_y, end - start));
+        }
+
+        // Merge neighbors at the same height.
+        self.spans.clear();
+        for span in spans {
+            match self.spans.last_mut() {
+                Some(last) if last.1 == span.1 => last.2 += span.2,
+                _ => self.spans.push(span),
+            }
+        }
+        Some((x, y))
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Page {
+    pixels: Vec<u8>,
+    skyline: Skyline,
+    dirty: Option<AtlasRect>,
+    /// The generation in which one of the page's glyphs was last looked up.
+    last_used: u64,
+}
+
+/// A cache of glyph coverage bitmaps packed into 8-bit atlas pages.
+///
+/// Glyphs are keyed by font and instance id, point size, glyph and subpixel position, which is
+/// quantized to a configurable number of steps per pixel. New pages are added as needed up to
+/// a limit, after which the least recently used page is cleared and reused. Each call to
+/// `take_dirty_rects` starts a new generation; pages used in the current generation are never
+/// evicted, so the placements returned since the last call stay valid until the next one.
+#[derive(Clone, Debug)]
+pub struct GlyphCache {
+    page_width: u32,
+    page_height: u32,
+    max_pages: usize,
+    subpixel_x: u8,
+    subpixel_y: u8,
+    pages: Vec<Page>,
+    entries: HashMap<GlyphKey, CachedGlyph>,
+    generation: u64,
+}
+
+impl GlyphCache {
+    /// Creates an empty cache of `page_width` by `page_height` pages, using up to
+    /// `max_pages` of them. Glyphs are positioned to a quarter pixel horizontally and to
+    /// whole pixels vertically.
+    pub fn new(page_width: u32, page_height: u32, max_pages: usize) -> GlyphCache {
+        GlyphCache {
+            page_width,
+            page_height,
+            max_pages,
+            subpixel_x: 4,
+            subpixel_y: 1,
+            pages: vec![],
+            entries: HashMap::new(),
+            generation: 0,
+        }
+    }
+
+    /// Sets the number of subpixel positions per pixel on each axis, clearing the cache.
+    /// Each is at least 1, for whole-pixel positioning.
+    pub fn with_subpixel_positions(mut self, horizontal: u8, vertical: u8) -> GlyphCache {
+        self.subpixel_x = horizontal.max(1);
+        self.subpixel_y = vertical.max(1);
+        self.clear();
+        self
+    }
+
+    pub fn page_size(&self) -> (u32, u32) {
+        (self.page_width, self.page_height)
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Returns a page's coverage, one byte per pixel, row by row from the top, or `None` if
+    /// there is no such page.
+    pub fn page_pixels(&self, page: usize) -> Option<&[u8]> {
+        self.pages.get(page).map(|page| &page.pixels[..])
+    }
+
+    /// Removes every glyph. Pages are kept, and reported dirty.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        for index in 0..self.pages.len() {
+            self.reset_page(index);
+        }
+    }
+
+    /// Returns the changed part of each page since the last call, for uploading, and starts a
+    /// new generation.
+    pub fn take_dirty_rects(&mut self) -> Vec<DirtyRect> {
+        self.generation += 1;
+        self.pages.iter_mut().enumerate().filter_map(|(page, state)| {
+            state.dirty.take().map(|rect| {
+                DirtyRect {
+                    page,
+                    rect,
+                }
+            })
+        }).collect()
+    }
+
+    /// Returns the placement of a glyph drawn with its origin at `position`, in y-down pixels,
+    /// rasterizing and packing it first if it isn't cached.
+    ///
+    /// `font_id` is the caller's name for the font's data, such as a hash of it or an index
+    /// into its own list of fonts. Fonts created from different data must have different ids;
+    /// the sizes of one font can share an id. `instance_id` likewise names the variation of a
+    /// variable font, such as an index into its named instances: fonts with the same data at
+    /// different variations must have different instance ids, and fonts that don't vary can
+    /// all use 0.
+    ///
+    /// Glyphs with nothing to draw, such as spaces, are placed on no page. Returns `None` if
+    /// the glyph is too big for a page, or there is no room without evicting a page used in
+    /// this generation.
+    pub fn glyph<F: FontBackend + ?Sized>(&mut self,
+                                          font_id: u64,
+                                          instance_id: u64,
+                                          font: &F,
+                                          glyph: CGGlyph,
+                                          position: CGPoint)
+                                          -> Option<GlyphPlacement> {
+        let (pixel_x, step_x) = quantize(position.x, self.subpixel_x);
+        let (pixel_y, step_y) = quantize(position.y, self.subpixel_y);
+        let key = GlyphKey {
+            font_id,
+            instance_id,
+            pt_size: font.pt_size().to_bits(),
+            glyph,
+            subpixel_x: step_x,
+            subpixel_y: step_y,
+        };
+
+        let cached = match self.entries.get(&key) {
+            Some(&cached) => cached,
+            None => {
+                let offset = CGPoint::new(step_x as CGFloat / self.subpixel_x as CGFloat,
+                                          -(step_y as CGFloat / self.subpixel_y as CGFloat));
+                let cached = self.insert(font, glyph, offset)?;
+                self.entries.insert(key, cached);
+                cached
+            }
+        };
+
+        if let Some(page) = cached.page {
+            self.pages[page].last_used = self.generation;
+        }
+        Some(GlyphPlacement {
+            page: cached.page,
+            rect: cached.rect,
+            x: pixel_x + cached.left,
+            y: pixel_y - cached.top,
+        })
+    }
+
+    /// Rasterizes and packs a glyph, returning `None` if there's no room.
+    fn insert<F: FontBackend + ?Sized>(&mut self, font: &F, glyph: CGGlyph, offset: CGPoint)
+                                       -> Option<CachedGlyph> {
+        let empty = CachedGlyph {
+            page: None,
+            rect: AtlasRect {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+            },
+            left: 0,
+            top: 0,
+        };
+        let bitmap = match font.rasterize_glyph(glyph, offset) {
+            Some(bitmap) => bitmap,
+            None => return Some(empty),
+        };
+        let (width, height) = (bitmap.width as u32, bitmap.height as u32);
+        if width == 0 || height == 0 {
+            return Some(empty);
+        }
+
+        let (page, x, y) = self.allocate(width + GLYPH_PADDING, height + GLYPH_PADDING)?;
+        let rect = AtlasRect {
+            x,
+            y,
+            width,
+            height,
+        };
+        let state = &mut self.pages[page];
+        for (row, coverage) in bitmap.coverage.chunks(bitmap.width).enumerate() {
+            let start = (y as usize + row) * self.page_width as usize + x as usize;
+            state.pixels[start..start + coverage.len()].copy_from_slice(coverage);
+        }
+        state.dirty = Some(match state.dirty {
+            Some(dirty) => dirty.union(&rect),
+            None => rect,
+        });
+        Some(CachedGlyph {
+            page: Some(page),
+            rect,
+            left: bitmap.left,
+            top: bitmap.top,
+        })
+    }
+
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(usize, u32, u32)> {
+        if width > self.page_width || height > self.page_height {
+            return None;
+        }
+        for (index, page) in self.pages.iter_mut().enumerate() {
+            if let Some((x, y)) = page.skyline.allocate(width, height) {
+                return Some((index, x, y));
+            }
+        }
+
+        let index = if self.pages.len() < self.max_pages {
+            self.pages.push(Page {
+                pixels: vec![0; self.page_width as usize * self.page_height as usize],
+                skyline: Skyline::new(self.page_width, self.page_height),
+                dirty: None,
+                last_used: self.generation,
+            });
+            self.pages.len() - 1
+        } else {
+            let (index, page) = self.pages
+                                    .iter()
+                                    .enumerate()
+                                    .min_by_key(|&(_, page)| page.last_used)?;
+            if page.last_used == self.generation {
+                return None;
+            }
+            self.entries.retain(|_, glyph| glyph.page != Some(index));
+            self.reset_page(index);
+            index
+        };
+        let (x, y) = self.pages[index].skyline.allocate(width, height)?;
+        Some((index, x, y))
+    }
+
+    fn reset_page(&mut self, index: usize) {
+        let (width, height) = (self.page_width, self.page_height);
+        let page = &mut self.pages[index];
+        page.pixels.iter_mut().for_each(|pixel| *pixel = 0);
+        page.skyline = Skyline::new(width, height);
+        page.dirty = Some(AtlasRect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        });
+    }
+}
+
+/// Splits a coordinate into whole pixels and the nearest of `steps` subpixel positions,
+/// rounding up into the next pixel when that's nearest.
+fn quantize(value: CGFloat, steps: u8) -> (i32, u8) {
+    let scaled = (value * steps as CGFloat).round() as i64;
+    let steps = steps as i64;
+    (scaled.div_euclid(steps) as i32, scaled.rem_euclid(steps) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use base::CGPoint;
+    use font_backend::FontBackend;
+    use test_font::{self, A, SPACE, T};
+    use super::{AtlasRect, DirtyRect, GlyphCache};
+
+    #[test]
+    fn packs_glyph_coverage_into_a_page() {
+        let font = test_font::dejavu_sans(16.0);
+        let mut cache = GlyphCache::new(64, 64, 1);
+
+        let a = cache.glyph(0, 0, &font, A, CGPoint::new(10.0, 20.0)).unwrap();
+        let t = cache.glyph(0, 0, &font, T, CGPoint::new(30.0, 20.0)).unwrap();
+        assert_eq!((a.page, t.page), (Some(0), Some(0)));
+        assert!(a.rect.x + a.rect.width < t.rect.x || t.rect.x + t.rect.width < a.rect.x ||
+                a.rect.y + a.rect.height < t.rect.y || t.rect.y + t.rect.height < a.rect.y);
+
+        let bitmap = font.rasterize_glyph(A, CGPoint::new(0.0, 0.0)).unwrap();
+        assert_eq!((a.rect.width as usize, a.rect.height as usize), (bitmap.width, bitmap.height));
+        assert_eq!((a.x, a.y), (10 + bitmap.left, 20 - bitmap.top));
+        for (row, coverage) in bitmap.coverage.chunks(bitmap.width).enumerate() {
+            let start = (a.rect.y as usize + row) * 64 + a.rect.x as usize;
+            assert_eq!(&cache.page_pixels(0).unwrap()[start..start + bitmap.width], coverage);
+        }
+
+        let dirty = cache.take_dirty_rects();
+        assert_eq!(dirty, vec![DirtyRect {
+            page: 0,
+            rect: a.rect.union(&t.rect),
+        }]);
+        assert!(cache.take_dirty_rects().is_empty());
+    }
+
+    #[test]
+    fn evicts_pages_not_used_in_this_generation() {
+        let font = test_font::dejavu_sans(32.0);
+        let mut cache = GlyphCache::new(32, 32, 1);
+        let origin = CGPoint::new(0.0, 0.0);
+
+        let first = cache.glyph(0, 0, &font, A, origin).unwrap();
+        assert_eq!(first.page, Some(0));
+        assert_eq!(cache.glyph(1, 0, &font, A, origin), None);
+
+        cache.take_dirty_rects();
+        let second = cache.glyph(1, 0, &font, A, origin).unwrap();
+        assert_eq!(second.page, Some(0));
+        assert_eq!(cache.page_count(), 1);
+        assert_eq!(cache.take_dirty_rects(), vec![DirtyRect {
+            page: 0,
+            rect: AtlasRect {
+                x: 0,
+                y: 0,
+                width: 32,
+                height: 32,
+            },
+        }]);
+        assert_eq!(cache.glyph(1, 0, &font, A, origin), Some(second));
+        assert_eq!(cache.glyph(0, 0, &font, A, origin), None);
+    }
+
+    #[test]
+    fn places_glyphs_without_pixels_on_no_page() {
+        let font = test_font::dejavu_sans(16.0);
+        let mut cache = GlyphCache::new(64, 64, 1);
+        let position = CGPoint::new(10.0, 20.0);
+
+        let space = cache.glyph(0, 0, &font, SPACE, position).unwrap();
+        assert_eq!(space.page, None);
+        assert_eq!((space.rect.width, space.rect.height), (0, 0));
+        assert_eq!((space.x, space.y), (10, 20));
+        assert_eq!(cache.page_count(), 0);
+        assert_eq!(cache.page_pixels(0), None);
+
+        assert_eq!(cache.glyph(0, 0, &font, A, position).unwrap().page, Some(0));
+        assert_eq!(cache.glyph(0, 0, &font, SPACE, position), Some(space));
+    }
+
+    #[test]
+    fn keys_glyphs_by_font_and_instance_id() {
+        let font = test_font::dejavu_sans(16.0);
+        let mut cache = GlyphCache::new(64, 64, 1);
+        let origin = CGPoint::new(0.0, 0.0);
+
+        let first = cache.glyph(0, 0, &font, A, origin).unwrap();
+        let second = cache.glyph(1, 0, &font, A, origin).unwrap();
+        assert_ne!(first.rect, second.rect);
+        assert_eq!(cache.glyph(0, 0, &font, A, origin), Some(first));
+
+        let instance = cache.glyph(0, 1, &font, A, origin).unwrap();
+        assert!(instance.rect != first.rect && instance.rect != second.rect);
+        assert_eq!(cache.glyph(0, 1, &font, A, origin), Some(instance));
+    }
+}
@@ -14,10 +14,35 @@
 
 extern crate libc;
 
+#[cfg(target_os = "macos")]
 extern crate core_foundation;
+#[cfg(target_os = "macos")]
 extern crate core_graphics;
 
+pub mod base;
+pub mod distance_field;
+pub mod error;
+pub mod font_backend;
+pub mod glyph_cache;
+pub mod memory_font;
+pub mod outline;
+pub mod rasterizer;
+pub mod shaping;
+pub mod tables;
+pub mod tag;
+
+mod encoding;
+mod language;
+mod parser;
+
+#[cfg(test)]
+mod test_font;
+
+#[cfg(target_os = "macos")]
 pub mod font;
+#[cfg(target_os = "macos")]
 pub mod font_collection;
+#[cfg(target_os = "macos")]
 pub mod font_descriptor;
+#[cfg(target_os = "macos")]
 pub mod font_manager;
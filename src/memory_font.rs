@@ -0,0 +1,1002 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use base::{CGFloat, CGGlyph, CGPoint, CGRect, CGSize, CTFontOrientation, UniChar};
+use base::{kCTFontHorizontalOrientation, kCTFontVerticalOrientation};
+use error::{Error, Result};
+use font_backend::{self, CharacterGlyphs, FontBackend, VariationAxis};
+use outline::{self, OutlineSink, PathElement};
+use parser::{self, Reader};
+use tables::avar::Avar;
+use tables::cff::Cff;
+use tables::cff2::Cff2;
+use tables::cmap::{Cmap, Subtable};
+use tables::fvar::Fvar;
+use tables::gdef::{CaretValue, Gdef};
+use tables::glyf::Glyf;
+use tables::gvar::Gvar;
+use tables::head::Head;
+use tables::hhea::Hhea;
+use tables::hmtx::Hmtx;
+use tables::hvar::Hvar;
+use tables::loca::Loca;
+use tables::maxp::Maxp;
+use tables::mvar::{self, Mvar};
+use tables::name::{LocalizedName, Name, NameId};
+use tables::os2::Os2;
+use tables::vhea::Vhea;
+use tables::vmtx::Vmtx;
+use tables::vvar::Vvar;
+use tag::*;
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+const SFNT_VERSION_TRUETYPE: u32 = 0x00010000;
+const SFNT_VERSION_APPLE_TRUETYPE: u32 = 0x74727565; // 'true'
+const SFNT_VERSION_OPENTYPE_CFF: u32 = 0x4F54544F; // 'OTTO'
+const TTC_TAG: u32 = 0x74746366; // 'ttcf'
+
+#[derive(Clone, Copy, Debug)]
+struct TableRecord {
+    tag: Tag,
+    start: usize,
+    end: usize,
+}
+
+/// The tables that per-glyph metrics and outlines come from, parsed once for a run of glyphs.
+struct GlyphTables<'a> {
+    hmtx: Option<Hmtx<'a>>,
+    vmtx: Option<Vmtx<'a>>,
+    hvar: Option<Hvar<'a>>,
+    vvar: Option<Vvar<'a>>,
+    glyf: Option<Glyf<'a>>,
+    /// Only read for fonts without `glyf` outlines.
+    cff: Option<Cff<'a>>,
+    cff2: Option<Cff2<'a>>,
+}
+
+/// A font read from TrueType or OpenType data held in memory, answering the same questions
+/// as `CTFont` without calling into Core Text.
+///
+/// The method names and units follow `CTFont`, so code can move between the two by changing
+/// a type. Clones share the font data.
+#[derive(Clone)]
+pub struct MemoryFont {
+    data: Arc<Vec<u8>>,
+    tables: Arc<Vec<TableRecord>>,
+    pt_size: CGFloat,
+    /// Read from `head` and `maxp` up front, since almost every answer needs them.
+    units_per_em: u16,
+    num_glyphs: u16,
+    /// The position on each `fvar` axis in user units, or empty for the default instance.
+    variation: Vec<f64>,
+    /// `variation` normalized through `fvar` and `avar`, as the variation tables use it.
+    coordinates: Vec<f64>,
+}
+
+/// The bytes of a single table of a `MemoryFont`. Cloning it doesn't copy the font data.
+#[derive(Clone)]
+pub struct FontTable {
+    tag: Tag,
+    data: Arc<Vec<u8>>,
+    start: usize,
+    end: usize,
+}
+
+impl Deref for FontTable {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data[self.start..self.end]
+    }
+}
+
+/// Shows where the table is rather than its bytes, which share the font's whole buffer.
+impl fmt::Debug for FontTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FontTable")
+         .field("tag", &self.tag)
+         .field("offset", &self.start)
+         .field("length", &(self.end - self.start))
+         .finish()
+    }
+}
+
+impl fmt::Debug for MemoryFont {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MemoryFont")
+         .field("postscript_name", &self.name(NameId::PostScriptName, None))
+         .field("pt_size", &self.pt_size)
+         .finish()
+    }
+}
+
+/// Creates a font from the contents of a TrueType or OpenType file. For a font collection
+/// this is its first font.
+pub fn new_from_bytes(data: Vec<u8>, pt_size: f64) -> Result<MemoryFont> {
+    new_from_collection_bytes(data, 0, pt_size)
+}
+
+/// Creates a font from the `index`th font of a TrueType or OpenType collection (`.ttc`). For a
+/// file holding a single font, only index 0 is valid.
+pub fn new_from_collection_bytes(data: Vec<u8>, index: u32, pt_size: f64)
+                                 -> Result<MemoryFont> {
+    let directory_offset = if parser::read_u32(&data, 0) == Some(TTC_TAG) {
+        let font_count = parser::read_u32(&data, 8).ok_or(Error::UnsupportedFormat)?;
+        if index >= font_count {
+            let description = format!("index {} of a {}-font collection", index, font_count);
+            return Err(Error::FontNotFound(description));
+        }
+        parser::read_u32(&data, 12 + index as usize * 4).ok_or(Error::UnsupportedFormat)? as usize
+    } else if index == 0 {
+        0
+    } else {
+        return Err(Error::FontNotFound(format!("index {} of a single-font file", index)));
+    };
+
+    let tables = read_table_directory(&data, directory_offset)?;
+    let mut font = MemoryFont {
+        data: Arc::new(data),
+        tables: Arc::new(tables),
+        pt_size: pt_size as CGFloat,
+        units_per_em: 0,
+        num_glyphs: 0,
+        variation: vec![],
+        coordinates: vec![],
+    };
+
+    // Everything else is optional, but without these there is no way to scale anything.
+    let head = font.table(kCTFontTableHead).ok_or(Error::MissingTable(kCTFontTableHead))?;
+    let units_per_em = Head::parse(head)?.units_per_em();
+    if units_per_em == 0 {
+        return Err(Error::MalformedTable(kCTFontTableHead));
+    }
+    Hhea::parse(font.table(kCTFontTableHhea).ok_or(Error::MissingTable(kCTFontTableHhea))?)?;
+    let num_glyphs = font.table(kCTFontTableMaxp)
+                         .and_then(|maxp| Maxp::parse(maxp).ok())
+                         .map_or(0, |maxp| maxp.num_glyphs());
+    font.units_per_em = units_per_em;
+    font.num_glyphs = num_glyphs;
+    Ok(font)
+}
+
+fn read_table_directory(data: &[u8], offset: usize) -> Result<Vec<TableRecord>> {
+    match parser::read_u32(data, offset) {
+        Some(SFNT_VERSION_TRUETYPE) |
+        Some(SFNT_VERSION_APPLE_TRUETYPE) |
+        Some(SFNT_VERSION_OPENTYPE_CFF) => {}
+        _ => return Err(Error::UnsupportedFormat),
+    }
+    read_table_records(data, offset).ok_or(Error::UnsupportedFormat)
+}
+
+fn read_table_records(data: &[u8], offset: usize) -> Option<Vec<TableRecord>> {
+    let mut reader = Reader::at(data, offset + 4);
+    let num_tables = reader.read_u16()?;
+    reader.skip(6)?; // searchRange, entrySelector, rangeShift
+
+    let mut tables = Vec::with_capacity(num_tables as usize);
+    for _ in 0..num_tables {
+        let tag = Tag(reader.read_u32()?);
+        reader.skip(4)?; // checksum
+        let start = reader.read_u32()? as usize;
+        let end = start.checked_add(reader.read_u32()? as usize)?;
+        if end > data.len() {
+            return None;
+        }
+        tables.push(TableRecord {
+            tag,
+            start,
+            end,
+        });
+    }
+    Some(tables)
+}
+
+impl MemoryFont {
+    fn table(&self, tag: Tag) -> Option<&[u8]> {
+        self.tables.iter()
+            .find(|record| record.tag == tag)
+            .map(|record| &self.data[record.start..record.end])
+    }
+
+    fn head(&self) -> Option<Head<'_>> {
+        self.table(kCTFontTableHead).and_then(|head| Head::parse(head).ok())
+    }
+
+    fn hhea(&self) -> Option<Hhea<'_>> {
+        self.table(kCTFontTableHhea).and_then(|hhea| Hhea::parse(hhea).ok())
+    }
+
+    fn os2(&self) -> Option<Os2<'_>> {
+        self.table(kCTFontTableOS2).and_then(|os2| Os2::parse(os2).ok())
+    }
+
+    fn hmtx(&self) -> Option<Hmtx<'_>> {
+        let long_metric_count = self.hhea()?.number_of_h_metrics();
+        Hmtx::parse(self.table(kCTFontTableHmtx)?, long_metric_count, self.num_glyphs).ok()
+    }
+
+    fn vmtx(&self) -> Option<Vmtx<'_>> {
+        let vhea = Vhea::parse(self.table(kCTFontTableVhea)?).ok()?;
+        let long_metric_count = vhea.number_of_v_metrics();
+        Vmtx::parse(self.table(kCTFontTableVmtx)?, long_metric_count, self.num_glyphs).ok()
+    }
+
+    /// Converts a value in font units to points at this font's size.
+    fn scale(&self, value: f64) -> CGFloat {
+        (value * self.pt_size / self.units_per_em as f64) as CGFloat
+    }
+
+    fn name_table(&self) -> Option<Name<'_>> {
+        self.table(kCTFontTableName).and_then(|name| Name::parse(name).ok())
+    }
+
+    /// Returns the `cmap` subtable that characters are looked up in.
+    fn cmap_subtable(&self) -> Option<Subtable<'_>> {
+        self.table(kCTFontTableCmap)
+            .and_then(|cmap| Cmap::parse(cmap).ok())
+            .and_then(|cmap| cmap.unicode_subtable())
+    }
+
+    fn glyf(&self) -> Option<Glyf<'_>> {
+        let long_offsets = self.head()?.has_long_loca_offsets();
+        let loca = Loca::parse(self.table(kCTFontTableLoca)?, self.num_glyphs, long_offsets).ok()?;
+        let glyf = Glyf::parse(self.table(kCTFontTableGlyf)?, loca).ok()?;
+        match self.table(kCTFontTableGvar).and_then(|gvar| Gvar::parse(gvar).ok()) {
+            Some(gvar) if !self.coordinates.is_empty() => {
+                Some(glyf.with_variations(gvar, &self.coordinates))
+            }
+            _ => Some(glyf),
+        }
+    }
+
+    fn fvar(&self) -> Option<Fvar<'_>> {
+        self.table(kCTFontTableFvar).and_then(|fvar| Fvar::parse(fvar).ok())
+    }
+
+    fn gdef(&self) -> Option<Gdef<'_>> {
+        self.table(kCTFontTableGDEF).and_then(|gdef| Gdef::parse(gdef).ok())
+    }
+
+    fn cff(&self) -> Option<Cff<'_>> {
+        self.table(kCTFontTableCFF).and_then(|cff| Cff::parse(cff).ok())
+    }
+
+    fn cff2(&self) -> Option<Cff2<'_>> {
+        self.table(kCTFontTableCFF2).and_then(|cff2| Cff2::parse(cff2).ok())
+    }
+
+    fn glyph_tables(&self) -> GlyphTables<'_> {
+        let glyf = self.glyf();
+        let has_glyf = glyf.is_some();
+        GlyphTables {
+            hmtx: self.hmtx(),
+            vmtx: self.vmtx(),
+            hvar: self.hvar(),
+            vvar: self.vvar(),
+            glyf,
+            cff: if has_glyf { None } else { self.cff() },
+            cff2: if has_glyf { None } else { self.cff2() },
+        }
+    }
+
+    /// Reads the top of a TrueType glyph's bounding box from its `glyf` header.
+    fn glyph_y_max(&self, tables: &GlyphTables, glyph: CGGlyph) -> Option<i16> {
+        tables.glyf?.bounding_box(glyph).map(|bounds| bounds.y_max)
+    }
+
+    /// Returns the glyph's ink bounds in points, relative to its horizontal origin, or `None` if
+    /// it has no outline.
+    ///
+    /// `glyf` headers only describe the default instance, so varied glyphs are measured.
+    fn glyph_bounds(&self, tables: &GlyphTables, glyph: CGGlyph) -> Option<CGRect> {
+        if let Some(glyf) = tables.glyf.filter(|_| self.coordinates.is_empty()) {
+            let bounds = glyf.bounding_box(glyph)?;
+            let (x_min, y_min) = (self.scale(bounds.x_min as f64), self.scale(bounds.y_min as f64));
+            return Some(CGRect::new(&CGPoint::new(x_min, y_min),
+                                    &CGSize::new(self.scale(bounds.x_max as f64) - x_min,
+                                                 self.scale(bounds.y_max as f64) - y_min)));
+        }
+        let mut elements: Vec<PathElement> = vec![];
+        if !self.draw_outline(tables, glyph, &mut elements) {
+            return None;
+        }
+        outline::bounding_box(&elements)
+    }
+
+    fn hvar(&self) -> Option<Hvar<'_>> {
+        self.table(kCTFontTableHVAR).and_then(|hvar| Hvar::parse(hvar).ok())
+    }
+
+    fn vvar(&self) -> Option<Vvar<'_>> {
+        self.table(kCTFontTableVVAR).and_then(|vvar| Vvar::parse(vvar).ok())
+    }
+
+    fn mvar(&self) -> Option<Mvar<'_>> {
+        self.table(kCTFontTableMVAR).and_then(|mvar| Mvar::parse(mvar).ok())
+    }
+
+    /// Returns the delta that `delta` finds at the font's variation, or 0 at the default
+    /// instance or if there is none.
+    fn variation_delta<F>(&self, delta: F) -> f64
+        where F: FnOnce(&[f64]) -> Option<f64>
+    {
+        if self.coordinates.is_empty() {
+            return 0.0;
+        }
+        delta(&self.coordinates).unwrap_or(0.0)
+    }
+
+    /// Returns the `MVAR` delta for a global metric at the font's variation, in font units.
+    fn metric_delta(&self, tag: Tag) -> f64 {
+        self.variation_delta(|coordinates| self.mvar()?.delta(tag, coordinates))
+    }
+
+    fn ascender(&self) -> f64 {
+        self.hhea().map_or(0, |hhea| hhea.ascender()) as f64 +
+        self.metric_delta(mvar::HORIZONTAL_ASCENDER)
+    }
+
+    fn descender(&self) -> f64 {
+        self.hhea().map_or(0, |hhea| hhea.descender()) as f64 +
+        self.metric_delta(mvar::HORIZONTAL_DESCENDER)
+    }
+
+    fn advance_in_font_units(&self,
+                             tables: &GlyphTables,
+                             orientation: CTFontOrientation,
+                             glyph: CGGlyph)
+                             -> f64 {
+        if orientation != kCTFontVerticalOrientation {
+            let advance = tables.hmtx.and_then(|hmtx| hmtx.advance_width(glyph)).unwrap_or(0);
+            return advance as f64 + self.variation_delta(|coordinates| match tables.hvar {
+                Some(ref hvar) => hvar.advance_delta(glyph, coordinates),
+                None => tables.glyf?.advance_deltas(glyph).map(|(horizontal, _)| horizontal),
+            });
+        }
+        match tables.vmtx {
+            Some(vmtx) => {
+                vmtx.advance_height(glyph).unwrap_or(0) as f64 +
+                self.variation_delta(|coordinates| match tables.vvar {
+                    Some(ref vvar) => vvar.advance_delta(glyph, coordinates),
+                    None => tables.glyf?.advance_deltas(glyph).map(|(_, vertical)| vertical),
+                })
+            }
+            // Fonts without vertical metrics advance by their line height, as in Core Text.
+            None if glyph < self.num_glyphs => self.ascender() - self.descender(),
+            None => 0.0,
+        }
+    }
+
+    fn side_bearing_in_font_units(&self,
+                                  tables: &GlyphTables,
+                                  orientation: CTFontOrientation,
+                                  glyph: CGGlyph)
+                                  -> f64 {
+        if orientation != kCTFontVerticalOrientation {
+            let side_bearing = tables.hmtx.and_then(|hmtx| hmtx.left_side_bearing(glyph));
+            return side_bearing.unwrap_or(0) as f64 + self.variation_delta(|coordinates| {
+                tables.hvar.as_ref()?.left_side_bearing_delta(glyph, coordinates)
+            });
+        }
+        match tables.vmtx {
+            Some(vmtx) => {
+                vmtx.top_side_bearing(glyph).unwrap_or(0) as f64 +
+                self.variation_delta(|coordinates| {
+                    tables.vvar.as_ref()?.top_side_bearing_delta(glyph, coordinates)
+                })
+            }
+            // Without vertical metrics the line's top is the ascender.
+            None if glyph < self.num_glyphs => {
+                self.ascender() - self.glyph_y_max(tables, glyph).unwrap_or(0) as f64
+            }
+            None => 0.0,
+        }
+    }
+}
+
+impl MemoryFont {
+    // Creation methods
+    pub fn clone_with_font_size(&self, size: f64) -> MemoryFont {
+        MemoryFont {
+            pt_size: size as CGFloat,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of the font at a position on its variation axes, as
+    /// `CTFont::with_variations` does.
+    ///
+    /// Values are in each axis's user units, and are clamped to its range. Axes without a
+    /// value stay at their defaults, and tags the font has no axis for are ignored.
+    pub fn with_variations(&self, variations: &[(Tag, f32)]) -> MemoryFont {
+        let axes = self.fvar().map_or(vec![], |fvar| fvar.axes());
+        let variation: Vec<f64> = axes.iter().map(|axis| {
+            let value = variations.iter()
+                                  .rev()
+                                  .find(|&&(tag, _)| tag == axis.tag)
+                                  .map_or(axis.default_value, |&(_, value)| value as f64);
+            value.clamp(axis.min_value, axis.max_value)
+        }).collect();
+
+        let avar = self.table(kCTFontTableAvar).and_then(|avar| Avar::parse(avar).ok());
+        let coordinates = axes.iter().zip(&variation).enumerate().map(|(index, (axis, &value))| {
+            let coordinate = axis.normalize(value);
+            avar.map_or(coordinate, |avar| avar.map(index as u16, coordinate))
+        }).collect();
+
+        MemoryFont {
+            variation,
+            coordinates,
+            ..self.clone()
+        }
+    }
+
+    /// The font's normalized variation coordinates, one per `fvar` axis from -1 to 1, or empty
+    /// for the default instance. These are what `ItemVariationStore` and the outline tables
+    /// take.
+    pub fn normalized_coordinates(&self) -> &[f64] {
+        &self.coordinates
+    }
+
+    pub fn variation_axes(&self) -> Vec<VariationAxis> {
+        let fvar = match self.fvar() {
+            Some(fvar) => fvar,
+            None => return vec![],
+        };
+        fvar.axes().into_iter().map(|axis| {
+            VariationAxis {
+                tag: axis.tag,
+                name: self.name(NameId::from(axis.name_id), None).unwrap_or_default(),
+                min_value: axis.min_value,
+                default_value: axis.default_value,
+                max_value: axis.max_value,
+                hidden: axis.is_hidden(),
+            }
+        }).collect()
+    }
+
+    pub fn variation(&self) -> Vec<(Tag, f64)> {
+        let axes = self.fvar().map_or(vec![], |fvar| fvar.axes());
+        axes.iter().enumerate().map(|(index, axis)| {
+            (axis.tag, self.variation.get(index).cloned().unwrap_or(axis.default_value))
+        }).collect()
+    }
+
+    // Names
+    pub fn family_name(&self) -> String {
+        self.try_family_name().expect("Fonts should always have a family name.")
+    }
+
+    /// Returns the typographic family name, or the legacy family name for fonts without one,
+    /// as Core Text does.
+    pub fn try_family_name(&self) -> Result<String> {
+        self.name(NameId::TypographicFamily, None)
+            .or_else(|| self.name(NameId::Family, None))
+            .ok_or(Error::MissingAttribute("family name"))
+    }
+
+    pub fn face_name(&self) -> String {
+        self.try_face_name().expect("Fonts should always have a face name.")
+    }
+
+    /// Returns the typographic style name, or the legacy one for fonts without one. A variable
+    /// font with a variation set is named for its position, as `names_for_variation` names it.
+    pub fn try_face_name(&self) -> Result<String> {
+        if !self.coordinates.is_empty() {
+            return self.names_for_variation(&self.variation()).map(|names| names.style_name);
+        }
+        self.name(NameId::TypographicSubfamily, None)
+            .or_else(|| self.name(NameId::Subfamily, None))
+            .ok_or(Error::MissingAttribute("face name"))
+    }
+
+    pub fn unique_name(&self) -> String {
+        self.try_unique_name().expect("Fonts should always have a unique name.")
+    }
+
+    pub fn try_unique_name(&self) -> Result<String> {
+        self.name(NameId::UniqueId, None).ok_or(Error::MissingAttribute("unique name"))
+    }
+
+    pub fn postscript_name(&self) -> String {
+        self.try_postscript_name().expect("Fonts should always have a PostScript name.")
+    }
+
+    pub fn try_postscript_name(&self) -> Result<String> {
+        self.name(NameId::PostScriptName, None).ok_or(Error::MissingAttribute("PostScript name"))
+    }
+
+    pub fn name(&self, name_id: NameId, language: Option<&str>) -> Option<String> {
+        self.name_table()?.get(name_id, language)
+    }
+
+    pub fn localized_names(&self) -> Vec<LocalizedName> {
+        self.name_table().map_or(vec![], |name| name.localized_names())
+    }
+
+    // Font metrics
+    pub fn pt_size(&self) -> CGFloat {
+        self.pt_size
+    }
+
+    /// Returns the ascent, including any `MVAR` delta at the font's variation, as for all of
+    /// the font's global metrics.
+    pub fn ascent(&self) -> CGFloat {
+        self.scale(self.ascender())
+    }
+
+    pub fn descent(&self) -> CGFloat {
+        self.scale(-self.descender())
+    }
+
+    pub fn leading(&self) -> CGFloat {
+        self.scale(self.hhea().map_or(0, |hhea| hhea.line_gap()) as f64 +
+                   self.metric_delta(mvar::HORIZONTAL_LINE_GAP))
+    }
+
+    pub fn units_per_em(&self) -> u32 {
+        self.units_per_em as u32
+    }
+
+    pub fn x_height(&self) -> CGFloat {
+        if let Some(x_height) = self.os2().and_then(|os2| os2.x_height()) {
+            return self.scale(x_height as f64 + self.metric_delta(mvar::X_HEIGHT));
+        }
+        // Like Core Text, fall back to measuring the 'x' glyph for fonts predating sxHeight.
+        self.cmap_subtable()
+            .and_then(|subtable| subtable.glyph_index('x' as u32))
+            .and_then(|glyph| self.glyph_bounds(&self.glyph_tables(), glyph))
+            .map_or(0.0, |bounds| bounds.origin.y + bounds.size.height)
+    }
+
+    pub fn underline_position(&self) -> CGFloat {
+        let position = self.table(kCTFontTablePost).and_then(|post| parser::read_i16(post, 8));
+        self.scale(position.unwrap_or(0) as f64 + self.metric_delta(mvar::UNDERLINE_OFFSET))
+    }
+
+    pub fn underline_thickness(&self) -> CGFloat {
+        let thickness = self.table(kCTFontTablePost).and_then(|post| parser::read_i16(post, 10));
+        self.scale(thickness.unwrap_or(0) as f64 + self.metric_delta(mvar::UNDERLINE_SIZE))
+    }
+
+    pub fn bounding_box(&self) -> CGRect {
+        let (x_min, y_min, x_max, y_max) = match self.head() {
+            Some(head) => {
+                (head.x_min() as f64, head.y_min() as f64, head.x_max() as f64, head.y_max() as f64)
+            }
+            None => (0.0, 0.0, 0.0, 0.0),
+        };
+        CGRect::new(&CGPoint::new(self.scale(x_min), self.scale(y_min)),
+                    &CGSize::new(self.scale(x_max - x_min), self.scale(y_max - y_min)))
+    }
+
+    // Glyphs
+    /// Maps UTF-16 code units to glyphs, reporting which characters the font can't map. The
+    /// glyph for a surrogate pair is at the high surrogate and the low surrogate's slot gets 0.
+    pub fn glyphs_for_characters(&self, characters: &[UniChar]) -> CharacterGlyphs {
+        let subtable = self.cmap_subtable();
+        let mut glyphs = vec![0; characters.len()];
+        let mut index = 0;
+        while index < characters.len() {
+            let mut code_point = characters[index] as u32;
+            let mut length = 1;
+            if font_backend::is_surrogate_pair(&characters[index..]) {
+                let low = characters[index + 1] as u32;
+                code_point = 0x10000 + ((code_point - 0xD800) << 10) + (low - 0xDC00);
+                length = 2;
+            }
+            // Lone surrogates don't encode a character, so never have a glyph.
+            glyphs[index] = match code_point {
+                0xD800..=0xDFFF => 0,
+                _ => subtable.and_then(|subtable| subtable.glyph_index(code_point)).unwrap_or(0),
+            };
+            index += length;
+        }
+        CharacterGlyphs::new(characters, glyphs)
+    }
+
+    /// Returns the advance of each glyph for `orientation`.
+    pub fn advances_for_glyphs(&self, orientation: CTFontOrientation, glyphs: &[CGGlyph])
+                               -> Vec<CGSize> {
+        let tables = self.glyph_tables();
+        glyphs.iter().map(|&glyph| {
+            let value = self.scale(self.advance_in_font_units(&tables, orientation, glyph));
+            if orientation == kCTFontVerticalOrientation {
+                CGSize::new(0.0, value)
+            } else {
+                CGSize::new(value, 0.0)
+            }
+        }).collect()
+    }
+
+    /// Returns each glyph's left side bearing, or its top side bearing for
+    /// `kCTFontVerticalOrientation`. A positive top side bearing is below the vertical origin.
+    pub fn side_bearings_for_glyphs(&self, orientation: CTFontOrientation, glyphs: &[CGGlyph])
+                                    -> Vec<CGFloat> {
+        let tables = self.glyph_tables();
+        glyphs.iter().map(|&glyph| {
+            self.scale(self.side_bearing_in_font_units(&tables, orientation, glyph))
+        }).collect()
+    }
+
+    /// Returns the ink bounds of each glyph for `orientation`, in points, along with their
+    /// union.
+    ///
+    /// TrueType glyphs use the bounding box in their `glyf` header, and PostScript glyphs the
+    /// tight bounds of their outline. For `kCTFontVerticalOrientation` the rects are relative to
+    /// the vertical origin, which is centered on the glyph's advance width and its top side
+    /// bearing above its top. Glyphs with no outline get an empty rect at the origin, which the
+    /// union leaves out.
+    pub fn bounding_rects_for_glyphs(&self, orientation: CTFontOrientation, glyphs: &[CGGlyph])
+                                     -> (CGRect, Vec<CGRect>) {
+        let empty = CGRect::new(&CGPoint::new(0.0, 0.0), &CGSize::new(0.0, 0.0));
+        let tables = self.glyph_tables();
+        let mut union: Option<CGRect> = None;
+        let rects = glyphs.iter().map(|&glyph| {
+            let mut bounds = match self.glyph_bounds(&tables, glyph) {
+                Some(bounds) => bounds,
+                None => return empty,
+            };
+            if orientation == kCTFontVerticalOrientation {
+                let horizontal = kCTFontHorizontalOrientation;
+                let advance = self.advance_in_font_units(&tables, horizontal, glyph);
+                let top_side_bearing = self.side_bearing_in_font_units(&tables, orientation, glyph);
+                bounds.origin.x -= self.scale(advance) / 2.0;
+                bounds.origin.y -= bounds.origin.y + bounds.size.height +
+                                   self.scale(top_side_bearing);
+            }
+            union = Some(match union {
+                Some(union) => rect_union(&union, &bounds),
+                None => bounds,
+            });
+            bounds
+        }).collect();
+        (union.unwrap_or(empty), rects)
+    }
+
+    pub fn get_font_table<T: Into<Tag>>(&self, tag: T) -> Option<FontTable> {
+        let tag = tag.into();
+        self.tables.iter().find(|record| record.tag == tag).map(|record| {
+            FontTable {
+                tag,
+                data: self.data.clone(),
+                start: record.start,
+                end: record.end,
+            }
+        })
+    }
+
+    /// Returns the tags of every table in the font, in table directory order.
+    pub fn available_tables(&self) -> Vec<Tag> {
+        self.tables.iter().map(|record| record.tag).collect()
+    }
+
+    /// Sends the glyph's outline to `sink`, in points relative to the glyph's origin.
+    ///
+    /// Outlines come from the `glyf` table, or from `CFF ` or `CFF2` for PostScript-flavored
+    /// fonts. `glyf` outlines with `gvar` data and `CFF2` outlines are drawn at the font's
+    /// variation. Returns false, without calling `sink`, if the glyph has no outline that can be
+    /// read, or an empty one such as a space's.
+    pub fn draw_glyph_outline(&self, glyph: CGGlyph, sink: &mut dyn OutlineSink) -> bool {
+        self.draw_outline(&self.glyph_tables(), glyph, sink)
+    }
+
+    fn draw_outline(&self, tables: &GlyphTables, glyph: CGGlyph, sink: &mut dyn OutlineSink)
+                    -> bool {
+        // Glyphs are recorded first, so that empty ones such as spaces aren't sent at all.
+        let mut elements: Vec<PathElement> = vec![];
+        let drawn = {
+            let mut scaled = ScaledSink {
+                sink: &mut elements,
+                scale: self.scale(1.0),
+            };
+            if let Some(glyf) = tables.glyf {
+                glyf.outline(glyph, &mut scaled)
+            } else if let Some(ref cff) = tables.cff {
+                cff.outline(glyph, &mut scaled)
+            } else {
+                tables.cff2.as_ref().is_some_and(|cff2| {
+                    cff2.outline(glyph, &self.coordinates, &mut scaled)
+                })
+            }
+        };
+        if !drawn || elements.is_empty() {
+            return false;
+        }
+        outline::replay(&elements, sink);
+        true
+    }
+
+    /// Returns the positions of the carets inside a ligature glyph, in points along the
+    /// baseline from its origin, as `CTFont::ligature_caret_positions` does.
+    ///
+    /// They come from the `GDEF` table, at the font's variation. Carets on outline points
+    /// that can't be read are at the origin.
+    pub fn ligature_caret_positions(&self, glyph: CGGlyph) -> Vec<CGFloat> {
+        let gdef = match self.gdef() {
+            Some(gdef) => gdef,
+            None => return vec![],
+        };
+        gdef.ligature_carets(glyph).into_iter().map(|caret| {
+            let x = match caret {
+                CaretValue::Coordinate(x, variation) => {
+                    x as f64 + variation.map_or(0.0, |(outer, inner)| {
+                        self.variation_delta(|coordinates| {
+                            gdef.variation_store()?.delta(outer, inner, coordinates)
+                        })
+                    })
+                }
+                CaretValue::PointIndex(index) => {
+                    self.glyf().and_then(|glyf| glyf.point(glyph, index)).map_or(0.0, |point| {
+                        point.x
+                    })
+                }
+            };
+            self.scale(x)
+        }).collect()
+    }
+}
+
+fn rect_union(a: &CGRect, b: &CGRect) -> CGRect {
+    let x = a.origin.x.min(b.origin.x);
+    let y = a.origin.y.min(b.origin.y);
+    CGRect::new(&CGPoint::new(x, y),
+                &CGSize::new((a.origin.x + a.size.width).max(b.origin.x + b.size.width) - x,
+                             (a.origin.y + a.size.height).max(b.origin.y + b.size.height) - y))
+}
+
+/// Scales an outline from font units to points on its way to another sink.
+struct ScaledSink<'a> {
+    sink: &'a mut dyn OutlineSink,
+    scale: CGFloat,
+}
+
+impl<'a> ScaledSink<'a> {
+    fn point(&self, point: CGPoint) -> CGPoint {
+        CGPoint::new(point.x * self.scale, point.y * self.scale)
+    }
+}
+
+impl<'a> OutlineSink for ScaledSink<'a> {
+    fn move_to(&mut self, to: CGPoint) {
+        let to = self.point(to);
+        self.sink.move_to(to);
+    }
+
+    fn line_to(&mut self, to: CGPoint) {
+        let to = self.point(to);
+        self.sink.line_to(to);
+    }
+
+    fn quad_to(&mut self, control: CGPoint, to: CGPoint) {
+        let (control, to) = (self.point(control), self.point(to));
+        self.sink.quad_to(control, to);
+    }
+
+    fn cubic_to(&mut self, control1: CGPoint, control2: CGPoint, to: CGPoint) {
+        let (control1, control2, to) = (self.point(control1), self.point(control2), self.point(to));
+        self.sink.cubic_to(control1, control2, to);
+    }
+
+    fn close(&mut self) {
+        self.sink.close();
+    }
+}
+
+impl FontBackend for MemoryFont {
+    type FontTable = FontTable;
+
+    fn try_family_name(&self) -> Result<String> {
+        MemoryFont::try_family_name(self)
+    }
+
+    fn try_face_name(&self) -> Result<String> {
+        MemoryFont::try_face_name(self)
+    }
+
+    fn try_unique_name(&self) -> Result<String> {
+        MemoryFont::try_unique_name(self)
+    }
+
+    fn try_postscript_name(&self) -> Result<String> {
+        MemoryFont::try_postscript_name(self)
+    }
+
+    fn name(&self, name_id: NameId, language: Option<&str>) -> Option<String> {
+        MemoryFont::name(self, name_id, language)
+    }
+
+    fn localized_names(&self) -> Vec<LocalizedName> {
+        MemoryFont::localized_names(self)
+    }
+
+    fn pt_size(&self) -> CGFloat {
+        MemoryFont::pt_size(self)
+    }
+
+    fn ascent(&self) -> CGFloat {
+        MemoryFont::ascent(self)
+    }
+
+    fn descent(&self) -> CGFloat {
+        MemoryFont::descent(self)
+    }
+
+    fn leading(&self) -> CGFloat {
+        MemoryFont::leading(self)
+    }
+
+    fn units_per_em(&self) -> u32 {
+        MemoryFont::units_per_em(self)
+    }
+
+    fn x_height(&self) -> CGFloat {
+        MemoryFont::x_height(self)
+    }
+
+    fn underline_position(&self) -> CGFloat {
+        MemoryFont::underline_position(self)
+    }
+
+    fn underline_thickness(&self) -> CGFloat {
+        MemoryFont::underline_thickness(self)
+    }
+
+    fn bounding_box(&self) -> CGRect {
+        MemoryFont::bounding_box(self)
+    }
+
+    fn glyphs_for_characters(&self, characters: &[UniChar]) -> CharacterGlyphs {
+        MemoryFont::glyphs_for_characters(self, characters)
+    }
+
+    fn advances_for_glyphs(&self, orientation: CTFontOrientation, glyphs: &[CGGlyph])
+                           -> Vec<CGSize> {
+        MemoryFont::advances_for_glyphs(self, orientation, glyphs)
+    }
+
+    fn bounding_rects_for_glyphs(&self, orientation: CTFontOrientation, glyphs: &[CGGlyph])
+                                 -> (CGRect, Vec<CGRect>) {
+        MemoryFont::bounding_rects_for_glyphs(self, orientation, glyphs)
+    }
+
+    fn get_font_table(&self, tag: Tag) -> Option<FontTable> {
+        MemoryFont::get_font_table(self, tag)
+    }
+
+    fn available_tables(&self) -> Vec<Tag> {
+        MemoryFont::available_tables(self)
+    }
+
+    fn variation_axes(&self) -> Vec<VariationAxis> {
+        MemoryFont::variation_axes(self)
+    }
+
+    fn variation(&self) -> Vec<(Tag, f64)> {
+        MemoryFont::variation(self)
+    }
+
+    fn draw_glyph_outline(&self, glyph: CGGlyph, sink: &mut dyn OutlineSink) -> bool {
+        MemoryFont::draw_glyph_outline(self, glyph, sink)
+    }
+
+    fn ligature_caret_positions(&self, glyph: CGGlyph) -> Vec<CGFloat> {
+        MemoryFont::ligature_caret_positions(self, glyph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base::{kCTFontHorizontalOrientation, kCTFontVerticalOrientation, CGPoint};
+    use distance_field::DistanceFieldKind;
+    use font_backend::FontBackend;
+    use outline::PathElement;
+    use tag::{kCTFontTableGDEF, kCTFontTableName};
+    use test_font::{self, A, FI, GRINNING_FACE, SPACE, UNITS_PER_EM};
+
+    #[test]
+    fn maps_characters_to_glyphs() {
+        let font = test_font::dejavu_sans(12.0);
+        let characters: Vec<u16> = "A \u{4E00}".encode_utf16().collect();
+        let result = font.glyphs_for_characters(&characters);
+        assert_eq!(result.glyphs, vec![A, SPACE, 0]);
+        assert_eq!(result.missing, vec![2]);
+    }
+
+    #[test]
+    fn maps_surrogate_pairs_to_one_glyph() {
+        let font = test_font::dejavu_sans(12.0);
+        let characters: Vec<u16> = "A\u{1F600}A".encode_utf16().collect();
+        let result = font.glyphs_for_characters(&characters);
+        assert_eq!(result.glyphs, vec![A, GRINNING_FACE, 0, A]);
+        assert!(result.missing.is_empty());
+    }
+
+    #[test]
+    fn lone_surrogates_have_no_glyph() {
+        let font = test_font::dejavu_sans(12.0);
+        let result = font.glyphs_for_characters(&[0xDE00, 0x41, 0xD83D]);
+        assert_eq!(result.glyphs, vec![0, A, 0]);
+        assert_eq!(result.missing, vec![0, 2]);
+    }
+
+    #[test]
+    fn scales_advances_to_points() {
+        let font = test_font::dejavu_sans(UNITS_PER_EM);
+        let advances = font.advances_for_glyphs(kCTFontHorizontalOrientation, &[A, SPACE]);
+        let sizes: Vec<_> = advances.iter().map(|size| (size.width, size.height)).collect();
+        assert_eq!(sizes, vec![(1401.0, 0.0), (651.0, 0.0)]);
+
+        let font = test_font::dejavu_sans(UNITS_PER_EM / 2.0);
+        let advances = font.advances_for_glyphs(kCTFontHorizontalOrientation, &[A]);
+        assert_eq!((advances[0].width, advances[0].height), (700.5, 0.0));
+    }
+
+    #[test]
+    fn vertical_advances_default_to_the_line_height() {
+        let font = test_font::dejavu_sans(UNITS_PER_EM);
+        let advances = font.advances_for_glyphs(kCTFontVerticalOrientation, &[A, SPACE]);
+        assert_eq!(advances[0].width, 0.0);
+        assert_eq!(advances[0].height, advances[1].height);
+        assert!(advances[0].height > 0.0);
+    }
+
+    #[test]
+    fn spaces_have_no_outline() {
+        let font = test_font::dejavu_sans(12.0);
+        let mut elements: Vec<PathElement> = vec![];
+        assert!(!font.draw_glyph_outline(SPACE, &mut elements));
+        assert!(elements.is_empty());
+        assert!(font.rasterize_glyph(SPACE, CGPoint::new(0.0, 0.0)).is_none());
+        let kind = DistanceFieldKind::SingleChannel;
+        assert!(font.glyph_distance_field(SPACE, kind, 4.0, 2.0).is_none());
+
+        assert!(font.draw_glyph_outline(A, &mut elements));
+        assert!(font.rasterize_glyph(A, CGPoint::new(0.0, 0.0)).is_some());
+        assert!(font.glyph_distance_field(A, kind, 4.0, 2.0).is_some());
+    }
+
+    #[test]
+    fn prefers_typographic_names() {
+        let names = [(1, "Family"), (2, "Bold"), (16, "Typographic"), (17, "Semibold Condensed")];
+        let tables = [(kCTFontTableName, test_font::name_table(&names))];
+        let font = test_font::dejavu_sans_with_tables(&tables, &[], 12.0);
+        assert_eq!(font.family_name(), "Typographic");
+        assert_eq!(font.face_name(), "Semibold Condensed");
+
+        let tables = [(kCTFontTableName, test_font::name_table(&names[..2]))];
+        let font = test_font::dejavu_sans_with_tables(&tables, &[], 12.0);
+        assert_eq!(font.family_name(), "Family");
+        assert_eq!(font.face_name(), "Bold");
+    }
+
+    #[test]
+    fn measures_the_x_height_without_sx_height() {
+        // The font's OS/2 table is version 1, which predates sxHeight.
+        let font = test_font::dejavu_sans(UNITS_PER_EM);
+        assert_eq!(font.x_height(), 1120.0);
+        let font = test_font::dejavu_sans(UNITS_PER_EM / 2.0);
+        assert_eq!(font.x_height(), 560.0);
+    }
+
+    #[test]
+    fn scales_ligature_carets_to_the_point_size() {
+        let tables = [(kCTFontTableGDEF, test_font::gdef_table())];
+        let font = test_font::dejavu_sans_with_tables(&tables, &[], UNITS_PER_EM);
+        let point = font.glyf().unwrap().point(FI, 5).unwrap();
+        assert_eq!(font.ligature_caret_positions(FI), vec![600.0, point.x, 1200.0]);
+        let font = test_font::dejavu_sans_with_tables(&tables, &[], UNITS_PER_EM / 2.0);
+        assert_eq!(font.ligature_caret_positions(FI), vec![300.0, point.x / 2.0, 600.0]);
+        assert!(font.ligature_caret_positions(A).is_empty());
+    }
+}
@@ -0,0 +1,240 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Glyph outlines as sequences of path commands.
+
+use base::{CGPoint, CGRect, CGSize};
+
+/// The most line segments `flatten_quadratic` and `flatten_cubic` split a curve into.
+const MAX_CURVE_SEGMENTS: usize = 128;
+
+/// Receives a glyph outline one path command at a time.
+///
+/// Coordinates have y pointing up, as in Core Text and the font's own tables. Every contour
+/// starts with `move_to` and ends with `close`.
+pub trait OutlineSink {
+    fn move_to(&mut self, to: CGPoint);
+    fn line_to(&mut self, to: CGPoint);
+    fn quad_to(&mut self, control: CGPoint, to: CGPoint);
+    fn cubic_to(&mut self, control1: CGPoint, control2: CGPoint, to: CGPoint);
+    fn close(&mut self);
+}
+
+/// One recorded path command.
+#[derive(Clone, Copy, Debug)]
+pub enum PathElement {
+    MoveTo(CGPoint),
+    LineTo(CGPoint),
+    QuadTo(CGPoint, CGPoint),
+    CubicTo(CGPoint, CGPoint, CGPoint),
+    Close,
+}
+
+impl PathElement {
+    /// Returns the command with `transform` applied to each of its points.
+    pub fn map<F: Fn(CGPoint) -> CGPoint>(self, transform: F) -> PathElement {
+        match self {
+            PathElement::MoveTo(to) => PathElement::MoveTo(transform(to)),
+            PathElement::LineTo(to) => PathElement::LineTo(transform(to)),
+            PathElement::QuadTo(control, to) => PathElement::QuadTo(transform(control), transform(to)),
+            PathElement::CubicTo(control1, control2, to) => {
+                PathElement::CubicTo(transform(control1), transform(control2), transform(to))
+            }
+            PathElement::Close => PathElement::Close,
+        }
+    }
+}
+
+/// Records an outline so it can be inspected or replayed later.
+impl OutlineSink for Vec<PathElement> {
+    fn move_to(&mut self, to: CGPoint) {
+        self.push(PathElement::MoveTo(to));
+    }
+
+    fn line_to(&mut self, to: CGPoint) {
+        self.push(PathElement::LineTo(to));
+    }
+
+    fn quad_to(&mut self, control: CGPoint, to: CGPoint) {
+        self.push(PathElement::QuadTo(control, to));
+    }
+
+    fn cubic_to(&mut self, control1: CGPoint, control2: CGPoint, to: CGPoint) {
+        self.push(PathElement::CubicTo(control1, control2, to));
+    }
+
+    fn close(&mut self) {
+        self.push(PathElement::Close);
+    }
+}
+
+/// Sends recorded path commands to `sink`.
+pub fn replay(elements: &[PathElement], sink: &mut dyn OutlineSink) {
+    for element in elements {
+        match *element {
+            PathElement::MoveTo(to) => sink.move_to(to),
+            PathElement::LineTo(to) => sink.line_to(to),
+            PathElement::QuadTo(control, to) => sink.quad_to(control, to),
+            PathElement::CubicTo(control1, control2, to) => sink.cubic_to(control1, control2, to),
+            PathElement::Close => sink.close(),
+        }
+    }
+}
+
+/// Returns the tight bounding box of recorded path commands, including the extremes of their
+/// curves, or `None` if there are no points.
+pub fn bounding_box(elements: &[PathElement]) -> Option<CGRect> {
+    let mut bounds = Bounds::default();
+    let mut current = CGPoint::new(0.0, 0.0);
+    for element in elements {
+        let to = match *element {
+            PathElement::MoveTo(to) | PathElement::LineTo(to) => to,
+            PathElement::QuadTo(control, to) => {
+                for t in quadratic_extrema(current, control, to) {
+                    bounds.add(quadratic_point(current, control, to, t));
+                }
+                to
+            }
+            PathElement::CubicTo(control1, control2, to) => {
+                for t in cubic_extrema(current, control1, control2, to) {
+                    bounds.add(cubic_point(current, control1, control2, to, t));
+                }
+                to
+            }
+            PathElement::Close => continue,
+        };
+        bounds.add(to);
+        current = to;
+    }
+    bounds.rect()
+}
+
+#[derive(Default)]
+struct Bounds {
+    min: Option<(f64, f64)>,
+    max: (f64, f64),
+}
+
+impl Bounds {
+    fn add(&mut self, point: CGPoint) {
+        let (x, y) = (point.x, point.y);
+        match self.min {
+            Some((min_x, min_y)) => {
+                self.min = Some((min_x.min(x), min_y.min(y)));
+                self.max = (self.max.0.max(x), self.max.1.max(y));
+            }
+            None => {
+                self.min = Some((x, y));
+                self.max = (x, y);
+            }
+        }
+    }
+
+    fn rect(&self) -> Option<CGRect> {
+        let (min_x, min_y) = self.min?;
+        Some(CGRect::new(&CGPoint::new(min_x, min_y),
+                         &CGSize::new(self.max.0 - min_x, self.max.1 - min_y)))
+    }
+}
+
+/// Returns the parameters, strictly inside the curve, where a quadratic curve turns in x or y.
+fn quadratic_extrema(from: CGPoint, control: CGPoint, to: CGPoint) -> Vec<f64> {
+    let mut extrema = vec![];
+    for &(a, b, c) in &[(from.x, control.x, to.x), (from.y, control.y, to.y)] {
+        let denominator = a - 2.0 * b + c;
+        if denominator != 0.0 {
+            let t = (a - b) / denominator;
+            if t > 0.0 && t < 1.0 {
+                extrema.push(t);
+            }
+        }
+    }
+    extrema
+}
+
+/// Returns the parameters, strictly inside the curve, where a cubic curve turns in x or y.
+fn cubic_extrema(from: CGPoint, control1: CGPoint, control2: CGPoint, to: CGPoint) -> Vec<f64> {
+    let mut extrema = vec![];
+    let axes = [(from.x, control1.x, control2.x, to.x), (from.y, control1.y, control2.y, to.y)];
+    for &(p0, p1, p2, p3) in &axes {
+        // The derivative is the quadratic a t^2 + b t + c.
+        let a = 3.0 * (-p0 + 3.0 * p1 - 3.0 * p2 + p3);
+        let b = 6.0 * (p0 - 2.0 * p1 + p2);
+        let c = 3.0 * (p1 - p0);
+        let mut roots = vec![];
+        if a.abs() < 1e-12 {
+            if b != 0.0 {
+                roots.push(-c / b);
+            }
+        } else {
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant >= 0.0 {
+                let root = discriminant.sqrt();
+                roots.push((-b + root) / (2.0 * a));
+                roots.push((-b - root) / (2.0 * a));
+            }
+        }
+        extrema.extend(roots.into_iter().filter(|&t| t > 0.0 && t < 1.0));
+    }
+    extrema
+}
+
+/// Returns the point at parameter `t`, from 0 to 1, along a quadratic curve.
+pub fn quadratic_point(from: CGPoint, control: CGPoint, to: CGPoint, t: f64) -> CGPoint {
+    let u = 1.0 - t;
+    let (a, b, c) = (u * u, 2.0 * u * t, t * t);
+    CGPoint::new(a * from.x + b * control.x + c * to.x, a * from.y + b * control.y + c * to.y)
+}
+
+/// Returns the point at parameter `t`, from 0 to 1, along a cubic curve.
+pub fn cubic_point(from: CGPoint, control1: CGPoint, control2: CGPoint, to: CGPoint, t: f64)
+                   -> CGPoint {
+    let u = 1.0 - t;
+    let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+    CGPoint::new(a * from.x + b * control1.x + c * control2.x + d * to.x,
+                 a * from.y + b * control1.y + c * control2.y + d * to.y)
+}
+
+/// Approximates a quadratic curve with evenly spaced line segments that stray from it by at
+/// most `tolerance`, returning the points after `from`, ending with `to`.
+pub fn flatten_quadratic(from: CGPoint, control: CGPoint, to: CGPoint, tolerance: f64)
+                         -> Vec<CGPoint> {
+    let deviation = second_difference(from, control, to);
+    segment_parameters(0.25 * deviation, tolerance).map(|t| {
+        quadratic_point(from, control, to, t)
+    }).collect()
+}
+
+/// Approximates a cubic curve with evenly spaced line segments that stray from it by at most
+/// `tolerance`, returning the points after `from`, ending with `to`.
+pub fn flatten_cubic(from: CGPoint,
+                     control1: CGPoint,
+                     control2: CGPoint,
+                     to: CGPoint,
+                     tolerance: f64)
+                     -> Vec<CGPoint> {
+    let deviation = second_difference(from, control1, control2)
+        .max(second_difference(control1, control2, to));
+    segment_parameters(0.75 * deviation, tolerance).map(|t| {
+        cubic_point(from, control1, control2, to, t)
+    }).collect()
+}
+
+/// Returns the size of the second difference of three control points, which bounds how far a
+/// curve bends away from its chord.
+fn second_difference(a: CGPoint, b: CGPoint, c: CGPoint) -> f64 {
+    (a.x - 2.0 * b.x + c.x).hypot(a.y - 2.0 * b.y + c.y)
+}
+
+/// Returns evenly spaced curve parameters ending at 1, enough that flattening a curve whose
+/// error with one segment is at most `error` stays within `tolerance`.
+fn segment_parameters(error: f64, tolerance: f64) -> impl Iterator<Item = f64> {
+    let count = ((error / tolerance).sqrt().ceil() as usize).clamp(1, MAX_CURVE_SEGMENTS);
+    (1..count + 1).map(move |index| index as f64 / count as f64)
+}
@@ -0,0 +1,155 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Bounds-checked big-endian reading, shared by the pure-Rust table parsers.
+
+use std::cmp::Ordering;
+
+/// A cursor over big-endian font data.
+///
+/// Every read is bounds-checked and returns `None` once the data runs out, so a truncated or
+/// hostile font can never cause a panic.
+#[derive(Clone, Copy, Debug)]
+pub struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    #[inline]
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader::at(data, 0)
+    }
+
+    /// Creates a reader positioned at `offset`. The offset is only checked by the first read.
+    #[inline]
+    pub fn at(data: &'a [u8], offset: usize) -> Reader<'a> {
+        Reader {
+            data,
+            offset,
+        }
+    }
+
+    #[inline]
+    pub fn skip(&mut self, count: usize) -> Option<()> {
+        self.read_bytes(count).map(|_| ())
+    }
+
+    #[inline]
+    pub fn read_bytes(&mut self, count: usize) -> Option<&'a [u8]> {
+        let end = self.offset.checked_add(count)?;
+        let bytes = self.data.get(self.offset..end)?;
+        self.offset = end;
+        Some(bytes)
+    }
+
+    #[inline]
+    pub fn read_u8(&mut self) -> Option<u8> {
+        self.read_bytes(1).map(|b| b[0])
+    }
+
+    #[inline]
+    pub fn read_u16(&mut self) -> Option<u16> {
+        self.read_bytes(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    #[inline]
+    pub fn read_i16(&mut self) -> Option<i16> {
+        self.read_u16().map(|v| v as i16)
+    }
+
+    #[inline]
+    pub fn read_u24(&mut self) -> Option<u32> {
+        self.read_bytes(3).map(|b| u32::from_be_bytes([0, b[0], b[1], b[2]]))
+    }
+
+    #[inline]
+    pub fn read_u32(&mut self) -> Option<u32> {
+        self.read_bytes(4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    #[inline]
+    pub fn read_i32(&mut self) -> Option<i32> {
+        self.read_u32().map(|v| v as i32)
+    }
+
+    #[inline]
+    pub fn read_i64(&mut self) -> Option<i64> {
+        let high = self.read_u32()? as u64;
+        let low = self.read_u32()? as u64;
+        Some((high << 32 | low) as i64)
+    }
+
+    /// Reads a signed 2.14 fixed-point number.
+    #[inline]
+    pub fn read_f2dot14(&mut self) -> Option<f64> {
+        self.read_i16().map(|v| v as f64 / 16384.0)
+    }
+
+    /// Reads a signed 16.16 fixed-point number.
+    #[inline]
+    pub fn read_fixed(&mut self) -> Option<f64> {
+        self.read_i32().map(|v| v as f64 / 65536.0)
+    }
+}
+
+#[inline]
+pub fn read_u8(data: &[u8], offset: usize) -> Option<u8> {
+    data.get(offset).cloned()
+}
+
+#[inline]
+pub fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Reader::at(data, offset).read_u16()
+}
+
+#[inline]
+pub fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    Reader::at(data, offset).read_i16()
+}
+
+#[inline]
+pub fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Reader::at(data, offset).read_u32()
+}
+
+#[inline]
+pub fn read_i64(data: &[u8], offset: usize) -> Option<i64> {
+    Reader::at(data, offset).read_i64()
+}
+
+#[inline]
+pub fn read_fixed(data: &[u8], offset: usize) -> Option<f64> {
+    Reader::at(data, offset).read_fixed()
+}
+
+/// Clamps a normalized variation coordinate to -1 to 1 and rounds it to the nearest 2.14
+/// fixed-point number, the precision that variation data works in.
+#[inline]
+pub fn round_to_f2dot14(coordinate: f64) -> f64 {
+    (coordinate.clamp(-1.0, 1.0) * 16384.0).round() / 16384.0
+}
+
+/// Binary searches `count` sorted records, comparing each record to the target with `compare`.
+///
+/// Returns the index of a matching record, or `None` if there is none or a record can't be read.
+pub fn binary_search<F>(count: usize, mut compare: F) -> Option<usize>
+    where F: FnMut(usize) -> Option<Ordering>
+{
+    let (mut low, mut high) = (0, count);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        match compare(middle)? {
+            Ordering::Less => low = middle + 1,
+            Ordering::Greater => high = middle,
+            Ordering::Equal => return Some(middle),
+        }
+    }
+    None
+}
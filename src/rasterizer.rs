@@ -0,0 +1,293 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A software rasterizer that turns glyph outlines into antialiased coverage bitmaps, for
+//! rendering without a `CGContext`.
+
+use base::{CGPoint, CGRect, CGSize};
+use outline::{self, OutlineSink, PathElement};
+
+/// How far, in pixels, the line segments that approximate a curve may stray from it.
+const FLATTENING_TOLERANCE: f64 = 0.05;
+
+/// A glyph rendered to 8-bit coverage, as returned by `FontBackend::rasterize_glyph`.
+#[derive(Clone, Debug)]
+pub struct GlyphBitmap {
+    pub width: usize,
+    pub height: usize,
+    /// The position of the bitmap's left edge, in pixels right of the glyph origin.
+    pub left: i32,
+    /// The position of the bitmap's top edge, in pixels above the glyph origin.
+    pub top: i32,
+    /// The coverage of each pixel, from 0 to 255, row by row from the top.
+    pub coverage: Vec<u8>,
+    /// The outline's bounding box in points, relative to the glyph origin and without the
+    /// subpixel offset, as `get_bounding_rects_for_glyphs` returns it. Glyphs with no outline
+    /// have an empty box at the origin.
+    pub bounds: CGRect,
+}
+
+/// Fills outlines by accumulating the exact area they cover in each pixel.
+///
+/// Coordinates are in pixels, with the origin at the bitmap's top-left corner and y pointing
+/// down. Contours are filled by the nonzero rule, and are closed automatically if needed.
+/// Anything outside the bitmap is clipped.
+pub struct Rasterizer {
+    width: usize,
+    height: usize,
+    /// The change in signed coverage from each pixel to the next, in row order. A row's
+    /// changes sum to zero, so running totals carry correctly from one row into the next.
+    accumulation: Vec<f64>,
+    start: CGPoint,
+    current: CGPoint,
+}
+
+impl Rasterizer {
+    pub fn new(width: usize, height: usize) -> Rasterizer {
+        Rasterizer {
+            width,
+            height,
+            // Lines touching the right edge write just past the end of their row.
+            accumulation: vec![0.0; width * height + 2],
+            start: CGPoint::new(0.0, 0.0),
+            current: CGPoint::new(0.0, 0.0),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the coverage of each pixel, from 0 to 255, row by row from the top.
+    pub fn coverage(&self) -> Vec<u8> {
+        let mut total = 0.0;
+        self.accumulation[..self.width * self.height].iter().map(|change| {
+            total += change;
+            (total.abs().min(1.0) * 255.0 + 0.5) as u8
+        }).collect()
+    }
+
+    fn clip(&self, point: CGPoint) -> (f64, f64) {
+        (point.x.max(0.0).min(self.width as f64), point.y.max(0.0).min(self.height as f64))
+    }
+
+    fn add_line(&mut self, from: CGPoint, to: CGPoint) {
+        // Clipping to the edges keeps every crossing that affects coverage inside the bitmap,
+        // since coverage only depends on the lines to a pixel's left.
+        let (from, to) = (self.clip(from), self.clip(to));
+        if from.1 == to.1 {
+            return;
+        }
+        let (direction, (x0, y0), (x1, y1)) = if from.1 < to.1 {
+            (1.0, from, to)
+        } else {
+            (-1.0, to, from)
+        };
+        let dxdy = (x1 - x0) / (y1 - y0);
+        let mut x = x0;
+        for row in y0 as usize..(y1.ceil() as usize).min(self.height) {
+            let row_start = row * self.width;
+            let dy = ((row + 1) as f64).min(y1) - (row as f64).max(y0);
+            let next_x = x + dxdy * dy;
+            let change = dy * direction;
+            let (left, right) = if x < next_x { (x, next_x) } else { (next_x, x) };
+            let left_floor = left.floor();
+            let left_column = left_floor as usize;
+            let right_ceil = right.ceil();
+            let right_column = right_ceil as usize;
+            let accumulation = &mut self.accumulation[row_start..];
+
+            if right_column <= left_column + 1 {
+                // The line stays within one column: split by where its midpoint falls.
+                let middle = 0.5 * (x + next_x) - left_floor;
+                accumulation[left_column] += change * (1.0 - middle);
+                accumulation[left_column + 1] += change * middle;
+            } else {
+                // Spread the change over the columns the line crosses, by the area of the
+                // triangles and trapezoids it cuts from each.
+                let slope = 1.0 / (right - left);
+                let left_fraction = left - left_floor;
+                let first = 0.5 * slope * (1.0 - left_fraction) * (1.0 - left_fraction);
+                let right_fraction = right - right_ceil + 1.0;
+                let last = 0.5 * slope * right_fraction * right_fraction;
+                accumulation[left_column] += change * first;
+                if right_column == left_column + 2 {
+                    accumulation[left_column + 1] += change * (1.0 - first - last);
+                } else {
+                    let second = slope * (1.5 - left_fraction);
+                    accumulation[left_column + 1] += change * (second - first);
+                    for value in &mut accumulation[left_column + 2..right_column - 1] {
+                        *value += change * slope;
+                    }
+                    let before_last = second + (right_column - left_column - 3) as f64 * slope;
+                    accumulation[right_column - 1] += change * (1.0 - before_last - last);
+                }
+                accumulation[right_column] += change * last;
+            }
+            x = next_x;
+        }
+    }
+}
+
+impl OutlineSink for Rasterizer {
+    fn move_to(&mut self, to: CGPoint) {
+        self.close();
+        self.start = to;
+        self.current = to;
+    }
+
+    fn line_to(&mut self, to: CGPoint) {
+        let from = self.current;
+        self.add_line(from, to);
+        self.current = to;
+    }
+
+    fn quad_to(&mut self, control: CGPoint, to: CGPoint) {
+        let from = self.current;
+        for point in outline::flatten_quadratic(from, control, to, FLATTENING_TOLERANCE) {
+            self.line_to(point);
+        }
+    }
+
+    fn cubic_to(&mut self, control1: CGPoint, control2: CGPoint, to: CGPoint) {
+        let from = self.current;
+        for point in outline::flatten_cubic(from, control1, control2, to, FLATTENING_TOLERANCE) {
+            self.line_to(point);
+        }
+    }
+
+    fn close(&mut self) {
+        let start = self.start;
+        self.line_to(start);
+    }
+}
+
+/// Renders an outline in points, one pixel per point, with its origin moved right and up by
+/// `subpixel_offset`, which is normally a fraction of a pixel.
+pub fn rasterize_outline(elements: &[PathElement], subpixel_offset: CGPoint) -> GlyphBitmap {
+    let bounds = match outline::bounding_box(elements) {
+        Some(bounds) => bounds,
+        None => {
+            return GlyphBitmap {
+                width: 0,
+                height: 0,
+                left: 0,
+                top: 0,
+                coverage: vec![],
+                bounds: CGRect::new(&CGPoint::new(0.0, 0.0), &CGSize::new(0.0, 0.0)),
+            }
+        }
+    };
+
+    let left = (bounds.origin.x + subpixel_offset.x).floor();
+    let right = (bounds.origin.x + bounds.size.width + subpixel_offset.x).ceil();
+    let bottom = (bounds.origin.y + subpixel_offset.y).floor();
+    let top = (bounds.origin.y + bounds.size.height + subpixel_offset.y).ceil();
+    let mut rasterizer = Rasterizer::new((right - left) as usize, (top - bottom) as usize);
+    for element in elements {
+        let element = element.map(|point| {
+            CGPoint::new(point.x + subpixel_offset.x - left, top - (point.y + subpixel_offset.y))
+        });
+        outline::replay(&[element], &mut rasterizer);
+    }
+
+    GlyphBitmap {
+        width: rasterizer.width(),
+        height: rasterizer.height(),
+        left: left as i32,
+        top: top as i32,
+        coverage: rasterizer.coverage(),
+        bounds,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base::CGPoint;
+    use outline::{OutlineSink, PathElement};
+    use test_font::{self, A};
+    use super::{Rasterizer, rasterize_outline};
+
+    fn rectangle(rasterizer: &mut Rasterizer, left: f64, top: f64, right: f64, bottom: f64) {
+        rasterizer.move_to(CGPoint::new(left, top));
+        rasterizer.line_to(CGPoint::new(right, top));
+        rasterizer.line_to(CGPoint::new(right, bottom));
+        rasterizer.line_to(CGPoint::new(left, bottom));
+        rasterizer.close();
+    }
+
+    #[test]
+    fn covers_pixels_by_the_area_inside_the_outline() {
+        let mut rasterizer = Rasterizer::new(3, 2);
+        rectangle(&mut rasterizer, 0.5, 0.25, 2.5, 1.75);
+        assert_eq!(rasterizer.coverage(), vec![96, 191, 96, 96, 191, 96]);
+
+        // The diagonal halves the pixels it crosses.
+        let mut rasterizer = Rasterizer::new(2, 2);
+        rasterizer.move_to(CGPoint::new(0.0, 0.0));
+        rasterizer.line_to(CGPoint::new(2.0, 0.0));
+        rasterizer.line_to(CGPoint::new(0.0, 2.0));
+        rasterizer.close();
+        assert_eq!(rasterizer.coverage(), vec![255, 128, 128, 0]);
+    }
+
+    #[test]
+    fn fills_by_the_nonzero_rule() {
+        // Wound the same way, the inner square is filled once.
+        let mut rasterizer = Rasterizer::new(3, 1);
+        rectangle(&mut rasterizer, 0.0, 0.0, 3.0, 1.0);
+        rectangle(&mut rasterizer, 1.0, 0.0, 2.0, 1.0);
+        assert_eq!(rasterizer.coverage(), vec![255, 255, 255]);
+
+        // Wound the other way, it cuts a hole.
+        let mut rasterizer = Rasterizer::new(3, 1);
+        rectangle(&mut rasterizer, 0.0, 0.0, 3.0, 1.0);
+        rectangle(&mut rasterizer, 2.0, 0.0, 1.0, 1.0);
+        assert_eq!(rasterizer.coverage(), vec![255, 0, 255]);
+    }
+
+    #[test]
+    fn covers_a_glyph_by_its_area() {
+        let font = test_font::dejavu_sans(64.0);
+        let mut elements: Vec<PathElement> = vec![];
+        assert!(font.draw_glyph_outline(A, &mut elements));
+
+        // `A` is made of lines, so the shoelace formula gives its area exactly.
+        let mut area = 0.0;
+        let mut start = CGPoint::new(0.0, 0.0);
+        let mut current = start;
+        for element in &elements {
+            let to = match *element {
+                PathElement::MoveTo(to) => {
+                    start = to;
+                    current = to;
+                    continue;
+                }
+                PathElement::LineTo(to) => to,
+                PathElement::Close => start,
+                _ => panic!("`A` has curves"),
+            };
+            area += current.x * to.y - to.x * current.y;
+            current = to;
+        }
+        let area = (area / 2.0).abs();
+
+        for offset in &[CGPoint::new(0.0, 0.0), CGPoint::new(0.25, 0.5)] {
+            let bitmap = rasterize_outline(&elements, *offset);
+            let coverage: f64 = bitmap.coverage.iter().map(|&value| value as f64 / 255.0).sum();
+            // Rounding each pixel to 8 bits is out by at most half a step.
+            let tolerance = bitmap.coverage.len() as f64 * 0.5 / 255.0;
+            assert!((coverage - area).abs() <= tolerance, "{} vs {}", coverage, area);
+        }
+    }
+}
@@ -0,0 +1,909 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Applies Apple Advanced Typography tables to a `GlyphBuffer`: `morx` subtables rewrite its
+//! glyphs, `kerx` subtables adjust their positions, and `trak` spaces them out by point size.
+//!
+//! Most AAT subtables are finite-state machines, which run over the glyphs one at a time and
+//! act on the current glyph and on glyphs they marked earlier.
+
+use base::CGFloat;
+use shaping::{Attachment, Direction, Feature, GlyphBuffer, GlyphInfo, GlyphPosition};
+use shaping::{IGNORE_MARKS, KERN_FEATURE, LookupFlags, feature_value, next_glyph};
+use shaping::resolve_attachments;
+use tables::aat::{DELETED_GLYPH_ID, END_OF_TEXT, START_OF_TEXT, Entry, StateTable};
+use tables::ankr::Ankr;
+use tables::gdef::Gdef;
+use tables::kerx::{AnchorAction, Kerx, KerxSubtable};
+use tables::morx::{Chain, ContextualSubtable, FeatureEntry, InsertionSubtable};
+use tables::morx::{LigatureSubtable, Morx, MorphSubtable, SubtableKind};
+use tables::trak::Trak;
+use tag::Tag;
+
+use std::cmp;
+
+/// An entry's action index when it has no action.
+const NO_ACTION: u16 = 0xFFFF;
+
+/// Entries with `DONT_ADVANCE` set run the state machine again on the same glyph. After this
+/// many such entries per glyph, the machine advances anyway so that it can't loop forever.
+const MAX_OPERATIONS_PER_GLYPH: usize = 64;
+
+/// Insertion subtables stop inserting once they have inserted this many glyphs per glyph of
+/// the original buffer.
+const MAX_INSERTIONS_PER_GLYPH: usize = 32;
+
+/// Ligature subtables remember this many components at most, forgetting the oldest.
+const MAX_LIGATURE_COMPONENTS: usize = 64;
+
+/// Contextual kerning subtables push this many glyphs at most before their stack is cleared.
+const MAX_KERNING_STACK_DEPTH: usize = 8;
+
+// Flags shared by every state table.
+const DONT_ADVANCE: u16 = 0x4000;
+
+// Rearrangement flags.
+const MARK_FIRST: u16 = 0x8000;
+const MARK_LAST: u16 = 0x2000;
+const VERB: u16 = 0x000F;
+
+// Contextual and insertion flags.
+const SET_MARK: u16 = 0x8000;
+
+// Ligature flags and actions.
+const SET_COMPONENT: u16 = 0x8000;
+const PERFORM_ACTION: u16 = 0x2000;
+const LIGATURE_ACTION_LAST: u32 = 0x80000000;
+const LIGATURE_ACTION_STORE: u32 = 0x40000000;
+const LIGATURE_ACTION_OFFSET: u32 = 0x3FFFFFFF;
+const LIGATURE_ACTION_OFFSET_SIGN: u32 = 0x20000000;
+
+// Insertion flags.
+const CURRENT_INSERT_BEFORE: u16 = 0x0800;
+const MARKED_INSERT_BEFORE: u16 = 0x0400;
+const CURRENT_INSERT_COUNT: u16 = 0x03E0;
+const CURRENT_INSERT_COUNT_SHIFT: u16 = 5;
+const MARKED_INSERT_COUNT: u16 = 0x001F;
+
+// Contextual kerning flags.
+const PUSH: u16 = 0x8000;
+const RESET: u16 = 0x2000;
+
+/// The value that resets a cross-stream contextual kerning adjustment to the baseline.
+const CROSS_STREAM_RESET: i16 = -0x8000;
+
+// Anchor attachment flags.
+const MARK: u16 = 0x8000;
+
+/// For each rearrangement verb, how many glyphs move from the start of the marked run to its
+/// end, and from its end to its start. 3 means two glyphs, which swap places.
+const REARRANGEMENTS: [(usize, usize); 16] = [
+    (0, 0), (1, 0), (0, 1), (1, 1), (2, 0), (3, 0), (0, 2), (0, 3),
+    (1, 2), (1, 3), (2, 1), (3, 1), (2, 2), (3, 2), (2, 3), (3, 3),
+];
+
+/// The AAT feature type of each OpenType feature, and the settings that turn it on and off.
+const FEATURE_SETTINGS: [(&[u8; 4], u16, u16, u16); 26] = [
+    (b"afrc", 11, 1, 0),
+    (b"c2pc", 38, 2, 0),
+    (b"c2sc", 38, 1, 0),
+    (b"calt", 36, 0, 1),
+    (b"case", 33, 0, 1),
+    (b"clig", 1, 18, 19),
+    (b"cswh", 36, 4, 5),
+    (b"dlig", 1, 4, 5),
+    (b"frac", 11, 2, 0),
+    (b"hlig", 1, 20, 21),
+    (b"liga", 1, 2, 3),
+    (b"lnum", 21, 1, 2),
+    (b"onum", 21, 0, 2),
+    (b"ordn", 10, 3, 0),
+    (b"pcap", 37, 2, 0),
+    (b"pnum", 6, 1, 4),
+    (b"rlig", 1, 0, 1),
+    (b"salt", 35, 2, 3),
+    (b"sinf", 10, 4, 0),
+    (b"smcp", 37, 1, 0),
+    (b"subs", 10, 2, 0),
+    (b"sups", 10, 1, 0),
+    (b"swsh", 36, 2, 3),
+    (b"titl", 19, 4, 0),
+    (b"tnum", 6, 0, 4),
+    (b"zero", 14, 4, 5),
+];
+
+/// The AAT feature type of the stylistic sets `ss01` to `ss20`, whose settings are `2n` to
+/// turn on set `n` and `2n + 1` to turn it off.
+const STYLISTIC_ALTERNATIVES: u16 = 35;
+
+/// Applies the `morx` chains to `buffer`, with the chain flags that `features` give each glyph
+/// on top of the font's defaults. Features with a value of 0 select their off setting.
+///
+/// Glyphs the chains delete are removed from the buffer at the end, and the classes of the
+/// rest are looked up in `gdef`, or forgotten without one.
+pub fn morph(morx: &Morx, gdef: Option<&Gdef>, buffer: &mut GlyphBuffer, features: &[Feature]) {
+    let right_to_left = buffer.direction == Direction::RightToLeft;
+    for chain in morx.chains() {
+        let settings = chain_settings(&chain, features);
+        let flags = |info: &GlyphInfo| {
+            settings.iter().fold(chain.default_flags, |flags, (feature, entry)| {
+                if feature.start <= info.cluster && info.cluster < feature.end {
+                    (flags & entry.disable_flags) | entry.enable_flags
+                } else {
+                    flags
+                }
+            })
+        };
+        for subtable in chain.subtables() {
+            if subtable.is_vertical() && !subtable.applies_to_all_directions() {
+                continue;
+            }
+            let enabled = |info: &GlyphInfo| flags(info) & subtable.feature_flags != 0;
+            if !buffer.glyphs.iter().any(&enabled) {
+                continue;
+            }
+            // The order a subtable runs in is in terms of layout unless it says it's logical.
+            let reverse = if subtable.is_logical() {
+                subtable.is_descending()
+            } else {
+                subtable.is_descending() != right_to_left
+            };
+            if reverse {
+                buffer.glyphs.reverse();
+            }
+            apply_subtable(&subtable, &mut buffer.glyphs, enabled);
+            if reverse {
+                buffer.glyphs.reverse();
+            }
+        }
+    }
+    buffer.glyphs.retain(|info| info.glyph != DELETED_GLYPH_ID);
+    for info in &mut buffer.glyphs {
+        info.set_classes(gdef);
+    }
+}
+
+/// Returns the chain's feature entries for the settings `features` select, in the order they
+/// apply.
+fn chain_settings(chain: &Chain, features: &[Feature]) -> Vec<(Feature, FeatureEntry)> {
+    let entries = chain.features();
+    features.iter().filter_map(|feature| {
+        let (feature_type, on, off) = feature_setting(feature.tag)?;
+        let setting = if feature.value != 0 { on } else { off };
+        let entry = entries.iter().find(|entry| {
+            entry.feature_type == feature_type && entry.setting == setting
+        })?;
+        Some((*feature, *entry))
+    }).collect()
+}
+
+/// Returns the AAT feature type of an OpenType feature, and the settings that turn it on and
+/// off.
+fn feature_setting(tag: Tag) -> Option<(u16, u16, u16)> {
+    let bytes = tag.to_bytes();
+    if &bytes[..2] == b"ss" {
+        let set = (bytes[2].wrapping_sub(b'0') as u16) * 10 + bytes[3].wrapping_sub(b'0') as u16;
+        return if bytes[2..].iter().all(u8::is_ascii_digit) && (1..=20).contains(&set) {
+            Some((STYLISTIC_ALTERNATIVES, set * 2, set * 2 + 1))
+        } else {
+            None
+        };
+    }
+    FEATURE_SETTINGS.iter()
+                    .find(|&&(feature_tag, ..)| Tag::from(feature_tag) == tag)
+                    .map(|&(_, feature_type, on, off)| (feature_type, on, off))
+}
+
+fn apply_subtable<F>(subtable: &MorphSubtable, glyphs: &mut Vec<GlyphInfo>, enabled: F)
+    where F: Fn(&GlyphInfo) -> bool
+{
+    match subtable.kind {
+        SubtableKind::Rearrangement(ref state_table) => {
+            let mut machine = Rearrangement {
+                glyphs,
+                start: 0,
+                end: 0,
+            };
+            drive(&mut machine, state_table, enabled);
+        }
+        SubtableKind::Contextual(ref contextual) => {
+            let mut machine = Contextual {
+                subtable: contextual,
+                glyphs,
+                mark: None,
+            };
+            drive(&mut machine, &contextual.state_table, enabled);
+        }
+        SubtableKind::Ligature(ref ligature) => {
+            let mut machine = Ligature {
+                subtable: ligature,
+                glyphs,
+                components: vec![],
+            };
+            drive(&mut machine, &ligature.state_table, enabled);
+        }
+        SubtableKind::Noncontextual(ref lookup) => {
+            for info in glyphs.iter_mut().filter(|info| enabled(info)) {
+                if let Some(glyph) = lookup.value(info.glyph) {
+                    info.glyph = glyph;
+                }
+            }
+        }
+        SubtableKind::Insertion(ref insertion) => {
+            let mut machine = Insertion {
+                subtable: insertion,
+                remaining_insertions: (glyphs.len() + 1) * MAX_INSERTIONS_PER_GLYPH,
+                glyphs,
+                mark: 0,
+            };
+            drive(&mut machine, &insertion.state_table, enabled);
+        }
+    }
+}
+
+/// Adds the `kerx` table's kerning and attachments to `positions`. Pair kerning applies to the
+/// glyphs the `kern` feature in `features` applies to, and passes over marks when their class
+/// is known. `ankr` gives the anchor points of attachment subtables that use them.
+pub fn kern(kerx: &Kerx,
+            ankr: Option<&Ankr>,
+            buffer: &GlyphBuffer,
+            features: &[Feature],
+            scale: CGFloat,
+            positions: &mut [GlyphPosition]) {
+    let features: Vec<Feature> =
+        features.iter().filter(|feature| feature.tag == KERN_FEATURE).cloned().collect();
+    let glyphs = &buffer.glyphs;
+    let skip_marks = LookupFlags::new(IGNORE_MARKS);
+    let mut attachments = vec![None; glyphs.len()];
+    for subtable in kerx.subtables() {
+        if !subtable.is_horizontal() || subtable.has_variation() {
+            continue;
+        }
+        match subtable.format() {
+            0 | 2 | 6 => {
+                let mut position = 0;
+                while let Some(next) = next_glyph(glyphs, position, skip_marks) {
+                    if feature_value(&features, glyphs[position].cluster) != 0 {
+                        let kerning = subtable.kerning(glyphs[position].glyph, glyphs[next].glyph)
+                                              .unwrap_or(0) as CGFloat * scale;
+                        if subtable.is_cross_stream() {
+                            positions[next].y_offset += kerning;
+                        } else {
+                            positions[position].x_advance += kerning;
+                        }
+                    }
+                    position = next;
+                }
+            }
+            1 => {
+                let state_table = match subtable.state_table() {
+                    Some(state_table) => state_table,
+                    None => continue,
+                };
+                let mut machine = ContextualKerning {
+                    subtable: &subtable,
+                    glyphs,
+                    positions,
+                    features: &features,
+                    scale,
+                    stack: vec![],
+                };
+                drive(&mut machine, &state_table, |_| true);
+            }
+            4 => {
+                let state_table = match subtable.state_table() {
+                    Some(state_table) => state_table,
+                    None => continue,
+                };
+                let mut machine = AnchorAttachment {
+                    subtable: &subtable,
+                    ankr,
+                    glyphs,
+                    positions,
+                    attachments: &mut attachments,
+                    scale,
+                    mark: None,
+                };
+                drive(&mut machine, &state_table, |_| true);
+            }
+            _ => {}
+        }
+    }
+    resolve_attachments(&attachments, positions, buffer.direction);
+}
+
+/// Adds the `trak` table's normal tracking at `pt_size` to the advance of the first glyph of
+/// each cluster. `scale` converts font units to points.
+pub fn track(trak: &Trak,
+             buffer: &GlyphBuffer,
+             pt_size: CGFloat,
+             scale: CGFloat,
+             positions: &mut [GlyphPosition]) {
+    let tracking = match trak.tracking(false, 0.0, pt_size) {
+        Some(tracking) if tracking != 0.0 => tracking as CGFloat * scale,
+        _ => return,
+    };
+    for (index, info) in buffer.glyphs.iter().enumerate() {
+        if index == 0 || buffer.glyphs[index - 1].cluster != info.cluster {
+            positions[index].x_advance += tracking;
+        }
+    }
+}
+
+/// A subtable's state machine, which `drive` feeds glyphs to.
+trait StateMachine<'a> {
+    fn glyphs(&self) -> &[GlyphInfo];
+
+    /// Performs the entry's actions for the glyph at `position`, which is the length of the
+    /// buffer at the end of the text. Returns the position of the same glyph, which
+    /// insertions can move.
+    fn transition(&mut self, position: usize, entry: Entry<'a>) -> usize;
+}
+
+/// Runs a state machine over its glyphs, from the start of the text to its end. Glyphs that
+/// `enabled` rejects are passed over, and put the machine back in its start state.
+fn drive<'a, M, F>(machine: &mut M, state_table: &StateTable<'a>, enabled: F)
+    where M: StateMachine<'a>,
+          F: Fn(&GlyphInfo) -> bool
+{
+    let mut operations = (machine.glyphs().len() + 1) * MAX_OPERATIONS_PER_GLYPH;
+    let mut state = START_OF_TEXT;
+    let mut position = 0;
+    loop {
+        let class = match machine.glyphs().get(position) {
+            Some(info) if !enabled(info) => {
+                state = START_OF_TEXT;
+                position += 1;
+                continue;
+            }
+            Some(info) => state_table.class(info.glyph),
+            None => END_OF_TEXT,
+        };
+        let entry = match state_table.entry(state, class) {
+            Some(entry) => entry,
+            None => break,
+        };
+        position = machine.transition(position, entry);
+        state = entry.new_state;
+        if position >= machine.glyphs().len() {
+            break;
+        }
+        if entry.flags & DONT_ADVANCE == 0 || operations == 0 {
+            position += 1;
+        } else {
+            operations -= 1;
+        }
+    }
+}
+
+/// Gives the glyphs the first of their clusters, once they've been reordered or combined.
+fn merge_clusters(glyphs: &mut [GlyphInfo]) {
+    if let Some(cluster) = glyphs.iter().map(|info| info.cluster).min() {
+        for info in glyphs {
+            info.cluster = cluster;
+        }
+    }
+}
+
+/// Reorders the run of glyphs between a marked first glyph and a marked last glyph.
+struct Rearrangement<'b> {
+    glyphs: &'b mut Vec<GlyphInfo>,
+    start: usize,
+    end: usize,
+}
+
+impl<'a, 'b> StateMachine<'a> for Rearrangement<'b> {
+    fn glyphs(&self) -> &[GlyphInfo] {
+        self.glyphs
+    }
+
+    fn transition(&mut self, position: usize, entry: Entry<'a>) -> usize {
+        if entry.flags & MARK_FIRST != 0 {
+            self.start = position;
+        }
+        if entry.flags & MARK_LAST != 0 {
+            self.end = cmp::min(position + 1, self.glyphs.len());
+        }
+        let verb = entry.flags & VERB;
+        let (start, end) = (self.start, self.end);
+        if verb == 0 {
+            return position;
+        }
+        let (from_start, from_end) = REARRANGEMENTS[verb as usize];
+        let (start_count, end_count) = (cmp::min(from_start, 2), cmp::min(from_end, 2));
+        if start >= end || end - start < start_count + end_count {
+            return position;
+        }
+        let current_end = cmp::min(position + 1, self.glyphs.len());
+        merge_clusters(&mut self.glyphs[start..current_end]);
+        merge_clusters(&mut self.glyphs[start..end]);
+
+        let run = &mut self.glyphs[start..end];
+        let mut first = run[..start_count].to_vec();
+        let mut last = run[run.len() - end_count..].to_vec();
+        if from_start == 3 {
+            first.reverse();
+        }
+        if from_end == 3 {
+            last.reverse();
+        }
+        let middle = run[start_count..run.len() - end_count].to_vec();
+        let rearranged: Vec<GlyphInfo> = last.into_iter().chain(middle).chain(first).collect();
+        run.copy_from_slice(&rearranged);
+        position
+    }
+}
+
+/// Substitutes the current glyph, and a marked glyph before it, depending on the context.
+struct Contextual<'a, 'b> {
+    subtable: &'b ContextualSubtable<'a>,
+    glyphs: &'b mut Vec<GlyphInfo>,
+    mark: Option<usize>,
+}
+
+impl<'a, 'b> StateMachine<'a> for Contextual<'a, 'b> {
+    fn glyphs(&self) -> &[GlyphInfo] {
+        self.glyphs
+    }
+
+    fn transition(&mut self, position: usize, entry: Entry<'a>) -> usize {
+        // Core Text substitutes nothing at the end of the text unless a glyph was marked.
+        if position == self.glyphs.len() && self.mark.is_none() {
+            return position;
+        }
+        let mark_index = entry.value(0).unwrap_or(NO_ACTION);
+        let current_index = entry.value(1).unwrap_or(NO_ACTION);
+        if let Some(mark) = self.mark.filter(|&mark| mark < self.glyphs.len()) {
+            if mark_index != NO_ACTION {
+                if let Some(glyph) = self.subtable.substitute(mark_index, self.glyphs[mark].glyph) {
+                    self.glyphs[mark].glyph = glyph;
+                }
+            }
+        }
+        // At the end of the text, the current glyph is the last one.
+        let current = cmp::min(position, self.glyphs.len() - 1);
+        if current_index != NO_ACTION {
+            if let Some(glyph) = self.subtable.substitute(current_index,
+                                                          self.glyphs[current].glyph) {
+                self.glyphs[current].glyph = glyph;
+            }
+        }
+        if entry.flags & SET_MARK != 0 {
+            self.mark = Some(position);
+        }
+        position
+    }
+}
+
+/// Replaces a run of component glyphs with a ligature, which takes the place of the first
+/// component. The other components are deleted.
+struct Ligature<'a, 'b> {
+    subtable: &'b LigatureSubtable<'a>,
+    glyphs: &'b mut Vec<GlyphInfo>,
+    /// The positions of the components, in order.
+    components: Vec<usize>,
+}
+
+impl<'a, 'b> StateMachine<'a> for Ligature<'a, 'b> {
+    fn glyphs(&self) -> &[GlyphInfo] {
+        self.glyphs
+    }
+
+    fn transition(&mut self, position: usize, entry: Entry<'a>) -> usize {
+        if entry.flags & SET_COMPONENT != 0 {
+            // A glyph that doesn't advance the machine can only be a component once.
+            if self.components.last() == Some(&position) {
+                self.components.pop();
+            }
+            if self.components.len() == MAX_LIGATURE_COMPONENTS {
+                self.components.remove(0);
+            }
+            self.components.push(position);
+        }
+        if entry.flags & PERFORM_ACTION == 0 || self.components.is_empty() ||
+           position >= self.glyphs.len() {
+            return position;
+        }
+
+        // Each action reads a component, from the last back, and adds the value its glyph
+        // selects from the component table to the ligature index. Stored ligatures replace
+        // the component just read.
+        let mut action_index = entry.value(0).unwrap_or(0) as usize;
+        let mut ligature_index: u32 = 0;
+        let mut cursor = self.components.len();
+        loop {
+            if cursor == 0 {
+                self.components.clear();
+                break;
+            }
+            cursor -= 1;
+            let component = self.components[cursor];
+            let action = match self.subtable.action(action_index) {
+                Some(action) => action,
+                None => break,
+            };
+            let mut offset = action & LIGATURE_ACTION_OFFSET;
+            if offset & LIGATURE_ACTION_OFFSET_SIGN != 0 {
+                offset |= !LIGATURE_ACTION_OFFSET;
+            }
+            let component_index = (self.glyphs[component].glyph as u32).wrapping_add(offset);
+            match self.subtable.component(component_index as usize) {
+                Some(value) => ligature_index = ligature_index.wrapping_add(value as u32),
+                None => break,
+            }
+            if action & (LIGATURE_ACTION_STORE | LIGATURE_ACTION_LAST) != 0 {
+                let ligature = match self.subtable.ligature(ligature_index as usize) {
+                    Some(ligature) => ligature,
+                    None => break,
+                };
+                self.glyphs[component].glyph = ligature;
+                let end = self.components[self.components.len() - 1] + 1;
+                while self.components.len() - 1 > cursor {
+                    let deleted = self.components.pop().unwrap();
+                    self.glyphs[deleted].glyph = DELETED_GLYPH_ID;
+                }
+                merge_clusters(&mut self.glyphs[component..end]);
+            }
+            if action & LIGATURE_ACTION_LAST != 0 {
+                break;
+            }
+            action_index += 1;
+        }
+        position
+    }
+}
+
+/// Inserts glyphs at the current glyph, and at a marked glyph before it.
+struct Insertion<'a, 'b> {
+    subtable: &'b InsertionSubtable<'a>,
+    glyphs: &'b mut Vec<GlyphInfo>,
+    mark: usize,
+    remaining_insertions: usize,
+}
+
+impl<'a, 'b> Insertion<'a, 'b> {
+    /// Inserts `count` glyphs from the `index`th entry of the insertion glyph table, before or
+    /// after the glyph at `position`, in its cluster. Returns false if nothing was inserted.
+    fn insert(&mut self, index: u16, count: usize, position: usize, before: bool) -> bool {
+        if count > self.remaining_insertions {
+            return false;
+        }
+        let glyphs = match self.subtable.glyphs(index, count) {
+            Some(glyphs) => glyphs,
+            None => return false,
+        };
+        self.remaining_insertions -= count;
+        let cluster = self.glyphs.get(position)
+                                 .or_else(|| self.glyphs.last())
+                                 .map_or(0, |info| info.cluster);
+        let at = if before || position >= self.glyphs.len() { position } else { position + 1 };
+        let inserted = glyphs.into_iter().map(|glyph| GlyphInfo::new(glyph, cluster));
+        self.glyphs.splice(at..at, inserted);
+        true
+    }
+}
+
+impl<'a, 'b> StateMachine<'a> for Insertion<'a, 'b> {
+    fn glyphs(&self) -> &[GlyphInfo] {
+        self.glyphs
+    }
+
+    fn transition(&mut self, mut position: usize, entry: Entry<'a>) -> usize {
+        let flags = entry.flags;
+        let current_index = entry.value(0).unwrap_or(NO_ACTION);
+        let marked_index = entry.value(1).unwrap_or(NO_ACTION);
+        let mark = position;
+
+        // The current glyph moves along by the glyphs inserted at the mark, which is never
+        // after it.
+        if marked_index != NO_ACTION {
+            let count = (flags & MARKED_INSERT_COUNT) as usize;
+            let before = flags & MARKED_INSERT_BEFORE != 0;
+            if self.insert(marked_index, count, self.mark, before) {
+                position += count;
+            }
+        }
+        if flags & SET_MARK != 0 {
+            self.mark = mark;
+        }
+        // Inserted glyphs are passed over, unless the machine doesn't advance, in which case it
+        // sees them next.
+        if current_index != NO_ACTION {
+            let count = ((flags & CURRENT_INSERT_COUNT) >> CURRENT_INSERT_COUNT_SHIFT) as usize;
+            let before = flags & CURRENT_INSERT_BEFORE != 0;
+            if self.insert(current_index, count, position, before) && flags & DONT_ADVANCE == 0 {
+                position += count;
+            }
+        }
+        position
+    }
+}
+
+/// Kerns the glyphs pushed onto a stack, when an entry pops them off.
+struct ContextualKerning<'a, 'b> {
+    subtable: &'b KerxSubtable<'a>,
+    glyphs: &'b [GlyphInfo],
+    positions: &'b mut [GlyphPosition],
+    /// The `kern` features, which select the glyphs to kern along the line.
+    features: &'b [Feature],
+    scale: CGFloat,
+    stack: Vec<usize>,
+}
+
+impl<'a, 'b> StateMachine<'a> for ContextualKerning<'a, 'b> {
+    fn glyphs(&self) -> &[GlyphInfo] {
+        self.glyphs
+    }
+
+    fn transition(&mut self, position: usize, entry: Entry<'a>) -> usize {
+        if entry.flags & RESET != 0 {
+            self.stack.clear();
+        }
+        if entry.flags & PUSH != 0 {
+            if self.stack.len() == MAX_KERNING_STACK_DEPTH {
+                self.stack.clear();
+            } else {
+                self.stack.push(position);
+            }
+        }
+        let index = entry.value(0).unwrap_or(NO_ACTION);
+        if index == NO_ACTION || self.stack.is_empty() {
+            return position;
+        }
+
+        let cross_stream = self.subtable.is_cross_stream();
+        for value in self.subtable.kerning_values(index, self.stack.len()) {
+            let glyph = self.stack.pop().unwrap();
+            if glyph >= self.glyphs.len() {
+                continue;
+            }
+            let glyph_position = &mut self.positions[glyph];
+            let kerning = value as CGFloat * self.scale;
+            if cross_stream {
+                if value == CROSS_STREAM_RESET {
+                    glyph_position.y_offset = 0.0;
+                } else {
+                    glyph_position.y_offset += kerning;
+                }
+            } else if feature_value(self.features, self.glyphs[glyph].cluster) != 0 {
+                // The glyph moves, and takes the glyphs after it along.
+                glyph_position.x_advance += kerning;
+                glyph_position.x_offset += kerning;
+            }
+        }
+        position
+    }
+}
+
+/// Attaches the current glyph to a marked glyph before it, so that a point on each meets.
+struct AnchorAttachment<'a, 'b> {
+    subtable: &'b KerxSubtable<'a>,
+    ankr: Option<&'b Ankr<'a>>,
+    glyphs: &'b [GlyphInfo],
+    positions: &'b mut [GlyphPosition],
+    attachments: &'b mut [Option<Attachment>],
+    scale: CGFloat,
+    mark: Option<usize>,
+}
+
+impl<'a, 'b> StateMachine<'a> for AnchorAttachment<'a, 'b> {
+    fn glyphs(&self) -> &[GlyphInfo] {
+        self.glyphs
+    }
+
+    fn transition(&mut self, position: usize, entry: Entry<'a>) -> usize {
+        let index = entry.value(0).unwrap_or(NO_ACTION);
+        if let Some(mark) = self.mark {
+            if index != NO_ACTION && position < self.glyphs.len() {
+                let (mark_glyph, glyph) = (self.glyphs[mark].glyph, self.glyphs[position].glyph);
+                // Attaching at outline points isn't supported.
+                let anchors = match self.subtable.anchor_action(index) {
+                    Some(AnchorAction::AnchorPoints(mark_point, point)) => {
+                        self.ankr.and_then(|ankr| {
+                            Some((ankr.anchor(mark_glyph, mark_point)?, ankr.anchor(glyph, point)?))
+                        })
+                    }
+                    Some(AnchorAction::Coordinates(mark_anchor, anchor)) => {
+                        Some((mark_anchor, anchor))
+                    }
+                    Some(AnchorAction::ControlPoints(..)) | None => None,
+                };
+                if let Some((mark_anchor, anchor)) = anchors {
+                    let glyph_position = &mut self.positions[position];
+                    glyph_position.x_offset = (mark_anchor.x as CGFloat - anchor.x as CGFloat) *
+                                              self.scale;
+                    glyph_position.y_offset = (mark_anchor.y as CGFloat - anchor.y as CGFloat) *
+                                              self.scale;
+                    self.attachments[position] = Some(Attachment::Mark(mark));
+                }
+            }
+        }
+        if entry.flags & MARK != 0 {
+            self.mark = Some(position);
+        }
+        position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shaping::{Direction, Feature, GlyphBuffer, GlyphInfo, GlyphPosition, KERN_FEATURE};
+    use tables::kerx::Kerx;
+    use tables::morx::Morx;
+    use test_font::words;
+    use super::{CURRENT_INSERT_BEFORE, CURRENT_INSERT_COUNT_SHIFT, LIGATURE_ACTION_LAST};
+    use super::{LIGATURE_ACTION_OFFSET, MARK, MARKED_INSERT_BEFORE, MARK_FIRST, MARK_LAST};
+    use super::{NO_ACTION, PERFORM_ACTION, PUSH, SET_COMPONENT, SET_MARK, kern, morph};
+
+    /// The glyph in class 4, the first class after the ones every state table has. The glyphs
+    /// after it are in the classes after that.
+    const FIRST_GLYPH: u16 = 10;
+
+    const NONE: i32 = NO_ACTION as i32;
+
+    /// Builds an extended state table with a row of entry indices for each state, one per
+    /// class. Its extra header fields are offsets from the end of the table, for data appended
+    /// to it, and are written as offsets from its start.
+    fn state_table(rows: &[&[i32]], entries: &[&[i32]], extra_header: &[u32]) -> Vec<u8> {
+        let class_count = rows[0].len();
+        let class_table = 16 + extra_header.len() * 4;
+        let state_array = class_table + 6 + (class_count - 4) * 2;
+        let entry_table = state_array + rows.len() * class_count * 2;
+        let length = entry_table + entries.iter().map(|entry| entry.len() * 2).sum::<usize>();
+        let mut table = vec![];
+        for &field in &[class_count, class_table, state_array, entry_table] {
+            table.extend((field as u32).to_be_bytes());
+        }
+        for &field in extra_header {
+            table.extend((field + length as u32).to_be_bytes());
+        }
+        table.extend(words(&[8, FIRST_GLYPH as i32, class_count as i32 - 4]));
+        table.extend(words(&(4..class_count as i32).collect::<Vec<_>>()));
+        for row in rows {
+            table.extend(words(row));
+        }
+        for entry in entries {
+            table.extend(words(entry));
+        }
+        table
+    }
+
+    /// Runs a `morx` table with one subtable over `glyphs`, whose clusters are their indices,
+    /// and returns the glyphs and clusters that come out.
+    fn morph_glyphs(subtable_type: u32, subtable: &[u8], glyphs: &[u16]) -> Vec<(u16, usize)> {
+        let mut data = words(&[2, 0, 0, 1]);
+        for &field in &[1, 28 + subtable.len() as u32, 0, 1, 12 + subtable.len() as u32,
+                        subtable_type, 1] {
+            data.extend(field.to_be_bytes());
+        }
+        data.extend(subtable);
+        let mut buffer = GlyphBuffer {
+            glyphs: glyphs.iter().enumerate().map(|(index, &glyph)| {
+                GlyphInfo::new(glyph, index)
+            }).collect(),
+            direction: Direction::LeftToRight,
+        };
+        morph(&Morx::parse(&data).unwrap(), None, &mut buffer, &[]);
+        buffer.glyphs.iter().map(|info| (info.glyph, info.cluster)).collect()
+    }
+
+    /// Runs a `kerx` table with one subtable over `glyphs` with advances of 1000 font units, at
+    /// half a point per unit, and returns their positions.
+    fn kern_glyphs(format: u32, subtable: &[u8], glyphs: &[u16]) -> Vec<GlyphPosition> {
+        let mut data = words(&[2, 0, 0, 1]);
+        for &field in &[12 + subtable.len() as u32, format, 0] {
+            data.extend(field.to_be_bytes());
+        }
+        data.extend(subtable);
+        let buffer = GlyphBuffer {
+            glyphs: glyphs.iter().map(|&glyph| GlyphInfo::new(glyph, 0)).collect(),
+            direction: Direction::LeftToRight,
+        };
+        let mut positions = vec![GlyphPosition::new(500.0, 0.0); glyphs.len()];
+        kern(&Kerx::parse(&data).unwrap(), None, &buffer, &[Feature::new(KERN_FEATURE)], 0.5,
+             &mut positions);
+        positions
+    }
+
+    /// Rearranges glyphs 10 to 13 with `verb`, marking the first and the last.
+    fn rearrange(verb: u16) -> Vec<u16> {
+        let entries: [&[i32]; 3] = [&[0, 0], &[0, MARK_FIRST as i32],
+                                    &[0, (MARK_LAST | verb) as i32]];
+        let subtable = state_table(&[&[0, 0, 0, 0, 1, 0, 0, 2]], &entries, &[]);
+        let glyphs = morph_glyphs(0, &subtable, &[10, 11, 12, 13]);
+        assert!(glyphs.iter().all(|&(_, cluster)| cluster == 0));
+        glyphs.into_iter().map(|(glyph, _)| glyph).collect()
+    }
+
+    #[test]
+    fn rearranges_the_marked_run() {
+        assert_eq!(rearrange(1), vec![11, 12, 13, 10]);
+        assert_eq!(rearrange(2), vec![13, 10, 11, 12]);
+        assert_eq!(rearrange(3), vec![13, 11, 12, 10]);
+        assert_eq!(rearrange(9), vec![13, 12, 11, 10]);
+        assert_eq!(rearrange(12), vec![12, 13, 10, 11]);
+    }
+
+    #[test]
+    fn substitutes_the_marked_and_current_glyphs() {
+        // Glyph 10 is marked, and glyph 11 substitutes the mark through the first lookup and
+        // itself through the second.
+        let entries: [&[i32]; 3] = [&[0, 0, NONE, NONE], &[0, SET_MARK as i32, NONE, NONE],
+                                    &[0, 0, 0, 1]];
+        let mut subtable = state_table(&[&[0, 0, 0, 0, 1, 2]], &entries, &[0]);
+        subtable.extend(words(&[0, 8, 0, 16, 8, 10, 1, 20, 8, 11, 1, 21]));
+        assert_eq!(morph_glyphs(1, &subtable, &[10, 11]), vec![(20, 0), (21, 1)]);
+        assert_eq!(morph_glyphs(1, &subtable, &[11, 10]), vec![(21, 0), (10, 1)]);
+    }
+
+    #[test]
+    fn forms_ligatures_from_the_component_stack() {
+        // Glyph 10 is f and 11 is i. "fi" is ligature 20, and "ffi" is 21.
+        let component = (SET_COMPONENT | PERFORM_ACTION) as i32;
+        let entries: [&[i32]; 5] = [&[0, 0, 0], &[1, SET_COMPONENT as i32, 0],
+                                    &[2, SET_COMPONENT as i32, 0], &[0, component, 0],
+                                    &[0, component, 2]];
+        let rows: [&[i32]; 3] = [&[0, 0, 0, 0, 1, 0, 0], &[0, 0, 0, 0, 2, 3, 0],
+                                 &[0, 0, 0, 0, 0, 4, 0]];
+        let mut subtable = state_table(&rows, &entries, &[0, 20, 24]);
+        // Each action adds the component table entry at the component's glyph ID plus its
+        // offset to the ligature index.
+        let action = |last: bool, offset: i32| {
+            let flag = if last { LIGATURE_ACTION_LAST } else { 0 };
+            flag | (offset as u32 & LIGATURE_ACTION_OFFSET)
+        };
+        for action in &[action(false, -11), action(true, -10), action(false, -11),
+                        action(false, -9), action(true, -10)] {
+            subtable.extend(action.to_be_bytes());
+        }
+        subtable.extend(words(&[0, 1, 20, 21]));
+        assert_eq!(morph_glyphs(2, &subtable, &[10, 11, 12]), vec![(20, 0), (12, 2)]);
+        assert_eq!(morph_glyphs(2, &subtable, &[12, 10, 10, 11]), vec![(12, 0), (21, 1)]);
+    }
+
+    #[test]
+    fn inserts_before_and_after_the_marked_and_current_glyphs() {
+        // Glyph 10 is marked, and glyph 11 inserts glyph 20 at the mark and 21 at itself.
+        let insert = |flags: u16| {
+            let flags = flags | 1 << CURRENT_INSERT_COUNT_SHIFT | 1;
+            let entries: [&[i32]; 3] = [&[0, 0, NONE, NONE], &[0, SET_MARK as i32, NONE, NONE],
+                                        &[0, flags as i32, 1, 0]];
+            let mut subtable = state_table(&[&[0, 0, 0, 0, 1, 2]], &entries, &[0]);
+            subtable.extend(words(&[20, 21]));
+            morph_glyphs(5, &subtable, &[10, 11])
+        };
+        assert_eq!(insert(CURRENT_INSERT_BEFORE), vec![(10, 0), (20, 0), (21, 1), (11, 1)]);
+        assert_eq!(insert(MARKED_INSERT_BEFORE), vec![(20, 0), (10, 0), (11, 1), (21, 1)]);
+    }
+
+    #[test]
+    fn kerns_the_glyphs_popped_off_the_stack() {
+        // Glyphs 10 and 11 are pushed, and glyph 12 pops them, kerning 11 by 60 units and 10
+        // by -40. The low bit of -39 ends the list.
+        let entries: [&[i32]; 3] = [&[0, 0, NONE], &[0, PUSH as i32, NONE], &[0, 0, 0]];
+        let mut subtable = state_table(&[&[0, 0, 0, 0, 1, 1, 2]], &entries, &[0]);
+        subtable.extend(words(&[60, -39]));
+        let positions = kern_glyphs(1, &subtable, &[10, 11, 12]);
+        let offsets: Vec<(f64, f64)> = positions.iter().map(|position| {
+            (position.x_advance, position.x_offset)
+        }).collect();
+        assert_eq!(offsets, vec![(480.0, -20.0), (530.0, 30.0), (500.0, 0.0)]);
+    }
+
+    #[test]
+    fn attaches_the_current_glyph_to_the_marked_one_at_anchors() {
+        // Glyph 11's anchor at (100, 0) meets marked glyph 10's at (500, 700).
+        let entries: [&[i32]; 3] = [&[0, 0, NONE], &[0, MARK as i32, NONE], &[0, 0, 0]];
+        let mut subtable = state_table(&[&[0, 0, 0, 0, 1, 2]], &entries, &[2 << 30]);
+        subtable.extend(words(&[500, 700, 100, 0]));
+        let positions = kern_glyphs(4, &subtable, &[10, 11]);
+        assert_eq!((positions[1].x_offset, positions[1].y_offset), (200.0 - 500.0, 350.0));
+        assert_eq!(positions[0], GlyphPosition::new(500.0, 0.0));
+    }
+}
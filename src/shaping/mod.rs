@@ -0,0 +1,449 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Applies a font's layout features to runs of glyphs, independently of Core Text.
+//!
+//! Text is first mapped to a `GlyphBuffer` with the font's `cmap`, one glyph per character,
+//! and then features such as `liga` rewrite the buffer. Positioning features such as `kern`
+//! and `mark` then adjust the glyphs' advances to give each a `GlyphPosition`.
+
+pub mod aat;
+pub mod positioning;
+pub mod substitution;
+
+use base::{CGFloat, CGGlyph, UniChar};
+use font_backend::{self, FontBackend};
+use tables::gdef::Gdef;
+use tables::layout::{ContextRule, Coverage, LayoutTable, Lookup};
+use tag::Tag;
+
+// Lookup flags.
+const RIGHT_TO_LEFT: u16 = 0x0001;
+const IGNORE_BASE_GLYPHS: u16 = 0x0002;
+const IGNORE_LIGATURES: u16 = 0x0004;
+const IGNORE_MARKS: u16 = 0x0008;
+const USE_MARK_FILTERING_SET: u16 = 0x0010;
+const MARK_ATTACHMENT_TYPE_SHIFT: u16 = 8;
+
+/// Contextual lookups can apply lookups that are themselves contextual. Nesting is cut off at
+/// this depth so that fonts whose lookups apply each other can't loop forever.
+const MAX_NESTING_DEPTH: usize = 16;
+
+const KERN_FEATURE: Tag = Tag::new(b"kern");
+
+// `GDEF` glyph classes.
+pub const BASE_GLYPH: u16 = 1;
+pub const LIGATURE_GLYPH: u16 = 2;
+pub const MARK_GLYPH: u16 = 3;
+pub const COMPONENT_GLYPH: u16 = 4;
+
+/// A glyph in a `GlyphBuffer`, and what layout needs to know about it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GlyphInfo {
+    pub glyph: CGGlyph,
+    /// The UTF-16 index of the first character the glyph was formed from. Glyphs from the
+    /// same characters, such as a ligature and the marks within it, share a cluster.
+    pub cluster: usize,
+    /// The glyph's `GDEF` class, such as `MARK_GLYPH`, or 0 if it's unknown.
+    pub glyph_class: u16,
+    /// The glyph's `GDEF` mark attachment class, for marks.
+    pub mark_attachment_class: u16,
+    /// For a ligature and the marks between its components, a number the buffer's other
+    /// ligatures don't share, or 0.
+    pub ligature_id: u16,
+    /// For a mark within a ligature, the 1-based index of the component it follows, or 0.
+    pub ligature_component: u16,
+}
+
+impl GlyphInfo {
+    pub fn new(glyph: CGGlyph, cluster: usize) -> GlyphInfo {
+        GlyphInfo {
+            glyph,
+            cluster,
+            glyph_class: 0,
+            mark_attachment_class: 0,
+            ligature_id: 0,
+            ligature_component: 0,
+        }
+    }
+
+    /// Looks the glyph's classes up in `gdef`. Without one they're unknown.
+    fn set_classes(&mut self, gdef: Option<&Gdef>) {
+        self.glyph_class = gdef.map_or(0, |gdef| gdef.glyph_class(self.glyph));
+        self.mark_attachment_class = gdef.map_or(0, |gdef| gdef.mark_attachment_class(self.glyph));
+    }
+
+    /// Whether a lookup with `flags` passes over the glyph, as it would a mark when
+    /// `IgnoreMarks` is set.
+    fn is_ignored(&self, flags: LookupFlags) -> bool {
+        match self.glyph_class {
+            BASE_GLYPH => flags.flags & IGNORE_BASE_GLYPHS != 0,
+            LIGATURE_GLYPH => flags.flags & IGNORE_LIGATURES != 0,
+            MARK_GLYPH => {
+                if flags.flags & IGNORE_MARKS != 0 {
+                    return true;
+                }
+                if flags.flags & USE_MARK_FILTERING_SET != 0 {
+                    return !flags.mark_glyph_set.is_some_and(|set| set.contains(self.glyph));
+                }
+                let attachment_type = flags.flags >> MARK_ATTACHMENT_TYPE_SHIFT;
+                attachment_type != 0 && self.mark_attachment_class != attachment_type
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A lookup's flags, which say which glyphs it passes over, and the `GDEF` mark glyph set it
+/// filters marks by.
+#[derive(Clone, Copy, Debug)]
+struct LookupFlags<'a> {
+    flags: u16,
+    /// The marks that a lookup filtering by set doesn't pass over. It passes over every mark if
+    /// the font doesn't have its set.
+    mark_glyph_set: Option<Coverage<'a>>,
+}
+
+impl<'a> LookupFlags<'a> {
+    /// Flags that don't filter marks by set.
+    fn new(flags: u16) -> LookupFlags<'a> {
+        LookupFlags {
+            flags: flags & !USE_MARK_FILTERING_SET,
+            mark_glyph_set: None,
+        }
+    }
+
+    fn for_lookup(lookup: &Lookup<'a>, gdef: Option<&Gdef<'a>>) -> LookupFlags<'a> {
+        LookupFlags {
+            flags: lookup.flags(),
+            mark_glyph_set: lookup.mark_filtering_set().and_then(|index| {
+                gdef?.mark_glyph_set(index)
+            }),
+        }
+    }
+}
+
+/// The direction text runs in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Direction {
+    #[default]
+    LeftToRight,
+    RightToLeft,
+}
+
+/// A run of glyphs in logical order, which layout features rewrite.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GlyphBuffer {
+    pub glyphs: Vec<GlyphInfo>,
+    /// The direction of the run, which decides how cursive and mark attachments combine with
+    /// advances.
+    pub direction: Direction,
+}
+
+impl GlyphBuffer {
+    /// Maps UTF-16 text to glyphs with the font's character map, one glyph per character.
+    /// Characters the font can't map get glyph 0.
+    pub fn new<F: FontBackend + ?Sized>(font: &F, characters: &[UniChar]) -> GlyphBuffer {
+        let glyphs = font.glyphs_for_characters(characters).glyphs;
+        let mut buffer = GlyphBuffer::default();
+        let mut index = 0;
+        while index < characters.len() {
+            buffer.glyphs.push(GlyphInfo::new(glyphs[index], index));
+            index += if font_backend::is_surrogate_pair(&characters[index..]) { 2 } else { 1 };
+        }
+        buffer
+    }
+
+    /// Maps a string to glyphs, exactly as `new` does its UTF-16 encoding. Clusters are UTF-16
+    /// indices.
+    pub fn from_str<F: FontBackend + ?Sized>(font: &F, string: &str) -> GlyphBuffer {
+        let characters: Vec<UniChar> = string.encode_utf16().collect();
+        GlyphBuffer::new(font, &characters)
+    }
+
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+
+    /// Returns the glyph IDs, in order.
+    pub fn glyph_ids(&self) -> Vec<CGGlyph> {
+        self.glyphs.iter().map(|info| info.glyph).collect()
+    }
+
+    /// Looks up every glyph's `GDEF` classes, which lookups pass over glyphs such as marks by.
+    /// Substitution keeps the classes of the glyphs it produces up to date.
+    pub fn set_glyph_classes(&mut self, gdef: &Gdef) {
+        for info in &mut self.glyphs {
+            info.set_classes(Some(gdef));
+        }
+    }
+}
+
+/// Where a glyph goes, in points, relative to the pen position that the advances of the glyphs
+/// before it reach.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GlyphPosition {
+    pub x_advance: CGFloat,
+    pub y_advance: CGFloat,
+    /// How far the glyph is drawn from the pen position, without moving the pen.
+    pub x_offset: CGFloat,
+    pub y_offset: CGFloat,
+}
+
+impl GlyphPosition {
+    /// A glyph drawn at the pen position, which then moves by the advance.
+    pub fn new(x_advance: CGFloat, y_advance: CGFloat) -> GlyphPosition {
+        GlyphPosition {
+            x_advance,
+            y_advance,
+            x_offset: 0.0,
+            y_offset: 0.0,
+        }
+    }
+}
+
+/// A glyph of shaped text, as returned by `FontBackend::shape`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ShapedGlyph {
+    pub glyph: CGGlyph,
+    /// The byte index in the text of the first character the glyph was formed from. Marks
+    /// share the cluster of the glyph before them.
+    pub cluster: usize,
+    pub position: GlyphPosition,
+}
+
+/// A layout feature to apply, such as `liga` or `smcp`, and the part of the text it applies
+/// to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Feature {
+    pub tag: Tag,
+    /// 0 to turn the feature off, 1 to turn it on, or for features with alternates such as
+    /// `salt`, the 1-based index of the alternate to use.
+    pub value: u32,
+    /// The UTF-16 range of the text the feature applies to, from `start` up to `end`.
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Feature {
+    /// Turns a feature on for the whole text.
+    pub fn new(tag: Tag) -> Feature {
+        Feature {
+            tag,
+            value: 1,
+            start: 0,
+            end: usize::MAX,
+        }
+    }
+
+    pub fn with_value(mut self, value: u32) -> Feature {
+        self.value = value;
+        self
+    }
+
+    /// Limits the feature to the glyphs whose clusters are from `start` up to `end`.
+    pub fn with_range(mut self, start: usize, end: usize) -> Feature {
+        self.start = start;
+        self.end = end;
+        self
+    }
+}
+
+/// Returns the value that the features a lookup belongs to give the glyph at `cluster`. Later
+/// features override earlier ones.
+fn feature_value(features: &[Feature], cluster: usize) -> u32 {
+    features.iter()
+            .rev()
+            .find(|feature| feature.start <= cluster && cluster < feature.end)
+            .map_or(0, |feature| feature.value)
+}
+
+/// Returns the lookups that apply `features` for `script` and `language`, in the order they
+/// are applied, each with the features it belongs to. The language system's required
+/// feature applies everywhere.
+fn feature_lookups(layout: &LayoutTable,
+                   script: Tag,
+                   language: Option<Tag>,
+                   features: &[Feature])
+                   -> Vec<(u16, Vec<Feature>)> {
+    let mut selected: Vec<(u16, Vec<Feature>)> = vec![];
+    if let Some(index) = layout.required_feature_index(script, language) {
+        if let Some((tag, _)) = layout.feature(index) {
+            selected.push((index, vec![Feature::new(tag)]));
+        }
+    }
+    for index in layout.feature_indices(script, language) {
+        let tag = match layout.feature(index) {
+            Some((tag, _)) => tag,
+            None => continue,
+        };
+        let requested: Vec<Feature> =
+            features.iter().filter(|feature| feature.tag == tag).cloned().collect();
+        if !requested.is_empty() {
+            selected.push((index, requested));
+        }
+    }
+
+    let mut lookups: Vec<(u16, Vec<Feature>)> = vec![];
+    for (index, features) in selected {
+        let lookup_indices = layout.feature(index).map_or(vec![], |(_, lookups)| lookups);
+        for lookup_index in lookup_indices {
+            match lookups.iter_mut().find(|lookup| lookup.0 == lookup_index) {
+                Some(lookup) => lookup.1.extend(features.iter().cloned()),
+                None => lookups.push((lookup_index, features.clone())),
+            }
+        }
+    }
+    lookups.sort_by_key(|&(index, _)| index);
+    lookups
+}
+
+/// Returns the index of the first glyph after `position` that a lookup with `flags` doesn't
+/// pass over.
+fn next_glyph(glyphs: &[GlyphInfo], position: usize, flags: LookupFlags) -> Option<usize> {
+    (position + 1..glyphs.len()).find(|&index| !glyphs[index].is_ignored(flags))
+}
+
+/// Returns the index of the last glyph before `position` that a lookup with `flags` doesn't
+/// pass over.
+fn previous_glyph(glyphs: &[GlyphInfo], position: usize, flags: LookupFlags) -> Option<usize> {
+    (0..position).rev().find(|&index| !glyphs[index].is_ignored(flags))
+}
+
+/// Matches a contextual rule against the glyphs around `start`, whose glyph the rule's
+/// subtable has already matched. Returns the indices of the input glyphs, starting with
+/// `start`, if the rule matches.
+fn match_rule(glyphs: &[GlyphInfo], start: usize, rule: &ContextRule, flags: LookupFlags)
+              -> Option<Vec<usize>> {
+    let mut positions = vec![start];
+    for index in 0..rule.input.len() {
+        let position = next_glyph(glyphs, *positions.last()?, flags)?;
+        if !rule.input.matches(index, glyphs[position].glyph) {
+            return None;
+        }
+        positions.push(position);
+    }
+    let mut position = *positions.last()?;
+    for index in 0..rule.lookahead.len() {
+        position = next_glyph(glyphs, position, flags)?;
+        if !rule.lookahead.matches(index, glyphs[position].glyph) {
+            return None;
+        }
+    }
+    let mut position = start;
+    for index in 0..rule.backtrack.len() {
+        position = previous_glyph(glyphs, position, flags)?;
+        if !rule.backtrack.matches(index, glyphs[position].glyph) {
+            return None;
+        }
+    }
+    Some(positions)
+}
+
+/// What a glyph's offsets are relative to, once the glyph it's attached to has been placed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Attachment {
+    /// A mark attached to the glyph at the index, relative to that glyph's origin.
+    Mark(usize),
+    /// A glyph joined cursively to the glyph at the index, which it shares a baseline with.
+    Cursive(usize),
+}
+
+/// Turns the offsets of attached glyphs, which are relative to the glyphs they're attached to,
+/// into offsets from their own pen positions.
+fn resolve_attachments(attachments: &[Option<Attachment>],
+                       positions: &mut [GlyphPosition],
+                       direction: Direction) {
+    let parent = |index: usize| {
+        match attachments[index]? {
+            Attachment::Mark(parent) | Attachment::Cursive(parent) => Some(parent),
+        }
+    };
+    let mut resolved = vec![false; positions.len()];
+    for index in 0..positions.len() {
+        // Follow the chain of attachments up to a glyph that's already placed, and place the
+        // glyphs on the way back down.
+        let mut chain = vec![index];
+        while let Some(parent) = parent(*chain.last().unwrap()) {
+            if resolved[parent] || chain.contains(&parent) {
+                break;
+            }
+            chain.push(parent);
+        }
+        for &glyph in chain.iter().rev() {
+            if !resolved[glyph] {
+                resolve_attachment(attachments[glyph], glyph, positions, direction);
+                resolved[glyph] = true;
+            }
+        }
+    }
+}
+
+fn resolve_attachment(attachment: Option<Attachment>,
+                      index: usize,
+                      positions: &mut [GlyphPosition],
+                      direction: Direction) {
+    match attachment {
+        Some(Attachment::Cursive(parent)) => {
+            positions[index].y_offset += positions[parent].y_offset;
+        }
+        Some(Attachment::Mark(base)) => {
+            let base_position = positions[base];
+            positions[index].x_offset += base_position.x_offset;
+            positions[index].y_offset += base_position.y_offset;
+            // Marks come after their base in logical order, so the pen has moved from the
+            // base's origin by the advances between them.
+            let advance: CGFloat = match direction {
+                Direction::LeftToRight => {
+                    -positions[base..index].iter().map(|p| p.x_advance).sum::<CGFloat>()
+                }
+                Direction::RightToLeft => {
+                    positions[base + 1..index + 1].iter().map(|p| p.x_advance).sum()
+                }
+            };
+            positions[index].x_offset += advance;
+        }
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tables::gdef::Gdef;
+    use test_font::{self, A, GRAVE_COMB};
+    use super::{GlyphInfo, LookupFlags, MARK_GLYPH, USE_MARK_FILTERING_SET};
+
+    fn mark(glyph: u16) -> GlyphInfo {
+        GlyphInfo { glyph_class: MARK_GLYPH, ..GlyphInfo::new(glyph, 0) }
+    }
+
+    #[test]
+    fn passes_over_marks_outside_the_filtering_set() {
+        let data = test_font::gdef_table();
+        let gdef = Gdef::parse(&data).unwrap();
+        let flags = LookupFlags {
+            flags: USE_MARK_FILTERING_SET,
+            mark_glyph_set: gdef.mark_glyph_set(0),
+        };
+        assert!(!mark(GRAVE_COMB).is_ignored(flags));
+        assert!(mark(A).is_ignored(flags));
+        assert!(!GlyphInfo::new(A, 0).is_ignored(flags));
+    }
+
+    #[test]
+    fn passes_over_every_mark_without_the_filtering_set() {
+        let flags = LookupFlags { flags: USE_MARK_FILTERING_SET, mark_glyph_set: None };
+        assert!(mark(GRAVE_COMB).is_ignored(flags));
+        assert!(!mark(GRAVE_COMB).is_ignored(LookupFlags::new(USE_MARK_FILTERING_SET)));
+    }
+}
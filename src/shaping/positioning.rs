@@ -0,0 +1,361 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Applies `GPOS` lookups to the positions of a `GlyphBuffer`'s glyphs.
+
+use base::CGFloat;
+use shaping::{Attachment, Direction, Feature, GlyphBuffer, GlyphInfo, GlyphPosition};
+use shaping::LookupFlags;
+use shaping::{IGNORE_MARKS, KERN_FEATURE, MARK_GLYPH, MAX_NESTING_DEPTH, RIGHT_TO_LEFT};
+use shaping::{feature_lookups, feature_value, match_rule, next_glyph, previous_glyph};
+use shaping::resolve_attachments;
+use tables::gpos::{Anchor, Gpos, Positioning, ValueRecord};
+use tables::kern::Kern;
+use tag::Tag;
+
+use std::borrow::Cow;
+use std::cmp;
+
+/// Applies the lookups of `features` for `script` and `language`, and of the language
+/// system's required feature, to `positions`, which start out holding the advances of
+/// `buffer`'s glyphs. `scale` converts font units to points.
+///
+/// If `gpos` has a `GDEF` table, the glyphs' classes are looked up in it, as substitution does.
+pub fn position(gpos: &Gpos,
+                buffer: &GlyphBuffer,
+                script: Tag,
+                language: Option<Tag>,
+                features: &[Feature],
+                scale: CGFloat,
+                positions: &mut [GlyphPosition]) {
+    let mut buffer = Cow::Borrowed(buffer);
+    if let Some(gdef) = gpos.gdef() {
+        buffer.to_mut().set_glyph_classes(gdef);
+    }
+    let mut positioner = Positioner {
+        gpos,
+        glyphs: &buffer.glyphs,
+        positions,
+        attachments: vec![None; buffer.len()],
+        features: vec![],
+        scale,
+        direction: buffer.direction,
+    };
+    for (lookup_index, features) in feature_lookups(gpos.layout(), script, language, features) {
+        positioner.features = features;
+        positioner.apply_lookup(lookup_index);
+    }
+    resolve_attachments(&positioner.attachments, positioner.positions, buffer.direction);
+}
+
+/// Whether the `GPOS` table kerns `script` and `language` itself, so that the legacy `kern`
+/// table should be left alone.
+pub fn has_kerning(gpos: &Gpos, script: Tag, language: Option<Tag>) -> bool {
+    gpos.layout().has_feature(script, language, KERN_FEATURE)
+}
+
+/// Adds the legacy `kern` table's kerning to the advances in `positions`, for the glyphs the
+/// `kern` feature in `features` applies to. Marks are passed over when their class is known.
+pub fn apply_kern_table(kern: &Kern,
+                        buffer: &GlyphBuffer,
+                        features: &[Feature],
+                        scale: CGFloat,
+                        positions: &mut [GlyphPosition]) {
+    let features: Vec<Feature> =
+        features.iter().filter(|feature| feature.tag == KERN_FEATURE).cloned().collect();
+    let glyphs = &buffer.glyphs;
+    let mut position = 0;
+    while let Some(next) = next_glyph(glyphs, position, LookupFlags::new(IGNORE_MARKS)) {
+        if feature_value(&features, glyphs[position].cluster) != 0 {
+            let kerning = kern.kerning(glyphs[position].glyph, glyphs[next].glyph);
+            positions[position].x_advance += kerning as CGFloat * scale;
+        }
+        position = next;
+    }
+}
+
+struct Positioner<'a, 'b> {
+    gpos: &'b Gpos<'a>,
+    glyphs: &'b [GlyphInfo],
+    positions: &'b mut [GlyphPosition],
+    attachments: Vec<Option<Attachment>>,
+    /// The features of the lookup being applied.
+    features: Vec<Feature>,
+    scale: CGFloat,
+    direction: Direction,
+}
+
+impl<'a, 'b> Positioner<'a, 'b> {
+    /// Applies a lookup across the buffer, to the glyphs its features select.
+    fn apply_lookup(&mut self, lookup_index: u16) {
+        let (lookup, subtables) = match self.gpos.lookup(lookup_index) {
+            Some(lookup) => lookup,
+            None => return,
+        };
+        let flags = LookupFlags::for_lookup(&lookup, self.gpos.gdef());
+        let mut position = 0;
+        while position < self.glyphs.len() {
+            let info = self.glyphs[position];
+            if feature_value(&self.features, info.cluster) == 0 || info.is_ignored(flags) {
+                position += 1;
+                continue;
+            }
+            position = self.apply_subtables(&subtables, flags, position, 0)
+                           .unwrap_or(position + 1);
+        }
+    }
+
+    /// Applies the `lookup_index`th lookup to the glyph at `position` only, as contextual
+    /// lookups do.
+    fn apply_nested_lookup(&mut self, lookup_index: u16, position: usize, depth: usize) {
+        if let Some((lookup, subtables)) = self.gpos.lookup(lookup_index) {
+            let flags = LookupFlags::for_lookup(&lookup, self.gpos.gdef());
+            if !self.glyphs[position].is_ignored(flags) {
+                self.apply_subtables(&subtables, flags, position, depth);
+            }
+        }
+    }
+
+    /// Applies the first of a lookup's subtables that applies to the glyph at `position`.
+    /// Returns the position to carry on from, or `None` if no subtable applied.
+    fn apply_subtables(&mut self,
+                       subtables: &[Positioning<'a>],
+                       flags: LookupFlags,
+                       position: usize,
+                       depth: usize)
+                       -> Option<usize> {
+        subtables.iter().filter_map(|subtable| {
+            self.apply_subtable(subtable, flags, position, depth)
+        }).next()
+    }
+
+    fn apply_subtable(&mut self,
+                      subtable: &Positioning<'a>,
+                      flags: LookupFlags,
+                      position: usize,
+                      depth: usize)
+                      -> Option<usize> {
+        let info = self.glyphs[position];
+        match *subtable {
+            Positioning::Single(ref single) => {
+                self.adjust(position, single.value(info.glyph)?);
+                Some(position + 1)
+            }
+            Positioning::Pair(ref pair) => {
+                let next = next_glyph(self.glyphs, position, flags)?;
+                let (first_value, second_value) = pair.values(info.glyph,
+                                                              self.glyphs[next].glyph)?;
+                self.adjust(position, first_value);
+                self.adjust(next, second_value);
+                // A second glyph the subtable left alone can start the next pair.
+                Some(if pair.adjusts_second_glyph() { next + 1 } else { next })
+            }
+            Positioning::Cursive(ref cursive) => {
+                let entry = cursive.anchors(info.glyph)?.0?;
+                let previous = previous_glyph(self.glyphs, position, flags)?;
+                let exit = cursive.anchors(self.glyphs[previous].glyph)?.1?;
+                self.join(previous, exit, position, entry, flags);
+                Some(position + 1)
+            }
+            Positioning::MarkToBase(ref attachment) => {
+                let (class, mark_anchor) = attachment.mark(info.glyph)?;
+                let base = self.previous_base(position, |glyph| {
+                    attachment.covers_mark(glyph)
+                })?;
+                let base_anchor = attachment.base_anchor(self.glyphs[base].glyph, class)?;
+                self.attach(position, mark_anchor, base, base_anchor);
+                Some(position + 1)
+            }
+            Positioning::MarkToLigature(ref attachment) => {
+                let (class, mark_anchor) = attachment.mark(info.glyph)?;
+                let ligature = self.previous_base(position, |glyph| {
+                    attachment.covers_mark(glyph)
+                })?;
+                let ligature_info = self.glyphs[ligature];
+                let component_count = attachment.component_count(ligature_info.glyph)?;
+                if component_count == 0 {
+                    return None;
+                }
+                // Marks that were between the ligature's components when it formed go on the
+                // component they followed. Marks after it go on its last component.
+                let component = if info.ligature_id != 0 &&
+                                   info.ligature_id == ligature_info.ligature_id &&
+                                   info.ligature_component > 0 {
+                    cmp::min(info.ligature_component, component_count) - 1
+                } else {
+                    component_count - 1
+                };
+                let ligature_anchor = attachment.ligature_anchor(ligature_info.glyph,
+                                                                 component,
+                                                                 class)?;
+                self.attach(position, mark_anchor, ligature, ligature_anchor);
+                Some(position + 1)
+            }
+            Positioning::MarkToMark(ref attachment) => {
+                let (class, mark_anchor) = attachment.mark(info.glyph)?;
+                let previous = previous_glyph(self.glyphs, position, flags)?;
+                let previous_info = self.glyphs[previous];
+                // Both marks must be on the same glyph, or the same component of a ligature,
+                // unless one of them is itself a ligature of marks.
+                let is_mark_ligature = |info: GlyphInfo| {
+                    info.ligature_id != 0 && info.ligature_component == 0
+                };
+                let same_base = if previous_info.ligature_id == info.ligature_id {
+                    info.ligature_id == 0 ||
+                    previous_info.ligature_component == info.ligature_component
+                } else {
+                    is_mark_ligature(info) || is_mark_ligature(previous_info)
+                };
+                if (previous_info.glyph_class != 0 && previous_info.glyph_class != MARK_GLYPH) ||
+                   !same_base {
+                    return None;
+                }
+                let base_anchor = attachment.base_anchor(previous_info.glyph, class)?;
+                self.attach(position, mark_anchor, previous, base_anchor);
+                Some(position + 1)
+            }
+            Positioning::Context(ref context) => {
+                let (rule, positions) = context.rules(info.glyph).into_iter().filter_map(|rule| {
+                    let positions = match_rule(self.glyphs, position, &rule, flags)?;
+                    Some((rule, positions))
+                }).next()?;
+                if depth < MAX_NESTING_DEPTH {
+                    for (sequence_index, lookup_index) in rule.lookups() {
+                        if let Some(&target) = positions.get(sequence_index as usize) {
+                            self.apply_nested_lookup(lookup_index, target, depth + 1);
+                        }
+                    }
+                }
+                Some(positions.last()? + 1)
+            }
+        }
+    }
+
+    /// Returns the glyph before the mark at `position` that it attaches to, passing over
+    /// other marks, whatever the lookup's own flags. Glyphs without a known class count as
+    /// marks if `is_mark` says so.
+    fn previous_base<F>(&self, position: usize, is_mark: F) -> Option<usize>
+        where F: Fn(u16) -> bool
+    {
+        let mut position = position;
+        loop {
+            position = previous_glyph(self.glyphs, position, LookupFlags::new(IGNORE_MARKS))?;
+            let info = self.glyphs[position];
+            if info.glyph_class != 0 || !is_mark(info.glyph) {
+                return Some(position);
+            }
+        }
+    }
+
+    fn adjust(&mut self, position: usize, value: ValueRecord) {
+        let scale = self.scale;
+        let glyph_position = &mut self.positions[position];
+        glyph_position.x_offset += value.x_placement as CGFloat * scale;
+        glyph_position.y_offset += value.y_placement as CGFloat * scale;
+        glyph_position.x_advance += value.x_advance as CGFloat * scale;
+        glyph_position.y_advance += value.y_advance as CGFloat * scale;
+    }
+
+    /// Moves the mark at `mark` so that its anchor meets `base_anchor` on the glyph at `base`.
+    fn attach(&mut self, mark: usize, mark_anchor: Anchor, base: usize, base_anchor: Anchor) {
+        let glyph_position = &mut self.positions[mark];
+        glyph_position.x_offset = (base_anchor.x as CGFloat - mark_anchor.x as CGFloat) *
+                                  self.scale;
+        glyph_position.y_offset = (base_anchor.y as CGFloat - mark_anchor.y as CGFloat) *
+                                  self.scale;
+        self.attachments[mark] = Some(Attachment::Mark(base));
+    }
+
+    /// Joins the exit anchor of the glyph at `previous` to the entry anchor of the glyph at
+    /// `next`. Advances close the gap between them along the line; across it, the later glyph
+    /// in logical order moves to meet the earlier, unless the lookup is right to left.
+    fn join(&mut self,
+            previous: usize,
+            exit: Anchor,
+            next: usize,
+            entry: Anchor,
+            flags: LookupFlags) {
+        let scale = self.scale;
+        let (exit_x, entry_x) = (exit.x as CGFloat * scale, entry.x as CGFloat * scale);
+        if self.direction == Direction::LeftToRight {
+            self.positions[previous].x_advance = exit_x + self.positions[previous].x_offset;
+            let distance = entry_x + self.positions[next].x_offset;
+            self.positions[next].x_advance -= distance;
+            self.positions[next].x_offset -= distance;
+        } else {
+            let distance = exit_x + self.positions[previous].x_offset;
+            self.positions[previous].x_advance -= distance;
+            self.positions[previous].x_offset -= distance;
+            self.positions[next].x_advance = entry_x + self.positions[next].x_offset;
+        }
+
+        let y_offset = (exit.y as CGFloat - entry.y as CGFloat) * scale;
+        let (child, parent, y_offset) = if flags.flags & RIGHT_TO_LEFT != 0 {
+            (previous, next, -y_offset)
+        } else {
+            (next, previous, y_offset)
+        };
+        // A glyph can't hang off its own child.
+        if self.attachments[parent] == Some(Attachment::Cursive(child)) {
+            self.attachments[parent] = None;
+        }
+        self.positions[child].y_offset = y_offset;
+        self.attachments[child] = Some(Attachment::Cursive(parent));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base::kCTFontHorizontalOrientation;
+    use shaping::{Feature, GlyphBuffer, GlyphPosition};
+    use tables::gdef::Gdef;
+    use tables::gpos::Gpos;
+    use tag::{Tag, kCTFontTableGDEF, kCTFontTableGPOS};
+    use test_font::{self, GRAVE_COMB, LOWER_A, T, UNITS_PER_EM};
+    use super::position;
+
+    const LATIN: Tag = Tag::new(b"latn");
+
+    /// Positions `string` in font units.
+    fn position_str(string: &str, features: &[Feature]) -> (GlyphBuffer, Vec<GlyphPosition>) {
+        let font = test_font::dejavu_sans(UNITS_PER_EM);
+        let gpos_table = font.get_font_table(kCTFontTableGPOS).unwrap();
+        let gdef_table = font.get_font_table(kCTFontTableGDEF).unwrap();
+        let gpos = Gpos::parse(&gpos_table).unwrap().with_gdef(Gdef::parse(&gdef_table).unwrap());
+        let buffer = GlyphBuffer::from_str(&font, string);
+        let advances = font.advances_for_glyphs(kCTFontHorizontalOrientation, &buffer.glyph_ids());
+        let mut positions: Vec<GlyphPosition> = advances.iter().map(|advance| {
+            GlyphPosition::new(advance.width, advance.height)
+        }).collect();
+        position(&gpos, &buffer, LATIN, None, features, 1.0, &mut positions);
+        (buffer, positions)
+    }
+
+    #[test]
+    fn kerns_pairs() {
+        let (buffer, positions) = position_str("Ta", &[Feature::new(Tag::new(b"kern"))]);
+        assert_eq!(buffer.glyph_ids(), vec![T, LOWER_A]);
+        assert_eq!(positions[0], GlyphPosition::new(912.0, 0.0));
+
+        let (_, positions) = position_str("Ta", &[]);
+        assert_eq!(positions[0], GlyphPosition::new(1251.0, 0.0));
+    }
+
+    #[test]
+    fn attaches_marks_to_bases() {
+        let (buffer, positions) = position_str("T\u{300}", &[Feature::new(Tag::new(b"mark"))]);
+        assert_eq!(buffer.glyph_ids(), vec![T, GRAVE_COMB]);
+        assert_eq!(positions[1], GlyphPosition {
+            x_advance: 0.0,
+            y_advance: 0.0,
+            x_offset: -112.0,
+            y_offset: 373.0,
+        });
+    }
+}
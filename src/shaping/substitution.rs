@@ -0,0 +1,279 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Applies `GSUB` lookups to a `GlyphBuffer`.
+
+use base::CGGlyph;
+use shaping::{Feature, GlyphBuffer, GlyphInfo, LookupFlags, MAX_NESTING_DEPTH};
+use shaping::{feature_lookups, feature_value, match_rule, next_glyph};
+use tables::gsub::{Gsub, Substitution};
+use tag::Tag;
+
+/// Applies the lookups of `features` for `script` and `language`, and of the language
+/// system's required feature, to `buffer`, in the order the font lists the lookups.
+///
+/// With no `language`, the script's default language system is used; scripts the font doesn't
+/// know fall back to `DFLT`. If `gsub` has a `GDEF` table, the glyphs' classes are looked up
+/// in it first.
+pub fn substitute(gsub: &Gsub,
+                  buffer: &mut GlyphBuffer,
+                  script: Tag,
+                  language: Option<Tag>,
+                  features: &[Feature]) {
+    if let Some(gdef) = gsub.gdef() {
+        buffer.set_glyph_classes(gdef);
+    }
+    let last_ligature_id = buffer.glyphs.iter().map(|info| info.ligature_id).max().unwrap_or(0);
+    let mut substituter = Substituter {
+        gsub,
+        glyphs: &mut buffer.glyphs,
+        features: vec![],
+        last_ligature_id,
+    };
+    for (lookup_index, features) in feature_lookups(gsub.layout(), script, language, features) {
+        substituter.features = features;
+        substituter.apply_lookup(lookup_index);
+    }
+}
+
+struct Substituter<'a, 'b> {
+    gsub: &'b Gsub<'a>,
+    glyphs: &'b mut Vec<GlyphInfo>,
+    /// The features of the lookup being applied.
+    features: Vec<Feature>,
+    last_ligature_id: u16,
+}
+
+impl<'a, 'b> Substituter<'a, 'b> {
+    /// Applies a lookup across the buffer, to the glyphs its features select.
+    fn apply_lookup(&mut self, lookup_index: u16) {
+        let (lookup, subtables) = match self.gsub.lookup(lookup_index) {
+            Some(lookup) => lookup,
+            None => return,
+        };
+        let flags = LookupFlags::for_lookup(&lookup, self.gsub.gdef());
+        let is_reverse = subtables.iter().any(|subtable| {
+            matches!(*subtable, Substitution::ReverseChainedSingle(_))
+        });
+
+        if is_reverse {
+            // Reverse chained substitutions replace one glyph with one, so the buffer's length
+            // doesn't change under us.
+            for position in (0..self.glyphs.len()).rev() {
+                let info = self.glyphs[position];
+                let value = feature_value(&self.features, info.cluster);
+                if value != 0 && !info.is_ignored(flags) {
+                    self.apply_subtables(&subtables, flags, position, value, 0);
+                }
+            }
+            return;
+        }
+
+        let mut position = 0;
+        while position < self.glyphs.len() {
+            let info = self.glyphs[position];
+            let value = feature_value(&self.features, info.cluster);
+            if value == 0 || info.is_ignored(flags) {
+                position += 1;
+                continue;
+            }
+            position = self.apply_subtables(&subtables, flags, position, value, 0)
+                           .unwrap_or(position + 1);
+        }
+    }
+
+    /// Applies the `lookup_index`th lookup to the glyph at `position` only, as contextual
+    /// lookups do. Returns how much longer the buffer got.
+    fn apply_nested_lookup(&mut self, lookup_index: u16, position: usize, value: u32,
+                           depth: usize)
+                           -> isize {
+        let (lookup, subtables) = match self.gsub.lookup(lookup_index) {
+            Some(lookup) => lookup,
+            None => return 0,
+        };
+        let flags = LookupFlags::for_lookup(&lookup, self.gsub.gdef());
+        if self.glyphs[position].is_ignored(flags) {
+            return 0;
+        }
+        let length = self.glyphs.len();
+        self.apply_subtables(&subtables, flags, position, value, depth);
+        self.glyphs.len() as isize - length as isize
+    }
+
+    /// Applies the first of a lookup's subtables that applies to the glyph at `position`.
+    /// Returns the position to carry on from, after the glyphs it produced, or `None` if no
+    /// subtable applied.
+    fn apply_subtables(&mut self,
+                       subtables: &[Substitution<'a>],
+                       flags: LookupFlags,
+                       position: usize,
+                       value: u32,
+                       depth: usize)
+                       -> Option<usize> {
+        subtables.iter().filter_map(|subtable| {
+            self.apply_subtable(subtable, flags, position, value, depth)
+        }).next()
+    }
+
+    fn apply_subtable(&mut self,
+                      subtable: &Substitution<'a>,
+                      flags: LookupFlags,
+                      position: usize,
+                      value: u32,
+                      depth: usize)
+                      -> Option<usize> {
+        let glyph = self.glyphs[position].glyph;
+        match *subtable {
+            Substitution::Single(ref single) => {
+                self.replace_glyph(position, single.substitute(glyph)?);
+                Some(position + 1)
+            }
+            Substitution::Multiple(ref multiple) => {
+                let sequence = multiple.sequence(glyph)?;
+                let info = self.glyphs[position];
+                let gsub = self.gsub;
+                let replacement = sequence.iter().map(|&glyph| {
+                    let mut replacement = GlyphInfo {
+                        glyph,
+                        ..info
+                    };
+                    replacement.set_classes(gsub.gdef());
+                    replacement
+                });
+                self.glyphs.splice(position..position + 1, replacement);
+                Some(position + sequence.len())
+            }
+            Substitution::Alternate(ref alternate) => {
+                let alternates = alternate.sequence(glyph)?;
+                self.replace_glyph(position, *alternates.get(value as usize - 1)?);
+                Some(position + 1)
+            }
+            Substitution::Ligature(ref substitution) => {
+                let ligatures = substitution.ligatures(glyph);
+                let (ligature, components) = ligatures.iter().filter_map(|ligature| {
+                    let mut components = vec![position];
+                    for &component in &ligature.components {
+                        let next = next_glyph(self.glyphs, *components.last()?, flags)?;
+                        let info = self.glyphs[next];
+                        // Every component must be in the range the feature applies to.
+                        if info.glyph != component ||
+                           feature_value(&self.features, info.cluster) == 0 {
+                            return None;
+                        }
+                        components.push(next);
+                    }
+                    Some((ligature.glyph, components))
+                }).next()?;
+                self.form_ligature(ligature, &components);
+                Some(position + 1)
+            }
+            Substitution::Context(ref context) => {
+                let (rule, mut positions) = context.rules(glyph).into_iter().filter_map(|rule| {
+                    let positions = match_rule(self.glyphs, position, &rule, flags)?;
+                    Some((rule, positions))
+                }).next()?;
+                if depth < MAX_NESTING_DEPTH {
+                    for (sequence_index, lookup_index) in rule.lookups() {
+                        let target = match positions.get(sequence_index as usize) {
+                            Some(&target) if target < self.glyphs.len() => target,
+                            _ => continue,
+                        };
+                        let delta = self.apply_nested_lookup(lookup_index, target, value,
+                                                             depth + 1);
+                        for later in &mut positions[sequence_index as usize + 1..] {
+                            *later = (*later as isize + delta).max(target as isize) as usize;
+                        }
+                    }
+                }
+                Some((positions.last()? + 1).min(self.glyphs.len()).max(position + 1))
+            }
+            Substitution::ReverseChainedSingle(ref reverse) => {
+                let (rule, substitute) = reverse.substitute(glyph)?;
+                match_rule(self.glyphs, position, &rule, flags)?;
+                self.replace_glyph(position, substitute);
+                Some(position + 1)
+            }
+        }
+    }
+
+    /// Replaces the glyph at `position`, and looks up the new glyph's classes.
+    fn replace_glyph(&mut self, position: usize, glyph: CGGlyph) {
+        let info = &mut self.glyphs[position];
+        info.glyph = glyph;
+        info.set_classes(self.gsub.gdef());
+    }
+
+    /// Replaces the glyphs at `components`, in order, with `ligature`. Glyphs the lookup
+    /// passed over between them, such as marks, stay after the ligature and remember which
+    /// component they followed.
+    fn form_ligature(&mut self, ligature: CGGlyph, components: &[usize]) {
+        let (first, last) = (components[0], components[components.len() - 1]);
+        let cluster = self.glyphs[first..last + 1].iter().map(|info| info.cluster).min();
+        self.last_ligature_id = self.last_ligature_id.wrapping_add(1).max(1);
+
+        let mut component = 0;
+        for index in first..last + 1 {
+            if components.contains(&index) {
+                component += 1;
+            } else {
+                self.glyphs[index].ligature_id = self.last_ligature_id;
+                self.glyphs[index].ligature_component = component;
+            }
+            self.glyphs[index].cluster = cluster.unwrap_or(self.glyphs[index].cluster);
+        }
+        self.replace_glyph(first, ligature);
+        self.glyphs[first].ligature_id = self.last_ligature_id;
+        self.glyphs[first].ligature_component = 0;
+        for &index in components[1..].iter().rev() {
+            self.glyphs.remove(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shaping::{Feature, GlyphBuffer, LIGATURE_GLYPH};
+    use tables::gdef::Gdef;
+    use tables::gsub::Gsub;
+    use tag::{Tag, kCTFontTableGDEF, kCTFontTableGSUB};
+    use test_font::{self, FI, LOWER_F, LOWER_I, LOWER_X};
+    use super::substitute;
+
+    const LATIN: Tag = Tag::new(b"latn");
+    const LIGA: Tag = Tag::new(b"liga");
+
+    fn substitute_str(string: &str, features: &[Feature]) -> GlyphBuffer {
+        let font = test_font::dejavu_sans(12.0);
+        let gsub_table = font.get_font_table(kCTFontTableGSUB).unwrap();
+        let gdef_table = font.get_font_table(kCTFontTableGDEF).unwrap();
+        let gsub = Gsub::parse(&gsub_table).unwrap().with_gdef(Gdef::parse(&gdef_table).unwrap());
+        let mut buffer = GlyphBuffer::from_str(&font, string);
+        substitute(&gsub, &mut buffer, LATIN, None, features);
+        buffer
+    }
+
+    #[test]
+    fn forms_ligatures() {
+        let buffer = substitute_str("fix", &[Feature::new(LIGA)]);
+        assert_eq!(buffer.glyph_ids(), vec![FI, LOWER_X]);
+        let clusters: Vec<usize> = buffer.glyphs.iter().map(|info| info.cluster).collect();
+        assert_eq!(clusters, vec![0, 2]);
+        assert_eq!(buffer.glyphs[0].glyph_class, LIGATURE_GLYPH);
+        assert_ne!(buffer.glyphs[0].ligature_id, 0);
+    }
+
+    #[test]
+    fn leaves_ligatures_alone_where_the_feature_is_off() {
+        let buffer = substitute_str("fix", &[Feature::new(LIGA).with_value(0)]);
+        assert_eq!(buffer.glyph_ids(), vec![LOWER_F, LOWER_I, LOWER_X]);
+
+        let buffer = substitute_str("fixfi", &[Feature::new(LIGA).with_range(3, 5)]);
+        assert_eq!(buffer.glyph_ids(), vec![LOWER_F, LOWER_I, LOWER_X, FI]);
+    }
+}
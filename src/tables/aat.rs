@@ -0,0 +1,218 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Structures shared by the Apple Advanced Typography tables, such as `morx` and `kerx`: lookup
+//! tables, which map glyphs to values, and extended state tables, which drive the finite-state
+//! machines that AAT subtables run over glyphs.
+
+use parser::{self, Reader};
+
+use std::cmp::Ordering;
+
+// Classes that every extended state table has.
+pub const END_OF_TEXT: u16 = 0;
+pub const OUT_OF_BOUNDS: u16 = 1;
+pub const DELETED_GLYPH: u16 = 2;
+pub const END_OF_LINE: u16 = 3;
+
+/// The state machines start in.
+pub const START_OF_TEXT: u16 = 0;
+
+/// The glyph ID `morx` subtables give glyphs they delete, which state tables put in the
+/// `DELETED_GLYPH` class.
+pub const DELETED_GLYPH_ID: u16 = 0xFFFF;
+
+const STATE_TABLE_HEADER_SIZE: usize = 16;
+const ENTRY_HEADER_SIZE: usize = 4;
+
+/// An AAT lookup table, which maps glyphs to 16-bit values, in any of its formats.
+#[derive(Clone, Copy, Debug)]
+pub struct LookupTable<'a> {
+    data: &'a [u8],
+    format: u16,
+}
+
+impl<'a> LookupTable<'a> {
+    pub fn parse(data: &'a [u8]) -> Option<LookupTable<'a>> {
+        let format = parser::read_u16(data, 0)?;
+        match format {
+            0 | 2 | 4 | 6 | 8 | 10 => {
+                Some(LookupTable {
+                    data,
+                    format,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the glyph's value, or `None` if the table doesn't have one.
+    pub fn value(&self, glyph: u16) -> Option<u16> {
+        // Binary searched tables can end with an entry for glyph 0xFFFF, which marks the end
+        // rather than being a real glyph.
+        if glyph == DELETED_GLYPH_ID && matches!(self.format, 2 | 4 | 6) {
+            return None;
+        }
+        let data = self.data;
+        match self.format {
+            0 => parser::read_u16(data, 2 + glyph as usize * 2),
+            2 | 4 => {
+                let segment = self.search(|segment| {
+                    let last_glyph = parser::read_u16(segment, 0)?;
+                    let first_glyph = parser::read_u16(segment, 2)?;
+                    Some(if last_glyph < glyph {
+                        Ordering::Less
+                    } else if first_glyph > glyph {
+                        Ordering::Greater
+                    } else {
+                        Ordering::Equal
+                    })
+                })?;
+                let first_glyph = parser::read_u16(segment, 2)?;
+                let value = parser::read_u16(segment, 4)?;
+                if self.format == 2 {
+                    return Some(value);
+                }
+                // Format 4 segments point to arrays of values, one per glyph.
+                parser::read_u16(data, value as usize + (glyph - first_glyph) as usize * 2)
+            }
+            6 => {
+                let entry = self.search(|entry| Some(parser::read_u16(entry, 0)?.cmp(&glyph)))?;
+                parser::read_u16(entry, 2)
+            }
+            8 => {
+                let mut reader = Reader::at(data, 2);
+                let first_glyph = reader.read_u16()?;
+                let count = reader.read_u16()?;
+                if glyph < first_glyph || glyph - first_glyph >= count {
+                    return None;
+                }
+                parser::read_u16(data, 6 + (glyph - first_glyph) as usize * 2)
+            }
+            10 => {
+                let mut reader = Reader::at(data, 2);
+                let value_size = reader.read_u16()? as usize;
+                let first_glyph = reader.read_u16()?;
+                let count = reader.read_u16()?;
+                if glyph < first_glyph || glyph - first_glyph >= count {
+                    return None;
+                }
+                let offset = 8 + (glyph - first_glyph) as usize * value_size;
+                match value_size {
+                    1 => parser::read_u8(data, offset).map(|value| value as u16),
+                    2 => parser::read_u16(data, offset),
+                    4 => parser::read_u32(data, offset).filter(|&value| value <= 0xFFFF)
+                                                       .map(|value| value as u16),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Binary searches the units of a format 2, 4 or 6 table, which follow a header giving
+    /// their size and number.
+    fn search<F>(&self, compare: F) -> Option<&'a [u8]>
+        where F: Fn(&'a [u8]) -> Option<Ordering>
+    {
+        let mut reader = Reader::at(self.data, 2);
+        let unit_size = reader.read_u16()? as usize;
+        let count = reader.read_u16()? as usize;
+        let units = self.data.get(12..)?;
+        let unit = |index: usize| units.get(index * unit_size..(index + 1) * unit_size);
+        let index = parser::binary_search(count, |index| compare(unit(index)?))?;
+        unit(index)
+    }
+}
+
+/// An entry of an extended state table: the state to go to next, flags saying what to do, and
+/// data whose meaning depends on the subtable.
+#[derive(Clone, Copy, Debug)]
+pub struct Entry<'a> {
+    pub new_state: u16,
+    pub flags: u16,
+    data: &'a [u8],
+}
+
+impl<'a> Entry<'a> {
+    /// Returns the `index`th 16-bit field of the entry's subtable-specific data.
+    pub fn value(&self, index: usize) -> Option<u16> {
+        parser::read_u16(self.data, index * 2)
+    }
+}
+
+/// An extended state table, as `morx` and `kerx` subtables use.
+#[derive(Clone, Copy, Debug)]
+pub struct StateTable<'a> {
+    data: &'a [u8],
+    class_count: u32,
+    classes: LookupTable<'a>,
+    states: &'a [u8],
+    entries: &'a [u8],
+    entry_size: usize,
+}
+
+impl<'a> StateTable<'a> {
+    /// Parses the state table at the start of `data`, whose entries have `data_size` bytes of
+    /// subtable-specific data.
+    pub fn parse(data: &'a [u8], data_size: usize) -> Option<StateTable<'a>> {
+        let mut reader = Reader::new(data);
+        let class_count = reader.read_u32()?;
+        let classes = LookupTable::parse(data.get(reader.read_u32()? as usize..)?)?;
+        let states = data.get(reader.read_u32()? as usize..)?;
+        let entries = data.get(reader.read_u32()? as usize..)?;
+        if class_count <= END_OF_LINE as u32 {
+            return None;
+        }
+        Some(StateTable {
+            data,
+            class_count,
+            classes,
+            states,
+            entries,
+            entry_size: ENTRY_HEADER_SIZE + data_size,
+        })
+    }
+
+    /// The subtable-specific header fields that follow the state table's header.
+    pub fn extra_header(&self) -> &'a [u8] {
+        &self.data[STATE_TABLE_HEADER_SIZE..]
+    }
+
+    /// The data the state table's header starts, which subtable-specific offsets are relative
+    /// to.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Returns the glyph's class. Glyphs the class table doesn't have are out of bounds.
+    pub fn class(&self, glyph: u16) -> u16 {
+        if glyph == DELETED_GLYPH_ID {
+            return DELETED_GLYPH;
+        }
+        match self.classes.value(glyph) {
+            Some(class) if (class as u32) < self.class_count => class,
+            _ => OUT_OF_BOUNDS,
+        }
+    }
+
+    /// Returns the entry for a glyph of `class` in `state`.
+    pub fn entry(&self, state: u16, class: u16) -> Option<Entry<'a>> {
+        let class = if (class as u32) < self.class_count { class } else { OUT_OF_BOUNDS };
+        let index = state as usize * self.class_count as usize + class as usize;
+        let entry_index = parser::read_u16(self.states, index * 2)? as usize;
+        let entry = Reader::at(self.entries, entry_index * self.entry_size)
+            .read_bytes(self.entry_size)?;
+        Some(Entry {
+            new_state: parser::read_u16(entry, 0)?,
+            flags: parser::read_u16(entry, 2)?,
+            data: &entry[ENTRY_HEADER_SIZE..],
+        })
+    }
+}
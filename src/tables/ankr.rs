@@ -0,0 +1,61 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The anchor point table, `ankr`, which lists the points that `kerx` attachment subtables
+//! join glyphs at.
+
+use error::{Error, Result};
+use parser::Reader;
+use tables::aat::LookupTable;
+use tables::gpos::Anchor;
+use tag::kCTFontTableAnkr;
+
+/// An `ankr` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Ankr<'a> {
+    lookup: LookupTable<'a>,
+    /// The glyph data table, which the lookup table's values are offsets into.
+    glyph_data: &'a [u8],
+}
+
+impl<'a> Ankr<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Ankr<'a>> {
+        Ankr::parse_table(data).ok_or(Error::MalformedTable(kCTFontTableAnkr))
+    }
+
+    fn parse_table(data: &'a [u8]) -> Option<Ankr<'a>> {
+        let mut reader = Reader::new(data);
+        let version = reader.read_u16()?;
+        reader.skip(2)?;
+        let lookup_offset = reader.read_u32()? as usize;
+        let glyph_data_offset = reader.read_u32()? as usize;
+        if version != 0 {
+            return None;
+        }
+        Some(Ankr {
+            lookup: LookupTable::parse(data.get(lookup_offset..)?)?,
+            glyph_data: data.get(glyph_data_offset..)?,
+        })
+    }
+
+    /// Returns the glyph's `index`th anchor point, in font units.
+    pub fn anchor(&self, glyph: u16, index: u16) -> Option<Anchor> {
+        let mut reader = Reader::at(self.glyph_data, self.lookup.value(glyph)? as usize);
+        let count = reader.read_u32()?;
+        if index as u32 >= count {
+            return None;
+        }
+        reader.skip(index as usize * 4)?;
+        Some(Anchor {
+            x: reader.read_i16()?,
+            y: reader.read_i16()?,
+        })
+    }
+}
+
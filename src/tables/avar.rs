@@ -0,0 +1,119 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `avar` table, which bends the linear normalization of a variable font's axes.
+
+use error::{Error, Result};
+use parser::{self, Reader};
+use tag::kCTFontTableAvar;
+
+/// An `avar` table. Version 2 tables are read for their segment maps alone; their
+/// cross-axis mappings aren't applied.
+#[derive(Clone, Copy, Debug)]
+pub struct Avar<'a> {
+    /// The segment maps, one per `fvar` axis in order.
+    segment_maps: &'a [u8],
+    axis_count: u16,
+}
+
+impl<'a> Avar<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Avar<'a>> {
+        Avar::parse_table(data).ok_or(Error::MalformedTable(kCTFontTableAvar))
+    }
+
+    fn parse_table(data: &'a [u8]) -> Option<Avar<'a>> {
+        let mut reader = Reader::new(data);
+        let major_version = reader.read_u16()?;
+        reader.skip(4)?; // minor version, reserved
+        let axis_count = reader.read_u16()?;
+        if major_version != 1 && major_version != 2 {
+            return None;
+        }
+        Some(Avar {
+            segment_maps: data.get(8..)?,
+            axis_count,
+        })
+    }
+
+    pub fn axis_count(&self) -> u16 {
+        self.axis_count
+    }
+
+    /// Maps a coordinate of the `axis`th axis, normalized by `AxisRecord::normalize`, through
+    /// the axis's segment map, rounding the result to 2.14 fixed point. Axes without a usable
+    /// map are left alone.
+    pub fn map(&self, axis: u16, coordinate: f64) -> f64 {
+        self.segment_map(axis)
+            .and_then(|map| map_coordinate(map, coordinate))
+            .map_or(coordinate, parser::round_to_f2dot14)
+    }
+
+    fn segment_map(&self, axis: u16) -> Option<&'a [u8]> {
+        if axis >= self.axis_count {
+            return None;
+        }
+        let mut reader = Reader::new(self.segment_maps);
+        for _ in 0..axis {
+            let count = reader.read_u16()?;
+            reader.skip(count as usize * 4)?;
+        }
+        let count = reader.read_u16()?;
+        reader.read_bytes(count as usize * 4)
+    }
+}
+
+/// Interpolates a coordinate between the `(from, to)` pairs of a segment map, which are
+/// sorted by `from`. Coordinates beyond the ends move with them.
+fn map_coordinate(map: &[u8], coordinate: f64) -> Option<f64> {
+    let mut reader = Reader::new(map);
+    let mut previous: Option<(f64, f64)> = None;
+    for _ in 0..map.len() / 4 {
+        let from = reader.read_f2dot14()?;
+        let to = reader.read_f2dot14()?;
+        if coordinate == from {
+            return Some(to);
+        }
+        if coordinate < from {
+            return Some(match previous {
+                Some((previous_from, previous_to)) => {
+                    previous_to + (to - previous_to) * (coordinate - previous_from) /
+                                  (from - previous_from)
+                }
+                None => coordinate - from + to,
+            });
+        }
+        previous = Some((from, to));
+    }
+    previous.map(|(from, to)| coordinate - from + to)
+}
+
+#[cfg(test)]
+mod tests {
+    use test_font;
+    use super::Avar;
+
+    #[test]
+    fn maps_coordinates_through_a_segment_map() {
+        // One axis, mapping -1 to -1, 0 to 0, 0.5 to 0.75 and 1 to 1.
+        let data = test_font::words(&[1, 0, 0, 1, 4,
+                                      -16384, -16384, 0, 0, 8192, 12288, 16384, 16384]);
+        let avar = Avar::parse(&data).unwrap();
+        assert_eq!(avar.map(0, -1.0), -1.0);
+        assert_eq!(avar.map(0, -0.5), -0.5);
+        assert_eq!(avar.map(0, 0.0), 0.0);
+        assert_eq!(avar.map(0, 0.25), 0.375);
+        assert_eq!(avar.map(0, 0.5), 0.75);
+        assert_eq!(avar.map(0, 0.75), 0.875);
+        assert_eq!(avar.map(0, 1.0), 1.0);
+        // 0.15 is 2457.6 units of 1/16384.
+        assert_eq!(avar.map(0, 0.1), 2458.0 / 16384.0);
+
+        assert_eq!(avar.map(1, 0.25), 0.25);
+    }
+}
@@ -0,0 +1,213 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `CFF ` table, which holds PostScript glyph outlines made of cubic curves.
+
+use error::{Error, Result};
+use outline::{self, OutlineSink, PathElement};
+use parser::Reader;
+use tables::compact::{self, CharstringContext, Dict, Index, DICT_CHARSET, DICT_CHARSTRING_TYPE,
+                      DICT_CHAR_STRINGS, DICT_FD_ARRAY, DICT_FD_SELECT, DICT_ROS};
+use tag::kCTFontTableCFF;
+
+/// The String IDs of the Standard Encoding's characters, which `seac` accents refer to.
+static STANDARD_ENCODING: [u8; 256] = [
+      0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,
+      1,   2,   3,   4,   5,   6,   7,   8,   9,  10,  11,  12,  13,  14,  15,  16,
+     17,  18,  19,  20,  21,  22,  23,  24,  25,  26,  27,  28,  29,  30,  31,  32,
+     33,  34,  35,  36,  37,  38,  39,  40,  41,  42,  43,  44,  45,  46,  47,  48,
+     49,  50,  51,  52,  53,  54,  55,  56,  57,  58,  59,  60,  61,  62,  63,  64,
+     65,  66,  67,  68,  69,  70,  71,  72,  73,  74,  75,  76,  77,  78,  79,  80,
+     81,  82,  83,  84,  85,  86,  87,  88,  89,  90,  91,  92,  93,  94,  95,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,
+      0,  96,  97,  98,  99, 100, 101, 102, 103, 104, 105, 106, 107, 108, 109, 110,
+      0, 111, 112, 113, 114,   0, 115, 116, 117, 118, 119, 120, 121, 122,   0, 123,
+      0, 124, 125, 126, 127, 128, 129, 130, 131,   0, 132, 133,   0, 134, 135, 136,
+    137,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,
+      0, 138,   0, 139,   0,   0,   0,   0, 140, 141, 142, 143,   0,   0,   0,   0,
+      0, 144,   0,   0,   0, 145,   0,   0, 146, 147, 148, 149,   0,   0,   0,   0,
+];
+
+/// Where glyphs find their local subroutines.
+#[derive(Clone, Copy, Debug)]
+enum FontDicts<'a> {
+    /// A name-keyed font, with one Private DICT for every glyph.
+    Single { local_subroutines: Index<'a> },
+    /// A CID-keyed font, where FDSelect picks each glyph's font DICT from the FDArray.
+    Cid { fd_array: Index<'a>, fd_select: &'a [u8] },
+}
+
+/// A `CFF ` table. Only the first font of the table's FontSet is read, as OpenType requires.
+#[derive(Clone, Copy, Debug)]
+pub struct Cff<'a> {
+    data: &'a [u8],
+    char_strings: Index<'a>,
+    global_subroutines: Index<'a>,
+    charset_offset: usize,
+    font_dicts: FontDicts<'a>,
+}
+
+impl<'a> Cff<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Cff<'a>> {
+        Cff::parse_font(data).ok_or(Error::MalformedTable(kCTFontTableCFF))
+    }
+
+    fn parse_font(data: &'a [u8]) -> Option<Cff<'a>> {
+        let mut reader = Reader::new(data);
+        let major_version = reader.read_u8()?;
+        reader.skip(1)?; // minor version
+        let header_size = reader.read_u8()?;
+        if major_version != 1 {
+            return None;
+        }
+
+        let mut reader = Reader::at(data, header_size as usize);
+        Index::read(&mut reader)?; // font names
+        let top_dict = Dict::new(Index::read(&mut reader)?.get(0)?);
+        Index::read(&mut reader)?; // strings
+        let global_subroutines = Index::read(&mut reader)?;
+
+        if top_dict.get(DICT_CHARSTRING_TYPE).is_some_and(|kind| kind != [2.0]) {
+            return None;
+        }
+        let char_strings_offset = top_dict.get_usize(DICT_CHAR_STRINGS)?;
+        let char_strings = Index::read(&mut Reader::at(data, char_strings_offset))?;
+        let font_dicts = if top_dict.get(DICT_ROS).is_some() {
+            let fd_array_offset = top_dict.get_usize(DICT_FD_ARRAY)?;
+            FontDicts::Cid {
+                fd_array: Index::read(&mut Reader::at(data, fd_array_offset))?,
+                fd_select: data.get(top_dict.get_usize(DICT_FD_SELECT)?..)?,
+            }
+        } else {
+            FontDicts::Single {
+                local_subroutines: compact::local_subroutines(data, &top_dict, false),
+            }
+        };
+
+        Some(Cff {
+            data,
+            char_strings,
+            global_subroutines,
+            charset_offset: top_dict.get_usize(DICT_CHARSET).unwrap_or(0),
+            font_dicts,
+        })
+    }
+
+    pub fn num_glyphs(&self) -> u16 {
+        self.char_strings.len() as u16
+    }
+
+    /// Whether the font is CID-keyed, with its glyphs' hinting split over several font DICTs.
+    pub fn is_cid_keyed(&self) -> bool {
+        match self.font_dicts {
+            FontDicts::Single { .. } => false,
+            FontDicts::Cid { .. } => true,
+        }
+    }
+
+    /// Sends the glyph's outline, in font units, to `sink`.
+    ///
+    /// Returns false, without calling `sink`, if the glyph isn't in the font or its
+    /// charstring is malformed.
+    pub fn outline(&self, glyph: u16, sink: &mut dyn OutlineSink) -> bool {
+        let mut elements = vec![];
+        if self.append_outline(glyph, (0.0, 0.0), true, &mut elements).is_none() {
+            return false;
+        }
+        outline::replay(&elements, sink);
+        true
+    }
+
+    fn append_outline(&self,
+                      glyph: u16,
+                      origin: (f64, f64),
+                      allow_seac: bool,
+                      elements: &mut Vec<PathElement>)
+                      -> Option<()> {
+        let charstring = self.char_strings.get(glyph as u32)?;
+        let context = CharstringContext {
+            global_subroutines: self.global_subroutines,
+            local_subroutines: self.local_subroutines(glyph)?,
+            variations: None,
+        };
+        let seac = match compact::draw_charstring(charstring, &context, origin, elements)? {
+            Some(seac) => seac,
+            None => return Some(()),
+        };
+
+        // An accented glyph is drawn from two other glyphs, named by their Standard Encoding
+        // codes, and can't nest.
+        if !allow_seac {
+            return None;
+        }
+        let base = self.glyph_for_code(seac.base_code)?;
+        let accent = self.glyph_for_code(seac.accent_code)?;
+        self.append_outline(base, (0.0, 0.0), false, elements)?;
+        self.append_outline(accent, (seac.accent_x, seac.accent_y), false, elements)
+    }
+
+    fn local_subroutines(&self, glyph: u16) -> Option<Index<'a>> {
+        match self.font_dicts {
+            FontDicts::Single { local_subroutines } => Some(local_subroutines),
+            FontDicts::Cid { fd_array, fd_select } => {
+                let fd = compact::fd_select(fd_select, glyph)?;
+                let font_dict = Dict::new(fd_array.get(fd as u32)?);
+                Some(compact::local_subroutines(self.data, &font_dict, false))
+            }
+        }
+    }
+
+    /// Returns the glyph of a Standard Encoding character, by looking up its String ID in the
+    /// charset.
+    fn glyph_for_code(&self, code: u8) -> Option<u16> {
+        let sid = STANDARD_ENCODING[code as usize] as u16;
+        if sid == 0 {
+            return None;
+        }
+        self.glyph_for_sid(sid)
+    }
+
+    fn glyph_for_sid(&self, sid: u16) -> Option<u16> {
+        let num_glyphs = self.num_glyphs();
+        match self.charset_offset {
+            // The ISOAdobe charset maps the first glyphs to the same-numbered SIDs.
+            0 => if sid < num_glyphs { Some(sid) } else { None },
+            // The Expert charsets don't include the Standard Encoding's characters.
+            1 | 2 => None,
+            offset => {
+                let mut reader = Reader::at(self.data, offset);
+                let format = reader.read_u8()?;
+                // Glyph 0 is always .notdef and isn't listed.
+                let mut glyph = 1;
+                while glyph < num_glyphs {
+                    if format == 0 {
+                        if reader.read_u16()? == sid {
+                            return Some(glyph);
+                        }
+                        glyph += 1;
+                        continue;
+                    }
+                    let first = reader.read_u16()?;
+                    let left = match format {
+                        1 => reader.read_u8()? as u16,
+                        2 => reader.read_u16()?,
+                        _ => return None,
+                    };
+                    if sid >= first && sid - first <= left {
+                        return Some(glyph + sid - first);
+                    }
+                    glyph = glyph.checked_add(left)?.checked_add(1)?;
+                }
+                None
+            }
+        }
+    }
+}
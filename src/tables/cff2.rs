@@ -0,0 +1,123 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `CFF2` table, which holds PostScript glyph outlines that can vary between the instances
+//! of a variable font.
+
+use error::{Error, Result};
+use outline::{self, OutlineSink, PathElement};
+use parser::Reader;
+use tables::compact::{self, CharstringContext, CharstringVariations, Dict, Index,
+                      DICT_CHAR_STRINGS, DICT_FD_ARRAY, DICT_FD_SELECT, DICT_VARIATION_STORE,
+                      DICT_VSINDEX};
+use tables::variation_store::ItemVariationStore;
+use tag::kCTFontTableCFF2;
+
+/// A `CFF2` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Cff2<'a> {
+    data: &'a [u8],
+    char_strings: Index<'a>,
+    global_subroutines: Index<'a>,
+    fd_array: Index<'a>,
+    fd_select: Option<&'a [u8]>,
+    variation_store: Option<ItemVariationStore<'a>>,
+}
+
+impl<'a> Cff2<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Cff2<'a>> {
+        Cff2::parse_font(data).ok_or(Error::MalformedTable(kCTFontTableCFF2))
+    }
+
+    fn parse_font(data: &'a [u8]) -> Option<Cff2<'a>> {
+        let mut reader = Reader::new(data);
+        let major_version = reader.read_u8()?;
+        reader.skip(1)?; // minor version
+        let header_size = reader.read_u8()?;
+        let top_dict_length = reader.read_u16()?;
+        if major_version != 2 {
+            return None;
+        }
+
+        // Unlike in CFF, the Top DICT isn't in an INDEX, and there are no names or strings.
+        let mut reader = Reader::at(data, header_size as usize);
+        let top_dict = Dict::new(reader.read_bytes(top_dict_length as usize)?);
+        let global_subroutines = Index::read_cff2(&mut reader)?;
+
+        let char_strings_offset = top_dict.get_usize(DICT_CHAR_STRINGS)?;
+        let char_strings = Index::read_cff2(&mut Reader::at(data, char_strings_offset))?;
+        let fd_array_offset = top_dict.get_usize(DICT_FD_ARRAY)?;
+        let fd_array = Index::read_cff2(&mut Reader::at(data, fd_array_offset))?;
+        let fd_select = match top_dict.get_usize(DICT_FD_SELECT) {
+            Some(offset) => Some(data.get(offset..)?),
+            None => None,
+        };
+        let variation_store = match top_dict.get_usize(DICT_VARIATION_STORE) {
+            // The store is preceded by its length.
+            Some(offset) => Some(ItemVariationStore::parse(data.get(offset.checked_add(2)?..)?)?),
+            None => None,
+        };
+
+        Some(Cff2 {
+            data,
+            char_strings,
+            global_subroutines,
+            fd_array,
+            fd_select,
+            variation_store,
+        })
+    }
+
+    pub fn num_glyphs(&self) -> u16 {
+        self.char_strings.len() as u16
+    }
+
+    /// The deltas that `blend` operators apply, if the font is variable.
+    pub fn variation_store(&self) -> Option<ItemVariationStore<'a>> {
+        self.variation_store
+    }
+
+    /// Sends the glyph's outline, in font units, to `sink`.
+    ///
+    /// `coordinates` are normalized, one per `fvar` axis, as for `ItemVariationStore`; pass an
+    /// empty slice for the default instance. Returns false, without calling `sink`, if the
+    /// glyph isn't in the font or its charstring is malformed.
+    pub fn outline(&self, glyph: u16, coordinates: &[f64], sink: &mut dyn OutlineSink) -> bool {
+        let mut elements: Vec<PathElement> = vec![];
+        if self.append_outline(glyph, coordinates, &mut elements).is_none() {
+            return false;
+        }
+        outline::replay(&elements, sink);
+        true
+    }
+
+    fn append_outline(&self, glyph: u16, coordinates: &[f64], elements: &mut Vec<PathElement>)
+                      -> Option<()> {
+        let charstring = self.char_strings.get(glyph as u32)?;
+        // Fonts with a single font DICT may leave out FDSelect.
+        let fd = match self.fd_select {
+            Some(fd_select) => compact::fd_select(fd_select, glyph)?,
+            None => 0,
+        };
+        let font_dict = Dict::new(self.fd_array.get(fd as u32)?);
+        let vsindex = compact::private_dict(self.data, &font_dict)
+            .and_then(|(private, _)| private.get_usize(DICT_VSINDEX))
+            .unwrap_or(0);
+        let context = CharstringContext {
+            global_subroutines: self.global_subroutines,
+            local_subroutines: compact::local_subroutines(self.data, &font_dict, true),
+            variations: Some(CharstringVariations {
+                store: self.variation_store,
+                vsindex: vsindex as u16,
+                coordinates,
+            }),
+        };
+        compact::draw_charstring(charstring, &context, (0.0, 0.0), elements).map(|_| ())
+    }
+}
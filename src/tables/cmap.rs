@@ -0,0 +1,602 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `cmap` table, which maps characters to glyphs.
+
+use encoding;
+use error::{Error, Result};
+use parser::{self, Reader};
+use tag::kCTFontTableCmap;
+
+use std::cmp::Ordering;
+
+pub const PLATFORM_UNICODE: u16 = 0;
+pub const PLATFORM_MACINTOSH: u16 = 1;
+pub const PLATFORM_WINDOWS: u16 = 3;
+
+/// The encodings used for Unicode lookups, most preferred first. Full-repertoire Unicode
+/// subtables win over BMP-only ones, and symbol and Mac Roman subtables are the last resort.
+const UNICODE_PREFERENCES: [(u16, u16); 10] = [
+    (PLATFORM_WINDOWS, 10),
+    (PLATFORM_UNICODE, 6),
+    (PLATFORM_UNICODE, 4),
+    (PLATFORM_WINDOWS, 1),
+    (PLATFORM_UNICODE, 3),
+    (PLATFORM_UNICODE, 2),
+    (PLATFORM_UNICODE, 1),
+    (PLATFORM_UNICODE, 0),
+    (PLATFORM_WINDOWS, 0),
+    (PLATFORM_MACINTOSH, 0),
+];
+
+/// The encoding of Unicode Variation Sequences subtables.
+const UNICODE_VARIATION_SEQUENCES: (u16, u16) = (PLATFORM_UNICODE, 5);
+
+/// The highest code point `mappings` will report, so corrupt ranges can't run forever.
+const MAX_CODE_POINT: u32 = 0x10FFFF;
+
+/// A `cmap` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Cmap<'a> {
+    data: &'a [u8],
+    subtable_count: u16,
+}
+
+impl<'a> Cmap<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Cmap<'a>> {
+        let malformed = Error::MalformedTable(kCTFontTableCmap);
+        let mut reader = Reader::new(data);
+        if reader.read_u16() != Some(0) {
+            return Err(malformed);
+        }
+        let subtable_count = reader.read_u16().ok_or(malformed.clone())?;
+        reader.skip(subtable_count as usize * 8).ok_or(malformed)?;
+        Ok(Cmap {
+            data,
+            subtable_count,
+        })
+    }
+
+    /// Returns every encoding subtable, in table order. Records pointing outside the table
+    /// are skipped.
+    pub fn subtables(&self) -> Subtables<'a> {
+        Subtables {
+            cmap: *self,
+            index: 0,
+        }
+    }
+
+    /// Returns the subtable used to look up Unicode characters, chosen in Core Text's order of
+    /// preference. Subtables in formats this parser can't read are passed over.
+    pub fn unicode_subtable(&self) -> Option<Subtable<'a>> {
+        self.subtables()
+            .filter(|subtable| subtable.is_supported())
+            .filter_map(|subtable| {
+                let encoding = (subtable.platform_id, subtable.encoding_id);
+                UNICODE_PREFERENCES.iter()
+                                   .position(|&preference| preference == encoding)
+                                   .map(|rank| (rank, subtable))
+            })
+            .min_by_key(|&(rank, _)| rank)
+            .map(|(_, subtable)| subtable)
+    }
+
+    /// Returns the format 14 subtable describing Unicode variation sequences, if any.
+    pub fn variation_subtable(&self) -> Option<Subtable<'a>> {
+        self.subtables().find(|subtable| {
+            (subtable.platform_id, subtable.encoding_id) == UNICODE_VARIATION_SEQUENCES &&
+                subtable.format() == 14
+        })
+    }
+
+    /// Returns the glyph for a Unicode code point, or `None` if the font doesn't map it.
+    pub fn glyph_index(&self, code_point: u32) -> Option<u16> {
+        self.unicode_subtable()?.glyph_index(code_point)
+    }
+
+    /// Returns the glyph for a code point followed by a variation selector such as U+FE0F.
+    ///
+    /// Falls back to the code point's default glyph when the font says to, and returns `None`
+    /// if the font doesn't support the sequence.
+    pub fn variation_glyph_index(&self, code_point: u32, selector: u32) -> Option<u16> {
+        match self.variation_subtable()?.variation(code_point, selector)? {
+            VariationGlyph::Default => self.glyph_index(code_point),
+            VariationGlyph::Found(glyph) => Some(glyph),
+        }
+    }
+
+    /// Iterates over every Unicode code point the font maps, with its glyph.
+    pub fn mappings(&self) -> Mappings<'a> {
+        match self.unicode_subtable() {
+            Some(subtable) => subtable.mappings(),
+            None => Mappings::empty(),
+        }
+    }
+}
+
+/// Iterator over the subtables of a `cmap` table.
+#[derive(Clone, Debug)]
+pub struct Subtables<'a> {
+    cmap: Cmap<'a>,
+    index: u16,
+}
+
+impl<'a> Iterator for Subtables<'a> {
+    type Item = Subtable<'a>;
+
+    fn next(&mut self) -> Option<Subtable<'a>> {
+        while self.index < self.cmap.subtable_count {
+            let mut reader = Reader::at(self.cmap.data, 4 + self.index as usize * 8);
+            self.index += 1;
+            let platform_id = reader.read_u16()?;
+            let encoding_id = reader.read_u16()?;
+            let offset = reader.read_u32()? as usize;
+            if let Some(data) = self.cmap.data.get(offset..) {
+                if data.len() >= 2 {
+                    return Some(Subtable {
+                        platform_id,
+                        encoding_id,
+                        data,
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+/// How a font renders a variation sequence, according to its format 14 subtable.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VariationGlyph {
+    /// The sequence uses the base character's usual glyph.
+    Default,
+    /// The sequence has a glyph of its own.
+    Found(u16),
+}
+
+/// One encoding subtable of a `cmap` table.
+///
+/// The subtable data runs to the end of the `cmap` table, since not every format records its
+/// length.
+#[derive(Clone, Copy, Debug)]
+pub struct Subtable<'a> {
+    pub platform_id: u16,
+    pub encoding_id: u16,
+    data: &'a [u8],
+}
+
+impl<'a> Subtable<'a> {
+    pub fn format(&self) -> u16 {
+        parser::read_u16(self.data, 0).unwrap_or(0)
+    }
+
+    /// Returns true if `glyph_index` and `mappings` can read this subtable's format.
+    pub fn is_supported(&self) -> bool {
+        matches!(self.format(), 0 | 4 | 6 | 10 | 12 | 13)
+    }
+
+    /// Returns the glyph for a Unicode code point, translating it to the subtable's own
+    /// encoding first.
+    ///
+    /// Symbol subtables conventionally store their characters at U+F000 to U+F0FF, so code
+    /// points below U+0100 are also looked up there, as Core Text does.
+    pub fn glyph_index(&self, code_point: u32) -> Option<u16> {
+        match (self.platform_id, self.encoding_id) {
+            (PLATFORM_MACINTOSH, 0) => {
+                let code = encoding::mac_roman_from_code_point(code_point)?;
+                self.glyph_index_for_code(code as u32)
+            }
+            (PLATFORM_WINDOWS, 0) => {
+                self.glyph_index_for_code(code_point).or_else(|| {
+                    if code_point < 0x100 {
+                        self.glyph_index_for_code(0xF000 + code_point)
+                    } else {
+                        None
+                    }
+                })
+            }
+            _ => self.glyph_index_for_code(code_point),
+        }
+    }
+
+    /// Returns the glyph for a character code in the subtable's own encoding.
+    pub fn glyph_index_for_code(&self, code: u32) -> Option<u16> {
+        let glyph = match self.format() {
+            0 => self.format0_glyph(code),
+            4 => {
+                let segment = self.format4_segment(code)?;
+                self.format4_glyph(segment, code)
+            }
+            6 => self.trimmed_table_glyph(2, code),
+            10 => self.trimmed_table_glyph(4, code),
+            12 | 13 => {
+                let group = self.group_for_code(code)?;
+                self.group_glyph(group, code)
+            }
+            _ => None,
+        };
+        glyph.filter(|&glyph| glyph != 0)
+    }
+
+    /// Looks up a variation sequence in a format 14 subtable.
+    pub fn variation(&self, code_point: u32, selector: u32) -> Option<VariationGlyph> {
+        if self.format() != 14 {
+            return None;
+        }
+        let record_count = parser::read_u32(self.data, 6)? as usize;
+        let record = parser::binary_search(record_count, |index| {
+            let record_selector = Reader::at(self.data, 10 + index * 11).read_u24()?;
+            Some(record_selector.cmp(&selector))
+        })?;
+
+        let mut reader = Reader::at(self.data, 10 + record * 11 + 3);
+        let default_offset = reader.read_u32()? as usize;
+        let non_default_offset = reader.read_u32()? as usize;
+
+        if default_offset != 0 {
+            let range_count = parser::read_u32(self.data, default_offset)? as usize;
+            let ranges = default_offset + 4;
+            let found = parser::binary_search(range_count, |index| {
+                let mut reader = Reader::at(self.data, ranges + index * 4);
+                let start = reader.read_u24()?;
+                let additional_count = reader.read_u8()? as u32;
+                Some(compare_range(start, start + additional_count, code_point))
+            });
+            if found.is_some() {
+                return Some(VariationGlyph::Default);
+            }
+        }
+
+        if non_default_offset != 0 {
+            let mapping_count = parser::read_u32(self.data, non_default_offset)? as usize;
+            let mappings = non_default_offset + 4;
+            let found = parser::binary_search(mapping_count, |index| {
+                let unicode_value = Reader::at(self.data, mappings + index * 5).read_u24()?;
+                Some(unicode_value.cmp(&code_point))
+            })?;
+            let glyph = parser::read_u16(self.data, mappings + found * 5 + 3)?;
+            return Some(VariationGlyph::Found(glyph));
+        }
+        None
+    }
+
+    /// Iterates over every code this subtable maps, with its glyph, in the order the subtable
+    /// stores them. That is ascending code point order for Unicode subtables. Mac Roman codes
+    /// are translated to Unicode, and other codes are reported as stored.
+    pub fn mappings(&self) -> Mappings<'a> {
+        if !self.is_supported() {
+            return Mappings::empty();
+        }
+        Mappings {
+            subtable: Some(*self),
+            group: 0,
+            next_code: None,
+        }
+    }
+
+    fn format0_glyph(&self, code: u32) -> Option<u16> {
+        if code > 0xFF {
+            return None;
+        }
+        parser::read_u8(self.data, 6 + code as usize).map(|glyph| glyph as u16)
+    }
+
+    /// Looks up a code in format 6 or 10, whose header fields are 2 or 4 bytes wide.
+    fn trimmed_table_glyph(&self, field_size: usize, code: u32) -> Option<u16> {
+        let (first_code, count) = self.trimmed_table_range()?;
+        if code < first_code || code - first_code >= count {
+            return None;
+        }
+        let glyphs = if field_size == 2 { 10 } else { 20 };
+        parser::read_u16(self.data, glyphs + (code - first_code) as usize * 2)
+    }
+
+    fn trimmed_table_range(&self) -> Option<(u32, u32)> {
+        if self.format() == 6 {
+            let mut reader = Reader::at(self.data, 6);
+            Some((reader.read_u16()? as u32, reader.read_u16()? as u32))
+        } else {
+            let mut reader = Reader::at(self.data, 12);
+            Some((reader.read_u32()?, reader.read_u32()?))
+        }
+    }
+
+    fn format4_segment_count(&self) -> Option<usize> {
+        parser::read_u16(self.data, 6).map(|count_x2| count_x2 as usize / 2)
+    }
+
+    /// Finds the format 4 segment whose range holds `code`.
+    fn format4_segment(&self, code: u32) -> Option<usize> {
+        if code > 0xFFFF {
+            return None;
+        }
+        let segment_count = self.format4_segment_count()?;
+        // Find the first segment whose end code is at least `code`.
+        let (mut low, mut high) = (0, segment_count);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if (parser::read_u16(self.data, 14 + middle * 2)? as u32) < code {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        if low == segment_count {
+            return None;
+        }
+        let (start, _) = self.format4_segment_range(low)?;
+        if start > code {
+            return None;
+        }
+        Some(low)
+    }
+
+    fn format4_segment_range(&self, segment: usize) -> Option<(u32, u32)> {
+        let segment_count = self.format4_segment_count()?;
+        let end = parser::read_u16(self.data, 14 + segment * 2)?;
+        let start = parser::read_u16(self.data, 16 + (segment_count + segment) * 2)?;
+        Some((start as u32, end as u32))
+    }
+
+    fn format4_glyph(&self, segment: usize, code: u32) -> Option<u16> {
+        let segment_count = self.format4_segment_count()?;
+        let (start, _) = self.format4_segment_range(segment)?;
+        let id_delta = parser::read_u16(self.data, 16 + (2 * segment_count + segment) * 2)?;
+        let range_offset_position = 16 + (3 * segment_count + segment) * 2;
+        let id_range_offset = parser::read_u16(self.data, range_offset_position)?;
+        if id_range_offset == 0 {
+            return Some((code as u16).wrapping_add(id_delta));
+        }
+        let glyph_position = range_offset_position +
+                             id_range_offset as usize +
+                             (code - start) as usize * 2;
+        match parser::read_u16(self.data, glyph_position)? {
+            0 => None,
+            glyph => Some(glyph.wrapping_add(id_delta)),
+        }
+    }
+
+    fn group_count(&self) -> Option<usize> {
+        parser::read_u32(self.data, 12).map(|count| count as usize)
+    }
+
+    fn group_range(&self, group: usize) -> Option<(u32, u32)> {
+        let mut reader = Reader::at(self.data, 16 + group * 12);
+        Some((reader.read_u32()?, reader.read_u32()?))
+    }
+
+    fn group_for_code(&self, code: u32) -> Option<usize> {
+        parser::binary_search(self.group_count()?, |group| {
+            let (start, end) = self.group_range(group)?;
+            Some(compare_range(start, end, code))
+        })
+    }
+
+    /// Returns the glyph for `code` in format 12 or 13 group `group`.
+    fn group_glyph(&self, group: usize, code: u32) -> Option<u16> {
+        let (start, _) = self.group_range(group)?;
+        let glyph = parser::read_u32(self.data, 16 + group * 12 + 8)?;
+        let glyph = if self.format() == 12 {
+            glyph.checked_add(code - start)?
+        } else {
+            glyph
+        };
+        if glyph > 0xFFFF {
+            None
+        } else {
+            Some(glyph as u16)
+        }
+    }
+
+    /// Returns the codes covered by the `index`th group of the subtable, or `None` past the
+    /// last group. Formats without groups have one, covering their whole range.
+    fn mapping_group_range(&self, index: usize) -> Option<(u32, u32)> {
+        match self.format() {
+            0 if index == 0 => Some((0, 0xFF)),
+            4 if index < self.format4_segment_count()? => self.format4_segment_range(index),
+            6 | 10 if index == 0 => {
+                let (first_code, count) = self.trimmed_table_range()?;
+                if count == 0 {
+                    Some((1, 0))
+                } else {
+                    Some((first_code, first_code.checked_add(count - 1)?))
+                }
+            }
+            12 | 13 if index < self.group_count()? => self.group_range(index),
+            _ => None,
+        }
+    }
+
+    fn mapping_group_glyph(&self, index: usize, code: u32) -> Option<u16> {
+        let glyph = match self.format() {
+            4 => self.format4_glyph(index, code),
+            12 | 13 => self.group_glyph(index, code),
+            _ => self.glyph_index_for_code(code),
+        };
+        glyph.filter(|&glyph| glyph != 0)
+    }
+}
+
+/// Orders the range `start..=end` relative to `value`, for binary searches.
+fn compare_range(start: u32, end: u32, value: u32) -> Ordering {
+    if end < value {
+        Ordering::Less
+    } else if start > value {
+        Ordering::Greater
+    } else {
+        Ordering::Equal
+    }
+}
+
+/// Iterator over the `(code point, glyph)` pairs of a `cmap` subtable.
+#[derive(Clone, Debug)]
+pub struct Mappings<'a> {
+    subtable: Option<Subtable<'a>>,
+    group: usize,
+    next_code: Option<u32>,
+}
+
+impl<'a> Mappings<'a> {
+    fn empty() -> Mappings<'a> {
+        Mappings {
+            subtable: None,
+            group: 0,
+            next_code: None,
+        }
+    }
+}
+
+impl<'a> Iterator for Mappings<'a> {
+    type Item = (u32, u16);
+
+    fn next(&mut self) -> Option<(u32, u16)> {
+        let subtable = self.subtable?;
+        loop {
+            let (start, end) = match subtable.mapping_group_range(self.group) {
+                Some(range) => range,
+                None => {
+                    self.subtable = None;
+                    return None;
+                }
+            };
+            let end = end.min(MAX_CODE_POINT);
+            let code = self.next_code.unwrap_or(start).max(start);
+            if code > end {
+                self.group += 1;
+                self.next_code = None;
+                continue;
+            }
+            self.next_code = Some(code + 1);
+
+            if let Some(glyph) = subtable.mapping_group_glyph(self.group, code) {
+                if (subtable.platform_id, subtable.encoding_id) == (PLATFORM_MACINTOSH, 0) {
+                    return Some((encoding::mac_roman_to_char(code as u8) as u32, glyph));
+                }
+                return Some((code, glyph));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_font;
+    use super::{Cmap, PLATFORM_MACINTOSH, PLATFORM_UNICODE, PLATFORM_WINDOWS};
+
+    /// Returns a `cmap` table holding `subtables`, each with its platform and encoding.
+    fn cmap(subtables: &[(u16, u16, Vec<u8>)]) -> Vec<u8> {
+        let mut table = test_font::words(&[0, subtables.len() as i32]);
+        let mut offset = 4 + subtables.len() * 8;
+        for &(platform_id, encoding_id, ref subtable) in subtables {
+            table.extend(test_font::words(&[platform_id as i32, encoding_id as i32,
+                                            (offset >> 16) as i32, offset as i32]));
+            offset += subtable.len();
+        }
+        for (_, _, subtable) in subtables {
+            table.extend_from_slice(subtable);
+        }
+        table
+    }
+
+    /// Maps A to C to glyphs 10 to 12 by delta, and a and c to glyphs 20 and 22 through the
+    /// glyph array, which leaves b unmapped.
+    fn format_4() -> Vec<u8> {
+        test_font::words(&[4, 46, 0, 6, 4, 1, 2,
+                           0x43, 0x63, 0xFFFF, 0,
+                           0x41, 0x61, 0xFFFF,
+                           10 - 0x41, 0, 1,
+                           0, 4, 0,
+                           20, 0, 22])
+    }
+
+    /// Maps A and B to glyphs 30 and 31, and U+1F600 and U+1F601 to glyphs 40 and 41.
+    fn format_12() -> Vec<u8> {
+        test_font::words(&[12, 0, 0, 40, 0, 0, 0, 2,
+                           0, 0x41, 0, 0x42, 0, 30,
+                           1, 0xF600, 1, 0xF601, 0, 40])
+    }
+
+    /// Maps the Mac Roman code for é to glyph 50.
+    fn format_0() -> Vec<u8> {
+        let mut subtable = test_font::words(&[0, 262, 0]);
+        subtable.extend((0..256).map(|code| if code == 0x8E { 50 } else { 0 }));
+        subtable
+    }
+
+    /// Gives A and B their default glyphs with U+FE0E, and A glyph 99 with U+FE0F.
+    fn format_14() -> Vec<u8> {
+        let mut subtable = test_font::words(&[14, 0, 49, 0, 2]);
+        subtable.extend_from_slice(&[0x00, 0xFE, 0x0E, 0, 0, 0, 32, 0, 0, 0, 0]);
+        subtable.extend_from_slice(&[0x00, 0xFE, 0x0F, 0, 0, 0, 0, 0, 0, 0, 40]);
+        subtable.extend_from_slice(&[0, 0, 0, 1, 0x00, 0x00, 0x41, 1]);
+        subtable.extend_from_slice(&[0, 0, 0, 1, 0x00, 0x00, 0x41, 0, 99]);
+        subtable
+    }
+
+    #[test]
+    fn reads_format_4_segments() {
+        let data = cmap(&[(PLATFORM_WINDOWS, 1, format_4())]);
+        let cmap = Cmap::parse(&data).unwrap();
+        let glyphs: Vec<Option<u16>> = [0x40, 0x41, 0x42, 0x43, 0x44, 0x61, 0x62, 0x63, 0xFFFF]
+            .iter()
+            .map(|&code_point| cmap.glyph_index(code_point))
+            .collect();
+        assert_eq!(glyphs, vec![None, Some(10), Some(11), Some(12), None,
+                                Some(20), None, Some(22), None]);
+        assert_eq!(cmap.mappings().collect::<Vec<_>>(),
+                   vec![(0x41, 10), (0x42, 11), (0x43, 12), (0x61, 20), (0x63, 22)]);
+    }
+
+    #[test]
+    fn reads_format_12_groups() {
+        let data = cmap(&[(PLATFORM_WINDOWS, 10, format_12())]);
+        let cmap = Cmap::parse(&data).unwrap();
+        assert_eq!(cmap.glyph_index(0x42), Some(31));
+        assert_eq!(cmap.glyph_index(0x1F601), Some(41));
+        assert_eq!(cmap.glyph_index(0x1F602), None);
+        assert_eq!(cmap.mappings().collect::<Vec<_>>(),
+                   vec![(0x41, 30), (0x42, 31), (0x1F600, 40), (0x1F601, 41)]);
+    }
+
+    #[test]
+    fn prefers_full_unicode_subtables_then_bmp_then_mac_roman() {
+        let unsupported = test_font::words(&[8, 0, 0]);
+        let data = cmap(&[(PLATFORM_MACINTOSH, 0, format_0()),
+                          (PLATFORM_UNICODE, 3, format_4()),
+                          (PLATFORM_WINDOWS, 10, format_12())]);
+        let cmap_12 = Cmap::parse(&data).unwrap();
+        assert_eq!(cmap_12.unicode_subtable().unwrap().format(), 12);
+        assert_eq!(cmap_12.glyph_index(0x41), Some(30));
+
+        let data = cmap(&[(PLATFORM_MACINTOSH, 0, format_0()),
+                          (PLATFORM_WINDOWS, 10, unsupported),
+                          (PLATFORM_UNICODE, 3, format_4())]);
+        let cmap_4 = Cmap::parse(&data).unwrap();
+        assert_eq!(cmap_4.unicode_subtable().unwrap().format(), 4);
+        assert_eq!(cmap_4.glyph_index(0x41), Some(10));
+
+        let data = cmap(&[(PLATFORM_MACINTOSH, 0, format_0())]);
+        let cmap_0 = Cmap::parse(&data).unwrap();
+        assert_eq!(cmap_0.glyph_index(0xE9), Some(50));
+        assert_eq!(cmap_0.glyph_index(0x8E), None);
+        assert_eq!(cmap_0.mappings().collect::<Vec<_>>(), vec![(0xE9, 50)]);
+    }
+
+    #[test]
+    fn reads_default_and_non_default_variation_sequences() {
+        let data = cmap(&[(PLATFORM_UNICODE, 5, format_14()), (PLATFORM_WINDOWS, 1, format_4())]);
+        let cmap = Cmap::parse(&data).unwrap();
+        assert_eq!(cmap.variation_glyph_index(0x41, 0xFE0E), Some(10));
+        assert_eq!(cmap.variation_glyph_index(0x42, 0xFE0E), Some(11));
+        assert_eq!(cmap.variation_glyph_index(0x43, 0xFE0E), None);
+        assert_eq!(cmap.variation_glyph_index(0x41, 0xFE0F), Some(99));
+        assert_eq!(cmap.variation_glyph_index(0x42, 0xFE0F), None);
+        assert_eq!(cmap.variation_glyph_index(0x41, 0xFE00), None);
+    }
+}
@@ -0,0 +1,746 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The INDEX and DICT structures and the Type 2 charstring interpreter that the Compact Font
+//! Format tables share.
+
+use base::CGPoint;
+use outline::OutlineSink;
+use parser::{self, Reader};
+use tables::variation_store::ItemVariationStore;
+
+/// How deeply subroutine calls may nest, as the Type 2 charstring spec limits them.
+const MAX_SUBROUTINE_DEPTH: u8 = 10;
+
+/// The argument stack limits for CFF and CFF2 charstrings.
+const CFF_MAX_STACK: usize = 48;
+const CFF2_MAX_STACK: usize = 513;
+
+// DICT operators. Two-byte operators are 12 followed by a second byte.
+pub const DICT_CHARSET: u16 = 15;
+pub const DICT_CHAR_STRINGS: u16 = 17;
+pub const DICT_PRIVATE: u16 = 18;
+pub const DICT_SUBRS: u16 = 19;
+pub const DICT_VSINDEX: u16 = 22;
+pub const DICT_VARIATION_STORE: u16 = 24;
+pub const DICT_CHARSTRING_TYPE: u16 = 12 << 8 | 6;
+pub const DICT_ROS: u16 = 12 << 8 | 30;
+pub const DICT_FD_ARRAY: u16 = 12 << 8 | 36;
+pub const DICT_FD_SELECT: u16 = 12 << 8 | 37;
+
+/// An array of variable-length objects.
+#[derive(Clone, Copy, Debug)]
+pub struct Index<'a> {
+    count: u32,
+    offset_size: u8,
+    offsets: &'a [u8],
+    data: &'a [u8],
+}
+
+impl<'a> Index<'a> {
+    /// Reads a CFF INDEX, which has a 16-bit count, leaving `reader` just past it.
+    pub fn read(reader: &mut Reader<'a>) -> Option<Index<'a>> {
+        let count = reader.read_u16()? as u32;
+        Index::read_with_count(reader, count)
+    }
+
+    /// Reads a CFF2 INDEX, which has a 32-bit count, leaving `reader` just past it.
+    pub fn read_cff2(reader: &mut Reader<'a>) -> Option<Index<'a>> {
+        let count = reader.read_u32()?;
+        Index::read_with_count(reader, count)
+    }
+
+    fn read_with_count(reader: &mut Reader<'a>, count: u32) -> Option<Index<'a>> {
+        if count == 0 {
+            return Some(Index::empty());
+        }
+        let offset_size = reader.read_u8()?;
+        if !(1..=4).contains(&offset_size) {
+            return None;
+        }
+        let offsets = reader.read_bytes((count as usize + 1) * offset_size as usize)?;
+        let mut index = Index {
+            count,
+            offset_size,
+            offsets,
+            data: &[],
+        };
+        // Offsets count from 1, the byte before the object data.
+        let data_size = index.offset(count)?.checked_sub(1)?;
+        index.data = reader.read_bytes(data_size)?;
+        Some(index)
+    }
+
+    pub fn empty() -> Index<'a> {
+        Index {
+            count: 0,
+            offset_size: 1,
+            offsets: &[],
+            data: &[],
+        }
+    }
+
+    pub fn len(&self) -> u32 {
+        self.count
+    }
+
+    fn offset(&self, index: u32) -> Option<usize> {
+        let size = self.offset_size as usize;
+        let start = index as usize * size;
+        let bytes = self.offsets.get(start..start + size)?;
+        Some(bytes.iter().fold(0, |offset, &byte| offset << 8 | byte as usize))
+    }
+
+    pub fn get(&self, index: u32) -> Option<&'a [u8]> {
+        if index >= self.count {
+            return None;
+        }
+        let start = self.offset(index)?.checked_sub(1)?;
+        let end = self.offset(index + 1)?.checked_sub(1)?;
+        self.data.get(start..end)
+    }
+}
+
+/// A DICT of operators and their operands.
+#[derive(Clone, Copy, Debug)]
+pub struct Dict<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Dict<'a> {
+    pub fn new(data: &'a [u8]) -> Dict<'a> {
+        Dict {
+            data,
+        }
+    }
+
+    /// Returns the operands of `operator`, or `None` if the DICT doesn't have it or can't be
+    /// read up to it.
+    pub fn get(&self, operator: u16) -> Option<Vec<f64>> {
+        let mut reader = Reader::new(self.data);
+        let mut operands = vec![];
+        loop {
+            let byte = reader.read_u8()?;
+            match byte {
+                0..=27 => {
+                    let found = if byte == 12 { 12 << 8 | reader.read_u8()? as u16 } else { byte as u16 };
+                    if found == operator {
+                        return Some(operands);
+                    }
+                    operands.clear();
+                }
+                28 => operands.push(reader.read_i16()? as f64),
+                29 => operands.push(reader.read_i32()? as f64),
+                30 => operands.push(read_real(&mut reader)?),
+                32..=246 => operands.push(byte as f64 - 139.0),
+                247..=250 => {
+                    let second = reader.read_u8()? as f64;
+                    operands.push((byte as f64 - 247.0) * 256.0 + second + 108.0)
+                }
+                251..=254 => {
+                    let second = reader.read_u8()? as f64;
+                    operands.push(-(byte as f64 - 251.0) * 256.0 - second - 108.0)
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    /// Returns the single operand of `operator` as an offset or count.
+    pub fn get_usize(&self, operator: u16) -> Option<usize> {
+        match *self.get(operator)? {
+            [value] if value >= 0.0 => Some(value as usize),
+            _ => None,
+        }
+    }
+
+    /// Returns the Private DICT's `(size, offset)`.
+    pub fn private_range(&self) -> Option<(usize, usize)> {
+        match *self.get(DICT_PRIVATE)? {
+            [size, offset] if size >= 0.0 && offset >= 0.0 => Some((size as usize, offset as usize)),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the Private DICT that a Top or font DICT points to, and its offset in `data`.
+pub fn private_dict<'a>(data: &'a [u8], dict: &Dict) -> Option<(Dict<'a>, usize)> {
+    let (size, offset) = dict.private_range()?;
+    Some((Dict::new(data.get(offset..offset.checked_add(size)?)?), offset))
+}
+
+/// Reads the local subroutines of a Top or font DICT's Private DICT. Fonts without any have an
+/// empty INDEX.
+pub fn local_subroutines<'a>(data: &'a [u8], dict: &Dict, cff2: bool) -> Index<'a> {
+    let subroutines = private_dict(data, dict).and_then(|(private, offset)| {
+        // The Subrs offset is relative to the Private DICT.
+        let mut reader = Reader::at(data, offset.checked_add(private.get_usize(DICT_SUBRS)?)?);
+        if cff2 { Index::read_cff2(&mut reader) } else { Index::read(&mut reader) }
+    });
+    subroutines.unwrap_or_else(Index::empty)
+}
+
+/// Reads a DICT real number, which is packed as nibbles of a decimal string.
+fn read_real(reader: &mut Reader) -> Option<f64> {
+    let mut string = String::new();
+    loop {
+        let byte = reader.read_u8()?;
+        for &nibble in &[byte >> 4, byte & 0xF] {
+            match nibble {
+                0..=9 => string.push((b'0' + nibble) as char),
+                0xA => string.push('.'),
+                0xB => string.push('E'),
+                0xC => string.push_str("E-"),
+                0xE => string.push('-'),
+                0xF => return string.parse().ok(),
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// Returns the number added to subroutine numbers, which are stored biased so that more of
+/// them fit in the short number encodings.
+fn subroutine_bias(subroutines: &Index) -> i32 {
+    match subroutines.len() {
+        0..=1239 => 107,
+        1240..=33899 => 1131,
+        _ => 32768,
+    }
+}
+
+/// A request, made by `endchar`, to draw the glyph as an accent over a base character.
+#[derive(Clone, Copy, Debug)]
+pub struct Seac {
+    pub accent_x: f64,
+    pub accent_y: f64,
+    /// The base and accent characters' codes in the Standard Encoding.
+    pub base_code: u8,
+    pub accent_code: u8,
+}
+
+/// What a charstring needs besides its own bytes.
+#[derive(Clone, Copy, Debug)]
+pub struct CharstringContext<'a> {
+    pub global_subroutines: Index<'a>,
+    pub local_subroutines: Index<'a>,
+    /// Set for `CFF2` charstrings, which have no advance widths or `endchar` but can blend
+    /// their operands between instances of a variable font.
+    pub variations: Option<CharstringVariations<'a>>,
+}
+
+/// The instance a `CFF2` charstring is drawn at.
+#[derive(Clone, Copy, Debug)]
+pub struct CharstringVariations<'a> {
+    pub store: Option<ItemVariationStore<'a>>,
+    /// The item variation data that `blend` uses until a `vsindex` picks another.
+    pub vsindex: u16,
+    /// Normalized coordinates, as for `ItemVariationStore`.
+    pub coordinates: &'a [f64],
+}
+
+impl<'a> CharstringContext<'a> {
+    fn max_stack(&self) -> usize {
+        if self.variations.is_some() { CFF2_MAX_STACK } else { CFF_MAX_STACK }
+    }
+}
+
+/// Runs a Type 2 charstring, sending its outline, offset by `origin`, to `sink`.
+///
+/// Returns `None` if the charstring is malformed, or the `endchar` accent request, if it made
+/// one.
+pub fn draw_charstring(charstring: &[u8],
+                       context: &CharstringContext,
+                       origin: (f64, f64),
+                       sink: &mut dyn OutlineSink)
+                       -> Option<Option<Seac>> {
+    let mut interpreter = Interpreter {
+        context,
+        sink,
+        stack: Vec::with_capacity(context.max_stack()),
+        x: origin.0,
+        y: origin.1,
+        stem_count: 0,
+        // CFF2 charstrings never start with a width.
+        width_seen: context.variations.is_some(),
+        vsindex: context.variations.map_or(0, |variations| variations.vsindex),
+        scalars: None,
+        contour_open: false,
+        ended: false,
+        seac: None,
+    };
+    interpreter.run(charstring, 0)?;
+    if interpreter.contour_open {
+        interpreter.sink.close();
+    }
+    Some(interpreter.seac)
+}
+
+struct Interpreter<'c, 'a: 'c> {
+    context: &'c CharstringContext<'a>,
+    sink: &'c mut dyn OutlineSink,
+    stack: Vec<f64>,
+    x: f64,
+    y: f64,
+    stem_count: usize,
+    /// Whether the first stack-clearing operator, which may carry the advance width as an
+    /// extra first operand, has run.
+    width_seen: bool,
+    vsindex: u16,
+    /// The current item variation data's region scalars, once a `blend` needs them.
+    scalars: Option<Vec<f64>>,
+    contour_open: bool,
+    ended: bool,
+    seac: Option<Seac>,
+}
+
+impl<'c, 'a> Interpreter<'c, 'a> {
+    fn run(&mut self, charstring: &[u8], depth: u8) -> Option<()> {
+        if depth > MAX_SUBROUTINE_DEPTH {
+            return None;
+        }
+        let mut reader = Reader::new(charstring);
+        while !self.ended {
+            let byte = match reader.read_u8() {
+                Some(byte) => byte,
+                None => return Some(()),
+            };
+            if let 5..=8 | 24..=27 | 30 | 31 = byte {
+                self.check_path_arguments(byte)?;
+            }
+            match byte {
+                1 | 18 => self.stems(),                             // hstem, hstemhm
+                3 | 23 => self.stems(),                             // vstem, vstemhm
+                4 => {                                              // vmoveto
+                    self.strip_width(1);
+                    let dy = self.move_arguments(1)?[0];
+                    self.move_by(0.0, dy);
+                }
+                5 => {                                              // rlineto
+                    for pair in self.stack.chunks(2) {
+                        if let [dx, dy] = *pair {
+                            self.x += dx;
+                            self.y += dy;
+                            self.sink.line_to(CGPoint::new(self.x, self.y));
+                        }
+                    }
+                }
+                6 | 7 => {                                          // hlineto, vlineto
+                    let mut horizontal = byte == 6;
+                    for index in 0..self.stack.len() {
+                        if horizontal {
+                            self.x += self.stack[index];
+                        } else {
+                            self.y += self.stack[index];
+                        }
+                        self.sink.line_to(CGPoint::new(self.x, self.y));
+                        horizontal = !horizontal;
+                    }
+                }
+                8 => {                                              // rrcurveto
+                    let args = self.stack.clone();
+                    for curve in args.chunks(6) {
+                        if let [dx1, dy1, dx2, dy2, dx3, dy3] = *curve {
+                            self.curve_by(dx1, dy1, dx2, dy2, dx3, dy3);
+                        }
+                    }
+                }
+                10 | 29 => {                                        // callsubr, callgsubr
+                    let subroutines = if byte == 10 {
+                        self.context.local_subroutines
+                    } else {
+                        self.context.global_subroutines
+                    };
+                    let number = self.stack.pop()? as i32 + subroutine_bias(&subroutines);
+                    let subroutine = subroutines.get(number as u32)?;
+                    self.run(subroutine, depth + 1)?;
+                    continue;
+                }
+                11 => return Some(()),                              // return
+                15 if self.context.variations.is_some() => {        // vsindex
+                    self.vsindex = self.stack.pop()? as u16;
+                    self.scalars = None;
+                }
+                16 if self.context.variations.is_some() => {        // blend
+                    self.blend()?;
+                    continue;
+                }
+                14 => {                                             // endchar
+                    if self.stack.len() == 1 || self.stack.len() == 5 {
+                        self.strip_width(0);
+                    }
+                    if let [accent_x, accent_y, base_code, accent_code] = *self.stack {
+                        self.seac = Some(Seac {
+                            accent_x,
+                            accent_y,
+                            base_code: base_code as u8,
+                            accent_code: accent_code as u8,
+                        });
+                    }
+                    self.ended = true;
+                }
+                19 | 20 => {                                        // hintmask, cntrmask
+                    // Operands here are an implied vstem.
+                    self.stems();
+                    reader.skip(self.stem_count.div_ceil(8))?;
+                }
+                21 => {                                             // rmoveto
+                    self.strip_width(2);
+                    let (dx, dy) = match *self.move_arguments(2)? {
+                        [dx, dy] => (dx, dy),
+                        _ => return None,
+                    };
+                    self.move_by(dx, dy);
+                }
+                22 => {                                             // hmoveto
+                    self.strip_width(1);
+                    let dx = self.move_arguments(1)?[0];
+                    self.move_by(dx, 0.0);
+                }
+                24 => {                                             // rcurveline
+                    let args = self.stack.clone();
+                    let (curves, line) = args.split_at(args.len() - 2);
+                    for curve in curves.chunks(6) {
+                        self.curve_by(curve[0], curve[1], curve[2], curve[3], curve[4], curve[5]);
+                    }
+                    self.x += line[0];
+                    self.y += line[1];
+                    self.sink.line_to(CGPoint::new(self.x, self.y));
+                }
+                25 => {                                             // rlinecurve
+                    let args = self.stack.clone();
+                    let (lines, curve) = args.split_at(args.len() - 6);
+                    for line in lines.chunks(2) {
+                        self.x += line[0];
+                        self.y += line[1];
+                        self.sink.line_to(CGPoint::new(self.x, self.y));
+                    }
+                    self.curve_by(curve[0], curve[1], curve[2], curve[3], curve[4], curve[5]);
+                }
+                26 | 27 => {                                        // vvcurveto, hhcurveto
+                    let mut args = &self.stack.clone()[..];
+                    // An odd argument count starts with the first curve's other coordinate.
+                    let mut first = 0.0;
+                    if args.len() % 4 == 1 {
+                        first = args[0];
+                        args = &args[1..];
+                    }
+                    for curve in args.chunks(4) {
+                        if let [a, dx2, dy2, b] = *curve {
+                            if byte == 26 {
+                                self.curve_by(first, a, dx2, dy2, 0.0, b);
+                            } else {
+                                self.curve_by(a, first, dx2, dy2, b, 0.0);
+                            }
+                        }
+                        first = 0.0;
+                    }
+                }
+                28 => {
+                    let value = reader.read_i16()? as f64;
+                    self.push(value)?;
+                    continue;
+                }
+                30 | 31 => {                                        // vhcurveto, hvcurveto
+                    let args = self.stack.clone();
+                    let mut horizontal = byte == 31;
+                    let mut index = 0;
+                    while index + 4 <= args.len() {
+                        let last = if args.len() - index == 5 { args[index + 4] } else { 0.0 };
+                        let (a, dx2, dy2, b) =
+                            (args[index], args[index + 1], args[index + 2], args[index + 3]);
+                        if horizontal {
+                            self.curve_by(a, 0.0, dx2, dy2, last, b);
+                        } else {
+                            self.curve_by(0.0, a, dx2, dy2, b, last);
+                        }
+                        horizontal = !horizontal;
+                        index += 4;
+                    }
+                }
+                12 => {
+                    let operator = reader.read_u8()?;
+                    self.flex(operator)?;
+                }
+                32..=246 => {
+                    self.push(byte as f64 - 139.0)?;
+                    continue;
+                }
+                247..=250 => {
+                    let second = reader.read_u8()? as f64;
+                    self.push((byte as f64 - 247.0) * 256.0 + second + 108.0)?;
+                    continue;
+                }
+                251..=254 => {
+                    let second = reader.read_u8()? as f64;
+                    self.push(-(byte as f64 - 251.0) * 256.0 - second - 108.0)?;
+                    continue;
+                }
+                255 => {
+                    let value = reader.read_u32()? as i32 as f64 / 65536.0;
+                    self.push(value)?;
+                    continue;
+                }
+                _ => return None,
+            }
+            self.stack.clear();
+        }
+        Some(())
+    }
+
+    fn push(&mut self, value: f64) -> Option<()> {
+        if self.stack.len() >= self.context.max_stack() {
+            return None;
+        }
+        self.stack.push(value);
+        Some(())
+    }
+
+    /// Replaces the top `n` default values on the stack, which are followed by `n` deltas for
+    /// every region of the current item variation data, with the blended values.
+    fn blend(&mut self) -> Option<()> {
+        if self.scalars.is_none() {
+            let variations = self.context.variations?;
+            let scalars = variations.store?.region_scalars(self.vsindex, variations.coordinates)?;
+            self.scalars = Some(scalars);
+        }
+        let scalars = self.scalars.take()?;
+        let count = self.stack.pop()? as usize;
+        let operand_count = count.checked_mul(scalars.len() + 1)?;
+        let start = self.stack.len().checked_sub(operand_count)?;
+        for index in 0..count {
+            let deltas = start + count + index * scalars.len();
+            let delta: f64 = scalars.iter()
+                                    .enumerate()
+                                    .map(|(region, scalar)| self.stack[deltas + region] * scalar)
+                                    .sum();
+            self.stack[start + index] += delta;
+        }
+        self.stack.truncate(start + count);
+        self.scalars = Some(scalars);
+        Some(())
+    }
+
+    /// Returns a move operator's arguments, once any advance width is stripped, if there are
+    /// exactly `count` of them.
+    fn move_arguments(&self, count: usize) -> Option<&[f64]> {
+        if self.stack.len() == count { Some(&self.stack) } else { None }
+    }
+
+    /// Checks that a line or curve operator has a whole number of segments' arguments and a
+    /// contour to add them to.
+    fn check_path_arguments(&self, operator: u8) -> Option<()> {
+        let count = self.stack.len();
+        let valid = match operator {
+            5 => count >= 2 && count.is_multiple_of(2),
+            6 | 7 => count >= 1,
+            8 => count >= 6 && count.is_multiple_of(6),
+            24 => count >= 8 && (count - 2).is_multiple_of(6),
+            25 => count >= 8 && count.is_multiple_of(2),
+            _ => count >= 4 && count % 4 <= 1,
+        };
+        if valid && self.contour_open { Some(()) } else { None }
+    }
+
+    /// Drops the advance width if this is the first stack-clearing operator and it has more
+    /// than its `expected` operands.
+    fn strip_width(&mut self, expected: usize) {
+        if !self.width_seen && self.stack.len() > expected {
+            self.stack.remove(0);
+        }
+        self.width_seen = true;
+    }
+
+    fn stems(&mut self) {
+        let expected = self.stack.len() & !1;
+        self.strip_width(expected);
+        self.stem_count += self.stack.len() / 2;
+    }
+
+    fn move_by(&mut self, dx: f64, dy: f64) {
+        if self.contour_open {
+            self.sink.close();
+        }
+        self.x += dx;
+        self.y += dy;
+        self.sink.move_to(CGPoint::new(self.x, self.y));
+        self.contour_open = true;
+    }
+
+    fn curve_by(&mut self, dx1: f64, dy1: f64, dx2: f64, dy2: f64, dx3: f64, dy3: f64) {
+        let control1 = (self.x + dx1, self.y + dy1);
+        let control2 = (control1.0 + dx2, control1.1 + dy2);
+        self.x = control2.0 + dx3;
+        self.y = control2.1 + dy3;
+        self.sink.cubic_to(CGPoint::new(control1.0, control1.1),
+                           CGPoint::new(control2.0, control2.1),
+                           CGPoint::new(self.x, self.y));
+    }
+
+    /// Runs one of the flex operators, which draw two curves that renderers may flatten at
+    /// small sizes.
+    fn flex(&mut self, operator: u8) -> Option<()> {
+        if !self.contour_open {
+            return None;
+        }
+        let args = self.stack.clone();
+        let start_y = self.y;
+        match (operator, &args[..]) {
+            (35, &[dx1, dy1, dx2, dy2, dx3, dy3, dx4, dy4, dx5, dy5, dx6, dy6, _]) => {
+                self.curve_by(dx1, dy1, dx2, dy2, dx3, dy3);
+                self.curve_by(dx4, dy4, dx5, dy5, dx6, dy6);
+            }
+            (34, &[dx1, dx2, dy2, dx3, dx4, dx5, dx6]) => {
+                self.curve_by(dx1, 0.0, dx2, dy2, dx3, 0.0);
+                self.curve_by(dx4, 0.0, dx5, -dy2, dx6, 0.0);
+            }
+            (36, &[dx1, dy1, dx2, dy2, dx3, dx4, dx5, dy5, dx6]) => {
+                self.curve_by(dx1, dy1, dx2, dy2, dx3, 0.0);
+                let dy6 = start_y - (self.y + dy5);
+                self.curve_by(dx4, 0.0, dx5, dy5, dx6, dy6);
+            }
+            (37, &[dx1, dy1, dx2, dy2, dx3, dy3, dx4, dy4, dx5, dy5, d6]) => {
+                let dx = dx1 + dx2 + dx3 + dx4 + dx5;
+                let dy = dy1 + dy2 + dy3 + dy4 + dy5;
+                let (dx6, dy6) = if dx.abs() > dy.abs() { (d6, -dy) } else { (-dx, d6) };
+                self.curve_by(dx1, dy1, dx2, dy2, dx3, dy3);
+                self.curve_by(dx4, dy4, dx5, dy5, dx6, dy6);
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+/// Returns the font DICT index of `glyph` from a CID font's or a `CFF2` table's FDSelect data.
+pub fn fd_select(data: &[u8], glyph: u16) -> Option<u16> {
+    let mut reader = Reader::new(data);
+    match reader.read_u8()? {
+        0 => parser::read_u8(data, 1 + glyph as usize).map(|fd| fd as u16),
+        3 => {
+            let range_count = reader.read_u16()?;
+            let mut first = reader.read_u16()?;
+            for _ in 0..range_count {
+                let fd = reader.read_u8()?;
+                let next = reader.read_u16()?;
+                if (first..next).contains(&glyph) {
+                    return Some(fd as u16);
+                }
+                first = next;
+            }
+            None
+        }
+        4 => {
+            let range_count = reader.read_u32()?;
+            let mut first = reader.read_u32()?;
+            for _ in 0..range_count {
+                let fd = reader.read_u16()?;
+                let next = reader.read_u32()?;
+                if (first..next).contains(&(glyph as u32)) {
+                    return Some(fd);
+                }
+                first = next;
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use outline::PathElement;
+    use tables::variation_store::ItemVariationStore;
+    use test_font;
+    use super::{CharstringContext, CharstringVariations, Index, draw_charstring};
+
+    const VSINDEX: u8 = 15;
+    const BLEND: u8 = 16;
+    const RLINETO: u8 = 5;
+    const RMOVETO: u8 = 21;
+
+    /// An item variation store for one axis, whose first item variation data uses a region
+    /// peaking at 1 and whose second adds one peaking at -1.
+    fn store_data() -> Vec<u8> {
+        test_font::words(&[1, 0, 16, 2, 0, 32, 0, 40,
+                           1, 2, 0, 16384, 16384, -16384, -16384, 0,
+                           0, 0, 1, 0,
+                           0, 0, 2, 0, 1])
+    }
+
+    /// Encodes `operands` as 16-bit numbers followed by `operator`.
+    fn operation(operands: &[i16], operator: u8) -> Vec<u8> {
+        let mut bytes = vec![];
+        for operand in operands {
+            bytes.push(28);
+            bytes.extend_from_slice(&operand.to_be_bytes());
+        }
+        bytes.push(operator);
+        bytes
+    }
+
+    /// Draws `charstring`, as CFF2 at `coordinate` if there is one and as CFF otherwise, and
+    /// returns the points it moves and draws lines to.
+    fn draw(charstring: &[u8], coordinate: Option<f64>) -> Option<Vec<(f64, f64)>> {
+        let data = store_data();
+        let coordinates = coordinate.map_or(vec![], |coordinate| vec![coordinate]);
+        let context = CharstringContext {
+            global_subroutines: Index::empty(),
+            local_subroutines: Index::empty(),
+            variations: coordinate.map(|_| {
+                CharstringVariations {
+                    store: ItemVariationStore::parse(&data),
+                    vsindex: 0,
+                    coordinates: &coordinates,
+                }
+            }),
+        };
+        let mut elements: Vec<PathElement> = vec![];
+        draw_charstring(charstring, &context, (0.0, 0.0), &mut elements)?;
+        Some(elements.iter().filter_map(|element| {
+            match *element {
+                PathElement::MoveTo(point) | PathElement::LineTo(point) => Some((point.x, point.y)),
+                _ => None,
+            }
+        }).collect())
+    }
+
+    #[test]
+    fn blends_operands_by_the_region_scalars() {
+        // 100 200 10 20 2 blend rmoveto 50 5 1 blend 0 rlineto
+        let mut charstring = operation(&[100, 200, 10, 20, 2], BLEND);
+        charstring.push(RMOVETO);
+        charstring.extend(operation(&[50, 5, 1], BLEND));
+        charstring.extend(operation(&[0], RLINETO));
+
+        assert_eq!(draw(&charstring, Some(0.0)).unwrap(), vec![(100.0, 200.0), (150.0, 200.0)]);
+        assert_eq!(draw(&charstring, Some(0.5)).unwrap(), vec![(105.0, 210.0), (157.5, 210.0)]);
+        assert_eq!(draw(&charstring, Some(1.0)).unwrap(), vec![(110.0, 220.0), (165.0, 220.0)]);
+        assert_eq!(draw(&charstring, Some(-1.0)).unwrap(), vec![(100.0, 200.0), (150.0, 200.0)]);
+    }
+
+    #[test]
+    fn vsindex_picks_the_item_variation_data() {
+        // 1 vsindex 100 200 10 -30 20 -40 2 blend rmoveto
+        let mut charstring = operation(&[1], VSINDEX);
+        charstring.extend(operation(&[100, 200, 10, -30, 20, -40, 2], BLEND));
+        charstring.push(RMOVETO);
+
+        assert_eq!(draw(&charstring, Some(0.0)).unwrap(), vec![(100.0, 200.0)]);
+        assert_eq!(draw(&charstring, Some(1.0)).unwrap(), vec![(110.0, 220.0)]);
+        assert_eq!(draw(&charstring, Some(-1.0)).unwrap(), vec![(70.0, 160.0)]);
+    }
+
+    #[test]
+    fn only_cff_charstrings_start_with_a_width() {
+        let charstring = operation(&[10, 100, 200], RMOVETO);
+        assert_eq!(draw(&charstring, None).unwrap(), vec![(100.0, 200.0)]);
+        assert_eq!(draw(&charstring, Some(0.0)), None);
+    }
+}
@@ -0,0 +1,97 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The feature name table, `feat`, which lists the AAT features an Apple font supports, the
+//! settings of each, and their names.
+
+use error::{Error, Result};
+use parser::{self, Reader};
+use tag::kCTFontTableFeat;
+
+const HEADER_SIZE: usize = 12;
+const FEATURE_NAME_SIZE: usize = 12;
+const SETTING_NAME_SIZE: usize = 4;
+
+// Feature flags.
+const EXCLUSIVE: u16 = 0x8000;
+const NOT_DEFAULT_SETTING: u16 = 0x4000;
+const DEFAULT_SETTING_INDEX: u16 = 0x00FF;
+
+/// A feature and its settings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeatureName {
+    /// The feature type, such as 1 for ligatures.
+    pub feature_type: u16,
+    /// The feature's settings, and the `name` table entries holding their names.
+    pub settings: Vec<(u16, u16)>,
+    /// Whether exactly one of the settings is on at a time. Otherwise each setting is one of a
+    /// pair, with even settings turning something on and the odd setting after turning it off.
+    pub exclusive: bool,
+    /// The index in `settings` of the setting that is on by default, for exclusive features.
+    pub default_setting_index: usize,
+    /// The `name` table entry holding the feature's name.
+    pub name_id: u16,
+}
+
+/// A `feat` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Feat<'a> {
+    data: &'a [u8],
+    feature_count: u16,
+}
+
+impl<'a> Feat<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Feat<'a>> {
+        Feat::parse_table(data).ok_or(Error::MalformedTable(kCTFontTableFeat))
+    }
+
+    fn parse_table(data: &'a [u8]) -> Option<Feat<'a>> {
+        let version = parser::read_u32(data, 0)?;
+        let feature_count = parser::read_u16(data, 4)?;
+        if version != 0x00010000 {
+            return None;
+        }
+        Some(Feat {
+            data,
+            feature_count,
+        })
+    }
+
+    /// Returns the font's features, in the table's order, leaving out any that are malformed.
+    pub fn features(&self) -> Vec<FeatureName> {
+        let names = match Reader::at(self.data, HEADER_SIZE)
+            .read_bytes(self.feature_count as usize * FEATURE_NAME_SIZE) {
+            Some(names) => names,
+            None => return vec![],
+        };
+        names.chunks(FEATURE_NAME_SIZE).filter_map(|name| {
+            let mut reader = Reader::new(name);
+            let feature_type = reader.read_u16()?;
+            let setting_count = reader.read_u16()? as usize;
+            let settings_offset = reader.read_u32()? as usize;
+            let flags = reader.read_u16()?;
+            let name_id = reader.read_u16()?;
+            let settings = Reader::at(self.data, settings_offset)
+                .read_bytes(setting_count * SETTING_NAME_SIZE)?;
+            Some(FeatureName {
+                feature_type,
+                settings: settings.chunks(SETTING_NAME_SIZE).filter_map(|setting| {
+                    Some((parser::read_u16(setting, 0)?, parser::read_u16(setting, 2)?))
+                }).collect(),
+                exclusive: flags & EXCLUSIVE != 0,
+                default_setting_index: if flags & NOT_DEFAULT_SETTING != 0 {
+                    (flags & DEFAULT_SETTING_INDEX) as usize
+                } else {
+                    0
+                },
+                name_id,
+            })
+        }).collect()
+    }
+}
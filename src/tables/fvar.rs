@@ -0,0 +1,216 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `fvar` table, which lists the design axes of a variable font.
+
+use error::{Error, Result};
+use parser::{self, Reader};
+use tag::{Tag, kCTFontTableFvar};
+
+const HEADER_SIZE: usize = 16;
+const AXIS_RECORD_SIZE: u16 = 20;
+const HIDDEN_AXIS: u16 = 0x0001;
+const NO_NAME_ID: u16 = 0xFFFF;
+
+/// One axis of a variable font, in the user units that `font-variation-settings` uses.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AxisRecord {
+    pub tag: Tag,
+    pub min_value: f64,
+    pub default_value: f64,
+    pub max_value: f64,
+    pub flags: u16,
+    /// The `name` table entry holding the axis's name.
+    pub name_id: u16,
+}
+
+impl AxisRecord {
+    /// Whether the font asks for the axis to be left out of user interfaces.
+    pub fn is_hidden(&self) -> bool {
+        self.flags & HIDDEN_AXIS != 0
+    }
+
+    /// Maps a user coordinate, clamped to the axis's range, to -1 at its minimum, 0 at its
+    /// default and 1 at its maximum, linearly on either side of the default and rounded to
+    /// 2.14 fixed point. This is before any `avar` mapping.
+    pub fn normalize(&self, value: f64) -> f64 {
+        let coordinate = if value < self.default_value && self.min_value < self.default_value {
+            (value.max(self.min_value) - self.default_value) /
+            (self.default_value - self.min_value)
+        } else if value > self.default_value && self.max_value > self.default_value {
+            (value.min(self.max_value) - self.default_value) /
+            (self.max_value - self.default_value)
+        } else {
+            0.0
+        };
+        parser::round_to_f2dot14(coordinate)
+    }
+}
+
+/// A named instance of a variable font, such as "Semibold".
+#[derive(Clone, Debug, PartialEq)]
+pub struct InstanceRecord {
+    /// The `name` table entry holding the instance's style name.
+    pub subfamily_name_id: u16,
+    /// The instance's position on each axis, in user units and axis order.
+    pub coordinates: Vec<f64>,
+    /// The `name` table entry holding the instance's PostScript name, if it has one.
+    pub postscript_name_id: Option<u16>,
+}
+
+/// An `fvar` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Fvar<'a> {
+    data: &'a [u8],
+    axes_offset: usize,
+    axis_count: u16,
+    instance_count: u16,
+    instance_size: u16,
+}
+
+impl<'a> Fvar<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Fvar<'a>> {
+        Fvar::parse_table(data).ok_or(Error::MalformedTable(kCTFontTableFvar))
+    }
+
+    fn parse_table(data: &'a [u8]) -> Option<Fvar<'a>> {
+        let mut reader = Reader::new(data);
+        let major_version = reader.read_u16()?;
+        reader.skip(2)?; // minor version
+        let axes_offset = reader.read_u16()? as usize;
+        reader.skip(2)?; // reserved
+        let axis_count = reader.read_u16()?;
+        let axis_size = reader.read_u16()?;
+        let instance_count = reader.read_u16()?;
+        let instance_size = reader.read_u16()?;
+        if major_version != 1 || axes_offset < HEADER_SIZE || axis_size != AXIS_RECORD_SIZE ||
+           (instance_count > 0 && (instance_size as usize) < axis_count as usize * 4 + 4) {
+            return None;
+        }
+        Reader::at(data, axes_offset).read_bytes(axis_count as usize * AXIS_RECORD_SIZE as usize +
+                                                 instance_count as usize * instance_size as usize)?;
+        Some(Fvar {
+            data,
+            axes_offset,
+            axis_count,
+            instance_count,
+            instance_size,
+        })
+    }
+
+    pub fn axis_count(&self) -> u16 {
+        self.axis_count
+    }
+
+    pub fn axis(&self, index: u16) -> Option<AxisRecord> {
+        if index >= self.axis_count {
+            return None;
+        }
+        let mut reader = Reader::at(self.data,
+                                    self.axes_offset + index as usize * AXIS_RECORD_SIZE as usize);
+        let tag = Tag(reader.read_u32()?);
+        let min_value = reader.read_fixed()?;
+        let default_value = reader.read_fixed()?;
+        let max_value = reader.read_fixed()?;
+        Some(AxisRecord {
+            tag,
+            // Keep inverted ranges from breaking normalization.
+            min_value: min_value.min(default_value),
+            default_value,
+            max_value: max_value.max(default_value),
+            flags: reader.read_u16()?,
+            name_id: reader.read_u16()?,
+        })
+    }
+
+    /// Returns every axis, in the order that normalized coordinates follow.
+    pub fn axes(&self) -> Vec<AxisRecord> {
+        (0..self.axis_count).filter_map(|index| self.axis(index)).collect()
+    }
+
+    pub fn instance_count(&self) -> u16 {
+        self.instance_count
+    }
+
+    pub fn instance(&self, index: u16) -> Option<InstanceRecord> {
+        if index >= self.instance_count {
+            return None;
+        }
+        let mut reader = Reader::at(self.data,
+                                    self.axes_offset +
+                                    self.axis_count as usize * AXIS_RECORD_SIZE as usize +
+                                    index as usize * self.instance_size as usize);
+        let subfamily_name_id = reader.read_u16()?;
+        reader.skip(2)?; // flags
+        let coordinates = (0..self.axis_count).map(|_| reader.read_fixed())
+                                              .collect::<Option<Vec<f64>>>()?;
+        // The PostScript name ID is only there if the record has room for it.
+        let postscript_name_id = if self.instance_size as usize >= self.axis_count as usize * 4 + 6 {
+            Some(reader.read_u16()?).filter(|&name_id| name_id != NO_NAME_ID && name_id != 0)
+        } else {
+            None
+        };
+        Some(InstanceRecord {
+            subfamily_name_id,
+            coordinates,
+            postscript_name_id,
+        })
+    }
+
+    /// Returns every named instance, in the font's order.
+    pub fn instances(&self) -> Vec<InstanceRecord> {
+        (0..self.instance_count).filter_map(|index| self.instance(index)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tag::Tag;
+    use super::AxisRecord;
+
+    fn weight_axis() -> AxisRecord {
+        AxisRecord {
+            tag: Tag::new(b"wght"),
+            min_value: 100.0,
+            default_value: 400.0,
+            max_value: 900.0,
+            flags: 0,
+            name_id: 256,
+        }
+    }
+
+    #[test]
+    fn normalizes_the_minimum_default_and_maximum() {
+        let axis = weight_axis();
+        assert_eq!(axis.normalize(100.0), -1.0);
+        assert_eq!(axis.normalize(250.0), -0.5);
+        assert_eq!(axis.normalize(400.0), 0.0);
+        assert_eq!(axis.normalize(650.0), 0.5);
+        assert_eq!(axis.normalize(900.0), 1.0);
+    }
+
+    #[test]
+    fn clamps_to_the_axis_range() {
+        let axis = weight_axis();
+        assert_eq!(axis.normalize(1.0), -1.0);
+        assert_eq!(axis.normalize(1000.0), 1.0);
+
+        let axis = AxisRecord {
+            min_value: 400.0,
+            ..weight_axis()
+        };
+        assert_eq!(axis.normalize(100.0), 0.0);
+    }
+
+    #[test]
+    fn rounds_to_f2dot14() {
+        // 1/500 is 32.768 units of 1/16384.
+        assert_eq!(weight_axis().normalize(401.0), 33.0 / 16384.0);
+    }
+}
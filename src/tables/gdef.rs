@@ -0,0 +1,200 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The glyph definition table, `GDEF`, which sorts glyphs into bases, ligatures and marks for
+//! `GSUB` and `GPOS`, and records attachment points and the caret positions inside ligatures.
+
+use error::{Error, Result};
+use parser::{self, Reader};
+use tables::layout::{ClassDef, Coverage};
+use tables::variation_store::ItemVariationStore;
+use tag::kCTFontTableGDEF;
+
+/// The delta format of a device table that is really a `VariationIndex` table.
+const VARIATION_INDEX_FORMAT: u16 = 0x8000;
+
+/// A caret position inside a ligature glyph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaretValue {
+    /// A distance along the baseline from the glyph's origin, in font units, and in variable
+    /// fonts, the outer and inner indices of the delta set in the variation store that varies
+    /// it.
+    Coordinate(i16, Option<(u16, u16)>),
+    /// The index of a point on the glyph's outline, whose position is the caret's.
+    PointIndex(u16),
+}
+
+/// A `GDEF` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Gdef<'a> {
+    data: &'a [u8],
+    glyph_classes: ClassDef<'a>,
+    attach_list_offset: u16,
+    ligature_caret_list_offset: u16,
+    mark_attachment_classes: ClassDef<'a>,
+    mark_glyph_sets_offset: u16,
+    variation_store_offset: u32,
+}
+
+impl<'a> Gdef<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Gdef<'a>> {
+        Gdef::parse_table(data).ok_or(Error::MalformedTable(kCTFontTableGDEF))
+    }
+
+    fn parse_table(data: &'a [u8]) -> Option<Gdef<'a>> {
+        let mut reader = Reader::new(data);
+        let major_version = reader.read_u16()?;
+        let minor_version = reader.read_u16()?;
+        if major_version != 1 {
+            return None;
+        }
+        let glyph_classes = ClassDef::parse_at(data, 4)?;
+        reader.skip(2)?;
+        let attach_list_offset = reader.read_u16()?;
+        let ligature_caret_list_offset = reader.read_u16()?;
+        let mark_attachment_classes = ClassDef::parse_at(data, 10)?;
+        reader.skip(2)?;
+        let mark_glyph_sets_offset = if minor_version >= 2 { reader.read_u16()? } else { 0 };
+        let variation_store_offset = if minor_version >= 3 { reader.read_u32()? } else { 0 };
+        Some(Gdef {
+            data,
+            glyph_classes,
+            attach_list_offset,
+            ligature_caret_list_offset,
+            mark_attachment_classes,
+            mark_glyph_sets_offset,
+            variation_store_offset,
+        })
+    }
+
+    /// Returns the glyph's class: 1 for base glyphs, 2 for ligatures, 3 for marks and 4 for
+    /// components, or 0 if the table doesn't say.
+    pub fn glyph_class(&self, glyph: u16) -> u16 {
+        self.glyph_classes.class(glyph)
+    }
+
+    /// Returns the mark attachment class of a mark, which lookups can filter marks by, or 0.
+    pub fn mark_attachment_class(&self, glyph: u16) -> u16 {
+        self.mark_attachment_classes.class(glyph)
+    }
+
+    /// Returns the indices of the outline points that other glyphs attach to on the glyph.
+    pub fn attachment_points(&self, glyph: u16) -> Vec<u16> {
+        self.attachment_points_for(glyph).unwrap_or_default()
+    }
+
+    fn attachment_points_for(&self, glyph: u16) -> Option<Vec<u16>> {
+        let list = self.subtable(self.attach_list_offset as usize)?;
+        let index = Coverage::parse_at(list, 0)?.index(glyph)? as usize;
+        let points = list.get(parser::read_u16(list, 4 + index * 2)? as usize..)?;
+        let mut reader = Reader::new(points);
+        let count = reader.read_u16()?;
+        (0..count).map(|_| reader.read_u16()).collect()
+    }
+
+    /// Returns the caret positions inside a ligature glyph, in order along the baseline. Glyphs
+    /// that aren't ligatures have none.
+    pub fn ligature_carets(&self, glyph: u16) -> Vec<CaretValue> {
+        self.ligature_carets_for(glyph).unwrap_or_default()
+    }
+
+    fn ligature_carets_for(&self, glyph: u16) -> Option<Vec<CaretValue>> {
+        let list = self.subtable(self.ligature_caret_list_offset as usize)?;
+        let index = Coverage::parse_at(list, 0)?.index(glyph)? as usize;
+        let ligature = list.get(parser::read_u16(list, 4 + index * 2)? as usize..)?;
+        let mut reader = Reader::new(ligature);
+        let count = reader.read_u16()?;
+        (0..count).map(|_| {
+            let caret = ligature.get(reader.read_u16()? as usize..)?;
+            let mut reader = Reader::new(caret);
+            match reader.read_u16()? {
+                1 => Some(CaretValue::Coordinate(reader.read_i16()?, None)),
+                2 => Some(CaretValue::PointIndex(reader.read_u16()?)),
+                3 => {
+                    let coordinate = reader.read_i16()?;
+                    // Device tables adjust hinted sizes, which aren't supported, so only
+                    // variation indices are kept.
+                    let device = match reader.read_u16()? {
+                        0 => None,
+                        offset => caret.get(offset as usize..),
+                    };
+                    let variation = device.and_then(|device| {
+                        let mut reader = Reader::new(device);
+                        let (outer, inner) = (reader.read_u16()?, reader.read_u16()?);
+                        if reader.read_u16()? != VARIATION_INDEX_FORMAT {
+                            return None;
+                        }
+                        Some((outer, inner))
+                    });
+                    Some(CaretValue::Coordinate(coordinate, variation))
+                }
+                _ => None,
+            }
+        }).collect()
+    }
+
+    /// Returns the number of mark glyph sets, which lookups can filter marks by.
+    pub fn mark_glyph_set_count(&self) -> u16 {
+        self.subtable(self.mark_glyph_sets_offset as usize)
+            .and_then(|sets| parser::read_u16(sets, 2))
+            .unwrap_or(0)
+    }
+
+    /// Returns the coverage table of the `index`th mark glyph set, or `None` if the table has
+    /// no such set.
+    pub fn mark_glyph_set(&self, index: u16) -> Option<Coverage<'a>> {
+        let sets = self.subtable(self.mark_glyph_sets_offset as usize)?;
+        if parser::read_u16(sets, 0)? != 1 || index >= parser::read_u16(sets, 2)? {
+            return None;
+        }
+        let offset = parser::read_u32(sets, 4 + index as usize * 4)?;
+        Coverage::parse(sets.get(offset as usize..)?)
+    }
+
+    /// Returns the variation store that varies caret positions and `GPOS` values, if the table
+    /// has one.
+    pub fn variation_store(&self) -> Option<ItemVariationStore<'a>> {
+        ItemVariationStore::parse(self.subtable(self.variation_store_offset as usize)?)
+    }
+
+    /// Returns the data at `offset`, or `None` for a null offset.
+    fn subtable(&self, offset: usize) -> Option<&'a [u8]> {
+        match offset {
+            0 => None,
+            offset => self.data.get(offset..),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_font::{self, A, FI, GRAVE_COMB};
+    use super::{CaretValue, Gdef};
+
+    #[test]
+    fn reads_all_three_caret_formats() {
+        let data = test_font::gdef_table();
+        let gdef = Gdef::parse(&data).unwrap();
+        assert_eq!(gdef.ligature_carets(FI), vec![CaretValue::Coordinate(600, None),
+                                                  CaretValue::PointIndex(5),
+                                                  CaretValue::Coordinate(1200, Some((0, 2)))]);
+        assert!(gdef.ligature_carets(A).is_empty());
+    }
+
+    #[test]
+    fn reads_mark_glyph_sets() {
+        let data = test_font::gdef_table();
+        let gdef = Gdef::parse(&data).unwrap();
+        assert_eq!(gdef.mark_glyph_set_count(), 1);
+        let set = gdef.mark_glyph_set(0).unwrap();
+        assert!(set.contains(GRAVE_COMB));
+        assert!(!set.contains(A));
+        assert!(gdef.mark_glyph_set(1).is_none());
+    }
+}
@@ -0,0 +1,414 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `glyf` table, which holds TrueType glyph outlines made of quadratic curves.
+
+use base::CGPoint;
+use error::Result;
+use outline::OutlineSink;
+use parser::Reader;
+use tables::gvar::Gvar;
+use tables::loca::Loca;
+
+// Simple glyph flags.
+const ON_CURVE_POINT: u8 = 0x01;
+const X_SHORT_VECTOR: u8 = 0x02;
+const Y_SHORT_VECTOR: u8 = 0x04;
+const REPEAT_FLAG: u8 = 0x08;
+const X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR: u8 = 0x10;
+const Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR: u8 = 0x20;
+
+// Composite glyph flags.
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+const SCALED_COMPONENT_OFFSET: u16 = 0x0800;
+const UNSCALED_COMPONENT_OFFSET: u16 = 0x1000;
+
+/// How deeply composite glyphs may nest, so that cycles can't recurse forever.
+const MAX_COMPONENT_DEPTH: u8 = 16;
+
+/// A glyph's bounding box, in font units, as recorded in its header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoundingBox {
+    pub x_min: i16,
+    pub y_min: i16,
+    pub x_max: i16,
+    pub y_max: i16,
+}
+
+/// The number of phantom points that follow a glyph's own points in `gvar`, standing for its
+/// horizontal and vertical metrics.
+const PHANTOM_POINT_COUNT: usize = 4;
+
+/// A `glyf` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Glyf<'a> {
+    data: &'a [u8],
+    loca: Loca<'a>,
+    variations: Option<(Gvar<'a>, &'a [f64])>,
+}
+
+impl<'a> Glyf<'a> {
+    /// Parses the table given the font's `loca` table. Glyph data is only checked when it's
+    /// read.
+    pub fn parse(data: &'a [u8], loca: Loca<'a>) -> Result<Glyf<'a>> {
+        Ok(Glyf {
+            data,
+            loca,
+            variations: None,
+        })
+    }
+
+    /// Draws outlines varied by `gvar` at the normalized `coordinates`, one per `fvar` axis.
+    /// Bounding boxes still come from glyph headers, which describe the default instance.
+    pub fn with_variations(mut self, gvar: Gvar<'a>, coordinates: &'a [f64]) -> Glyf<'a> {
+        self.variations = Some((gvar, coordinates));
+        self
+    }
+
+    fn glyph_data(&self, glyph: u16) -> Option<&'a [u8]> {
+        self.data.get(self.loca.glyph_range(glyph)?)
+    }
+
+    /// Returns the glyph's bounding box, or `None` if the glyph has no outline.
+    pub fn bounding_box(&self, glyph: u16) -> Option<BoundingBox> {
+        let mut reader = Reader::new(self.glyph_data(glyph)?);
+        reader.skip(2)?; // numberOfContours
+        Some(BoundingBox {
+            x_min: reader.read_i16()?,
+            y_min: reader.read_i16()?,
+            x_max: reader.read_i16()?,
+            y_max: reader.read_i16()?,
+        })
+    }
+
+    /// Sends the glyph's outline, in font units, to `sink`.
+    ///
+    /// Returns false, without calling `sink`, if the glyph isn't in the font or its data is
+    /// malformed. Glyphs with no outline, such as spaces, succeed without drawing anything.
+    pub fn outline(&self, glyph: u16, sink: &mut dyn OutlineSink) -> bool {
+        match self.contours(glyph, 0) {
+            Some(contours) => {
+                contours.draw(sink);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the `index`th point of the glyph's outline, in font units, counting the points
+    /// of composite glyphs' components in order. Off-curve points count.
+    pub fn point(&self, glyph: u16, index: u16) -> Option<CGPoint> {
+        self.contours(glyph, 0)?.points.get(index as usize).map(|point| point.to_cg_point())
+    }
+
+    /// Returns the changes that `gvar` makes to the glyph's horizontal and vertical advances,
+    /// in font units, or `None` without variations.
+    ///
+    /// Fonts without `HVAR` or `VVAR` vary their metrics this way, through the phantom points.
+    pub fn advance_deltas(&self, glyph: u16) -> Option<(f64, f64)> {
+        let (gvar, coordinates) = self.variations?;
+        let data = self.glyph_data(glyph)?;
+        let point_count = if data.is_empty() {
+            0
+        } else {
+            let mut reader = Reader::new(data);
+            let contour_count = reader.read_i16()?;
+            reader.skip(8)?; // bounding box
+            if contour_count >= 0 {
+                simple_contours(reader, contour_count as u16)?.points.len()
+            } else {
+                read_components(reader)?.len()
+            }
+        };
+
+        // Only the phantom points matter, and their deltas don't depend on where points are.
+        let mut points = vec![CGPoint::new(0.0, 0.0); point_count + PHANTOM_POINT_COUNT];
+        gvar.apply(glyph, coordinates, &mut points, &[])?;
+        let phantom = &points[point_count..];
+        Some((phantom[1].x - phantom[0].x, phantom[2].y - phantom[3].y))
+    }
+
+    fn contours(&self, glyph: u16, depth: u8) -> Option<Contours> {
+        if depth > MAX_COMPONENT_DEPTH {
+            return None;
+        }
+        let data = self.glyph_data(glyph)?;
+        if data.is_empty() {
+            return Some(Contours::default());
+        }
+        let mut reader = Reader::new(data);
+        let contour_count = reader.read_i16()?;
+        reader.skip(8)?; // bounding box
+        if contour_count >= 0 {
+            let mut contours = simple_contours(reader, contour_count as u16)?;
+            if let Some((gvar, coordinates)) = self.variations {
+                let mut points: Vec<CGPoint> =
+                    contours.points.iter().map(|point| point.to_cg_point()).collect();
+                points.resize(points.len() + PHANTOM_POINT_COUNT, CGPoint::new(0.0, 0.0));
+                gvar.apply(glyph, coordinates, &mut points, &contours.ends)?;
+                for (point, varied) in contours.points.iter_mut().zip(points) {
+                    point.x = varied.x;
+                    point.y = varied.y;
+                }
+            }
+            Some(contours)
+        } else {
+            self.composite_contours(glyph, reader, depth)
+        }
+    }
+
+    fn composite_contours(&self, glyph: u16, reader: Reader<'a>, depth: u8) -> Option<Contours> {
+        let components = read_components(reader)?;
+
+        // Variations move each component's offset, which is the component's point in `gvar`.
+        let mut offsets: Vec<CGPoint> = components.iter().map(|component| {
+            CGPoint::new(component.arg1 as f64, component.arg2 as f64)
+        }).collect();
+        if let Some((gvar, coordinates)) = self.variations {
+            offsets.resize(offsets.len() + PHANTOM_POINT_COUNT, CGPoint::new(0.0, 0.0));
+            gvar.apply(glyph, coordinates, &mut offsets, &[])?;
+        }
+
+        let mut contours = Contours::default();
+        for (component, offset) in components.iter().zip(offsets) {
+            let mut child = self.contours(component.glyph, depth + 1)?;
+            let [xx, xy, yx, yy] = component.matrix;
+            for point in &mut child.points {
+                let (x, y) = (point.x, point.y);
+                point.x = xx * x + yx * y;
+                point.y = xy * x + yy * y;
+            }
+
+            let flags = component.flags;
+            let (dx, dy) = if flags & ARGS_ARE_XY_VALUES != 0 {
+                let (dx, dy) = (offset.x, offset.y);
+                let scaled = flags & SCALED_COMPONENT_OFFSET != 0 &&
+                             flags & UNSCALED_COMPONENT_OFFSET == 0;
+                if scaled {
+                    (xx * dx + yx * dy, xy * dx + yy * dy)
+                } else {
+                    (dx, dy)
+                }
+            } else {
+                // Move the component so its point arg2 lands on the glyph's point arg1.
+                let parent = contours.points.get(component.arg1 as usize)?;
+                let child = child.points.get(component.arg2 as usize)?;
+                (parent.x - child.x, parent.y - child.y)
+            };
+            contours.append(&child, dx, dy);
+        }
+        Some(contours)
+    }
+}
+
+/// One component of a composite glyph.
+struct Component {
+    flags: u16,
+    glyph: u16,
+    arg1: i32,
+    arg2: i32,
+    /// The 2x2 matrix [xx xy; yx yy], which maps (x, y) to (xx x + yx y, xy x + yy y).
+    matrix: [f64; 4],
+}
+
+fn read_components(mut reader: Reader) -> Option<Vec<Component>> {
+    let mut components = vec![];
+    loop {
+        let flags = reader.read_u16()?;
+        let glyph = reader.read_u16()?;
+        let (arg1, arg2) = match (flags & ARG_1_AND_2_ARE_WORDS != 0,
+                                  flags & ARGS_ARE_XY_VALUES != 0) {
+            (true, true) => (reader.read_i16()? as i32, reader.read_i16()? as i32),
+            (true, false) => (reader.read_u16()? as i32, reader.read_u16()? as i32),
+            (false, true) => (reader.read_u8()? as i8 as i32, reader.read_u8()? as i8 as i32),
+            (false, false) => (reader.read_u8()? as i32, reader.read_u8()? as i32),
+        };
+
+        let matrix = if flags & WE_HAVE_A_SCALE != 0 {
+            let scale = reader.read_f2dot14()?;
+            [scale, 0.0, 0.0, scale]
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            [reader.read_f2dot14()?, 0.0, 0.0, reader.read_f2dot14()?]
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            [reader.read_f2dot14()?,
+             reader.read_f2dot14()?,
+             reader.read_f2dot14()?,
+             reader.read_f2dot14()?]
+        } else {
+            [1.0, 0.0, 0.0, 1.0]
+        };
+
+        components.push(Component {
+            flags,
+            glyph,
+            arg1,
+            arg2,
+            matrix,
+        });
+        if flags & MORE_COMPONENTS == 0 {
+            return Some(components);
+        }
+    }
+}
+
+fn simple_contours(mut reader: Reader, contour_count: u16) -> Option<Contours> {
+    let mut ends = Vec::with_capacity(contour_count as usize);
+    for _ in 0..contour_count {
+        let end = reader.read_u16()? as usize;
+        if ends.last().is_some_and(|&last| end <= last) {
+            return None;
+        }
+        ends.push(end);
+    }
+    let point_count = ends.last().map_or(0, |&last| last + 1);
+    let instruction_length = reader.read_u16()? as usize;
+    reader.skip(instruction_length)?;
+
+    let mut flags = Vec::with_capacity(point_count);
+    while flags.len() < point_count {
+        let flag = reader.read_u8()?;
+        let repeat = if flag & REPEAT_FLAG != 0 { reader.read_u8()? as usize } else { 0 };
+        for _ in 0..repeat + 1 {
+            flags.push(flag);
+        }
+    }
+    flags.truncate(point_count);
+
+    let xs = read_coordinates(&mut reader,
+                              &flags,
+                              X_SHORT_VECTOR,
+                              X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR)?;
+    let ys = read_coordinates(&mut reader,
+                              &flags,
+                              Y_SHORT_VECTOR,
+                              Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR)?;
+    let points = flags.iter().zip(xs).zip(ys).map(|((&flag, x), y)| {
+        Point {
+            x: x as f64,
+            y: y as f64,
+            on_curve: flag & ON_CURVE_POINT != 0,
+        }
+    }).collect();
+    Some(Contours {
+        points,
+        ends,
+    })
+}
+
+/// Reads one axis of a simple glyph's delta-encoded coordinates, returning absolute values.
+fn read_coordinates(reader: &mut Reader, flags: &[u8], short: u8, same_or_positive: u8)
+                    -> Option<Vec<i32>> {
+    let mut value = 0i32;
+    let mut coordinates = Vec::with_capacity(flags.len());
+    for &flag in flags {
+        if flag & short != 0 {
+            let delta = reader.read_u8()? as i32;
+            value += if flag & same_or_positive != 0 { delta } else { -delta };
+        } else if flag & same_or_positive == 0 {
+            value += reader.read_i16()? as i32;
+        }
+        coordinates.push(value);
+    }
+    Some(coordinates)
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Point {
+    x: f64,
+    y: f64,
+    on_curve: bool,
+}
+
+impl Point {
+    fn midpoint(&self, other: &Point) -> Point {
+        Point {
+            x: (self.x + other.x) / 2.0,
+            y: (self.y + other.y) / 2.0,
+            on_curve: true,
+        }
+    }
+
+    fn to_cg_point(self) -> CGPoint {
+        CGPoint::new(self.x, self.y)
+    }
+}
+
+/// A glyph's points, and the index of the last point of each contour.
+#[derive(Default)]
+struct Contours {
+    points: Vec<Point>,
+    ends: Vec<usize>,
+}
+
+impl Contours {
+    /// Adds another glyph's contours, moved by `(dx, dy)`.
+    fn append(&mut self, other: &Contours, dx: f64, dy: f64) {
+        let offset = self.points.len();
+        self.points.extend(other.points.iter().map(|point| {
+            Point {
+                x: point.x + dx,
+                y: point.y + dy,
+                on_curve: point.on_curve,
+            }
+        }));
+        self.ends.extend(other.ends.iter().map(|end| end + offset));
+    }
+
+    fn draw(&self, sink: &mut dyn OutlineSink) {
+        let mut start = 0;
+        for &end in &self.ends {
+            if let Some(points) = self.points.get(start..end + 1) {
+                draw_contour(points, sink);
+            }
+            start = end + 1;
+        }
+    }
+}
+
+/// Draws one closed contour of on- and off-curve points. Two off-curve points in a row have an
+/// implied on-curve point between them.
+fn draw_contour(points: &[Point], sink: &mut dyn OutlineSink) {
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return,
+    };
+    let (start, rest) = if first.on_curve {
+        (first, &points[1..])
+    } else if last.on_curve {
+        (last, &points[..points.len() - 1])
+    } else {
+        (first.midpoint(&last), points)
+    };
+
+    sink.move_to(start.to_cg_point());
+    let mut control: Option<Point> = None;
+    for point in rest {
+        match (point.on_curve, control) {
+            (true, Some(previous)) => {
+                sink.quad_to(previous.to_cg_point(), point.to_cg_point());
+                control = None;
+            }
+            (true, None) => sink.line_to(point.to_cg_point()),
+            (false, Some(previous)) => {
+                sink.quad_to(previous.to_cg_point(), previous.midpoint(point).to_cg_point());
+                control = Some(*point);
+            }
+            (false, None) => control = Some(*point),
+        }
+    }
+    if let Some(previous) = control {
+        sink.quad_to(previous.to_cg_point(), start.to_cg_point());
+    }
+    sink.close();
+}
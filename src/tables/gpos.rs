@@ -0,0 +1,437 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `GPOS` table, which adjusts glyph positions for kerning, mark attachment, cursive
+//! scripts and other features.
+
+use error::{Error, Result};
+use parser::{self, Reader};
+use tables::gdef::Gdef;
+use tables::layout::{ClassDef, Context, Coverage, LayoutTable, Lookup};
+use tag::kCTFontTableGPOS;
+
+const SINGLE: u16 = 1;
+const PAIR: u16 = 2;
+const CURSIVE: u16 = 3;
+const MARK_TO_BASE: u16 = 4;
+const MARK_TO_LIGATURE: u16 = 5;
+const MARK_TO_MARK: u16 = 6;
+const CONTEXT: u16 = 7;
+const CHAINED_CONTEXT: u16 = 8;
+const EXTENSION: u16 = 9;
+
+// Value formats.
+const X_PLACEMENT: u16 = 0x0001;
+const Y_PLACEMENT: u16 = 0x0002;
+const X_ADVANCE: u16 = 0x0004;
+const Y_ADVANCE: u16 = 0x0008;
+
+/// A `GPOS` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Gpos<'a> {
+    layout: LayoutTable<'a>,
+    gdef: Option<Gdef<'a>>,
+}
+
+impl<'a> Gpos<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Gpos<'a>> {
+        let layout = LayoutTable::parse(data).ok_or(Error::MalformedTable(kCTFontTableGPOS))?;
+        Ok(Gpos {
+            layout,
+            gdef: None,
+        })
+    }
+
+    /// Uses the font's `GDEF` table for the glyph classes and mark glyph sets that lookups
+    /// skip glyphs by.
+    pub fn with_gdef(mut self, gdef: Gdef<'a>) -> Gpos<'a> {
+        self.gdef = Some(gdef);
+        self
+    }
+
+    pub fn gdef(&self) -> Option<&Gdef<'a>> {
+        self.gdef.as_ref()
+    }
+
+    /// The table's scripts, features and lookups.
+    pub fn layout(&self) -> &LayoutTable<'a> {
+        &self.layout
+    }
+
+    /// Returns the `index`th lookup and its subtables, leaving out subtables that are
+    /// malformed or of an unknown type.
+    pub fn lookup(&self, index: u16) -> Option<(Lookup<'a>, Vec<Positioning<'a>>)> {
+        let lookup = self.layout.lookup(index)?;
+        let subtables = lookup.subtables(EXTENSION).into_iter().filter_map(|(lookup_type, data)| {
+            Positioning::parse(lookup_type, data)
+        }).collect();
+        Some((lookup, subtables))
+    }
+}
+
+/// A `GPOS` subtable.
+#[derive(Clone, Copy, Debug)]
+pub enum Positioning<'a> {
+    Single(SingleAdjustment<'a>),
+    Pair(PairAdjustment<'a>),
+    Cursive(CursiveAttachment<'a>),
+    MarkToBase(MarkAttachment<'a>),
+    MarkToLigature(MarkToLigatureAttachment<'a>),
+    /// Attaches marks to the marks before them, with the earlier marks as bases.
+    MarkToMark(MarkAttachment<'a>),
+    /// A contextual or chained contextual subtable, which applies other lookups.
+    Context(Context<'a>),
+}
+
+impl<'a> Positioning<'a> {
+    fn parse(lookup_type: u16, data: &'a [u8]) -> Option<Positioning<'a>> {
+        Some(match lookup_type {
+            SINGLE => Positioning::Single(SingleAdjustment::parse(data)?),
+            PAIR => Positioning::Pair(PairAdjustment::parse(data)?),
+            CURSIVE => Positioning::Cursive(CursiveAttachment::parse(data)?),
+            MARK_TO_BASE => Positioning::MarkToBase(MarkAttachment::parse(data)?),
+            MARK_TO_LIGATURE => {
+                Positioning::MarkToLigature(MarkToLigatureAttachment::parse(data)?)
+            }
+            MARK_TO_MARK => Positioning::MarkToMark(MarkAttachment::parse(data)?),
+            CONTEXT => Positioning::Context(Context::parse(data, false)?),
+            CHAINED_CONTEXT => Positioning::Context(Context::parse(data, true)?),
+            _ => return None,
+        })
+    }
+}
+
+/// An adjustment to a glyph's position and advance, in font units. Device and variation
+/// adjustments are not applied.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ValueRecord {
+    pub x_placement: i16,
+    pub y_placement: i16,
+    pub x_advance: i16,
+    pub y_advance: i16,
+}
+
+impl ValueRecord {
+    /// Reads a value record whose fields are given by `format`.
+    fn read(reader: &mut Reader, format: u16) -> Option<ValueRecord> {
+        let mut record = ValueRecord::default();
+        let fields = [
+            (X_PLACEMENT, &mut record.x_placement),
+            (Y_PLACEMENT, &mut record.y_placement),
+            (X_ADVANCE, &mut record.x_advance),
+            (Y_ADVANCE, &mut record.y_advance),
+        ];
+        for (flag, field) in fields {
+            if format & flag != 0 {
+                *field = reader.read_i16()?;
+            }
+        }
+        // The device and variation table offsets that follow are skipped.
+        reader.skip(value_record_size(format) - (format & 0x000F).count_ones() as usize * 2)?;
+        Some(record)
+    }
+}
+
+/// The size in bytes of a value record in `format`.
+fn value_record_size(format: u16) -> usize {
+    (format & 0x00FF).count_ones() as usize * 2
+}
+
+/// A point on a glyph that another glyph attaches to, in font units.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Anchor {
+    pub x: i16,
+    pub y: i16,
+}
+
+impl Anchor {
+    /// Reads the anchor at `offset` in `data`, which is absent if the offset is 0. Contour
+    /// point and device adjustments are not applied.
+    fn read(data: &[u8], offset: u16) -> Option<Anchor> {
+        if offset == 0 {
+            return None;
+        }
+        let mut reader = Reader::at(data, offset as usize);
+        let format = reader.read_u16()?;
+        if !(1..=3).contains(&format) {
+            return None;
+        }
+        Some(Anchor {
+            x: reader.read_i16()?,
+            y: reader.read_i16()?,
+        })
+    }
+}
+
+/// A single adjustment subtable, which moves glyphs or changes their advances.
+#[derive(Clone, Copy, Debug)]
+pub struct SingleAdjustment<'a> {
+    data: &'a [u8],
+    coverage: Coverage<'a>,
+}
+
+impl<'a> SingleAdjustment<'a> {
+    fn parse(data: &'a [u8]) -> Option<SingleAdjustment<'a>> {
+        match parser::read_u16(data, 0)? {
+            1 | 2 => {
+                Some(SingleAdjustment {
+                    data,
+                    coverage: Coverage::parse_at(data, 2)?,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn value(&self, glyph: u16) -> Option<ValueRecord> {
+        let index = self.coverage.index(glyph)? as usize;
+        let format = parser::read_u16(self.data, 4)?;
+        if parser::read_u16(self.data, 0)? == 1 {
+            return ValueRecord::read(&mut Reader::at(self.data, 6), format);
+        }
+        if index >= parser::read_u16(self.data, 6)? as usize {
+            return None;
+        }
+        ValueRecord::read(&mut Reader::at(self.data, 8 + index * value_record_size(format)),
+                          format)
+    }
+}
+
+/// A pair adjustment subtable, which adjusts pairs of glyphs, as kerning does.
+#[derive(Clone, Copy, Debug)]
+pub struct PairAdjustment<'a> {
+    data: &'a [u8],
+    format: u16,
+    coverage: Coverage<'a>,
+    first_value_format: u16,
+    second_value_format: u16,
+}
+
+impl<'a> PairAdjustment<'a> {
+    fn parse(data: &'a [u8]) -> Option<PairAdjustment<'a>> {
+        let mut reader = Reader::new(data);
+        let format = reader.read_u16()?;
+        if format != 1 && format != 2 {
+            return None;
+        }
+        let coverage = Coverage::parse_at(data, 2)?;
+        reader.skip(2)?; // coverageOffset
+        Some(PairAdjustment {
+            data,
+            format,
+            coverage,
+            first_value_format: reader.read_u16()?,
+            second_value_format: reader.read_u16()?,
+        })
+    }
+
+    /// Whether the subtable adjusts the second glyph of its pairs. If it doesn't, the second
+    /// glyph can start another pair.
+    pub fn adjusts_second_glyph(&self) -> bool {
+        self.second_value_format != 0
+    }
+
+    /// Returns the adjustments to `first` and `second`, if the subtable has the pair.
+    pub fn values(&self, first: u16, second: u16) -> Option<(ValueRecord, ValueRecord)> {
+        let index = self.coverage.index(first)? as usize;
+        let record_size = value_record_size(self.first_value_format) +
+                          value_record_size(self.second_value_format);
+        let offset = if self.format == 1 {
+            if index >= parser::read_u16(self.data, 8)? as usize {
+                return None;
+            }
+            let pair_set_offset = parser::read_u16(self.data, 10 + index * 2)? as usize;
+            let pair_set = self.data.get(pair_set_offset..)?;
+            let count = parser::read_u16(pair_set, 0)?;
+            let pair_size = 2 + record_size;
+            let pair = parser::binary_search(count as usize, |pair| {
+                Some(parser::read_u16(pair_set, 2 + pair * pair_size)?.cmp(&second))
+            })?;
+            pair_set_offset + 2 + pair * pair_size + 2
+        } else {
+            let first_classes = ClassDef::parse_at(self.data, 8)?;
+            let second_classes = ClassDef::parse_at(self.data, 10)?;
+            let first_class_count = parser::read_u16(self.data, 12)?;
+            let second_class_count = parser::read_u16(self.data, 14)?;
+            let (first_class, second_class) =
+                (first_classes.class(first), second_classes.class(second));
+            if first_class >= first_class_count || second_class >= second_class_count {
+                return None;
+            }
+            let record = first_class as usize * second_class_count as usize +
+                         second_class as usize;
+            16 + record * record_size
+        };
+        let mut reader = Reader::at(self.data, offset);
+        let first_value = ValueRecord::read(&mut reader, self.first_value_format)?;
+        let second_value = ValueRecord::read(&mut reader, self.second_value_format)?;
+        Some((first_value, second_value))
+    }
+}
+
+/// A cursive attachment subtable, which joins each glyph's exit point to the entry point of
+/// the glyph after it.
+#[derive(Clone, Copy, Debug)]
+pub struct CursiveAttachment<'a> {
+    data: &'a [u8],
+    coverage: Coverage<'a>,
+}
+
+impl<'a> CursiveAttachment<'a> {
+    fn parse(data: &'a [u8]) -> Option<CursiveAttachment<'a>> {
+        if parser::read_u16(data, 0)? != 1 {
+            return None;
+        }
+        Some(CursiveAttachment {
+            data,
+            coverage: Coverage::parse_at(data, 2)?,
+        })
+    }
+
+    /// Returns the glyph's entry and exit anchors, if the subtable covers it.
+    pub fn anchors(&self, glyph: u16) -> Option<(Option<Anchor>, Option<Anchor>)> {
+        let index = self.coverage.index(glyph)? as usize;
+        if index >= parser::read_u16(self.data, 4)? as usize {
+            return None;
+        }
+        let mut reader = Reader::at(self.data, 6 + index * 4);
+        let entry = reader.read_u16()?;
+        let exit = reader.read_u16()?;
+        Some((Anchor::read(self.data, entry), Anchor::read(self.data, exit)))
+    }
+}
+
+/// The marks of a mark attachment subtable, each with its class and anchor.
+#[derive(Clone, Copy, Debug)]
+struct MarkArray<'a> {
+    coverage: Coverage<'a>,
+    data: &'a [u8],
+}
+
+impl<'a> MarkArray<'a> {
+    fn parse(subtable: &'a [u8]) -> Option<MarkArray<'a>> {
+        Some(MarkArray {
+            coverage: Coverage::parse_at(subtable, 2)?,
+            data: subtable.get(parser::read_u16(subtable, 8)? as usize..)?,
+        })
+    }
+
+    fn mark(&self, glyph: u16) -> Option<(u16, Anchor)> {
+        let index = self.coverage.index(glyph)? as usize;
+        if index >= parser::read_u16(self.data, 0)? as usize {
+            return None;
+        }
+        let mut reader = Reader::at(self.data, 2 + index * 4);
+        let class = reader.read_u16()?;
+        Some((class, Anchor::read(self.data, reader.read_u16()?)?))
+    }
+}
+
+/// A mark-to-base or mark-to-mark attachment subtable, which puts marks on the glyph they
+/// follow.
+#[derive(Clone, Copy, Debug)]
+pub struct MarkAttachment<'a> {
+    marks: MarkArray<'a>,
+    base_coverage: Coverage<'a>,
+    mark_class_count: u16,
+    bases: &'a [u8],
+}
+
+impl<'a> MarkAttachment<'a> {
+    fn parse(data: &'a [u8]) -> Option<MarkAttachment<'a>> {
+        if parser::read_u16(data, 0)? != 1 {
+            return None;
+        }
+        Some(MarkAttachment {
+            marks: MarkArray::parse(data)?,
+            base_coverage: Coverage::parse_at(data, 4)?,
+            mark_class_count: parser::read_u16(data, 6)?,
+            bases: data.get(parser::read_u16(data, 10)? as usize..)?,
+        })
+    }
+
+    /// Whether the subtable attaches `glyph` as a mark.
+    pub fn covers_mark(&self, glyph: u16) -> bool {
+        self.marks.coverage.contains(glyph)
+    }
+
+    /// Returns the mark's class and anchor, if the subtable covers it.
+    pub fn mark(&self, glyph: u16) -> Option<(u16, Anchor)> {
+        self.marks.mark(glyph)
+    }
+
+    /// Returns the anchor on `base` for marks of `class`.
+    pub fn base_anchor(&self, base: u16, class: u16) -> Option<Anchor> {
+        let index = self.base_coverage.index(base)? as usize;
+        if index >= parser::read_u16(self.bases, 0)? as usize || class >= self.mark_class_count {
+            return None;
+        }
+        let record = index * self.mark_class_count as usize + class as usize;
+        Anchor::read(self.bases, parser::read_u16(self.bases, 2 + record * 2)?)
+    }
+}
+
+/// A mark-to-ligature attachment subtable, which puts marks on one component of the ligature
+/// they follow.
+#[derive(Clone, Copy, Debug)]
+pub struct MarkToLigatureAttachment<'a> {
+    marks: MarkArray<'a>,
+    ligature_coverage: Coverage<'a>,
+    mark_class_count: u16,
+    ligatures: &'a [u8],
+}
+
+impl<'a> MarkToLigatureAttachment<'a> {
+    fn parse(data: &'a [u8]) -> Option<MarkToLigatureAttachment<'a>> {
+        if parser::read_u16(data, 0)? != 1 {
+            return None;
+        }
+        Some(MarkToLigatureAttachment {
+            marks: MarkArray::parse(data)?,
+            ligature_coverage: Coverage::parse_at(data, 4)?,
+            mark_class_count: parser::read_u16(data, 6)?,
+            ligatures: data.get(parser::read_u16(data, 10)? as usize..)?,
+        })
+    }
+
+    /// Whether the subtable attaches `glyph` as a mark.
+    pub fn covers_mark(&self, glyph: u16) -> bool {
+        self.marks.coverage.contains(glyph)
+    }
+
+    /// Returns the mark's class and anchor, if the subtable covers it.
+    pub fn mark(&self, glyph: u16) -> Option<(u16, Anchor)> {
+        self.marks.mark(glyph)
+    }
+
+    /// Returns the number of components of `ligature`, if the subtable covers it.
+    pub fn component_count(&self, ligature: u16) -> Option<u16> {
+        parser::read_u16(self.ligature_attach(ligature)?, 0)
+    }
+
+    /// Returns the anchor on the `component`th component of `ligature`, counting from 0, for
+    /// marks of `class`.
+    pub fn ligature_anchor(&self, ligature: u16, component: u16, class: u16) -> Option<Anchor> {
+        let attach = self.ligature_attach(ligature)?;
+        if component >= parser::read_u16(attach, 0)? || class >= self.mark_class_count {
+            return None;
+        }
+        let record = component as usize * self.mark_class_count as usize + class as usize;
+        Anchor::read(attach, parser::read_u16(attach, 2 + record * 2)?)
+    }
+
+    fn ligature_attach(&self, ligature: u16) -> Option<&'a [u8]> {
+        let index = self.ligature_coverage.index(ligature)? as usize;
+        if index >= parser::read_u16(self.ligatures, 0)? as usize {
+            return None;
+        }
+        let offset = parser::read_u16(self.ligatures, 2 + index * 2)? as usize;
+        self.ligatures.get(offset..)
+    }
+}
@@ -0,0 +1,261 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `GSUB` table, which substitutes glyphs for ligatures, alternates and other forms that
+//! depend on features, script and context.
+
+use error::{Error, Result};
+use parser::{self, Reader};
+use tables::gdef::Gdef;
+use tables::layout::{Context, ContextRule, Coverage, LayoutTable, Lookup, SequenceMatcher};
+use tag::kCTFontTableGSUB;
+
+const SINGLE: u16 = 1;
+const MULTIPLE: u16 = 2;
+const ALTERNATE: u16 = 3;
+const LIGATURE: u16 = 4;
+const CONTEXT: u16 = 5;
+const CHAINED_CONTEXT: u16 = 6;
+const EXTENSION: u16 = 7;
+const REVERSE_CHAINED_SINGLE: u16 = 8;
+
+/// A `GSUB` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Gsub<'a> {
+    layout: LayoutTable<'a>,
+    gdef: Option<Gdef<'a>>,
+}
+
+impl<'a> Gsub<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Gsub<'a>> {
+        let layout = LayoutTable::parse(data).ok_or(Error::MalformedTable(kCTFontTableGSUB))?;
+        Ok(Gsub {
+            layout,
+            gdef: None,
+        })
+    }
+
+    /// Uses the font's `GDEF` table for the glyph classes and mark glyph sets that lookups
+    /// skip glyphs by.
+    pub fn with_gdef(mut self, gdef: Gdef<'a>) -> Gsub<'a> {
+        self.gdef = Some(gdef);
+        self
+    }
+
+    pub fn gdef(&self) -> Option<&Gdef<'a>> {
+        self.gdef.as_ref()
+    }
+
+    /// The table's scripts, features and lookups.
+    pub fn layout(&self) -> &LayoutTable<'a> {
+        &self.layout
+    }
+
+    /// Returns the `index`th lookup and its subtables, leaving out subtables that are
+    /// malformed or of an unknown type.
+    pub fn lookup(&self, index: u16) -> Option<(Lookup<'a>, Vec<Substitution<'a>>)> {
+        let lookup = self.layout.lookup(index)?;
+        let subtables = lookup.subtables(EXTENSION).into_iter().filter_map(|(lookup_type, data)| {
+            Substitution::parse(lookup_type, data)
+        }).collect();
+        Some((lookup, subtables))
+    }
+}
+
+/// A `GSUB` subtable.
+#[derive(Clone, Copy, Debug)]
+pub enum Substitution<'a> {
+    Single(SingleSubstitution<'a>),
+    Multiple(SequenceSubstitution<'a>),
+    Alternate(SequenceSubstitution<'a>),
+    Ligature(LigatureSubstitution<'a>),
+    /// A contextual or chained contextual subtable, which applies other lookups.
+    Context(Context<'a>),
+    ReverseChainedSingle(ReverseChainedSubstitution<'a>),
+}
+
+impl<'a> Substitution<'a> {
+    fn parse(lookup_type: u16, data: &'a [u8]) -> Option<Substitution<'a>> {
+        Some(match lookup_type {
+            SINGLE => Substitution::Single(SingleSubstitution::parse(data)?),
+            MULTIPLE => Substitution::Multiple(SequenceSubstitution::parse(data)?),
+            ALTERNATE => Substitution::Alternate(SequenceSubstitution::parse(data)?),
+            LIGATURE => Substitution::Ligature(LigatureSubstitution::parse(data)?),
+            CONTEXT => Substitution::Context(Context::parse(data, false)?),
+            CHAINED_CONTEXT => Substitution::Context(Context::parse(data, true)?),
+            REVERSE_CHAINED_SINGLE => {
+                Substitution::ReverseChainedSingle(ReverseChainedSubstitution::parse(data)?)
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// A single substitution subtable, which replaces one glyph with another.
+#[derive(Clone, Copy, Debug)]
+pub struct SingleSubstitution<'a> {
+    data: &'a [u8],
+    coverage: Coverage<'a>,
+}
+
+impl<'a> SingleSubstitution<'a> {
+    fn parse(data: &'a [u8]) -> Option<SingleSubstitution<'a>> {
+        match parser::read_u16(data, 0)? {
+            1 | 2 => {
+                Some(SingleSubstitution {
+                    data,
+                    coverage: Coverage::parse_at(data, 2)?,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn substitute(&self, glyph: u16) -> Option<u16> {
+        let index = self.coverage.index(glyph)?;
+        if parser::read_u16(self.data, 0)? == 1 {
+            let delta = parser::read_i16(self.data, 4)?;
+            return Some(glyph.wrapping_add(delta as u16));
+        }
+        let count = parser::read_u16(self.data, 4)?;
+        if index >= count {
+            return None;
+        }
+        parser::read_u16(self.data, 6 + index as usize * 2)
+    }
+}
+
+/// A multiple or alternate substitution subtable, which gives each glyph it covers a sequence
+/// of glyphs: the glyphs to replace it with, or the alternates to choose from.
+#[derive(Clone, Copy, Debug)]
+pub struct SequenceSubstitution<'a> {
+    data: &'a [u8],
+    coverage: Coverage<'a>,
+}
+
+impl<'a> SequenceSubstitution<'a> {
+    fn parse(data: &'a [u8]) -> Option<SequenceSubstitution<'a>> {
+        if parser::read_u16(data, 0)? != 1 {
+            return None;
+        }
+        Some(SequenceSubstitution {
+            data,
+            coverage: Coverage::parse_at(data, 2)?,
+        })
+    }
+
+    pub fn sequence(&self, glyph: u16) -> Option<Vec<u16>> {
+        let index = self.coverage.index(glyph)?;
+        if index >= parser::read_u16(self.data, 4)? {
+            return None;
+        }
+        let offset = parser::read_u16(self.data, 6 + index as usize * 2)? as usize;
+        let mut reader = Reader::at(self.data, offset);
+        let count = reader.read_u16()?;
+        (0..count).map(|_| reader.read_u16()).collect()
+    }
+}
+
+/// A ligature, and the glyphs after the first that it replaces.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ligature {
+    pub glyph: u16,
+    pub components: Vec<u16>,
+}
+
+/// A ligature substitution subtable, which replaces sequences of glyphs with one.
+#[derive(Clone, Copy, Debug)]
+pub struct LigatureSubstitution<'a> {
+    data: &'a [u8],
+    coverage: Coverage<'a>,
+}
+
+impl<'a> LigatureSubstitution<'a> {
+    fn parse(data: &'a [u8]) -> Option<LigatureSubstitution<'a>> {
+        if parser::read_u16(data, 0)? != 1 {
+            return None;
+        }
+        Some(LigatureSubstitution {
+            data,
+            coverage: Coverage::parse_at(data, 2)?,
+        })
+    }
+
+    /// Returns the ligatures that start with `glyph`, in order of preference.
+    pub fn ligatures(&self, glyph: u16) -> Vec<Ligature> {
+        self.read_ligatures(glyph).unwrap_or_default()
+    }
+
+    fn read_ligatures(&self, glyph: u16) -> Option<Vec<Ligature>> {
+        let index = self.coverage.index(glyph)?;
+        if index >= parser::read_u16(self.data, 4)? {
+            return None;
+        }
+        let offset = parser::read_u16(self.data, 6 + index as usize * 2)? as usize;
+        let ligature_set = self.data.get(offset..)?;
+        let count = parser::read_u16(ligature_set, 0)?;
+        Some((0..count as usize).filter_map(|index| {
+            let offset = parser::read_u16(ligature_set, 2 + index * 2)? as usize;
+            let mut reader = Reader::at(ligature_set, offset);
+            let glyph = reader.read_u16()?;
+            let component_count = reader.read_u16()?;
+            let components = (1..component_count).map(|_| reader.read_u16())
+                                                 .collect::<Option<Vec<u16>>>()?;
+            Some(Ligature {
+                glyph,
+                components,
+            })
+        }).collect())
+    }
+}
+
+/// A reverse chained single substitution subtable, which replaces one glyph with another
+/// depending on the glyphs around it, working from the end of the text back.
+#[derive(Clone, Copy, Debug)]
+pub struct ReverseChainedSubstitution<'a> {
+    data: &'a [u8],
+    coverage: Coverage<'a>,
+    backtrack: &'a [u8],
+    lookahead: &'a [u8],
+    substitutes: &'a [u8],
+}
+
+impl<'a> ReverseChainedSubstitution<'a> {
+    fn parse(data: &'a [u8]) -> Option<ReverseChainedSubstitution<'a>> {
+        let mut reader = Reader::new(data);
+        if reader.read_u16()? != 1 {
+            return None;
+        }
+        let coverage = Coverage::parse_at(data, 2)?;
+        reader.skip(2)?; // coverageOffset
+        let count = reader.read_u16()? as usize;
+        let backtrack = reader.read_bytes(count * 2)?;
+        let count = reader.read_u16()? as usize;
+        let lookahead = reader.read_bytes(count * 2)?;
+        let count = reader.read_u16()? as usize;
+        Some(ReverseChainedSubstitution {
+            data,
+            coverage,
+            backtrack,
+            lookahead,
+            substitutes: reader.read_bytes(count * 2)?,
+        })
+    }
+
+    /// Returns the context the glyph must be in, whose rule has no input after the glyph and
+    /// no lookups, and its substitute, if the subtable covers the glyph.
+    pub fn substitute(&self, glyph: u16) -> Option<(ContextRule<'a>, u16)> {
+        let index = self.coverage.index(glyph)?;
+        let substitute = parser::read_u16(self.substitutes, index as usize * 2)?;
+        let rule = ContextRule::new(SequenceMatcher::Coverages(self.data, self.backtrack),
+                                    SequenceMatcher::Coverages(self.data, &[]),
+                                    SequenceMatcher::Coverages(self.data, self.lookahead));
+        Some((rule, substitute))
+    }
+}
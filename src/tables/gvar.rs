@@ -0,0 +1,410 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `gvar` table, which varies the points of a variable font's TrueType outlines.
+
+use base::CGPoint;
+use error::{Error, Result};
+use parser::{self, Reader};
+use tag::kCTFontTableGvar;
+
+const HEADER_SIZE: usize = 20;
+const LONG_OFFSETS: u16 = 0x0001;
+
+// Glyph variation data flags.
+const SHARED_POINT_NUMBERS: u16 = 0x8000;
+const TUPLE_COUNT_MASK: u16 = 0x0FFF;
+
+// Tuple variation header flags.
+const EMBEDDED_PEAK_TUPLE: u16 = 0x8000;
+const INTERMEDIATE_REGION: u16 = 0x4000;
+const PRIVATE_POINT_NUMBERS: u16 = 0x2000;
+const TUPLE_INDEX_MASK: u16 = 0x0FFF;
+
+// Packed point number and delta run flags.
+const POINTS_ARE_WORDS: u8 = 0x80;
+const POINT_RUN_COUNT_MASK: u8 = 0x7F;
+const DELTAS_ARE_ZERO: u8 = 0x80;
+const DELTAS_ARE_WORDS: u8 = 0x40;
+const DELTA_RUN_COUNT_MASK: u8 = 0x3F;
+
+/// A `gvar` table.
+///
+/// Each glyph's variation data is a list of tuples, each with a region of the design space and
+/// deltas for some or all of the glyph's points. A simple glyph's points are its outline's
+/// points, and a composite glyph's are the offsets of its components; either way four phantom
+/// points for the glyph's metrics follow.
+#[derive(Clone, Copy, Debug)]
+pub struct Gvar<'a> {
+    data: &'a [u8],
+    shared_tuples: &'a [u8],
+    glyph_data: &'a [u8],
+    axis_count: u16,
+    glyph_count: u16,
+    long_offsets: bool,
+}
+
+impl<'a> Gvar<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Gvar<'a>> {
+        Gvar::parse_table(data).ok_or(Error::MalformedTable(kCTFontTableGvar))
+    }
+
+    fn parse_table(data: &'a [u8]) -> Option<Gvar<'a>> {
+        let mut reader = Reader::new(data);
+        let major_version = reader.read_u16()?;
+        reader.skip(2)?; // minor version
+        let axis_count = reader.read_u16()?;
+        let shared_tuple_count = reader.read_u16()?;
+        let shared_tuples_offset = reader.read_u32()? as usize;
+        let glyph_count = reader.read_u16()?;
+        let flags = reader.read_u16()?;
+        let glyph_data_offset = reader.read_u32()? as usize;
+        if major_version != 1 {
+            return None;
+        }
+        let long_offsets = flags & LONG_OFFSETS != 0;
+        let offset_size = if long_offsets { 4 } else { 2 };
+        Reader::at(data, HEADER_SIZE).read_bytes((glyph_count as usize + 1) * offset_size)?;
+        Some(Gvar {
+            data,
+            shared_tuples: Reader::at(data, shared_tuples_offset)
+                .read_bytes(shared_tuple_count as usize * axis_count as usize * 2)?,
+            glyph_data: data.get(glyph_data_offset..)?,
+            axis_count,
+            glyph_count,
+            long_offsets,
+        })
+    }
+
+    fn variation_data(&self, glyph: u16) -> Option<&'a [u8]> {
+        if glyph >= self.glyph_count {
+            return None;
+        }
+        let offset = |index: usize| if self.long_offsets {
+            parser::read_u32(self.data, HEADER_SIZE + index * 4).map(|offset| offset as usize)
+        } else {
+            parser::read_u16(self.data, HEADER_SIZE + index * 2).map(|offset| offset as usize * 2)
+        };
+        let (start, end) = (offset(glyph as usize)?, offset(glyph as usize + 1)?);
+        if start >= end {
+            return None;
+        }
+        self.glyph_data.get(start..end)
+    }
+
+    /// Moves the glyph's points to where they lie at `coordinates`.
+    ///
+    /// `points` holds the glyph's points, phantom points included, at the default instance.
+    /// `ends` holds the index of the last point of each contour, so that points without
+    /// explicit deltas can be moved along with their neighbours; it is empty for composite
+    /// glyphs, whose points are independent. Glyphs without variation data are left alone.
+    /// Returns `None` if the data is malformed, leaving `points` unchanged.
+    pub fn apply(&self, glyph: u16, coordinates: &[f64], points: &mut [CGPoint], ends: &[usize])
+                 -> Option<()> {
+        let data = match self.variation_data(glyph) {
+            Some(data) => data,
+            None => return Some(()),
+        };
+        let mut reader = Reader::new(data);
+        let tuple_count = reader.read_u16()?;
+        let mut serialized = Reader::at(data, reader.read_u16()? as usize);
+        let shared_points = if tuple_count & SHARED_POINT_NUMBERS != 0 {
+            read_point_numbers(&mut serialized)?
+        } else {
+            None
+        };
+
+        let mut deltas = vec![CGPoint::new(0.0, 0.0); points.len()];
+        for _ in 0..tuple_count & TUPLE_COUNT_MASK {
+            let data_size = reader.read_u16()? as usize;
+            let tuple_index = reader.read_u16()?;
+            let peak = if tuple_index & EMBEDDED_PEAK_TUPLE != 0 {
+                reader.read_bytes(self.axis_count as usize * 2)?
+            } else {
+                let tuple_size = self.axis_count as usize * 2;
+                let offset = (tuple_index & TUPLE_INDEX_MASK) as usize * tuple_size;
+                self.shared_tuples.get(offset..offset + tuple_size)?
+            };
+            let region = if tuple_index & INTERMEDIATE_REGION != 0 {
+                let start = reader.read_bytes(self.axis_count as usize * 2)?;
+                Some((start, reader.read_bytes(self.axis_count as usize * 2)?))
+            } else {
+                None
+            };
+            let mut tuple_data = Reader::new(serialized.read_bytes(data_size)?);
+
+            let scalar = tuple_scalar(peak, region, coordinates)?;
+            if scalar == 0.0 {
+                continue;
+            }
+            let private_points;
+            let point_numbers = if tuple_index & PRIVATE_POINT_NUMBERS != 0 {
+                private_points = read_point_numbers(&mut tuple_data)?;
+                private_points.as_ref()
+            } else {
+                shared_points.as_ref()
+            };
+
+            match point_numbers {
+                None => {
+                    let xs = read_deltas(&mut tuple_data, points.len())?;
+                    let ys = read_deltas(&mut tuple_data, points.len())?;
+                    for ((delta, x), y) in deltas.iter_mut().zip(xs).zip(ys) {
+                        delta.x += x * scalar;
+                        delta.y += y * scalar;
+                    }
+                }
+                Some(point_numbers) => {
+                    let xs = read_deltas(&mut tuple_data, point_numbers.len())?;
+                    let ys = read_deltas(&mut tuple_data, point_numbers.len())?;
+                    let mut tuple_deltas = vec![None; points.len()];
+                    for ((&point, x), y) in point_numbers.iter().zip(xs).zip(ys) {
+                        if let Some(delta) = tuple_deltas.get_mut(point as usize) {
+                            *delta = Some(CGPoint::new(x, y));
+                        }
+                    }
+                    infer_deltas(points, ends, &mut tuple_deltas);
+                    for (delta, tuple_delta) in deltas.iter_mut().zip(tuple_deltas) {
+                        if let Some(tuple_delta) = tuple_delta {
+                            delta.x += tuple_delta.x * scalar;
+                            delta.y += tuple_delta.y * scalar;
+                        }
+                    }
+                }
+            }
+        }
+
+        for (point, delta) in points.iter_mut().zip(deltas) {
+            point.x += delta.x;
+            point.y += delta.y;
+        }
+        Some(())
+    }
+}
+
+/// Returns how much of a tuple's deltas apply at `coordinates`, from 0 to 1. Tuples without an
+/// intermediate region span from their peak to the default on each axis.
+fn tuple_scalar(peak: &[u8], region: Option<(&[u8], &[u8])>, coordinates: &[f64])
+                -> Option<f64> {
+    let mut scalar = 1.0;
+    for axis in 0..peak.len() / 2 {
+        let read = |tuple: &[u8]| {
+            parser::read_u16(tuple, axis * 2).map(|value| value as i16 as f64 / 16384.0)
+        };
+        let peak_value = read(peak)?;
+        let (start, end) = match region {
+            Some((start, end)) => (read(start)?, read(end)?),
+            None => (peak_value.min(0.0), peak_value.max(0.0)),
+        };
+        let coordinate = coordinates.get(axis).cloned().unwrap_or(0.0);
+
+        // Axes the tuple doesn't depend on, and invalid ranges, don't limit it.
+        if peak_value == 0.0 || start > peak_value || peak_value > end ||
+           (start < 0.0 && end > 0.0) {
+            continue;
+        }
+        if coordinate == peak_value {
+            continue;
+        }
+        if coordinate <= start || coordinate >= end {
+            return Some(0.0);
+        }
+        scalar *= if coordinate < peak_value {
+            (coordinate - start) / (peak_value - start)
+        } else {
+            (end - coordinate) / (end - peak_value)
+        };
+    }
+    Some(scalar)
+}
+
+/// Reads packed point numbers, returning `None` inside the result when they cover every point.
+fn read_point_numbers(reader: &mut Reader) -> Option<Option<Vec<u16>>> {
+    let first = reader.read_u8()?;
+    let count = if first & POINTS_ARE_WORDS != 0 {
+        ((first & POINT_RUN_COUNT_MASK) as usize) << 8 | reader.read_u8()? as usize
+    } else {
+        first as usize
+    };
+    if count == 0 {
+        return Some(None);
+    }
+
+    // Runs of increments from the previous point number.
+    let mut point_numbers = Vec::with_capacity(count);
+    let mut point = 0u16;
+    while point_numbers.len() < count {
+        let control = reader.read_u8()?;
+        for _ in 0..(control & POINT_RUN_COUNT_MASK) as usize + 1 {
+            let increment = if control & POINTS_ARE_WORDS != 0 {
+                reader.read_u16()?
+            } else {
+                reader.read_u8()? as u16
+            };
+            point = point.wrapping_add(increment);
+            point_numbers.push(point);
+        }
+    }
+    point_numbers.truncate(count);
+    Some(Some(point_numbers))
+}
+
+/// Reads `count` packed deltas for one coordinate axis.
+fn read_deltas(reader: &mut Reader, count: usize) -> Option<Vec<f64>> {
+    let mut deltas = Vec::with_capacity(count);
+    while deltas.len() < count {
+        let control = reader.read_u8()?;
+        for _ in 0..(control & DELTA_RUN_COUNT_MASK) as usize + 1 {
+            let delta = match (control & DELTAS_ARE_ZERO != 0, control & DELTAS_ARE_WORDS != 0) {
+                (true, true) => reader.read_i32()? as f64,
+                (true, false) => 0.0,
+                (false, true) => reader.read_i16()? as f64,
+                (false, false) => reader.read_u8()? as i8 as f64,
+            };
+            deltas.push(delta);
+        }
+    }
+    deltas.truncate(count);
+    Some(deltas)
+}
+
+/// Fills in the deltas of a contour's untouched points from the nearest touched points on
+/// either side, as the spec's interpolation of untouched points does. Contours with no touched
+/// points stay put, and points outside every contour, such as phantom points, are left alone.
+fn infer_deltas(points: &[CGPoint], ends: &[usize], deltas: &mut [Option<CGPoint>]) {
+    let mut start = 0;
+    for &end in ends {
+        if end >= points.len() || end < start {
+            return;
+        }
+        let touched: Vec<usize> = (start..end + 1).filter(|&point| deltas[point].is_some())
+                                                   .collect();
+        if !touched.is_empty() && touched.len() <= end - start {
+            for (index, &previous) in touched.iter().enumerate() {
+                let next = touched[(index + 1) % touched.len()];
+                // The untouched points after `previous`, wrapping around the contour.
+                let mut point = previous;
+                loop {
+                    point = if point == end { start } else { point + 1 };
+                    if point == next {
+                        break;
+                    }
+                    let (previous_delta, next_delta) = match (deltas[previous], deltas[next]) {
+                        (Some(previous_delta), Some(next_delta)) => (previous_delta, next_delta),
+                        _ => break,
+                    };
+                    deltas[point] = Some(CGPoint::new(
+                        interpolate(points[point].x,
+                                    (points[previous].x, previous_delta.x),
+                                    (points[next].x, next_delta.x)),
+                        interpolate(points[point].y,
+                                    (points[previous].y, previous_delta.y),
+                                    (points[next].y, next_delta.y))));
+                }
+            }
+        }
+        start = end + 1;
+    }
+}
+
+/// Interpolates a delta for `coordinate` between two reference `(coordinate, delta)` pairs,
+/// using the nearer reference's delta outside their range.
+fn interpolate(coordinate: f64, a: (f64, f64), b: (f64, f64)) -> f64 {
+    let ((low, low_delta), (high, high_delta)) = if a.0 <= b.0 { (a, b) } else { (b, a) };
+    if low == high {
+        return if low_delta == high_delta { low_delta } else { 0.0 };
+    }
+    if coordinate <= low {
+        low_delta
+    } else if coordinate >= high {
+        high_delta
+    } else {
+        low_delta + (coordinate - low) * (high_delta - low_delta) / (high - low)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base::CGPoint;
+    use parser::Reader;
+    use test_font;
+    use super::{infer_deltas, read_deltas, read_point_numbers, tuple_scalar};
+
+    fn points(coordinates: &[(f64, f64)]) -> Vec<CGPoint> {
+        coordinates.iter().map(|&(x, y)| CGPoint::new(x, y)).collect()
+    }
+
+    fn coordinates(deltas: &[Option<CGPoint>]) -> Vec<Option<(f64, f64)>> {
+        deltas.iter().map(|delta| delta.map(|delta| (delta.x, delta.y))).collect()
+    }
+
+    #[test]
+    fn one_touched_point_moves_its_whole_contour() {
+        // A square, then a phantom point outside every contour.
+        let points = points(&[(0.0, 0.0), (100.0, 0.0), (100.0, 100.0), (0.0, 100.0),
+                              (200.0, 0.0)]);
+        let mut deltas = vec![None; 5];
+        deltas[2] = Some(CGPoint::new(10.0, -5.0));
+        infer_deltas(&points, &[3], &mut deltas);
+        let mut expected = vec![Some((10.0, -5.0)); 4];
+        expected.push(None);
+        assert_eq!(coordinates(&deltas), expected);
+    }
+
+    #[test]
+    fn interpolates_between_touched_points_and_clamps_outside_them() {
+        let points = points(&[(0.0, 0.0), (50.0, 0.0), (100.0, 0.0), (150.0, 0.0),
+                              (0.0, 0.0), (10.0, 10.0)]);
+        let mut deltas = vec![None; 6];
+        deltas[0] = Some(CGPoint::new(10.0, 0.0));
+        deltas[2] = Some(CGPoint::new(20.0, 0.0));
+        infer_deltas(&points, &[3, 5], &mut deltas);
+        assert_eq!(coordinates(&deltas), vec![Some((10.0, 0.0)), Some((15.0, 0.0)),
+                                              Some((20.0, 0.0)), Some((20.0, 0.0)),
+                                              None, None]);
+    }
+
+    #[test]
+    fn scales_tuples_by_peak_and_intermediate_region() {
+        let peak = test_font::words(&[16384, -16384, 0]);
+        assert_eq!(tuple_scalar(&peak, None, &[1.0, -1.0, 0.7]), Some(1.0));
+        assert_eq!(tuple_scalar(&peak, None, &[0.5, -0.5, 0.0]), Some(0.25));
+        assert_eq!(tuple_scalar(&peak, None, &[0.5, 0.5, 0.0]), Some(0.0));
+
+        // Peaking at 0.5 and running from 0.25 to 1.
+        let (peak, start, end) = (test_font::words(&[8192]), test_font::words(&[4096]),
+                                  test_font::words(&[16384]));
+        let region = Some((&start[..], &end[..]));
+        assert_eq!(tuple_scalar(&peak, region, &[0.5]), Some(1.0));
+        assert_eq!(tuple_scalar(&peak, region, &[0.375]), Some(0.5));
+        assert_eq!(tuple_scalar(&peak, region, &[0.75]), Some(0.5));
+        assert_eq!(tuple_scalar(&peak, region, &[0.2]), Some(0.0));
+        assert_eq!(tuple_scalar(&peak, region, &[1.0]), Some(0.0));
+    }
+
+    #[test]
+    fn reads_byte_word_and_zero_delta_runs() {
+        let data = [0x01, 0x05, 0xFB, 0x41, 0x01, 0x00, 0xFF, 0x00, 0x82];
+        let deltas = read_deltas(&mut Reader::new(&data), 7).unwrap();
+        assert_eq!(deltas, vec![5.0, -5.0, 256.0, -256.0, 0.0, 0.0, 0.0]);
+        assert_eq!(read_deltas(&mut Reader::new(&data[..8]), 7), None);
+    }
+
+    #[test]
+    fn reads_point_number_runs() {
+        assert_eq!(read_point_numbers(&mut Reader::new(&[0x00])), Some(None));
+        let bytes = [0x03, 0x02, 1, 2, 3];
+        assert_eq!(read_point_numbers(&mut Reader::new(&bytes)), Some(Some(vec![1, 3, 6])));
+        let words = [0x02, 0x81, 0x01, 0x00, 0x00, 0x01];
+        assert_eq!(read_point_numbers(&mut Reader::new(&words)), Some(Some(vec![256, 257])));
+        // A count of more than 127 takes two bytes.
+        let long_count = [0x80, 0x02, 0x01, 5, 1];
+        assert_eq!(read_point_numbers(&mut Reader::new(&long_count)), Some(Some(vec![5, 6])));
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `head` table, which holds global information about the font.
+
+use error::{Error, Result};
+use parser;
+use tag::kCTFontTableHead;
+
+const TABLE_SIZE: usize = 54;
+
+pub const MAC_STYLE_BOLD: u16 = 1 << 0;
+pub const MAC_STYLE_ITALIC: u16 = 1 << 1;
+pub const MAC_STYLE_UNDERLINE: u16 = 1 << 2;
+pub const MAC_STYLE_OUTLINE: u16 = 1 << 3;
+pub const MAC_STYLE_SHADOW: u16 = 1 << 4;
+pub const MAC_STYLE_CONDENSED: u16 = 1 << 5;
+pub const MAC_STYLE_EXTENDED: u16 = 1 << 6;
+
+/// A `head` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Head<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Head<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Head<'a>> {
+        if data.len() < TABLE_SIZE || parser::read_u16(data, 0) != Some(1) {
+            return Err(Error::MalformedTable(kCTFontTableHead));
+        }
+        Ok(Head {
+            data,
+        })
+    }
+
+    fn u16_at(&self, offset: usize) -> u16 {
+        parser::read_u16(self.data, offset).unwrap_or(0)
+    }
+
+    fn i16_at(&self, offset: usize) -> i16 {
+        parser::read_i16(self.data, offset).unwrap_or(0)
+    }
+
+    /// The font's own version number, set by its vendor.
+    pub fn font_revision(&self) -> f64 {
+        parser::read_fixed(self.data, 4).unwrap_or(0.0)
+    }
+
+    pub fn flags(&self) -> u16 {
+        self.u16_at(16)
+    }
+
+    pub fn units_per_em(&self) -> u16 {
+        self.u16_at(18)
+    }
+
+    /// When the font was created, in seconds since 1904-01-01 00:00 UTC.
+    pub fn created(&self) -> i64 {
+        parser::read_i64(self.data, 20).unwrap_or(0)
+    }
+
+    /// When the font was last modified, in seconds since 1904-01-01 00:00 UTC.
+    pub fn modified(&self) -> i64 {
+        parser::read_i64(self.data, 28).unwrap_or(0)
+    }
+
+    pub fn x_min(&self) -> i16 {
+        self.i16_at(36)
+    }
+
+    pub fn y_min(&self) -> i16 {
+        self.i16_at(38)
+    }
+
+    pub fn x_max(&self) -> i16 {
+        self.i16_at(40)
+    }
+
+    pub fn y_max(&self) -> i16 {
+        self.i16_at(42)
+    }
+
+    /// The `MAC_STYLE_*` bits describing the face.
+    pub fn mac_style(&self) -> u16 {
+        self.u16_at(44)
+    }
+
+    /// The smallest size, in pixels per em, at which the font is readable.
+    pub fn lowest_rec_ppem(&self) -> u16 {
+        self.u16_at(46)
+    }
+
+    pub fn font_direction_hint(&self) -> i16 {
+        self.i16_at(48)
+    }
+
+    /// Returns true if the `loca` table holds 32-bit offsets rather than halved 16-bit ones.
+    pub fn has_long_loca_offsets(&self) -> bool {
+        self.i16_at(50) != 0
+    }
+
+    pub fn glyph_data_format(&self) -> i16 {
+        self.i16_at(52)
+    }
+}
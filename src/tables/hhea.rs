@@ -0,0 +1,91 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `hhea` table, which holds the font's horizontal line metrics.
+
+use error::{Error, Result};
+use parser;
+use tag::kCTFontTableHhea;
+
+const TABLE_SIZE: usize = 36;
+
+/// An `hhea` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Hhea<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Hhea<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Hhea<'a>> {
+        if data.len() < TABLE_SIZE || parser::read_u16(data, 0) != Some(1) {
+            return Err(Error::MalformedTable(kCTFontTableHhea));
+        }
+        Ok(Hhea {
+            data,
+        })
+    }
+
+    fn u16_at(&self, offset: usize) -> u16 {
+        parser::read_u16(self.data, offset).unwrap_or(0)
+    }
+
+    fn i16_at(&self, offset: usize) -> i16 {
+        parser::read_i16(self.data, offset).unwrap_or(0)
+    }
+
+    /// The distance from the baseline to the top of the line. This is what Core Text's
+    /// `ascent` reports.
+    pub fn ascender(&self) -> i16 {
+        self.i16_at(4)
+    }
+
+    /// The distance from the baseline to the bottom of the line, negative below the baseline.
+    pub fn descender(&self) -> i16 {
+        self.i16_at(6)
+    }
+
+    pub fn line_gap(&self) -> i16 {
+        self.i16_at(8)
+    }
+
+    pub fn advance_width_max(&self) -> u16 {
+        self.u16_at(10)
+    }
+
+    pub fn min_left_side_bearing(&self) -> i16 {
+        self.i16_at(12)
+    }
+
+    pub fn min_right_side_bearing(&self) -> i16 {
+        self.i16_at(14)
+    }
+
+    pub fn x_max_extent(&self) -> i16 {
+        self.i16_at(16)
+    }
+
+    /// The rise of the caret's slope; 1 with a run of 0 for an upright caret.
+    pub fn caret_slope_rise(&self) -> i16 {
+        self.i16_at(18)
+    }
+
+    pub fn caret_slope_run(&self) -> i16 {
+        self.i16_at(20)
+    }
+
+    pub fn caret_offset(&self) -> i16 {
+        self.i16_at(22)
+    }
+
+    /// The number of glyphs with their own advance in the `hmtx` table. Every later glyph
+    /// shares the last of these advances.
+    pub fn number_of_h_metrics(&self) -> u16 {
+        self.u16_at(34)
+    }
+}
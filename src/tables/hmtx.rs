@@ -0,0 +1,73 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `hmtx` table, which holds each glyph's advance width and left side bearing.
+
+use error::{Error, Result};
+use parser;
+use tag::kCTFontTableHmtx;
+
+const LONG_METRIC_SIZE: usize = 4;
+
+/// An `hmtx` table.
+///
+/// The table starts with a full metric for each of the first `hhea.number_of_h_metrics()`
+/// glyphs. The remaining glyphs, typically monospaced ones, repeat the last advance and store
+/// only their side bearings.
+#[derive(Clone, Copy, Debug)]
+pub struct Hmtx<'a> {
+    data: &'a [u8],
+    long_metric_count: u16,
+    num_glyphs: u16,
+}
+
+impl<'a> Hmtx<'a> {
+    /// Parses the table given `hhea.number_of_h_metrics()` and `maxp.num_glyphs()`.
+    pub fn parse(data: &'a [u8], long_metric_count: u16, num_glyphs: u16) -> Result<Hmtx<'a>> {
+        // Fonts often truncate the trailing side bearings, so only the long metrics are
+        // required to be present.
+        if long_metric_count == 0 || data.len() < long_metric_count as usize * LONG_METRIC_SIZE {
+            return Err(Error::MalformedTable(kCTFontTableHmtx));
+        }
+        Ok(Hmtx {
+            data,
+            long_metric_count,
+            num_glyphs,
+        })
+    }
+
+    /// The number of glyphs the table covers.
+    pub fn num_glyphs(&self) -> u16 {
+        self.num_glyphs
+    }
+
+    /// Returns the glyph's advance width in font units, or `None` if it isn't in the font.
+    pub fn advance_width(&self, glyph: u16) -> Option<u16> {
+        if glyph >= self.num_glyphs {
+            return None;
+        }
+        let index = glyph.min(self.long_metric_count - 1) as usize;
+        parser::read_u16(self.data, index * LONG_METRIC_SIZE)
+    }
+
+    /// Returns the distance from the glyph's origin to the left of its bounding box, in font
+    /// units, or `None` if it isn't in the font or its side bearing is missing.
+    pub fn left_side_bearing(&self, glyph: u16) -> Option<i16> {
+        if glyph >= self.num_glyphs {
+            return None;
+        }
+        let offset = if glyph < self.long_metric_count {
+            glyph as usize * LONG_METRIC_SIZE + 2
+        } else {
+            self.long_metric_count as usize * LONG_METRIC_SIZE +
+                (glyph - self.long_metric_count) as usize * 2
+        };
+        parser::read_i16(self.data, offset)
+    }
+}
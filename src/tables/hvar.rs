@@ -0,0 +1,72 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `HVAR` table, which varies the horizontal metrics of a variable font's glyphs.
+
+use error::{Error, Result};
+use parser::Reader;
+use tables::variation_store::{DeltaSetIndexMap, ItemVariationStore};
+use tag::kCTFontTableHVAR;
+
+/// An `HVAR` table. `VVAR` shares its layout.
+///
+/// Every glyph has an advance delta. Side bearing deltas are optional, and fonts without them
+/// leave side bearings to vary with the outlines.
+#[derive(Clone, Copy, Debug)]
+pub struct Hvar<'a> {
+    store: ItemVariationStore<'a>,
+    advance_map: Option<DeltaSetIndexMap<'a>>,
+    start_side_bearing_map: Option<DeltaSetIndexMap<'a>>,
+    end_side_bearing_map: Option<DeltaSetIndexMap<'a>>,
+}
+
+impl<'a> Hvar<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Hvar<'a>> {
+        Hvar::parse_table(data).ok_or(Error::MalformedTable(kCTFontTableHVAR))
+    }
+
+    fn parse_table(data: &'a [u8]) -> Option<Hvar<'a>> {
+        let mut reader = Reader::new(data);
+        if reader.read_u16()? != 1 {
+            return None;
+        }
+        reader.skip(2)?; // minor version
+        let store = ItemVariationStore::parse(data.get(reader.read_u32()? as usize..)?)?;
+        Some(Hvar {
+            store,
+            advance_map: DeltaSetIndexMap::parse_at(data, reader.read_u32()?)?,
+            start_side_bearing_map: DeltaSetIndexMap::parse_at(data, reader.read_u32()?)?,
+            end_side_bearing_map: DeltaSetIndexMap::parse_at(data, reader.read_u32()?)?,
+        })
+    }
+
+    /// Returns the change to the glyph's advance width, in font units, at `coordinates`.
+    pub fn advance_delta(&self, glyph: u16, coordinates: &[f64]) -> Option<f64> {
+        // Without a map, glyphs index the first subtable directly.
+        let (outer, inner) = match self.advance_map {
+            Some(map) => map.get(glyph as u32)?,
+            None => (0, glyph),
+        };
+        self.store.delta(outer, inner, coordinates)
+    }
+
+    /// Returns the change to the glyph's left side bearing, in font units, if the table has
+    /// side bearing deltas.
+    pub fn left_side_bearing_delta(&self, glyph: u16, coordinates: &[f64]) -> Option<f64> {
+        let (outer, inner) = self.start_side_bearing_map?.get(glyph as u32)?;
+        self.store.delta(outer, inner, coordinates)
+    }
+
+    /// Returns the change to the glyph's right side bearing, in font units, if the table has
+    /// side bearing deltas.
+    pub fn right_side_bearing_delta(&self, glyph: u16, coordinates: &[f64]) -> Option<f64> {
+        let (outer, inner) = self.end_side_bearing_map?.get(glyph as u32)?;
+        self.store.delta(outer, inner, coordinates)
+    }
+}
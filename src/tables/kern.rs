@@ -0,0 +1,359 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The legacy `kern` table, which fonts without `GPOS` kerning use to adjust the space between
+//! pairs of glyphs.
+//!
+//! Microsoft and Apple define different headers for the table. Both versions' ordered pair
+//! lists (format 0) and class-based arrays (format 2), and Apple's compact class arrays
+//! (format 3), are supported; Apple's contextual state tables (format 1) are not.
+
+use error::{Error, Result};
+use parser::{self, Reader};
+use tag::kCTFontTableKern;
+
+const APPLE_VERSION: u32 = 0x00010000;
+const MICROSOFT_HEADER_SIZE: usize = 6;
+const APPLE_HEADER_SIZE: usize = 8;
+const PAIR_SIZE: usize = 6;
+
+// Microsoft coverage flags.
+const MICROSOFT_HORIZONTAL: u16 = 0x0001;
+const MICROSOFT_MINIMUM: u16 = 0x0002;
+const MICROSOFT_CROSS_STREAM: u16 = 0x0004;
+const MICROSOFT_OVERRIDE: u16 = 0x0008;
+
+// Apple coverage flags.
+const APPLE_VERTICAL: u16 = 0x8000;
+const APPLE_CROSS_STREAM: u16 = 0x4000;
+const APPLE_VARIATION: u16 = 0x2000;
+
+/// A `kern` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Kern<'a> {
+    data: &'a [u8],
+    is_apple: bool,
+    subtable_count: u32,
+}
+
+impl<'a> Kern<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Kern<'a>> {
+        Kern::parse_table(data).ok_or(Error::MalformedTable(kCTFontTableKern))
+    }
+
+    fn parse_table(data: &'a [u8]) -> Option<Kern<'a>> {
+        if parser::read_u16(data, 0)? == 0 {
+            return Some(Kern {
+                data: data.get(4..)?,
+                is_apple: false,
+                subtable_count: parser::read_u16(data, 2)? as u32,
+            });
+        }
+        if parser::read_u32(data, 0)? != APPLE_VERSION {
+            return None;
+        }
+        Some(Kern {
+            data: data.get(8..)?,
+            is_apple: true,
+            subtable_count: parser::read_u32(data, 4)?,
+        })
+    }
+
+    /// Returns the table's subtables, stopping at the first that is malformed.
+    pub fn subtables(&self) -> Vec<KernSubtable<'a>> {
+        let mut subtables = vec![];
+        let mut offset = 0;
+        for _ in 0..self.subtable_count {
+            let subtable = match KernSubtable::parse(self.data.get(offset..).unwrap_or(&[]),
+                                                     self.is_apple) {
+                Some(subtable) => subtable,
+                None => break,
+            };
+            offset += subtable.length;
+            subtables.push(subtable);
+        }
+        subtables
+    }
+
+    /// Returns the horizontal kerning between `left` and `right`, in font units, summed over
+    /// the subtables that apply to horizontal text without other options.
+    pub fn kerning(&self, left: u16, right: u16) -> i32 {
+        let mut kerning = 0;
+        for subtable in self.subtables() {
+            if !subtable.is_horizontal() || subtable.is_cross_stream() ||
+               subtable.is_minimum() || subtable.has_variation() {
+                continue;
+            }
+            if let Some(value) = subtable.kerning(left, right) {
+                if subtable.overrides() {
+                    kerning = value as i32;
+                } else {
+                    kerning += value as i32;
+                }
+            }
+        }
+        kerning
+    }
+}
+
+/// A `kern` subtable.
+#[derive(Clone, Copy, Debug)]
+pub struct KernSubtable<'a> {
+    /// The subtable, starting with its header.
+    data: &'a [u8],
+    is_apple: bool,
+    coverage: u16,
+    format: u8,
+    length: usize,
+}
+
+impl<'a> KernSubtable<'a> {
+    fn parse(data: &'a [u8], is_apple: bool) -> Option<KernSubtable<'a>> {
+        let (length, coverage, format) = if is_apple {
+            let coverage = parser::read_u16(data, 4)?;
+            (parser::read_u32(data, 0)? as usize, coverage, coverage as u8)
+        } else {
+            let coverage = parser::read_u16(data, 4)?;
+            let format = (coverage >> 8) as u8;
+            // The 16-bit length field overflows for large format 0 subtables, so their length
+            // comes from their number of pairs instead.
+            let length = if format == 0 {
+                let count = parser::read_u16(data, MICROSOFT_HEADER_SIZE)? as usize;
+                MICROSOFT_HEADER_SIZE + 8 + count * PAIR_SIZE
+            } else {
+                parser::read_u16(data, 2)? as usize
+            };
+            (length, coverage, format)
+        };
+        // A subtable shorter than its header would leave the next one's offset inconsistent.
+        if length < if is_apple { APPLE_HEADER_SIZE } else { MICROSOFT_HEADER_SIZE } {
+            return None;
+        }
+        Some(KernSubtable {
+            data,
+            is_apple,
+            coverage,
+            format,
+            length,
+        })
+    }
+
+    /// The subtable's format: 0, 1, 2 or 3.
+    pub fn format(&self) -> u8 {
+        self.format
+    }
+
+    /// Whether the subtable kerns horizontal text, rather than vertical.
+    pub fn is_horizontal(&self) -> bool {
+        if self.is_apple {
+            self.coverage & APPLE_VERTICAL == 0
+        } else {
+            self.coverage & MICROSOFT_HORIZONTAL != 0
+        }
+    }
+
+    /// Whether the subtable's values move glyphs across the line, up or down for horizontal
+    /// text, rather than along it.
+    pub fn is_cross_stream(&self) -> bool {
+        let flag = if self.is_apple { APPLE_CROSS_STREAM } else { MICROSOFT_CROSS_STREAM };
+        self.coverage & flag != 0
+    }
+
+    /// Whether the subtable's values are minimums rather than kerning.
+    pub fn is_minimum(&self) -> bool {
+        !self.is_apple && self.coverage & MICROSOFT_MINIMUM != 0
+    }
+
+    /// Whether the subtable's values replace those of the subtables before it, rather than
+    /// adding to them.
+    pub fn overrides(&self) -> bool {
+        !self.is_apple && self.coverage & MICROSOFT_OVERRIDE != 0
+    }
+
+    /// Whether the subtable's values vary with the font's variation axes.
+    pub fn has_variation(&self) -> bool {
+        self.is_apple && self.coverage & APPLE_VARIATION != 0
+    }
+
+    /// Returns the subtable's value for the pair, in font units, if it has one.
+    pub fn kerning(&self, left: u16, right: u16) -> Option<i16> {
+        let header_size = if self.is_apple { APPLE_HEADER_SIZE } else { MICROSOFT_HEADER_SIZE };
+        let body = self.data.get(header_size..)?;
+        match self.format {
+            0 => {
+                let count = parser::read_u16(body, 0)? as usize;
+                let pairs = Reader::at(body, 8).read_bytes(count * PAIR_SIZE)?;
+                let pair = ((left as u32) << 16) | right as u32;
+                let index = parser::binary_search(count, |index| {
+                    Some(parser::read_u32(pairs, index * PAIR_SIZE)?.cmp(&pair))
+                })?;
+                parser::read_i16(pairs, index * PAIR_SIZE + 4)
+            }
+            2 => {
+                // Class values are byte offsets from the start of the subtable: the left
+                // class's includes the array offset and the row, and the right's the column.
+                let left_class = class(self.data, parser::read_u16(body, 2)?, left)?;
+                let right_class = class(self.data, parser::read_u16(body, 4)?, right)?;
+                let array_offset = parser::read_u16(body, 6)? as usize;
+                let offset = left_class as usize + right_class as usize;
+                if offset < array_offset {
+                    return None;
+                }
+                parser::read_i16(self.data, offset)
+            }
+            3 if self.is_apple => {
+                let mut reader = Reader::new(body);
+                let glyph_count = reader.read_u16()? as usize;
+                let value_count = reader.read_u8()? as usize;
+                let left_class_count = reader.read_u8()? as usize;
+                let right_class_count = reader.read_u8()? as usize;
+                reader.skip(1)?; // flags
+                let values = reader.read_bytes(value_count * 2)?;
+                let left_classes = reader.read_bytes(glyph_count)?;
+                let right_classes = reader.read_bytes(glyph_count)?;
+                let indices = reader.read_bytes(left_class_count * right_class_count)?;
+                let left_class = *left_classes.get(left as usize)? as usize;
+                let right_class = *right_classes.get(right as usize)? as usize;
+                if left_class >= left_class_count || right_class >= right_class_count {
+                    return None;
+                }
+                let index = *indices.get(left_class * right_class_count + right_class)?;
+                parser::read_i16(values, index as usize * 2)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Looks a glyph up in the format 2 class table at `offset` in `subtable`.
+fn class(subtable: &[u8], offset: u16, glyph: u16) -> Option<u16> {
+    let mut reader = Reader::at(subtable, offset as usize);
+    let first_glyph = reader.read_u16()?;
+    let count = reader.read_u16()?;
+    if glyph < first_glyph || glyph - first_glyph >= count {
+        return None;
+    }
+    reader.skip((glyph - first_glyph) as usize * 2)?;
+    reader.read_u16()
+}
+
+#[cfg(test)]
+mod tests {
+    use test_font;
+    use super::Kern;
+
+    /// Returns a Microsoft format 0 subtable with `coverage` flags holding `pairs`, which are
+    /// sorted. Its length field wraps around like those of real fonts with many pairs.
+    fn microsoft_format_0(coverage: u16, pairs: &[(u16, u16, i16)]) -> Vec<u8> {
+        let length = 6 + 8 + pairs.len() * 6;
+        let mut subtable = test_font::words(&[0, length as u16 as i32, coverage as i32,
+                                              pairs.len() as i32, 0, 0, 0]);
+        for &(left, right, value) in pairs {
+            subtable.extend(test_font::words(&[left as i32, right as i32, value as i32]));
+        }
+        subtable
+    }
+
+    fn microsoft_kern(subtables: &[Vec<u8>]) -> Vec<u8> {
+        let mut table = test_font::words(&[0, subtables.len() as i32]);
+        for subtable in subtables {
+            table.extend_from_slice(subtable);
+        }
+        table
+    }
+
+    #[test]
+    fn reads_microsoft_format_0_pairs() {
+        let subtable = microsoft_format_0(0x0001, &[(1, 2, -50), (1, 3, 20), (4, 2, -10)]);
+        let data = microsoft_kern(&[subtable]);
+        let kern = Kern::parse(&data).unwrap();
+        assert_eq!(kern.subtables().len(), 1);
+        assert_eq!(kern.subtables()[0].format(), 0);
+        assert_eq!(kern.kerning(1, 2), -50);
+        assert_eq!(kern.kerning(1, 3), 20);
+        assert_eq!(kern.kerning(4, 2), -10);
+        assert_eq!(kern.kerning(2, 1), 0);
+    }
+
+    #[test]
+    fn finds_subtables_after_one_whose_length_overflows() {
+        // 11,000 pairs take 66,000 bytes, more than the length field holds.
+        let pairs: Vec<(u16, u16, i16)> = (0..11000).map(|index| {
+            ((index / 256) as u16, (index % 256) as u16, 1 + (index % 5) as i16)
+        }).collect();
+        let vertical = microsoft_format_0(0x0000, &[(1000, 2000, 30)]);
+        let horizontal = microsoft_format_0(0x0001, &[(1000, 2000, -70)]);
+        let data = microsoft_kern(&[microsoft_format_0(0x0001, &pairs), vertical, horizontal]);
+        let kern = Kern::parse(&data).unwrap();
+        assert_eq!(kern.subtables().len(), 3);
+        assert_eq!(kern.kerning(0, 1), 2);
+        assert_eq!(kern.kerning(42, 247), 1 + (42 * 256 + 247) % 5);
+        assert_eq!(kern.kerning(1000, 2000), -70);
+    }
+
+    #[test]
+    fn sums_subtables_until_one_overrides() {
+        let data = microsoft_kern(&[microsoft_format_0(0x0001, &[(1, 2, -50)]),
+                                    microsoft_format_0(0x0001, &[(1, 2, -20)]),
+                                    microsoft_format_0(0x0009, &[(1, 2, 15), (3, 4, 5)]),
+                                    microsoft_format_0(0x0001, &[(3, 4, 5)])]);
+        let kern = Kern::parse(&data).unwrap();
+        assert_eq!(kern.kerning(1, 2), 15);
+        assert_eq!(kern.kerning(3, 4), 10);
+    }
+
+    #[test]
+    fn stops_at_a_subtable_shorter_than_its_header() {
+        let truncated = test_font::words(&[0, 4, 0x0201]);
+        let data = microsoft_kern(&[microsoft_format_0(0x0001, &[(1, 2, -50)]), truncated,
+                                    microsoft_format_0(0x0001, &[(1, 2, -20)])]);
+        let kern = Kern::parse(&data).unwrap();
+        assert_eq!(kern.subtables().len(), 1);
+        assert_eq!(kern.kerning(1, 2), -50);
+    }
+
+    #[test]
+    fn reads_apple_class_subtables() {
+        // Format 2: glyphs 10 and 11 are rows 0 and 1, glyphs 20 and 22 column 0 and glyph 21
+        // column 1. Class values are byte offsets from the start of the subtable.
+        let format_2 = test_font::words(&[0, 42, 0x0002, 0,
+                                          4, 16, 24, 34,
+                                          10, 2, 34, 38,
+                                          20, 3, 0, 2, 0,
+                                          0, -30, 40, 0]);
+        // Format 3: four glyphs, three values, two left classes and two right classes. Bytes
+        // are paired into words.
+        let format_3 = test_font::words(&[0, 32, 0x0003, 0,
+                                          4, 0x0302, 0x0200,
+                                          0, -25, 60,
+                                          0x0001, 0x0001,
+                                          0x0000, 0x0101,
+                                          0x0001, 0x0200]);
+        let mut data = test_font::words(&[1, 0, 0, 2]);
+        data.extend(format_2);
+        data.extend(format_3);
+        let kern = Kern::parse(&data).unwrap();
+        let subtables = kern.subtables();
+        assert_eq!(subtables.iter().map(|subtable| subtable.format()).collect::<Vec<_>>(),
+                   vec![2, 3]);
+
+        assert_eq!(subtables[0].kerning(10, 21), Some(-30));
+        assert_eq!(subtables[0].kerning(11, 20), Some(40));
+        assert_eq!(subtables[0].kerning(11, 22), Some(40));
+        assert_eq!(subtables[0].kerning(12, 20), None);
+
+        assert_eq!(subtables[1].kerning(0, 2), Some(-25));
+        assert_eq!(subtables[1].kerning(1, 0), Some(60));
+        assert_eq!(subtables[1].kerning(1, 3), Some(0));
+        assert_eq!(subtables[1].kerning(4, 0), None);
+
+        assert_eq!(kern.kerning(10, 21), -30);
+        assert_eq!(kern.kerning(1, 0), 60);
+    }
+}
@@ -0,0 +1,261 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The extended kerning table, `kerx`, which Apple fonts use instead of `GPOS` kerning.
+//!
+//! Ordered pair lists (format 0), class arrays (formats 2 and 6), contextual kerning state
+//! tables (format 1) and anchor attachment state tables (format 4) are supported. Variation
+//! tuples are not.
+
+use error::{Error, Result};
+use parser::{self, Reader};
+use tables::aat::{LookupTable, StateTable};
+use tables::gpos::Anchor;
+use tag::kCTFontTableKerx;
+
+const HEADER_SIZE: usize = 8;
+const SUBTABLE_HEADER_SIZE: usize = 12;
+const PAIR_SIZE: usize = 6;
+
+// Subtable coverage flags.
+const VERTICAL: u32 = 0x80000000;
+const CROSS_STREAM: u32 = 0x40000000;
+const VARIATION: u32 = 0x20000000;
+const SUBTABLE_FORMAT: u32 = 0x000000FF;
+
+// Format 4 flags.
+const ACTION_TYPE_SHIFT: u32 = 30;
+const ACTION_DATA_OFFSET: u32 = 0x00FFFFFF;
+
+// Format 6 flags.
+const VALUES_ARE_LONG: u32 = 0x00000001;
+
+/// A `kerx` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Kerx<'a> {
+    data: &'a [u8],
+    subtable_count: u32,
+}
+
+impl<'a> Kerx<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Kerx<'a>> {
+        Kerx::parse_table(data).ok_or(Error::MalformedTable(kCTFontTableKerx))
+    }
+
+    fn parse_table(data: &'a [u8]) -> Option<Kerx<'a>> {
+        let mut reader = Reader::new(data);
+        let version = reader.read_u16()?;
+        reader.skip(2)?;
+        let subtable_count = reader.read_u32()?;
+        if !(2..=4).contains(&version) {
+            return None;
+        }
+        Some(Kerx {
+            data: data.get(HEADER_SIZE..)?,
+            subtable_count,
+        })
+    }
+
+    /// Returns the table's subtables, leaving out any after a malformed one.
+    pub fn subtables(&self) -> Vec<KerxSubtable<'a>> {
+        let mut subtables = vec![];
+        let mut offset = 0;
+        for _ in 0..self.subtable_count {
+            let subtable = match self.data.get(offset..).and_then(KerxSubtable::parse) {
+                Some(subtable) => subtable,
+                None => break,
+            };
+            offset += subtable.data.len();
+            subtables.push(subtable);
+        }
+        subtables
+    }
+}
+
+/// How a `kerx` format 4 subtable finds the points it attaches glyphs at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnchorAction {
+    /// Indices of the glyphs' outline points.
+    ControlPoints(u16, u16),
+    /// Indices of the glyphs' points in the `ankr` table.
+    AnchorPoints(u16, u16),
+    /// The points themselves, in font units.
+    Coordinates(Anchor, Anchor),
+}
+
+/// A `kerx` subtable.
+#[derive(Clone, Copy, Debug)]
+pub struct KerxSubtable<'a> {
+    /// The subtable, starting with its header.
+    data: &'a [u8],
+    coverage: u32,
+    tuple_count: u32,
+}
+
+impl<'a> KerxSubtable<'a> {
+    fn parse(data: &'a [u8]) -> Option<KerxSubtable<'a>> {
+        let mut reader = Reader::new(data);
+        let length = reader.read_u32()? as usize;
+        let coverage = reader.read_u32()?;
+        let tuple_count = reader.read_u32()?;
+        if length < SUBTABLE_HEADER_SIZE {
+            return None;
+        }
+        Some(KerxSubtable {
+            data: data.get(..length)?,
+            coverage,
+            tuple_count,
+        })
+    }
+
+    /// The subtable's format: 0, 1, 2, 4 or 6.
+    pub fn format(&self) -> u8 {
+        (self.coverage & SUBTABLE_FORMAT) as u8
+    }
+
+    /// Whether the subtable kerns horizontal text, rather than vertical.
+    pub fn is_horizontal(&self) -> bool {
+        self.coverage & VERTICAL == 0
+    }
+
+    /// Whether the subtable's values move glyphs across the line, up or down for horizontal
+    /// text, rather than along it.
+    pub fn is_cross_stream(&self) -> bool {
+        self.coverage & CROSS_STREAM != 0
+    }
+
+    /// Whether the subtable's values vary with the font's variation axes.
+    pub fn has_variation(&self) -> bool {
+        self.coverage & VARIATION != 0 || self.tuple_count != 0
+    }
+
+    fn body(&self) -> &'a [u8] {
+        &self.data[SUBTABLE_HEADER_SIZE..]
+    }
+
+    /// Returns the value of a format 0, 2 or 6 subtable for the pair, in font units, if it has
+    /// one.
+    pub fn kerning(&self, left: u16, right: u16) -> Option<i16> {
+        let body = self.body();
+        match self.format() {
+            0 => {
+                let count = parser::read_u32(body, 0)? as usize;
+                let pairs = Reader::at(body, 16).read_bytes(count.checked_mul(PAIR_SIZE)?)?;
+                let pair = ((left as u32) << 16) | right as u32;
+                let index = parser::binary_search(count, |index| {
+                    Some(parser::read_u32(pairs, index * PAIR_SIZE)?.cmp(&pair))
+                })?;
+                parser::read_i16(pairs, index * PAIR_SIZE + 4)
+            }
+            2 => {
+                // Class values are byte offsets into the array: the left class's includes the
+                // row, and the right's the column. Offsets are from the start of the subtable.
+                let mut reader = Reader::at(body, 4);
+                let left_classes = self.lookup_table(reader.read_u32()?)?;
+                let right_classes = self.lookup_table(reader.read_u32()?)?;
+                let array_offset = reader.read_u32()? as usize;
+                let left_class = left_classes.value(left).unwrap_or(0) as usize;
+                let right_class = right_classes.value(right).unwrap_or(0) as usize;
+                parser::read_i16(self.data, array_offset + left_class + right_class)
+            }
+            6 => {
+                // Row and column values are indices into the array.
+                let mut reader = Reader::new(body);
+                let flags = reader.read_u32()?;
+                reader.skip(4)?; // row and column counts
+                if flags & VALUES_ARE_LONG != 0 {
+                    return None;
+                }
+                let rows = self.lookup_table(reader.read_u32()?)?;
+                let columns = self.lookup_table(reader.read_u32()?)?;
+                let array_offset = reader.read_u32()? as usize;
+                let row = rows.value(left).unwrap_or(0) as usize;
+                let index = row + columns.value(right).unwrap_or(0) as usize;
+                parser::read_i16(self.data, array_offset + index * 2)
+            }
+            _ => None,
+        }
+    }
+
+    fn lookup_table(&self, offset: u32) -> Option<LookupTable<'a>> {
+        LookupTable::parse(self.data.get(offset as usize..)?)
+    }
+
+    /// Returns the state table of a format 1 or 4 subtable. Each entry holds the index of its
+    /// action, or 0xFFFF for none.
+    pub fn state_table(&self) -> Option<StateTable<'a>> {
+        match self.format() {
+            1 | 4 => StateTable::parse(self.body(), 2),
+            _ => None,
+        }
+    }
+
+    /// Returns the kerning values that a format 1 subtable's `index`th action applies, in font
+    /// units, to at most `max_count` glyphs popped off its stack. The first value is for the
+    /// glyph pushed last.
+    pub fn kerning_values(&self, index: u16, max_count: usize) -> Vec<i16> {
+        let mut values = vec![];
+        let state_table = match self.state_table() {
+            Some(state_table) if self.format() == 1 => state_table,
+            _ => return values,
+        };
+        let offset = match parser::read_u32(state_table.extra_header(), 0) {
+            Some(offset) => offset as usize + index as usize * 2,
+            None => return values,
+        };
+        let mut reader = Reader::at(state_table.data(), offset);
+        while values.len() < max_count {
+            // The low bit of each value marks the last, and isn't part of the value.
+            let value = match reader.read_i16() {
+                Some(value) => value,
+                None => break,
+            };
+            values.push(value & !1);
+            if value & 1 != 0 {
+                break;
+            }
+        }
+        values
+    }
+
+    /// Returns the points that a format 4 subtable's `index`th action attaches the current
+    /// glyph and the marked glyph at.
+    pub fn anchor_action(&self, index: u16) -> Option<AnchorAction> {
+        if self.format() != 4 {
+            return None;
+        }
+        let state_table = self.state_table()?;
+        let flags = parser::read_u32(state_table.extra_header(), 0)?;
+        let actions = state_table.data().get((flags & ACTION_DATA_OFFSET) as usize..)?;
+        match flags >> ACTION_TYPE_SHIFT {
+            0 | 1 => {
+                let mut reader = Reader::at(actions, index as usize * 4);
+                let (mark_point, current_point) = (reader.read_u16()?, reader.read_u16()?);
+                Some(if flags >> ACTION_TYPE_SHIFT == 0 {
+                    AnchorAction::ControlPoints(mark_point, current_point)
+                } else {
+                    AnchorAction::AnchorPoints(mark_point, current_point)
+                })
+            }
+            2 => {
+                let mut reader = Reader::at(actions, index as usize * 8);
+                let mark_anchor = Anchor {
+                    x: reader.read_i16()?,
+                    y: reader.read_i16()?,
+                };
+                let current_anchor = Anchor {
+                    x: reader.read_i16()?,
+                    y: reader.read_i16()?,
+                };
+                Some(AnchorAction::Coordinates(mark_anchor, current_anchor))
+            }
+            _ => None,
+        }
+    }
+}
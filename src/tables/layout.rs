@@ -0,0 +1,545 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The structures that the OpenType layout tables, `GSUB` and `GPOS`, share: script, feature
+//! and lookup lists, coverage and class definition tables, and contextual lookups.
+
+use parser::{self, Reader};
+use tag::Tag;
+
+use std::cmp::Ordering;
+
+const NO_REQUIRED_FEATURE: u16 = 0xFFFF;
+const USE_MARK_FILTERING_SET: u16 = 0x0010;
+
+/// The script whose features apply when a table has none for the requested script.
+pub const DEFAULT_SCRIPT: Tag = Tag::new(b"DFLT");
+
+/// The header that `GSUB` and `GPOS` share, leading to their script, feature and lookup lists.
+#[derive(Clone, Copy, Debug)]
+pub struct LayoutTable<'a> {
+    scripts: &'a [u8],
+    features: &'a [u8],
+    lookups: &'a [u8],
+}
+
+impl<'a> LayoutTable<'a> {
+    /// Parses the header, returning `None` if it or any of its lists is malformed. The tables
+    /// that embed the header report that as their own error.
+    pub fn parse(data: &'a [u8]) -> Option<LayoutTable<'a>> {
+        let mut reader = Reader::new(data);
+        if reader.read_u16()? != 1 {
+            return None;
+        }
+        reader.skip(2)?; // minor version
+        let table = LayoutTable {
+            scripts: data.get(reader.read_u16()? as usize..)?,
+            features: data.get(reader.read_u16()? as usize..)?,
+            lookups: data.get(reader.read_u16()? as usize..)?,
+        };
+        // Check the record arrays, so that counts can be trusted later.
+        for list in &[table.scripts, table.features] {
+            let count = parser::read_u16(list, 0)? as usize;
+            Reader::at(list, 2).read_bytes(count * 6)?;
+        }
+        let count = parser::read_u16(table.lookups, 0)? as usize;
+        Reader::at(table.lookups, 2).read_bytes(count * 2)?;
+        Some(table)
+    }
+
+    /// Returns the tag of every script the table has features for.
+    pub fn scripts(&self) -> Vec<Tag> {
+        let count = parser::read_u16(self.scripts, 0).unwrap_or(0) as usize;
+        (0..count).filter_map(|index| parser::read_u32(self.scripts, 2 + index * 6).map(Tag))
+                  .collect()
+    }
+
+    fn script(&self, script: Tag) -> Option<&'a [u8]> {
+        let count = parser::read_u16(self.scripts, 0)? as usize;
+        let index = parser::binary_search(count, |index| {
+            Some(parser::read_u32(self.scripts, 2 + index * 6)?.cmp(&script.0))
+        });
+        // Some fonts don't sort their records, so fall back to a linear search.
+        let index = index.or_else(|| {
+            (0..count).find(|&index| {
+                parser::read_u32(self.scripts, 2 + index * 6) == Some(script.0)
+            })
+        })?;
+        let offset = parser::read_u16(self.scripts, 2 + index * 6 + 4)? as usize;
+        self.scripts.get(offset..)
+    }
+
+    /// Returns the tags of the languages that `script` has its own features for.
+    pub fn languages(&self, script: Tag) -> Vec<Tag> {
+        let script = match self.script(script) {
+            Some(script) => script,
+            None => return vec![],
+        };
+        let count = parser::read_u16(script, 2).unwrap_or(0) as usize;
+        (0..count).filter_map(|index| parser::read_u32(script, 4 + index * 6).map(Tag)).collect()
+    }
+
+    /// Returns the language system for `script` and `language`, falling back to the script's
+    /// default language and then to the default script.
+    fn language_system(&self, script: Tag, language: Option<Tag>) -> Option<&'a [u8]> {
+        let script = self.script(script).or_else(|| self.script(DEFAULT_SCRIPT))?;
+        let language = language.and_then(|language| {
+            let count = parser::read_u16(script, 2)? as usize;
+            let index = (0..count).find(|&index| {
+                parser::read_u32(script, 4 + index * 6) == Some(language.0)
+            })?;
+            let offset = parser::read_u16(script, 4 + index * 6 + 4)? as usize;
+            script.get(offset..)
+        });
+        match language {
+            Some(language) => Some(language),
+            None => {
+                let offset = parser::read_u16(script, 0)? as usize;
+                if offset == 0 {
+                    return None;
+                }
+                script.get(offset..)
+            }
+        }
+    }
+
+    /// Returns the index of the feature that always applies to `script` and `language`, if
+    /// there is one.
+    pub fn required_feature_index(&self, script: Tag, language: Option<Tag>) -> Option<u16> {
+        let language_system = self.language_system(script, language)?;
+        // The reserved lookupOrderOffset comes first.
+        parser::read_u16(language_system, 2).filter(|&index| index != NO_REQUIRED_FEATURE)
+    }
+
+    /// Returns the indices of the features that `script` and `language` can turn on.
+    pub fn feature_indices(&self, script: Tag, language: Option<Tag>) -> Vec<u16> {
+        let language_system = match self.language_system(script, language) {
+            Some(language_system) => language_system,
+            None => return vec![],
+        };
+        let mut reader = Reader::at(language_system, 4);
+        let count = reader.read_u16().unwrap_or(0);
+        (0..count).filter_map(|_| reader.read_u16()).collect()
+    }
+
+    /// Whether `script` and `language` have a feature with the given tag, required or not.
+    pub fn has_feature(&self, script: Tag, language: Option<Tag>, tag: Tag) -> bool {
+        self.required_feature_index(script, language)
+            .into_iter()
+            .chain(self.feature_indices(script, language))
+            .any(|index| self.feature(index).is_some_and(|(feature, _)| feature == tag))
+    }
+
+    pub fn feature_count(&self) -> u16 {
+        parser::read_u16(self.features, 0).unwrap_or(0)
+    }
+
+    /// Returns the tag of the `index`th feature and the indices of its lookups.
+    pub fn feature(&self, index: u16) -> Option<(Tag, Vec<u16>)> {
+        if index >= self.feature_count() {
+            return None;
+        }
+        let mut reader = Reader::at(self.features, 2 + index as usize * 6);
+        let tag = Tag(reader.read_u32()?);
+        let feature = self.features.get(reader.read_u16()? as usize..)?;
+        let mut reader = Reader::at(feature, 2); // featureParamsOffset
+        let count = reader.read_u16()?;
+        let lookups = (0..count).map(|_| reader.read_u16()).collect::<Option<Vec<u16>>>()?;
+        Some((tag, lookups))
+    }
+
+    pub fn lookup_count(&self) -> u16 {
+        parser::read_u16(self.lookups, 0).unwrap_or(0)
+    }
+
+    pub fn lookup(&self, index: u16) -> Option<Lookup<'a>> {
+        if index >= self.lookup_count() {
+            return None;
+        }
+        let offset = parser::read_u16(self.lookups, 2 + index as usize * 2)? as usize;
+        let data = self.lookups.get(offset..)?;
+        let mut reader = Reader::new(data);
+        let lookup_type = reader.read_u16()?;
+        let flags = reader.read_u16()?;
+        let subtable_count = reader.read_u16()?;
+        reader.skip(subtable_count as usize * 2)?;
+        let mark_filtering_set = if flags & USE_MARK_FILTERING_SET != 0 {
+            Some(reader.read_u16()?)
+        } else {
+            None
+        };
+        Some(Lookup {
+            data,
+            lookup_type,
+            flags,
+            subtable_count,
+            mark_filtering_set,
+        })
+    }
+}
+
+/// A lookup: a list of subtables of one type, applied together.
+#[derive(Clone, Copy, Debug)]
+pub struct Lookup<'a> {
+    data: &'a [u8],
+    lookup_type: u16,
+    flags: u16,
+    subtable_count: u16,
+    mark_filtering_set: Option<u16>,
+}
+
+impl<'a> Lookup<'a> {
+    /// The lookup type, whose meaning depends on the table. Extension lookups report the
+    /// extension type.
+    pub fn lookup_type(&self) -> u16 {
+        self.lookup_type
+    }
+
+    /// The lookup flags, which say which glyphs the lookup skips over.
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    /// The `GDEF` mark glyph set whose marks are the only ones the lookup doesn't skip, if any.
+    pub fn mark_filtering_set(&self) -> Option<u16> {
+        self.mark_filtering_set
+    }
+
+    /// Returns the type and data of each subtable, looking through extension subtables, whose
+    /// lookup type is `extension_type`, to the subtables they wrap.
+    pub fn subtables(&self, extension_type: u16) -> Vec<(u16, &'a [u8])> {
+        (0..self.subtable_count as usize).filter_map(|index| {
+            let offset = parser::read_u16(self.data, 6 + index * 2)? as usize;
+            let data = self.data.get(offset..)?;
+            if self.lookup_type != extension_type {
+                return Some((self.lookup_type, data));
+            }
+            let mut reader = Reader::new(data);
+            if reader.read_u16()? != 1 {
+                return None;
+            }
+            let lookup_type = reader.read_u16()?;
+            Some((lookup_type, data.get(reader.read_u32()? as usize..)?))
+        }).collect()
+    }
+}
+
+/// A coverage table: a sorted set of glyphs, each with an index into its subtable's arrays.
+#[derive(Clone, Copy, Debug)]
+pub struct Coverage<'a> {
+    format: u16,
+    records: &'a [u8],
+    count: u16,
+}
+
+impl<'a> Coverage<'a> {
+    pub fn parse(data: &'a [u8]) -> Option<Coverage<'a>> {
+        let mut reader = Reader::new(data);
+        let format = reader.read_u16()?;
+        let count = reader.read_u16()?;
+        let record_size = match format {
+            1 => 2,
+            2 => 6,
+            _ => return None,
+        };
+        Some(Coverage {
+            format,
+            records: reader.read_bytes(count as usize * record_size)?,
+            count,
+        })
+    }
+
+    /// Parses the coverage table at `offset` in a subtable.
+    pub fn parse_at(data: &'a [u8], offset: usize) -> Option<Coverage<'a>> {
+        Coverage::parse(data.get(parser::read_u16(data, offset)? as usize..)?)
+    }
+
+    /// Returns the glyph's coverage index, or `None` if the table doesn't cover it.
+    pub fn index(&self, glyph: u16) -> Option<u16> {
+        if self.format == 1 {
+            return parser::binary_search(self.count as usize, |index| {
+                Some(parser::read_u16(self.records, index * 2)?.cmp(&glyph))
+            }).map(|index| index as u16);
+        }
+        let index = parser::binary_search(self.count as usize, |index| {
+            range_ordering(self.records, index * 6, glyph)
+        })?;
+        let start = parser::read_u16(self.records, index * 6)?;
+        let start_index = parser::read_u16(self.records, index * 6 + 4)?;
+        Some(start_index.wrapping_add(glyph - start))
+    }
+
+    pub fn contains(&self, glyph: u16) -> bool {
+        self.index(glyph).is_some()
+    }
+}
+
+/// A class definition table, which sorts glyphs into numbered classes. Glyphs it doesn't list
+/// are in class 0.
+#[derive(Clone, Copy, Debug)]
+pub struct ClassDef<'a> {
+    format: u16,
+    /// The first glyph of a format 1 table.
+    start_glyph: u16,
+    records: &'a [u8],
+    count: u16,
+}
+
+impl<'a> ClassDef<'a> {
+    pub fn parse(data: &'a [u8]) -> Option<ClassDef<'a>> {
+        let mut reader = Reader::new(data);
+        let format = reader.read_u16()?;
+        let (start_glyph, record_size) = match format {
+            1 => (reader.read_u16()?, 2),
+            2 => (0, 6),
+            _ => return None,
+        };
+        let count = reader.read_u16()?;
+        Some(ClassDef {
+            format,
+            start_glyph,
+            records: reader.read_bytes(count as usize * record_size)?,
+            count,
+        })
+    }
+
+    /// Parses the class definition table at `offset` in a subtable. An offset of 0 means every
+    /// glyph is in class 0.
+    pub fn parse_at(data: &'a [u8], offset: usize) -> Option<ClassDef<'a>> {
+        match parser::read_u16(data, offset)? {
+            0 => Some(ClassDef {
+                format: 1,
+                start_glyph: 0,
+                records: &[],
+                count: 0,
+            }),
+            offset => ClassDef::parse(data.get(offset as usize..)?),
+        }
+    }
+
+    pub fn class(&self, glyph: u16) -> u16 {
+        if self.format == 1 {
+            if glyph < self.start_glyph {
+                return 0;
+            }
+            let index = (glyph - self.start_glyph) as usize;
+            return parser::read_u16(self.records, index * 2).unwrap_or(0);
+        }
+        parser::binary_search(self.count as usize, |index| {
+            range_ordering(self.records, index * 6, glyph)
+        }).and_then(|index| parser::read_u16(self.records, index * 6 + 4)).unwrap_or(0)
+    }
+}
+
+/// Compares the glyph range record at `offset` to `glyph`.
+fn range_ordering(records: &[u8], offset: usize, glyph: u16) -> Option<Ordering> {
+    let start = parser::read_u16(records, offset)?;
+    let end = parser::read_u16(records, offset + 2)?;
+    Some(if end < glyph {
+        Ordering::Less
+    } else if start > glyph {
+        Ordering::Greater
+    } else {
+        Ordering::Equal
+    })
+}
+
+/// A sequence of glyphs that a contextual rule matches, given as glyph IDs, classes or
+/// coverage tables depending on the subtable's format.
+#[derive(Clone, Copy, Debug)]
+pub enum SequenceMatcher<'a> {
+    Glyphs(&'a [u8]),
+    Classes(ClassDef<'a>, &'a [u8]),
+    /// Offsets to coverage tables, relative to the subtable.
+    Coverages(&'a [u8], &'a [u8]),
+}
+
+impl<'a> SequenceMatcher<'a> {
+    pub fn len(&self) -> usize {
+        match *self {
+            SequenceMatcher::Glyphs(glyphs) => glyphs.len() / 2,
+            SequenceMatcher::Classes(_, classes) => classes.len() / 2,
+            SequenceMatcher::Coverages(_, offsets) => offsets.len() / 2,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether `glyph` matches the `index`th glyph of the sequence.
+    pub fn matches(&self, index: usize, glyph: u16) -> bool {
+        match *self {
+            SequenceMatcher::Glyphs(glyphs) => parser::read_u16(glyphs, index * 2) == Some(glyph),
+            SequenceMatcher::Classes(class_def, classes) => {
+                parser::read_u16(classes, index * 2) == Some(class_def.class(glyph))
+            }
+            SequenceMatcher::Coverages(subtable, offsets) => {
+                parser::read_u16(offsets, index * 2)
+                    .and_then(|offset| Coverage::parse(subtable.get(offset as usize..)?))
+                    .is_some_and(|coverage| coverage.contains(glyph))
+            }
+        }
+    }
+}
+
+/// One rule of a contextual subtable that applies to some first glyph: the glyphs that must
+/// come before, after and with it, and the lookups to apply to the input glyphs if they do.
+#[derive(Clone, Copy, Debug)]
+pub struct ContextRule<'a> {
+    /// The glyphs before the input, closest first.
+    pub backtrack: SequenceMatcher<'a>,
+    /// The input glyphs after the first, which the subtable's coverage already matched.
+    pub input: SequenceMatcher<'a>,
+    pub lookahead: SequenceMatcher<'a>,
+    lookup_records: &'a [u8],
+}
+
+impl<'a> ContextRule<'a> {
+    /// Creates a rule without lookups, for subtables that act on what it matches themselves.
+    pub fn new(backtrack: SequenceMatcher<'a>,
+               input: SequenceMatcher<'a>,
+               lookahead: SequenceMatcher<'a>)
+               -> ContextRule<'a> {
+        ContextRule {
+            backtrack,
+            input,
+            lookahead,
+            lookup_records: &[],
+        }
+    }
+
+    /// Returns the input sequence index and lookup index of each lookup to apply, in order.
+    pub fn lookups(&self) -> Vec<(u16, u16)> {
+        self.lookup_records.chunks(4).filter_map(|record| {
+            Some((parser::read_u16(record, 0)?, parser::read_u16(record, 2)?))
+        }).collect()
+    }
+}
+
+/// A contextual or chained contextual subtable, in any of their three formats. Contextual
+/// subtables are chained ones without backtrack or lookahead glyphs.
+#[derive(Clone, Copy, Debug)]
+pub struct Context<'a> {
+    data: &'a [u8],
+    format: u16,
+    chained: bool,
+}
+
+impl<'a> Context<'a> {
+    pub fn parse(data: &'a [u8], chained: bool) -> Option<Context<'a>> {
+        let format = parser::read_u16(data, 0)?;
+        if !(1..=3).contains(&format) {
+            return None;
+        }
+        Some(Context {
+            data,
+            format,
+            chained,
+        })
+    }
+
+    /// Returns the rules to try, in order, for a sequence starting with `glyph`.
+    pub fn rules(&self, glyph: u16) -> Vec<ContextRule<'a>> {
+        self.read_rules(glyph).unwrap_or_default()
+    }
+
+    fn read_rules(&self, glyph: u16) -> Option<Vec<ContextRule<'a>>> {
+        let data = self.data;
+        if self.format == 3 {
+            return self.read_coverage_rule(glyph).map(|rule| rule.into_iter().collect());
+        }
+
+        let coverage_index = Coverage::parse_at(data, 2)?.index(glyph)?;
+        let (rule_set_index, class_defs) = if self.format == 1 {
+            (coverage_index, None)
+        } else if self.chained {
+            let backtrack = ClassDef::parse_at(data, 4)?;
+            let input = ClassDef::parse_at(data, 6)?;
+            let lookahead = ClassDef::parse_at(data, 8)?;
+            (input.class(glyph), Some((backtrack, input, lookahead)))
+        } else {
+            let input = ClassDef::parse_at(data, 4)?;
+            (input.class(glyph), Some((input, input, input)))
+        };
+        let rule_sets_offset = match (self.format, self.chained) {
+            (1, _) => 4,
+            (_, true) => 10,
+            (_, false) => 6,
+        };
+        let rule_set_count = parser::read_u16(data, rule_sets_offset)?;
+        if rule_set_index >= rule_set_count {
+            return None;
+        }
+        let offset = parser::read_u16(data, rule_sets_offset + 2 + rule_set_index as usize * 2)?;
+        if offset == 0 {
+            return None;
+        }
+        let rule_set = data.get(offset as usize..)?;
+
+        let matcher = |class_def: Option<ClassDef<'a>>, sequence: &'a [u8]| match class_def {
+            Some(class_def) => SequenceMatcher::Classes(class_def, sequence),
+            None => SequenceMatcher::Glyphs(sequence),
+        };
+        let rule_count = parser::read_u16(rule_set, 0)?;
+        Some((0..rule_count as usize).filter_map(|index| {
+            let offset = parser::read_u16(rule_set, 2 + index * 2)? as usize;
+            let mut reader = Reader::at(rule_set, offset);
+            let backtrack = if self.chained {
+                let count = reader.read_u16()? as usize;
+                reader.read_bytes(count * 2)?
+            } else {
+                &[]
+            };
+            let input_count = reader.read_u16()? as usize;
+            let (input, lookahead, lookup_count) = if self.chained {
+                let input = reader.read_bytes(input_count.checked_sub(1)? * 2)?;
+                let count = reader.read_u16()? as usize;
+                (input, reader.read_bytes(count * 2)?, reader.read_u16()?)
+            } else {
+                let lookup_count = reader.read_u16()?;
+                (reader.read_bytes(input_count.checked_sub(1)? * 2)?, &[][..], lookup_count)
+            };
+            Some(ContextRule {
+                backtrack: matcher(class_defs.map(|class_defs| class_defs.0), backtrack),
+                input: matcher(class_defs.map(|class_defs| class_defs.1), input),
+                lookahead: matcher(class_defs.map(|class_defs| class_defs.2), lookahead),
+                lookup_records: reader.read_bytes(lookup_count as usize * 4)?,
+            })
+        }).collect())
+    }
+
+    /// Reads the single rule of a format 3 subtable, whose sequences are coverage tables.
+    fn read_coverage_rule(&self, glyph: u16) -> Option<Option<ContextRule<'a>>> {
+        let data = self.data;
+        let mut reader = Reader::at(data, 2);
+        let (backtrack, input, lookahead, lookup_count) = if self.chained {
+            let count = reader.read_u16()? as usize;
+            let backtrack = reader.read_bytes(count * 2)?;
+            let count = reader.read_u16()? as usize;
+            let input = reader.read_bytes(count * 2)?;
+            let count = reader.read_u16()? as usize;
+            (backtrack, input, reader.read_bytes(count * 2)?, reader.read_u16()?)
+        } else {
+            let count = reader.read_u16()? as usize;
+            let lookup_count = reader.read_u16()?;
+            (&[][..], reader.read_bytes(count * 2)?, &[][..], lookup_count)
+        };
+        let first = SequenceMatcher::Coverages(data, input);
+        if input.is_empty() || !first.matches(0, glyph) {
+            return Some(None);
+        }
+        Some(Some(ContextRule {
+            backtrack: SequenceMatcher::Coverages(data, backtrack),
+            input: SequenceMatcher::Coverages(data, &input[2..]),
+            lookahead: SequenceMatcher::Coverages(data, lookahead),
+            lookup_records: reader.read_bytes(lookup_count as usize * 4)?,
+        }))
+    }
+}
@@ -0,0 +1,65 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `loca` table, which locates each glyph's data in the `glyf` table.
+
+use error::{Error, Result};
+use parser;
+use tag::kCTFontTableLoca;
+
+use std::ops::Range;
+
+/// A `loca` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Loca<'a> {
+    data: &'a [u8],
+    num_glyphs: u16,
+    long_offsets: bool,
+}
+
+impl<'a> Loca<'a> {
+    /// Parses the table given `maxp.num_glyphs()` and `head.has_long_loca_offsets()`.
+    pub fn parse(data: &'a [u8], num_glyphs: u16, long_offsets: bool) -> Result<Loca<'a>> {
+        let entry_size = if long_offsets { 4 } else { 2 };
+        if data.len() < (num_glyphs as usize + 1) * entry_size {
+            return Err(Error::MalformedTable(kCTFontTableLoca));
+        }
+        Ok(Loca {
+            data,
+            num_glyphs,
+            long_offsets,
+        })
+    }
+
+    pub fn num_glyphs(&self) -> u16 {
+        self.num_glyphs
+    }
+
+    fn offset(&self, index: usize) -> Option<usize> {
+        if self.long_offsets {
+            parser::read_u32(self.data, index * 4).map(|offset| offset as usize)
+        } else {
+            parser::read_u16(self.data, index * 2).map(|offset| offset as usize * 2)
+        }
+    }
+
+    /// Returns the byte range of the glyph's data in `glyf`, or `None` if the glyph isn't in
+    /// the font or its offsets are out of order. Glyphs with no outline have an empty range.
+    pub fn glyph_range(&self, glyph: u16) -> Option<Range<usize>> {
+        if glyph >= self.num_glyphs {
+            return None;
+        }
+        let start = self.offset(glyph as usize)?;
+        let end = self.offset(glyph as usize + 1)?;
+        if start > end {
+            return None;
+        }
+        Some(start..end)
+    }
+}
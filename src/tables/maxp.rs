@@ -0,0 +1,105 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `maxp` table, which holds the glyph count and TrueType memory requirements.
+
+use error::{Error, Result};
+use parser;
+use tag::kCTFontTableMaxp;
+
+/// Version 0.5, used by CFF fonts, holds only the glyph count.
+const VERSION_0_5: u32 = 0x00005000;
+const VERSION_1_0: u32 = 0x00010000;
+const VERSION_1_0_SIZE: usize = 32;
+
+/// A `maxp` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Maxp<'a> {
+    data: &'a [u8],
+    has_truetype_limits: bool,
+}
+
+impl<'a> Maxp<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Maxp<'a>> {
+        let has_truetype_limits = match parser::read_u32(data, 0) {
+            Some(VERSION_0_5) if data.len() >= 6 => false,
+            Some(VERSION_1_0) if data.len() >= VERSION_1_0_SIZE => true,
+            _ => return Err(Error::MalformedTable(kCTFontTableMaxp)),
+        };
+        Ok(Maxp {
+            data,
+            has_truetype_limits,
+        })
+    }
+
+    pub fn num_glyphs(&self) -> u16 {
+        parser::read_u16(self.data, 4).unwrap_or(0)
+    }
+
+    /// Reads a version 1.0 field, which only TrueType-outline fonts have.
+    fn limit(&self, offset: usize) -> Option<u16> {
+        if self.has_truetype_limits {
+            parser::read_u16(self.data, offset)
+        } else {
+            None
+        }
+    }
+
+    pub fn max_points(&self) -> Option<u16> {
+        self.limit(6)
+    }
+
+    pub fn max_contours(&self) -> Option<u16> {
+        self.limit(8)
+    }
+
+    pub fn max_composite_points(&self) -> Option<u16> {
+        self.limit(10)
+    }
+
+    pub fn max_composite_contours(&self) -> Option<u16> {
+        self.limit(12)
+    }
+
+    pub fn max_zones(&self) -> Option<u16> {
+        self.limit(14)
+    }
+
+    pub fn max_twilight_points(&self) -> Option<u16> {
+        self.limit(16)
+    }
+
+    pub fn max_storage(&self) -> Option<u16> {
+        self.limit(18)
+    }
+
+    pub fn max_function_defs(&self) -> Option<u16> {
+        self.limit(20)
+    }
+
+    pub fn max_instruction_defs(&self) -> Option<u16> {
+        self.limit(22)
+    }
+
+    pub fn max_stack_elements(&self) -> Option<u16> {
+        self.limit(24)
+    }
+
+    pub fn max_size_of_instructions(&self) -> Option<u16> {
+        self.limit(26)
+    }
+
+    pub fn max_component_elements(&self) -> Option<u16> {
+        self.limit(28)
+    }
+
+    pub fn max_component_depth(&self) -> Option<u16> {
+        self.limit(30)
+    }
+}
@@ -0,0 +1,48 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Pure-Rust parsers for font tables, as returned by `get_font_table` on any `FontBackend`.
+//!
+//! The parsers borrow the table data rather than copying it, and check every read, so
+//! malformed tables produce errors or missing values instead of panics.
+
+pub mod aat;
+pub mod ankr;
+pub mod avar;
+pub mod cff;
+pub mod cff2;
+pub mod cmap;
+pub mod feat;
+pub mod fvar;
+pub mod gdef;
+pub mod glyf;
+pub mod gpos;
+pub mod gsub;
+pub mod gvar;
+pub mod head;
+pub mod hhea;
+pub mod hmtx;
+pub mod hvar;
+pub mod kern;
+pub mod kerx;
+pub mod layout;
+pub mod loca;
+pub mod maxp;
+pub mod morx;
+pub mod mvar;
+pub mod name;
+pub mod os2;
+pub mod stat;
+pub mod trak;
+pub mod variation_store;
+pub mod vhea;
+pub mod vmtx;
+pub mod vvar;
+
+mod compact;
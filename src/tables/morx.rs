@@ -0,0 +1,304 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The extended glyph metamorphosis table, `morx`, which Apple fonts use instead of `GSUB`.
+//!
+//! The table holds chains of subtables, each of which rearranges, substitutes, ligates or
+//! inserts glyphs. Features turn subtables on and off through the chain's flags. The older
+//! `mort` table is not supported.
+
+use error::{Error, Result};
+use parser::{self, Reader};
+use tables::aat::{LookupTable, StateTable};
+use tag::kCTFontTableMorx;
+
+const CHAIN_HEADER_SIZE: usize = 16;
+const FEATURE_ENTRY_SIZE: usize = 12;
+const SUBTABLE_HEADER_SIZE: usize = 12;
+
+// Subtable coverage flags.
+const VERTICAL: u32 = 0x80000000;
+const DESCENDING: u32 = 0x40000000;
+const ALL_DIRECTIONS: u32 = 0x20000000;
+const LOGICAL: u32 = 0x10000000;
+const SUBTABLE_TYPE: u32 = 0x000000FF;
+
+/// A `morx` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Morx<'a> {
+    data: &'a [u8],
+    chain_count: u32,
+}
+
+impl<'a> Morx<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Morx<'a>> {
+        Morx::parse_table(data).ok_or(Error::MalformedTable(kCTFontTableMorx))
+    }
+
+    fn parse_table(data: &'a [u8]) -> Option<Morx<'a>> {
+        let mut reader = Reader::new(data);
+        let version = reader.read_u16()?;
+        reader.skip(2)?;
+        let chain_count = reader.read_u32()?;
+        if version != 2 && version != 3 {
+            return None;
+        }
+        Some(Morx {
+            data: data.get(8..)?,
+            chain_count,
+        })
+    }
+
+    /// Returns the table's chains, which apply one after another. Chains after a malformed one
+    /// are left out.
+    pub fn chains(&self) -> Vec<Chain<'a>> {
+        let mut chains = vec![];
+        let mut offset = 0;
+        for _ in 0..self.chain_count {
+            let chain = match self.data.get(offset..).and_then(Chain::parse) {
+                Some(chain) => chain,
+                None => break,
+            };
+            offset += chain.length;
+            chains.push(chain);
+        }
+        chains
+    }
+}
+
+/// A feature setting and the chain flags it turns on and off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeatureEntry {
+    /// The feature type, such as 1 for ligatures.
+    pub feature_type: u16,
+    /// The feature selector, such as 2 for common ligatures on.
+    pub setting: u16,
+    pub enable_flags: u32,
+    /// The flags to keep, which are ANDed with the chain's flags before `enable_flags` are
+    /// set.
+    pub disable_flags: u32,
+}
+
+/// A chain of `morx` subtables, with the flags that say which of them run by default.
+#[derive(Clone, Copy, Debug)]
+pub struct Chain<'a> {
+    pub default_flags: u32,
+    features: &'a [u8],
+    subtables: &'a [u8],
+    subtable_count: u32,
+    length: usize,
+}
+
+impl<'a> Chain<'a> {
+    fn parse(data: &'a [u8]) -> Option<Chain<'a>> {
+        let mut reader = Reader::new(data);
+        let default_flags = reader.read_u32()?;
+        let length = reader.read_u32()? as usize;
+        let feature_count = reader.read_u32()? as usize;
+        let subtable_count = reader.read_u32()?;
+        let features = reader.read_bytes(feature_count.checked_mul(FEATURE_ENTRY_SIZE)?)?;
+        let subtables_offset = CHAIN_HEADER_SIZE + features.len();
+        if length < subtables_offset {
+            return None;
+        }
+        Some(Chain {
+            default_flags,
+            features,
+            subtables: data.get(subtables_offset..length)?,
+            subtable_count,
+            length,
+        })
+    }
+
+    /// Returns the feature settings that change the chain's flags.
+    pub fn features(&self) -> Vec<FeatureEntry> {
+        self.features.chunks(FEATURE_ENTRY_SIZE).filter_map(|entry| {
+            let mut reader = Reader::new(entry);
+            Some(FeatureEntry {
+                feature_type: reader.read_u16()?,
+                setting: reader.read_u16()?,
+                enable_flags: reader.read_u32()?,
+                disable_flags: reader.read_u32()?,
+            })
+        }).collect()
+    }
+
+    /// Returns the chain's subtables, in the order they apply. Subtables of unknown types are
+    /// left out, as are any after a malformed one.
+    pub fn subtables(&self) -> Vec<MorphSubtable<'a>> {
+        let mut subtables = vec![];
+        let mut offset = 0;
+        for _ in 0..self.subtable_count {
+            let mut reader = Reader::at(self.subtables, offset);
+            let (length, coverage, feature_flags) = match (reader.read_u32(),
+                                                           reader.read_u32(),
+                                                           reader.read_u32()) {
+                (Some(length), Some(coverage), Some(flags)) => (length as usize, coverage, flags),
+                _ => break,
+            };
+            let body = match self.subtables.get(offset + SUBTABLE_HEADER_SIZE..offset + length) {
+                Some(body) if length >= SUBTABLE_HEADER_SIZE => body,
+                _ => break,
+            };
+            offset += length;
+            if let Some(kind) = SubtableKind::parse(body, coverage & SUBTABLE_TYPE) {
+                subtables.push(MorphSubtable {
+                    coverage,
+                    feature_flags,
+                    kind,
+                });
+            }
+        }
+        subtables
+    }
+}
+
+/// A subtable of a `morx` chain.
+#[derive(Clone, Copy, Debug)]
+pub struct MorphSubtable<'a> {
+    coverage: u32,
+    /// The chain flags that turn the subtable on, any of which will do.
+    pub feature_flags: u32,
+    pub kind: SubtableKind<'a>,
+}
+
+impl<'a> MorphSubtable<'a> {
+    /// Whether the subtable only applies to vertical text.
+    pub fn is_vertical(&self) -> bool {
+        self.coverage & VERTICAL != 0
+    }
+
+    /// Whether the subtable applies to both horizontal and vertical text.
+    pub fn applies_to_all_directions(&self) -> bool {
+        self.coverage & ALL_DIRECTIONS != 0
+    }
+
+    /// Whether the subtable processes glyphs from last to first.
+    pub fn is_descending(&self) -> bool {
+        self.coverage & DESCENDING != 0
+    }
+
+    /// Whether `is_descending` refers to the glyphs' logical order, rather than the order
+    /// they are laid out in.
+    pub fn is_logical(&self) -> bool {
+        self.coverage & LOGICAL != 0
+    }
+}
+
+/// What a `morx` subtable does.
+#[derive(Clone, Copy, Debug)]
+pub enum SubtableKind<'a> {
+    /// Reorders runs of glyphs. The state table's entries have no data.
+    Rearrangement(StateTable<'a>),
+    Contextual(ContextualSubtable<'a>),
+    Ligature(LigatureSubtable<'a>),
+    /// Replaces glyphs one for one, regardless of context.
+    Noncontextual(LookupTable<'a>),
+    Insertion(InsertionSubtable<'a>),
+}
+
+impl<'a> SubtableKind<'a> {
+    fn parse(data: &'a [u8], subtable_type: u32) -> Option<SubtableKind<'a>> {
+        Some(match subtable_type {
+            0 => SubtableKind::Rearrangement(StateTable::parse(data, 0)?),
+            1 => {
+                let state_table = StateTable::parse(data, 4)?;
+                let offset = parser::read_u32(state_table.extra_header(), 0)? as usize;
+                SubtableKind::Contextual(ContextualSubtable {
+                    state_table,
+                    substitutions: data.get(offset..)?,
+                })
+            }
+            2 => {
+                let state_table = StateTable::parse(data, 2)?;
+                let mut reader = Reader::new(state_table.extra_header());
+                let actions = data.get(reader.read_u32()? as usize..)?;
+                let components = data.get(reader.read_u32()? as usize..)?;
+                let ligatures = data.get(reader.read_u32()? as usize..)?;
+                SubtableKind::Ligature(LigatureSubtable {
+                    state_table,
+                    actions,
+                    components,
+                    ligatures,
+                })
+            }
+            4 => SubtableKind::Noncontextual(LookupTable::parse(data)?),
+            5 => {
+                let state_table = StateTable::parse(data, 4)?;
+                let offset = parser::read_u32(state_table.extra_header(), 0)? as usize;
+                SubtableKind::Insertion(InsertionSubtable {
+                    state_table,
+                    glyphs: data.get(offset..)?,
+                })
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// A subtable that replaces the current glyph, or a marked glyph before it, depending on the
+/// context. Its state table's entries hold the indices of the substitutions to apply to each.
+#[derive(Clone, Copy, Debug)]
+pub struct ContextualSubtable<'a> {
+    pub state_table: StateTable<'a>,
+    /// The offsets of the substitution lookup tables, from the start of the offset array.
+    substitutions: &'a [u8],
+}
+
+impl<'a> ContextualSubtable<'a> {
+    /// Returns what the `index`th substitution replaces `glyph` with, if it replaces it.
+    pub fn substitute(&self, index: u16, glyph: u16) -> Option<u16> {
+        let offset = parser::read_u32(self.substitutions, index as usize * 4)? as usize;
+        LookupTable::parse(self.substitutions.get(offset..)?)?.value(glyph)
+    }
+}
+
+/// A subtable that replaces a run of component glyphs with a ligature. Its state table's
+/// entries hold the index of the first ligature action to perform.
+#[derive(Clone, Copy, Debug)]
+pub struct LigatureSubtable<'a> {
+    pub state_table: StateTable<'a>,
+    actions: &'a [u8],
+    components: &'a [u8],
+    ligatures: &'a [u8],
+}
+
+impl<'a> LigatureSubtable<'a> {
+    /// Returns the `index`th ligature action.
+    pub fn action(&self, index: usize) -> Option<u32> {
+        parser::read_u32(self.actions, index * 4)
+    }
+
+    /// Returns the `index`th entry of the component table, which ligature actions sum to find
+    /// the ligature.
+    pub fn component(&self, index: usize) -> Option<u16> {
+        parser::read_u16(self.components, index * 2)
+    }
+
+    /// Returns the `index`th ligature glyph.
+    pub fn ligature(&self, index: usize) -> Option<u16> {
+        parser::read_u16(self.ligatures, index * 2)
+    }
+}
+
+/// A subtable that inserts glyphs at the current glyph, or a marked glyph before it. Its state
+/// table's entries hold the indices of the glyphs to insert at each.
+#[derive(Clone, Copy, Debug)]
+pub struct InsertionSubtable<'a> {
+    pub state_table: StateTable<'a>,
+    glyphs: &'a [u8],
+}
+
+impl<'a> InsertionSubtable<'a> {
+    /// Returns the `count` glyphs to insert starting at `index` in the insertion glyph table.
+    pub fn glyphs(&self, index: u16, count: usize) -> Option<Vec<u16>> {
+        let glyphs = Reader::at(self.glyphs, index as usize * 2).read_bytes(count * 2)?;
+        Some(glyphs.chunks(2).map(|glyph| ((glyph[0] as u16) << 8) | glyph[1] as u16).collect())
+    }
+}
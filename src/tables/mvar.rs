@@ -0,0 +1,95 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `MVAR` table, which varies a variable font's global metrics, such as its ascender and
+//! x-height.
+
+use error::{Error, Result};
+use parser::{self, Reader};
+use tables::variation_store::ItemVariationStore;
+use tag::{Tag, kCTFontTableMVAR};
+
+const HEADER_SIZE: usize = 12;
+const MIN_VALUE_RECORD_SIZE: u16 = 8;
+
+/// `OS/2.sTypoAscender`, and the `hhea` ascender.
+pub const HORIZONTAL_ASCENDER: Tag = Tag::new(b"hasc");
+/// `OS/2.sTypoDescender`, and the `hhea` descender.
+pub const HORIZONTAL_DESCENDER: Tag = Tag::new(b"hdsc");
+/// `OS/2.sTypoLineGap`, and the `hhea` line gap.
+pub const HORIZONTAL_LINE_GAP: Tag = Tag::new(b"hlgp");
+/// `OS/2.usWinAscent`.
+pub const HORIZONTAL_CLIPPING_ASCENT: Tag = Tag::new(b"hcla");
+/// `OS/2.usWinDescent`.
+pub const HORIZONTAL_CLIPPING_DESCENT: Tag = Tag::new(b"hcld");
+/// `vhea.ascent`.
+pub const VERTICAL_ASCENDER: Tag = Tag::new(b"vasc");
+/// `vhea.descent`.
+pub const VERTICAL_DESCENDER: Tag = Tag::new(b"vdsc");
+/// `vhea.lineGap`.
+pub const VERTICAL_LINE_GAP: Tag = Tag::new(b"vlgp");
+/// `OS/2.sxHeight`.
+pub const X_HEIGHT: Tag = Tag::new(b"xhgt");
+/// `OS/2.sCapHeight`.
+pub const CAP_HEIGHT: Tag = Tag::new(b"cpht");
+/// `post.underlinePosition`.
+pub const UNDERLINE_OFFSET: Tag = Tag::new(b"undo");
+/// `post.underlineThickness`.
+pub const UNDERLINE_SIZE: Tag = Tag::new(b"unds");
+/// `OS/2.yStrikeoutPosition`.
+pub const STRIKEOUT_OFFSET: Tag = Tag::new(b"stro");
+/// `OS/2.yStrikeoutSize`.
+pub const STRIKEOUT_SIZE: Tag = Tag::new(b"strs");
+
+/// An `MVAR` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Mvar<'a> {
+    records: &'a [u8],
+    record_size: u16,
+    record_count: u16,
+    store: ItemVariationStore<'a>,
+}
+
+impl<'a> Mvar<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Mvar<'a>> {
+        Mvar::parse_table(data).ok_or(Error::MalformedTable(kCTFontTableMVAR))
+    }
+
+    fn parse_table(data: &'a [u8]) -> Option<Mvar<'a>> {
+        let mut reader = Reader::new(data);
+        let major_version = reader.read_u16()?;
+        reader.skip(4)?; // minor version, reserved
+        let record_size = reader.read_u16()?;
+        let record_count = reader.read_u16()?;
+        let store_offset = reader.read_u16()? as usize;
+        if major_version != 1 || record_size < MIN_VALUE_RECORD_SIZE {
+            return None;
+        }
+        Some(Mvar {
+            records: Reader::at(data, HEADER_SIZE).read_bytes(record_count as usize *
+                                                              record_size as usize)?,
+            record_size,
+            record_count,
+            store: ItemVariationStore::parse(data.get(store_offset..)?)?,
+        })
+    }
+
+    /// Returns the change to a metric, in font units, at `coordinates`, or `None` if the
+    /// table doesn't vary it.
+    pub fn delta(&self, tag: Tag, coordinates: &[f64]) -> Option<f64> {
+        let index = parser::binary_search(self.record_count as usize, |index| {
+            let record_tag = parser::read_u32(self.records, index * self.record_size as usize)?;
+            Some(record_tag.cmp(&tag.0))
+        })?;
+        let mut reader = Reader::at(self.records, index * self.record_size as usize + 4);
+        let outer = reader.read_u16()?;
+        let inner = reader.read_u16()?;
+        self.store.delta(outer, inner, coordinates)
+    }
+}
@@ -0,0 +1,369 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `name` table, which holds the font's human-readable strings in any number of
+//! languages.
+
+use encoding;
+use error::{Error, Result};
+use language;
+use parser::{self, Reader};
+use tables::cmap::{PLATFORM_MACINTOSH, PLATFORM_UNICODE, PLATFORM_WINDOWS};
+use tag::kCTFontTableName;
+
+const PLATFORM_ISO: u16 = 2;
+const NAME_RECORD_SIZE: usize = 12;
+const LANGUAGE_TAG_RECORD_SIZE: usize = 4;
+const WINDOWS_ENGLISH_US: u16 = 0x0409;
+
+/// Language IDs from this value up index the format 1 language tag records.
+const FIRST_LANGUAGE_TAG_ID: u16 = 0x8000;
+
+/// Identifies what a `name` record describes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum NameId {
+    Copyright,
+    Family,
+    Subfamily,
+    UniqueId,
+    FullName,
+    Version,
+    PostScriptName,
+    Trademark,
+    Manufacturer,
+    Designer,
+    Description,
+    VendorUrl,
+    DesignerUrl,
+    License,
+    LicenseUrl,
+    /// The family name for fonts with more than the four R/I/B/BI styles per family.
+    TypographicFamily,
+    /// The style name within the `TypographicFamily`.
+    TypographicSubfamily,
+    /// The full name Macintosh menus show, if it differs from `FullName`.
+    CompatibleFullName,
+    SampleText,
+    PostScriptCidName,
+    WwsFamily,
+    WwsSubfamily,
+    LightBackgroundPalette,
+    DarkBackgroundPalette,
+    VariationsPostScriptNamePrefix,
+    /// A reserved ID, or one of the font-specific IDs from 256 up that other tables such as
+    /// `fvar` refer to.
+    Other(u16),
+}
+
+impl From<u16> for NameId {
+    fn from(id: u16) -> NameId {
+        match id {
+            0 => NameId::Copyright,
+            1 => NameId::Family,
+            2 => NameId::Subfamily,
+            3 => NameId::UniqueId,
+            4 => NameId::FullName,
+            5 => NameId::Version,
+            6 => NameId::PostScriptName,
+            7 => NameId::Trademark,
+            8 => NameId::Manufacturer,
+            9 => NameId::Designer,
+            10 => NameId::Description,
+            11 => NameId::VendorUrl,
+            12 => NameId::DesignerUrl,
+            13 => NameId::License,
+            14 => NameId::LicenseUrl,
+            16 => NameId::TypographicFamily,
+            17 => NameId::TypographicSubfamily,
+            18 => NameId::CompatibleFullName,
+            19 => NameId::SampleText,
+            20 => NameId::PostScriptCidName,
+            21 => NameId::WwsFamily,
+            22 => NameId::WwsSubfamily,
+            23 => NameId::LightBackgroundPalette,
+            24 => NameId::DarkBackgroundPalette,
+            25 => NameId::VariationsPostScriptNamePrefix,
+            other => NameId::Other(other),
+        }
+    }
+}
+
+impl From<NameId> for u16 {
+    fn from(id: NameId) -> u16 {
+        match id {
+            NameId::Copyright => 0,
+            NameId::Family => 1,
+            NameId::Subfamily => 2,
+            NameId::UniqueId => 3,
+            NameId::FullName => 4,
+            NameId::Version => 5,
+            NameId::PostScriptName => 6,
+            NameId::Trademark => 7,
+            NameId::Manufacturer => 8,
+            NameId::Designer => 9,
+            NameId::Description => 10,
+            NameId::VendorUrl => 11,
+            NameId::DesignerUrl => 12,
+            NameId::License => 13,
+            NameId::LicenseUrl => 14,
+            NameId::TypographicFamily => 16,
+            NameId::TypographicSubfamily => 17,
+            NameId::CompatibleFullName => 18,
+            NameId::SampleText => 19,
+            NameId::PostScriptCidName => 20,
+            NameId::WwsFamily => 21,
+            NameId::WwsSubfamily => 22,
+            NameId::LightBackgroundPalette => 23,
+            NameId::DarkBackgroundPalette => 24,
+            NameId::VariationsPostScriptNamePrefix => 25,
+            NameId::Other(other) => other,
+        }
+    }
+}
+
+/// A decoded name string and the language it's in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LocalizedName {
+    pub name_id: NameId,
+    /// The BCP 47 tag of the name's language, such as `en-US`, or `None` if the record
+    /// doesn't say.
+    pub language: Option<String>,
+    pub value: String,
+}
+
+/// A `name` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Name<'a> {
+    data: &'a [u8],
+    record_count: u16,
+    storage_offset: usize,
+    /// The format 1 language tag records, or empty for format 0.
+    language_tags: &'a [u8],
+}
+
+impl<'a> Name<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Name<'a>> {
+        Name::parse_header(data).ok_or(Error::MalformedTable(kCTFontTableName))
+    }
+
+    fn parse_header(data: &'a [u8]) -> Option<Name<'a>> {
+        let mut reader = Reader::new(data);
+        let format = reader.read_u16()?;
+        let record_count = reader.read_u16()?;
+        let storage_offset = reader.read_u16()? as usize;
+        reader.skip(record_count as usize * NAME_RECORD_SIZE)?;
+        let language_tags = match format {
+            0 => &[][..],
+            1 => {
+                let count = reader.read_u16()? as usize;
+                reader.read_bytes(count * LANGUAGE_TAG_RECORD_SIZE)?
+            }
+            _ => return None,
+        };
+        Some(Name {
+            data,
+            record_count,
+            storage_offset,
+            language_tags,
+        })
+    }
+
+    /// Returns every name record, in table order.
+    pub fn records(&self) -> NameRecords<'a> {
+        NameRecords {
+            name: *self,
+            index: 0,
+        }
+    }
+
+    /// Returns the string for `name_id` in `language`, a BCP 47 tag such as `ja` or `zh-Hant`.
+    ///
+    /// With no language, prefers US English, as Core Text's `CTFontCopyName` does. Otherwise
+    /// an exact match wins, then a more specific tag (`en` finds `en-GB`), then any tag with
+    /// the same primary language (`en-AU` finds `en-US`). Returns `None` if no record
+    /// matches or none of the matches can be decoded.
+    pub fn get(&self, name_id: NameId, language: Option<&str>) -> Option<String> {
+        self.records()
+            .filter(|record| record.name_id() == name_id)
+            .filter_map(|record| {
+                let language_rank = match language {
+                    None => record.default_language_rank()?,
+                    Some(language) => language_match_rank(language, &record.language()?)?,
+                };
+                Some(((language_rank, record.platform_rank()), record))
+            })
+            .filter_map(|(rank, record)| record.decode().map(|value| (rank, value)))
+            .min_by_key(|&(rank, _)| rank)
+            .map(|(_, value)| value)
+    }
+
+    /// Decodes every name the table holds, one per name ID and language. When several records
+    /// share both, the Windows or Unicode one wins.
+    pub fn localized_names(&self) -> Vec<LocalizedName> {
+        let mut records: Vec<NameRecord<'a>> = self.records().collect();
+        records.sort_by_key(|record| record.platform_rank());
+
+        let mut names: Vec<LocalizedName> = vec![];
+        for record in records {
+            let language = record.language();
+            let duplicate = names.iter().any(|name| {
+                name.name_id == record.name_id() && name.language == language
+            });
+            if duplicate {
+                continue;
+            }
+            if let Some(value) = record.decode() {
+                names.push(LocalizedName {
+                    name_id: record.name_id(),
+                    language,
+                    value,
+                });
+            }
+        }
+        names.sort_by_key(|name| name.name_id);
+        names
+    }
+
+    fn language_tag(&self, index: usize) -> Option<String> {
+        let offset = index.checked_mul(LANGUAGE_TAG_RECORD_SIZE)?;
+        let length = parser::read_u16(self.language_tags, offset)? as usize;
+        let start = self.storage_offset + parser::read_u16(self.language_tags, offset + 2)? as usize;
+        self.data.get(start..start + length).map(encoding::decode_utf16_be)
+    }
+}
+
+/// Ranks how well a record's language tag serves a requested one; lower is better.
+fn language_match_rank(requested: &str, actual: &str) -> Option<u8> {
+    let requested = requested.to_ascii_lowercase().replace('_', "-");
+    let actual = actual.to_ascii_lowercase();
+    let primary = |tag: &str| tag.split('-').next().unwrap_or("").to_owned();
+    if actual == requested {
+        Some(0)
+    } else if actual.starts_with(&requested) && actual[requested.len()..].starts_with('-') {
+        Some(1)
+    } else if primary(&actual) == primary(&requested) {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+/// Iterates over the records of a `name` table.
+#[derive(Clone, Debug)]
+pub struct NameRecords<'a> {
+    name: Name<'a>,
+    index: u16,
+}
+
+impl<'a> Iterator for NameRecords<'a> {
+    type Item = NameRecord<'a>;
+
+    fn next(&mut self) -> Option<NameRecord<'a>> {
+        while self.index < self.name.record_count {
+            let offset = 6 + self.index as usize * NAME_RECORD_SIZE;
+            self.index += 1;
+
+            let mut reader = Reader::at(self.name.data, offset);
+            let platform_id = reader.read_u16()?;
+            let encoding_id = reader.read_u16()?;
+            let language_id = reader.read_u16()?;
+            let name_id = reader.read_u16()?;
+            let length = reader.read_u16()? as usize;
+            let start = self.name.storage_offset + reader.read_u16()? as usize;
+            // Skip records whose strings lie outside the table rather than ending early.
+            if let Some(bytes) = self.name.data.get(start..start + length) {
+                return Some(NameRecord {
+                    platform_id,
+                    encoding_id,
+                    language_id,
+                    name_id,
+                    bytes,
+                    name: self.name,
+                });
+            }
+        }
+        None
+    }
+}
+
+/// One string in a `name` table, still in its original encoding.
+#[derive(Clone, Copy, Debug)]
+pub struct NameRecord<'a> {
+    pub platform_id: u16,
+    pub encoding_id: u16,
+    pub language_id: u16,
+    name_id: u16,
+    bytes: &'a [u8],
+    name: Name<'a>,
+}
+
+impl<'a> NameRecord<'a> {
+    pub fn name_id(&self) -> NameId {
+        NameId::from(self.name_id)
+    }
+
+    /// The string's raw bytes.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// The BCP 47 tag of the string's language, or `None` if it's unknown or unspecified.
+    pub fn language(&self) -> Option<String> {
+        if self.language_id >= FIRST_LANGUAGE_TAG_ID {
+            return self.name.language_tag((self.language_id - FIRST_LANGUAGE_TAG_ID) as usize);
+        }
+        let tag = match self.platform_id {
+            PLATFORM_MACINTOSH => language::mac_language_tag(self.language_id),
+            PLATFORM_WINDOWS => language::windows_language_tag(self.language_id),
+            _ => None,
+        };
+        tag.map(|tag| tag.to_owned())
+    }
+
+    /// Decodes the string, or returns `None` if it's in an encoding this crate can't read,
+    /// such as the Windows and Macintosh CJK encodings.
+    pub fn decode(&self) -> Option<String> {
+        match (self.platform_id, self.encoding_id) {
+            (PLATFORM_UNICODE, _) |
+            (PLATFORM_ISO, 1) |
+            (PLATFORM_WINDOWS, 0) |
+            (PLATFORM_WINDOWS, 1) |
+            (PLATFORM_WINDOWS, 10) => Some(encoding::decode_utf16_be(self.bytes)),
+            (PLATFORM_ISO, 0) if self.bytes.is_ascii() => Some(encoding::decode_latin1(self.bytes)),
+            (PLATFORM_ISO, 2) => Some(encoding::decode_latin1(self.bytes)),
+            (PLATFORM_MACINTOSH, script) => {
+                encoding::decode_mac(script, self.language_id, self.bytes)
+            }
+            _ => None,
+        }
+    }
+
+    /// Ranks the record's encoding; Unicode encodings beat legacy ones.
+    fn platform_rank(&self) -> u8 {
+        match self.platform_id {
+            PLATFORM_WINDOWS => 0,
+            PLATFORM_UNICODE => 1,
+            PLATFORM_MACINTOSH => 2,
+            _ => 3,
+        }
+    }
+
+    /// Ranks the record for a lookup with no language: US English first, then Unicode
+    /// platform names, which have no language, then other Windows languages, and finally
+    /// English Macintosh names.
+    fn default_language_rank(&self) -> Option<u8> {
+        match (self.platform_id, self.language_id) {
+            (PLATFORM_WINDOWS, WINDOWS_ENGLISH_US) => Some(0),
+            (PLATFORM_UNICODE, _) => Some(1),
+            (PLATFORM_WINDOWS, _) => Some(2),
+            (PLATFORM_MACINTOSH, 0) => Some(3),
+            _ => None,
+        }
+    }
+}
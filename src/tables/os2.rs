@@ -0,0 +1,285 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `OS/2` table, which holds the font's classification and its typographic and Windows
+//! line metrics.
+//!
+//! Core Text's `ascent` and `descent` come from `hhea`. Layout engines following the CSS rules
+//! need to choose between those, the typographic metrics here (when `use_typo_metrics` is set)
+//! and the Windows metrics themselves.
+
+use error::{Error, Result};
+use parser;
+use tag::{Tag, kCTFontTableOS2};
+
+/// Every version has at least the fields up to `usWinDescent`.
+const VERSION_0_SIZE: usize = 78;
+const VERSION_1_SIZE: usize = 86;
+const VERSION_2_SIZE: usize = 96;
+const VERSION_5_SIZE: usize = 100;
+
+pub const FS_SELECTION_ITALIC: u16 = 1 << 0;
+pub const FS_SELECTION_UNDERSCORE: u16 = 1 << 1;
+pub const FS_SELECTION_NEGATIVE: u16 = 1 << 2;
+pub const FS_SELECTION_OUTLINED: u16 = 1 << 3;
+pub const FS_SELECTION_STRIKEOUT: u16 = 1 << 4;
+pub const FS_SELECTION_BOLD: u16 = 1 << 5;
+pub const FS_SELECTION_REGULAR: u16 = 1 << 6;
+pub const FS_SELECTION_USE_TYPO_METRICS: u16 = 1 << 7;
+pub const FS_SELECTION_WWS: u16 = 1 << 8;
+pub const FS_SELECTION_OBLIQUE: u16 = 1 << 9;
+
+const FS_TYPE_RESTRICTED: u16 = 0x0002;
+const FS_TYPE_PREVIEW_AND_PRINT: u16 = 0x0004;
+const FS_TYPE_EDITABLE: u16 = 0x0008;
+const FS_TYPE_NO_SUBSETTING: u16 = 0x0100;
+const FS_TYPE_BITMAP_ONLY: u16 = 0x0200;
+
+/// How a document may embed the font, from the `fsType` field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmbeddingPermissions {
+    /// The font may be embedded and permanently installed.
+    Installable,
+    /// The font must not be embedded without the owner's permission.
+    Restricted,
+    /// The font may be embedded for viewing and printing only.
+    PreviewAndPrint,
+    /// The font may be embedded in documents that are then edited.
+    Editable,
+}
+
+/// The size and offset of synthesized subscript or superscript glyphs, in font units.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScriptMetrics {
+    pub x_size: i16,
+    pub y_size: i16,
+    pub x_offset: i16,
+    pub y_offset: i16,
+}
+
+/// An `OS/2` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Os2<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Os2<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Os2<'a>> {
+        if data.len() < VERSION_0_SIZE {
+            return Err(Error::MalformedTable(kCTFontTableOS2));
+        }
+        Ok(Os2 {
+            data,
+        })
+    }
+
+    fn u16_at(&self, offset: usize) -> u16 {
+        parser::read_u16(self.data, offset).unwrap_or(0)
+    }
+
+    fn i16_at(&self, offset: usize) -> i16 {
+        parser::read_i16(self.data, offset).unwrap_or(0)
+    }
+
+    fn u32_at(&self, offset: usize) -> u32 {
+        parser::read_u32(self.data, offset).unwrap_or(0)
+    }
+
+    /// Reads a field added in a later version, checking the table is long enough as well as
+    /// new enough, since some fonts claim a version they don't fill in.
+    fn versioned<T, F>(&self, version: u16, size: usize, read: F) -> Option<T>
+        where F: FnOnce() -> T
+    {
+        if self.version() >= version && self.data.len() >= size {
+            Some(read())
+        } else {
+            None
+        }
+    }
+
+    pub fn version(&self) -> u16 {
+        self.u16_at(0)
+    }
+
+    pub fn average_char_width(&self) -> i16 {
+        self.i16_at(2)
+    }
+
+    /// The visual weight, from 100 (thin) to 900 (black), with 400 regular and 700 bold.
+    pub fn weight_class(&self) -> u16 {
+        self.u16_at(4)
+    }
+
+    /// The relative width, from 1 (ultra-condensed) to 9 (ultra-expanded), with 5 normal.
+    pub fn width_class(&self) -> u16 {
+        self.u16_at(6)
+    }
+
+    /// The raw `fsType` embedding licensing flags.
+    pub fn fs_type(&self) -> u16 {
+        self.u16_at(8)
+    }
+
+    /// The embedding permission level. Old fonts may set several levels, in which case the
+    /// least restrictive applies.
+    pub fn embedding_permissions(&self) -> EmbeddingPermissions {
+        let fs_type = self.fs_type();
+        if fs_type & FS_TYPE_EDITABLE != 0 {
+            EmbeddingPermissions::Editable
+        } else if fs_type & FS_TYPE_PREVIEW_AND_PRINT != 0 {
+            EmbeddingPermissions::PreviewAndPrint
+        } else if fs_type & FS_TYPE_RESTRICTED != 0 {
+            EmbeddingPermissions::Restricted
+        } else {
+            EmbeddingPermissions::Installable
+        }
+    }
+
+    /// Returns false if the font may only be embedded whole.
+    pub fn allows_subsetting(&self) -> bool {
+        self.fs_type() & FS_TYPE_NO_SUBSETTING == 0
+    }
+
+    /// Returns false if only the font's bitmaps may be embedded.
+    pub fn allows_outline_embedding(&self) -> bool {
+        self.fs_type() & FS_TYPE_BITMAP_ONLY == 0
+    }
+
+    pub fn subscript(&self) -> ScriptMetrics {
+        ScriptMetrics {
+            x_size: self.i16_at(10),
+            y_size: self.i16_at(12),
+            x_offset: self.i16_at(14),
+            y_offset: self.i16_at(16),
+        }
+    }
+
+    pub fn superscript(&self) -> ScriptMetrics {
+        ScriptMetrics {
+            x_size: self.i16_at(18),
+            y_size: self.i16_at(20),
+            x_offset: self.i16_at(22),
+            y_offset: self.i16_at(24),
+        }
+    }
+
+    pub fn strikeout_size(&self) -> i16 {
+        self.i16_at(26)
+    }
+
+    /// The top of the strikeout stroke, above the baseline.
+    pub fn strikeout_position(&self) -> i16 {
+        self.i16_at(28)
+    }
+
+    /// The IBM font family class and subclass.
+    pub fn family_class(&self) -> i16 {
+        self.i16_at(30)
+    }
+
+    /// The ten PANOSE classification digits.
+    pub fn panose(&self) -> [u8; 10] {
+        let mut panose = [0; 10];
+        panose.copy_from_slice(&self.data[32..42]);
+        panose
+    }
+
+    /// The 128 Unicode block bits, with bit 0 the lowest bit of the first element.
+    pub fn unicode_ranges(&self) -> [u32; 4] {
+        [self.u32_at(42), self.u32_at(46), self.u32_at(50), self.u32_at(54)]
+    }
+
+    /// Returns true if the font claims to cover the Unicode block with this bit number.
+    pub fn has_unicode_range(&self, bit: u32) -> bool {
+        bit < 128 && self.unicode_ranges()[bit as usize / 32] & (1 << (bit % 32)) != 0
+    }
+
+    /// The four-character ID of the font's vendor.
+    pub fn vendor_id(&self) -> Tag {
+        Tag(self.u32_at(58))
+    }
+
+    /// The `FS_SELECTION_*` bits describing the face.
+    pub fn fs_selection(&self) -> u16 {
+        self.u16_at(62)
+    }
+
+    /// Returns true if the typographic metrics should be used for line layout in preference
+    /// to the Windows or `hhea` metrics.
+    pub fn use_typo_metrics(&self) -> bool {
+        self.fs_selection() & FS_SELECTION_USE_TYPO_METRICS != 0
+    }
+
+    /// The lowest BMP code point the font maps.
+    pub fn first_char_index(&self) -> u16 {
+        self.u16_at(64)
+    }
+
+    /// The highest BMP code point the font maps, or 0xFFFF if it maps any beyond it.
+    pub fn last_char_index(&self) -> u16 {
+        self.u16_at(66)
+    }
+
+    pub fn typo_ascender(&self) -> i16 {
+        self.i16_at(68)
+    }
+
+    /// The typographic descender, negative below the baseline.
+    pub fn typo_descender(&self) -> i16 {
+        self.i16_at(70)
+    }
+
+    pub fn typo_line_gap(&self) -> i16 {
+        self.i16_at(72)
+    }
+
+    /// The top of the Windows clipping region, above the baseline.
+    pub fn win_ascent(&self) -> u16 {
+        self.u16_at(74)
+    }
+
+    /// The bottom of the Windows clipping region, positive below the baseline.
+    pub fn win_descent(&self) -> u16 {
+        self.u16_at(76)
+    }
+
+    /// The 64 Windows code page bits, with bit 0 the lowest bit of the first element.
+    pub fn code_page_ranges(&self) -> Option<[u32; 2]> {
+        self.versioned(1, VERSION_1_SIZE, || [self.u32_at(78), self.u32_at(82)])
+    }
+
+    pub fn x_height(&self) -> Option<i16> {
+        self.versioned(2, VERSION_2_SIZE, || self.i16_at(86))
+    }
+
+    pub fn cap_height(&self) -> Option<i16> {
+        self.versioned(2, VERSION_2_SIZE, || self.i16_at(88))
+    }
+
+    pub fn default_char(&self) -> Option<u16> {
+        self.versioned(2, VERSION_2_SIZE, || self.u16_at(90))
+    }
+
+    pub fn break_char(&self) -> Option<u16> {
+        self.versioned(2, VERSION_2_SIZE, || self.u16_at(92))
+    }
+
+    /// The longest context any of the font's features looks at, in glyphs.
+    pub fn max_context(&self) -> Option<u16> {
+        self.versioned(2, VERSION_2_SIZE, || self.u16_at(94))
+    }
+
+    /// The range of sizes, in points, the font was designed for.
+    pub fn optical_point_size_range(&self) -> Option<(f64, f64)> {
+        // Stored in twentieths of a point.
+        self.versioned(5, VERSION_5_SIZE, || {
+            (self.u16_at(96) as f64 / 20.0, self.u16_at(98) as f64 / 20.0)
+        })
+    }
+}
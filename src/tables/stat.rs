@@ -0,0 +1,297 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `STAT` table, which names positions on a font family's design axes so that style names
+//! such as "Bold Condensed Italic" can be composed for any instance.
+
+use error::{Error, Result};
+use parser::{self, Reader};
+use tag::{Tag, kCTFontTableSTAT};
+
+const MIN_DESIGN_AXIS_SIZE: u16 = 8;
+const OLDER_SIBLING_FONT_ATTRIBUTE: u16 = 0x0001;
+const ELIDABLE_AXIS_VALUE_NAME: u16 = 0x0002;
+
+/// Values closer than this are the same 16.16 fixed-point number.
+const FIXED_PRECISION: f64 = 1.0 / 65536.0;
+
+/// The subfamily name, "Regular" in most fonts, which version 1.0 tables fall back to when
+/// every axis value name is elided.
+const DEFAULT_ELIDED_FALLBACK_NAME_ID: u16 = 2;
+
+/// An axis that the family's fonts are designed along, whether or not it varies in this font.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DesignAxis {
+    pub tag: Tag,
+    /// The `name` table entry holding the axis's name.
+    pub name_id: u16,
+    /// Where the axis's value names go in a composed style name, lowest first.
+    pub ordering: u16,
+}
+
+/// A name for a value, or a range of values, on one or more design axes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AxisValue {
+    /// The design axis index and value of each axis the name applies to. Only combinations of
+    /// values, such as "Caption" for a size and weight, have more than one.
+    pub values: Vec<(u16, f64)>,
+    /// The range of values the name covers on its axis, with `values` holding the nominal one.
+    pub range: Option<(f64, f64)>,
+    /// The value that style-linking pairs this one with, such as Bold for Regular.
+    pub linked_value: Option<f64>,
+    pub flags: u16,
+    /// The `name` table entry holding the name.
+    pub name_id: u16,
+}
+
+impl AxisValue {
+    /// Whether the name is only there for compatibility with older fonts in the family, and is
+    /// described as well by another font.
+    pub fn is_older_sibling(&self) -> bool {
+        self.flags & OLDER_SIBLING_FONT_ATTRIBUTE != 0
+    }
+
+    /// Whether the name is left out of composed style names, as "Regular" usually is.
+    pub fn is_elidable(&self) -> bool {
+        self.flags & ELIDABLE_AXIS_VALUE_NAME != 0
+    }
+
+    /// Whether the name describes `position`, which holds a value for each design axis.
+    pub fn matches(&self, position: &[f64]) -> bool {
+        match (self.range, &self.values[..]) {
+            (Some((min, max)), &[(axis, _)]) => {
+                position.get(axis as usize).is_some_and(|&value| min <= value && value <= max)
+            }
+            _ => {
+                self.values.iter().all(|&(axis, value)| {
+                    position.get(axis as usize).is_some_and(|&position| {
+                        (position - value).abs() < FIXED_PRECISION
+                    })
+                })
+            }
+        }
+    }
+}
+
+/// A `STAT` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Stat<'a> {
+    design_axes: &'a [u8],
+    design_axis_size: u16,
+    design_axis_count: u16,
+    /// The offsets of the axis value tables, from the start of the offset array.
+    axis_values: &'a [u8],
+    axis_value_count: u16,
+    elided_fallback_name_id: u16,
+}
+
+impl<'a> Stat<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Stat<'a>> {
+        Stat::parse_table(data).ok_or(Error::MalformedTable(kCTFontTableSTAT))
+    }
+
+    fn parse_table(data: &'a [u8]) -> Option<Stat<'a>> {
+        let mut reader = Reader::new(data);
+        let major_version = reader.read_u16()?;
+        let minor_version = reader.read_u16()?;
+        let design_axis_size = reader.read_u16()?;
+        let design_axis_count = reader.read_u16()?;
+        let design_axes_offset = reader.read_u32()? as usize;
+        let axis_value_count = reader.read_u16()?;
+        let axis_value_offsets = reader.read_u32()? as usize;
+        let elided_fallback_name_id = if minor_version >= 1 {
+            reader.read_u16()?
+        } else {
+            DEFAULT_ELIDED_FALLBACK_NAME_ID
+        };
+        if major_version != 1 || design_axis_size < MIN_DESIGN_AXIS_SIZE {
+            return None;
+        }
+        Some(Stat {
+            design_axes: Reader::at(data, design_axes_offset)
+                .read_bytes(design_axis_count as usize * design_axis_size as usize)?,
+            design_axis_size,
+            design_axis_count,
+            axis_values: data.get(axis_value_offsets..)?,
+            axis_value_count,
+            elided_fallback_name_id,
+        })
+    }
+
+    pub fn design_axis_count(&self) -> u16 {
+        self.design_axis_count
+    }
+
+    pub fn design_axis(&self, index: u16) -> Option<DesignAxis> {
+        if index >= self.design_axis_count {
+            return None;
+        }
+        let mut reader = Reader::at(self.design_axes,
+                                    index as usize * self.design_axis_size as usize);
+        Some(DesignAxis {
+            tag: Tag(reader.read_u32()?),
+            name_id: reader.read_u16()?,
+            ordering: reader.read_u16()?,
+        })
+    }
+
+    pub fn design_axes(&self) -> Vec<DesignAxis> {
+        (0..self.design_axis_count).filter_map(|index| self.design_axis(index)).collect()
+    }
+
+    pub fn axis_value_count(&self) -> u16 {
+        self.axis_value_count
+    }
+
+    /// Returns the `index`th axis value, or `None` if it is malformed or in an unknown format.
+    pub fn axis_value(&self, index: u16) -> Option<AxisValue> {
+        if index >= self.axis_value_count {
+            return None;
+        }
+        let offset = parser::read_u16(self.axis_values, index as usize * 2)? as usize;
+        let mut reader = Reader::at(self.axis_values, offset);
+        let format = reader.read_u16()?;
+        let axis_or_count = reader.read_u16()?;
+        let flags = reader.read_u16()?;
+        let name_id = reader.read_u16()?;
+        let (values, range, linked_value) = match format {
+            1 => (vec![(axis_or_count, reader.read_fixed()?)], None, None),
+            2 => {
+                let nominal_value = reader.read_fixed()?;
+                let range = (reader.read_fixed()?, reader.read_fixed()?);
+                (vec![(axis_or_count, nominal_value)], Some(range), None)
+            }
+            3 => {
+                let value = reader.read_fixed()?;
+                (vec![(axis_or_count, value)], None, Some(reader.read_fixed()?))
+            }
+            4 => {
+                let values = (0..axis_or_count).map(|_| {
+                    Some((reader.read_u16()?, reader.read_fixed()?))
+                }).collect::<Option<Vec<_>>>()?;
+                (values, None, None)
+            }
+            _ => return None,
+        };
+        Some(AxisValue {
+            values,
+            range,
+            linked_value,
+            flags,
+            name_id,
+        })
+    }
+
+    /// Returns every axis value in a known format, in the font's order.
+    pub fn axis_values(&self) -> Vec<AxisValue> {
+        (0..self.axis_value_count).filter_map(|index| self.axis_value(index)).collect()
+    }
+
+    /// The `name` table entry to use as the style name when every axis value name is elided.
+    pub fn elided_fallback_name_id(&self) -> u16 {
+        self.elided_fallback_name_id
+    }
+
+    /// Returns the name IDs that make up the style name of `position`, which holds a value for
+    /// each design axis, in the order the names go.
+    ///
+    /// Axis values that name several axes at once win over single-axis ones, and exact values
+    /// win over ranges. Axes with no name for their position, and elidable names, are left
+    /// out; if that leaves nothing, the elided fallback name is used.
+    pub fn style_name_ids(&self, position: &[f64]) -> Vec<u16> {
+        let axes = self.design_axes();
+        let values: Vec<AxisValue> = self.axis_values()
+                                         .into_iter()
+                                         .filter(|value| value.matches(position))
+                                         .collect();
+        let mut named = vec![false; axes.len()];
+        let mut names: Vec<(u16, &AxisValue)> = vec![];
+        let precedence: [fn(&AxisValue) -> bool; 3] = [
+            |value| value.values.len() > 1,
+            |value| value.values.len() == 1 && value.range.is_none(),
+            |value| value.range.is_some(),
+        ];
+        for is_preferred in &precedence {
+            for value in values.iter().filter(|value| is_preferred(value)) {
+                let axes_named = value.values.iter().any(|&(axis, _)| {
+                    named.get(axis as usize).cloned().unwrap_or(true)
+                });
+                if axes_named {
+                    continue;
+                }
+                for &(axis, _) in &value.values {
+                    named[axis as usize] = true;
+                }
+                let ordering = value.values.iter()
+                                    .map(|&(axis, _)| axes[axis as usize].ordering)
+                                    .min()
+                                    .unwrap_or(0);
+                names.push((ordering, value));
+            }
+        }
+        names.sort_by_key(|&(ordering, _)| ordering);
+
+        let name_ids: Vec<u16> = names.into_iter()
+                                      .filter(|&(_, value)| !value.is_elidable())
+                                      .map(|(_, value)| value.name_id)
+                                      .collect();
+        if name_ids.is_empty() {
+            vec![self.elided_fallback_name_id]
+        } else {
+            name_ids
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_font;
+    use super::Stat;
+
+    #[test]
+    fn prefers_multi_axis_names_then_single_values_then_ranges() {
+        let data = test_font::stat_table(1);
+        let stat = Stat::parse(&data).unwrap();
+        assert_eq!(stat.axis_values().len(), 6);
+        assert_eq!(stat.style_name_ids(&[700.0, 75.0]), vec![305]);
+        assert_eq!(stat.style_name_ids(&[700.0, 90.0]), vec![300]);
+        assert_eq!(stat.style_name_ids(&[800.0, 75.0]), vec![303, 302]);
+    }
+
+    #[test]
+    fn matches_values_to_fixed_point_precision() {
+        let data = test_font::stat_table(1);
+        let stat = Stat::parse(&data).unwrap();
+        assert_eq!(stat.style_name_ids(&[700.00001, 75.0]), vec![305]);
+        assert_eq!(stat.style_name_ids(&[700.001, 75.0]), vec![303, 302]);
+    }
+
+    #[test]
+    fn leaves_out_elidable_names() {
+        let data = test_font::stat_table(1);
+        let stat = Stat::parse(&data).unwrap();
+        assert_eq!(stat.style_name_ids(&[700.0, 100.0]), vec![300]);
+        assert_eq!(stat.style_name_ids(&[400.0, 75.0]), vec![303]);
+    }
+
+    #[test]
+    fn falls_back_to_the_elided_fallback_name() {
+        let data = test_font::stat_table(1);
+        let stat = Stat::parse(&data).unwrap();
+        assert_eq!(stat.elided_fallback_name_id(), 310);
+        assert_eq!(stat.style_name_ids(&[400.0, 100.0]), vec![310]);
+        assert_eq!(stat.style_name_ids(&[500.0, 90.0]), vec![310]);
+
+        // Version 1.0 tables have no fallback name of their own.
+        let data = test_font::stat_table(0);
+        let stat = Stat::parse(&data).unwrap();
+        assert_eq!(stat.style_name_ids(&[400.0, 100.0]), vec![2]);
+        assert_eq!(stat.style_name_ids(&[700.0, 75.0]), vec![305]);
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The tracking table, `trak`, which loosens or tightens the spacing of Apple fonts depending
+//! on their point size.
+
+use error::{Error, Result};
+use parser::{self, Reader};
+use tag::kCTFontTableTrak;
+
+const TRACK_ENTRY_SIZE: usize = 8;
+
+/// A `trak` table.
+#[derive(Clone, Copy, Debug)]
+pub struct Trak<'a> {
+    data: &'a [u8],
+    horizontal_offset: u16,
+    vertical_offset: u16,
+}
+
+impl<'a> Trak<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Trak<'a>> {
+        Trak::parse_table(data).ok_or(Error::MalformedTable(kCTFontTableTrak))
+    }
+
+    fn parse_table(data: &'a [u8]) -> Option<Trak<'a>> {
+        let mut reader = Reader::new(data);
+        let version = reader.read_u32()?;
+        let format = reader.read_u16()?;
+        let horizontal_offset = reader.read_u16()?;
+        let vertical_offset = reader.read_u16()?;
+        if version != 0x00010000 || format != 0 {
+            return None;
+        }
+        Some(Trak {
+            data,
+            horizontal_offset,
+            vertical_offset,
+        })
+    }
+
+    /// Returns the tracking of `track`, 0 for normal and negative for tighter, at `pt_size`,
+    /// in font units. The table's values are interpolated between the sizes it lists, and
+    /// extrapolated beyond them.
+    ///
+    /// Returns `None` if the table has no such track for the orientation.
+    pub fn tracking(&self, vertical: bool, track: f64, pt_size: f64) -> Option<f64> {
+        let offset = if vertical { self.vertical_offset } else { self.horizontal_offset };
+        if offset == 0 {
+            return None;
+        }
+        let mut reader = Reader::at(self.data, offset as usize);
+        let track_count = reader.read_u16()? as usize;
+        let size_count = reader.read_u16()? as usize;
+        let sizes = Reader::at(self.data, reader.read_u32()? as usize).read_bytes(size_count * 4)?;
+        let tracks = reader.read_bytes(track_count * TRACK_ENTRY_SIZE)?;
+        let entry = tracks.chunks(TRACK_ENTRY_SIZE).find(|entry| {
+            parser::read_fixed(entry, 0) == Some(track)
+        })?;
+        let values = Reader::at(self.data, parser::read_u16(entry, 6)? as usize)
+            .read_bytes(size_count * 2)?;
+        let value = |index: usize| parser::read_i16(values, index * 2).map(|value| value as f64);
+        let size = |index: usize| parser::read_fixed(sizes, index * 4);
+
+        match size_count {
+            0 => return Some(0.0),
+            1 => return value(0),
+            _ => {}
+        }
+        // Interpolate along the line through the two sizes around `pt_size`, or the two at
+        // the nearer end.
+        let upper = (1..size_count - 1).find(|&index| {
+            size(index).is_some_and(|size| size >= pt_size)
+        }).unwrap_or(size_count - 1);
+        let (size_0, size_1) = (size(upper - 1)?, size(upper)?);
+        let t = if size_0 == size_1 { 0.0 } else { (pt_size - size_0) / (size_1 - size_0) };
+        Some(t * value(upper)? + (1.0 - t) * value(upper - 1)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_font::words;
+    use super::Trak;
+
+    /// Returns a `trak` table with horizontal tracks at sizes 9, 12 and 24: a normal track of
+    /// 40, 0 and -60, and a tight track, -1, of -20, -40 and -80.
+    fn trak_table() -> Vec<u8> {
+        let mut table = words(&[1, 0, 0, 12, 0, 0]);
+        table.extend(words(&[2, 3, 0, 36, 0, 0, 256, 48, -1, 0, 257, 54]));
+        table.extend(words(&[9, 0, 12, 0, 24, 0]));
+        table.extend(words(&[40, 0, -60, -20, -40, -80]));
+        table
+    }
+
+    #[test]
+    fn reads_tracking_at_the_listed_sizes() {
+        let data = trak_table();
+        let trak = Trak::parse(&data).unwrap();
+        assert_eq!(trak.tracking(false, 0.0, 9.0), Some(40.0));
+        assert_eq!(trak.tracking(false, 0.0, 12.0), Some(0.0));
+        assert_eq!(trak.tracking(false, -1.0, 24.0), Some(-80.0));
+        assert_eq!(trak.tracking(false, 1.0, 12.0), None);
+        assert_eq!(trak.tracking(true, 0.0, 12.0), None);
+    }
+
+    #[test]
+    fn interpolates_between_sizes_and_extrapolates_beyond_them() {
+        let data = trak_table();
+        let trak = Trak::parse(&data).unwrap();
+        assert_eq!(trak.tracking(false, 0.0, 10.5), Some(20.0));
+        assert_eq!(trak.tracking(false, 0.0, 18.0), Some(-30.0));
+        assert_eq!(trak.tracking(false, -1.0, 18.0), Some(-60.0));
+        assert_eq!(trak.tracking(false, 0.0, 6.0), Some(80.0));
+        assert_eq!(trak.tracking(false, 0.0, 30.0), Some(-90.0));
+    }
+}
@@ -0,0 +1,207 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The item variation store, which variable fonts embed in several tables to hold the deltas
+//! that adjust values away from the default instance.
+
+use parser::{self, Reader};
+
+const REGION_AXIS_SIZE: usize = 6;
+const LONG_WORDS: u16 = 0x8000;
+
+/// An item variation store.
+///
+/// Deltas are grouped into item variation data subtables, addressed by an outer index, each
+/// holding rows of deltas addressed by an inner index. Every delta in a row applies to one of
+/// the subtable's regions of the design space, scaled by how far into that region the
+/// instance is.
+///
+/// Coordinates are normalized, one per `fvar` axis in order, from -1 at an axis minimum
+/// through 0 at its default to 1 at its maximum. Missing coordinates are 0.
+#[derive(Clone, Copy, Debug)]
+pub struct ItemVariationStore<'a> {
+    data: &'a [u8],
+    regions: &'a [u8],
+    axis_count: u16,
+    region_count: u16,
+    data_count: u16,
+}
+
+impl<'a> ItemVariationStore<'a> {
+    /// Parses a store, returning `None` if its header or region list is malformed. The tables
+    /// that embed stores report that as their own error.
+    pub fn parse(data: &'a [u8]) -> Option<ItemVariationStore<'a>> {
+        let mut reader = Reader::new(data);
+        if reader.read_u16()? != 1 {
+            return None;
+        }
+        let region_list_offset = reader.read_u32()? as usize;
+        let data_count = reader.read_u16()?;
+        reader.skip(data_count as usize * 4)?;
+
+        let mut reader = Reader::at(data, region_list_offset);
+        let axis_count = reader.read_u16()?;
+        let region_count = reader.read_u16()?;
+        let regions = reader.read_bytes(region_count as usize * axis_count as usize *
+                                        REGION_AXIS_SIZE)?;
+        Some(ItemVariationStore {
+            data,
+            regions,
+            axis_count,
+            region_count,
+            data_count,
+        })
+    }
+
+    /// The number of item variation data subtables, which outer indices count.
+    pub fn data_count(&self) -> u16 {
+        self.data_count
+    }
+
+    fn item_data(&self, outer: u16) -> Option<&'a [u8]> {
+        if outer >= self.data_count {
+            return None;
+        }
+        let offset = parser::read_u32(self.data, 8 + outer as usize * 4)? as usize;
+        self.data.get(offset..)
+    }
+
+    /// Returns how much of the region's deltas apply at `coordinates`, from 0 to 1.
+    fn region_scalar(&self, region: u16, coordinates: &[f64]) -> Option<f64> {
+        if region >= self.region_count {
+            return None;
+        }
+        let mut reader = Reader::at(self.regions,
+                                    region as usize * self.axis_count as usize * REGION_AXIS_SIZE);
+        let mut scalar = 1.0;
+        for axis in 0..self.axis_count as usize {
+            let start = reader.read_f2dot14()?;
+            let peak = reader.read_f2dot14()?;
+            let end = reader.read_f2dot14()?;
+            let coordinate = coordinates.get(axis).cloned().unwrap_or(0.0);
+
+            // Axes the region doesn't depend on, and invalid ranges, don't limit it.
+            if peak == 0.0 || start > peak || peak > end || (start < 0.0 && end > 0.0) {
+                continue;
+            }
+            if coordinate == peak {
+                continue;
+            }
+            if coordinate <= start || coordinate >= end {
+                return Some(0.0);
+            }
+            scalar *= if coordinate < peak {
+                (coordinate - start) / (peak - start)
+            } else {
+                (end - coordinate) / (end - peak)
+            };
+        }
+        Some(scalar)
+    }
+
+    /// Returns the scalar of each region that the `outer` subtable's deltas apply to, in the
+    /// order its rows list them.
+    pub fn region_scalars(&self, outer: u16, coordinates: &[f64]) -> Option<Vec<f64>> {
+        let mut reader = Reader::new(self.item_data(outer)?);
+        reader.skip(4)?; // itemCount, wordDeltaCount
+        let region_index_count = reader.read_u16()?;
+        (0..region_index_count).map(|_| {
+            self.region_scalar(reader.read_u16()?, coordinates)
+        }).collect()
+    }
+
+    /// Returns the interpolated delta for the item at `outer` and `inner` at `coordinates`.
+    pub fn delta(&self, outer: u16, inner: u16, coordinates: &[f64]) -> Option<f64> {
+        let data = self.item_data(outer)?;
+        let mut reader = Reader::new(data);
+        let item_count = reader.read_u16()?;
+        let word_delta_count = reader.read_u16()?;
+        let region_index_count = reader.read_u16()?;
+        if inner >= item_count {
+            return None;
+        }
+
+        // The first `word_count` deltas of each row are twice as wide as the rest.
+        let long_words = word_delta_count & LONG_WORDS != 0;
+        let word_count = (word_delta_count & !LONG_WORDS).min(region_index_count) as usize;
+        let (word_size, short_size) = if long_words { (4, 2) } else { (2, 1) };
+        let row_size = word_count * word_size +
+                       (region_index_count as usize - word_count) * short_size;
+
+        let mut region_indices = Reader::at(data, 6);
+        let mut deltas = Reader::at(data, 6 + region_index_count as usize * 2 +
+                                          inner as usize * row_size);
+        let mut total = 0.0;
+        for index in 0..region_index_count as usize {
+            let delta = match (index < word_count, long_words) {
+                (true, true) => deltas.read_i32()? as f64,
+                (true, false) | (false, true) => deltas.read_i16()? as f64,
+                (false, false) => deltas.read_u8()? as i8 as f64,
+            };
+            let region = region_indices.read_u16()?;
+            if delta != 0.0 {
+                total += delta * self.region_scalar(region, coordinates)?;
+            }
+        }
+        Some(total)
+    }
+}
+
+/// A delta-set index map, which tables such as `HVAR` use to point glyphs or other items at
+/// rows of an item variation store.
+#[derive(Clone, Copy, Debug)]
+pub struct DeltaSetIndexMap<'a> {
+    entries: &'a [u8],
+    entry_size: usize,
+    inner_bit_count: u32,
+    map_count: u32,
+}
+
+impl<'a> DeltaSetIndexMap<'a> {
+    /// Parses a map in either format, returning `None` if it is malformed.
+    pub fn parse(data: &'a [u8]) -> Option<DeltaSetIndexMap<'a>> {
+        let mut reader = Reader::new(data);
+        let format = reader.read_u8()?;
+        let entry_format = reader.read_u8()?;
+        let map_count = match format {
+            0 => reader.read_u16()? as u32,
+            1 => reader.read_u32()?,
+            _ => return None,
+        };
+        let entry_size = ((entry_format >> 4) & 0x3) as usize + 1;
+        Some(DeltaSetIndexMap {
+            entries: reader.read_bytes(map_count as usize * entry_size)?,
+            entry_size,
+            inner_bit_count: (entry_format & 0xF) as u32 + 1,
+            map_count,
+        })
+    }
+
+    /// Parses the map at `offset` in a table, where an offset of 0 means the table has no map.
+    /// Returns `None` if the map is malformed.
+    pub fn parse_at(data: &'a [u8], offset: u32) -> Option<Option<DeltaSetIndexMap<'a>>> {
+        if offset == 0 {
+            return Some(None);
+        }
+        DeltaSetIndexMap::parse(data.get(offset as usize..)?).map(Some)
+    }
+
+    /// Returns the outer and inner store indices for an item. Items past the end of the map
+    /// use its last entry.
+    pub fn get(&self, index: u32) -> Option<(u16, u16)> {
+        if self.map_count == 0 {
+            return None;
+        }
+        let index = index.min(self.map_count - 1) as usize;
+        let bytes = self.entries.get(index * self.entry_size..(index + 1) * self.entry_size)?;
+        let entry = bytes.iter().fold(0u32, |entry, &byte| entry << 8 | byte as u32);
+        Some(((entry >> self.inner_bit_count) as u16,
+              (entry & ((1 << self.inner_bit_count) - 1)) as u16))
+    }
+}
@@ -0,0 +1,49 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `vmtx` table, which holds each glyph's advance height and top side bearing.
+
+use error::{Error, Result};
+use tables::hmtx::Hmtx;
+use tag::kCTFontTableVmtx;
+
+/// A `vmtx` table. It has the same layout as `hmtx`, with `vhea.number_of_v_metrics()` full
+/// metrics.
+#[derive(Clone, Copy, Debug)]
+pub struct Vmtx<'a> {
+    metrics: Hmtx<'a>,
+}
+
+impl<'a> Vmtx<'a> {
+    /// Parses the table given `vhea.number_of_v_metrics()` and `maxp.num_glyphs()`.
+    pub fn parse(data: &'a [u8], long_metric_count: u16, num_glyphs: u16) -> Result<Vmtx<'a>> {
+        match Hmtx::parse(data, long_metric_count, num_glyphs) {
+            Ok(metrics) => Ok(Vmtx {
+                metrics,
+            }),
+            Err(_) => Err(Error::MalformedTable(kCTFontTableVmtx)),
+        }
+    }
+
+    /// The number of glyphs the table covers.
+    pub fn num_glyphs(&self) -> u16 {
+        self.metrics.num_glyphs()
+    }
+
+    /// Returns the glyph's advance height in font units, or `None` if it isn't in the font.
+    pub fn advance_height(&self, glyph: u16) -> Option<u16> {
+        self.metrics.advance_width(glyph)
+    }
+
+    /// Returns the distance from the glyph's vertical origin down to the top of its bounding
+    /// box, in font units, or `None` if it isn't in the font or its side bearing is missing.
+    pub fn top_side_bearing(&self, glyph: u16) -> Option<i16> {
+        self.metrics.left_side_bearing(glyph)
+    }
+}
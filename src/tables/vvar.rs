@@ -0,0 +1,65 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `VVAR` table, which varies the vertical metrics of a variable font's glyphs.
+
+use error::{Error, Result};
+use parser;
+use tables::hvar::Hvar;
+use tables::variation_store::{DeltaSetIndexMap, ItemVariationStore};
+use tag::kCTFontTableVVAR;
+
+/// A `VVAR` table. It has the same layout as `HVAR`, for advance heights and top and bottom
+/// side bearings, followed by an optional map for `VORG` vertical origin deltas.
+#[derive(Clone, Copy, Debug)]
+pub struct Vvar<'a> {
+    metrics: Hvar<'a>,
+    store: ItemVariationStore<'a>,
+    vertical_origin_map: Option<DeltaSetIndexMap<'a>>,
+}
+
+impl<'a> Vvar<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Vvar<'a>> {
+        Vvar::parse_table(data).ok_or(Error::MalformedTable(kCTFontTableVVAR))
+    }
+
+    fn parse_table(data: &'a [u8]) -> Option<Vvar<'a>> {
+        let metrics = Hvar::parse(data).ok()?;
+        let store_offset = parser::read_u32(data, 4)? as usize;
+        Some(Vvar {
+            metrics,
+            store: ItemVariationStore::parse(data.get(store_offset..)?)?,
+            vertical_origin_map: DeltaSetIndexMap::parse_at(data, parser::read_u32(data, 20)?)?,
+        })
+    }
+
+    /// Returns the change to the glyph's advance height, in font units, at `coordinates`.
+    pub fn advance_delta(&self, glyph: u16, coordinates: &[f64]) -> Option<f64> {
+        self.metrics.advance_delta(glyph, coordinates)
+    }
+
+    /// Returns the change to the glyph's top side bearing, in font units, if the table has
+    /// side bearing deltas.
+    pub fn top_side_bearing_delta(&self, glyph: u16, coordinates: &[f64]) -> Option<f64> {
+        self.metrics.left_side_bearing_delta(glyph, coordinates)
+    }
+
+    /// Returns the change to the glyph's bottom side bearing, in font units, if the table has
+    /// side bearing deltas.
+    pub fn bottom_side_bearing_delta(&self, glyph: u16, coordinates: &[f64]) -> Option<f64> {
+        self.metrics.right_side_bearing_delta(glyph, coordinates)
+    }
+
+    /// Returns the change to the glyph's `VORG` vertical origin, in font units, if the table
+    /// has vertical origin deltas.
+    pub fn vertical_origin_delta(&self, glyph: u16, coordinates: &[f64]) -> Option<f64> {
+        let (outer, inner) = self.vertical_origin_map?.get(glyph as u32)?;
+        self.store.delta(outer, inner, coordinates)
+    }
+}
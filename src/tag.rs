@@ -0,0 +1,194 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#![allow(non_upper_case_globals)]
+
+use error::Error;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A four-character font table tag, such as `GSUB` or `OS/2`.
+///
+/// The wrapped integer is the tag's four bytes read big-endian, which is what Core Text's
+/// `CTFontTableTag` holds.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Tag(pub u32);
+
+impl Tag {
+    #[inline]
+    pub const fn new(bytes: &[u8; 4]) -> Tag {
+        Tag(u32::from_be_bytes(*bytes))
+    }
+
+    #[inline]
+    pub fn to_bytes(self) -> [u8; 4] {
+        self.0.to_be_bytes()
+    }
+}
+
+impl From<u32> for Tag {
+    #[inline]
+    fn from(value: u32) -> Tag {
+        Tag(value)
+    }
+}
+
+impl From<Tag> for u32 {
+    #[inline]
+    fn from(tag: Tag) -> u32 {
+        tag.0
+    }
+}
+
+impl<'a> From<&'a [u8; 4]> for Tag {
+    #[inline]
+    fn from(bytes: &'a [u8; 4]) -> Tag {
+        Tag::new(bytes)
+    }
+}
+
+/// Parses a tag from one to four printable ASCII characters. Shorter strings are padded with
+/// spaces, so `"cvt"` is the `cvt ` tag; spaces are only allowed as that trailing padding.
+impl FromStr for Tag {
+    type Err = Error;
+
+    fn from_str(string: &str) -> Result<Tag, Error> {
+        let bytes = string.as_bytes();
+        let padding = bytes.iter().rev().take_while(|&&byte| byte == b' ').count();
+        if bytes.is_empty() || bytes.len() > 4 || padding == bytes.len() ||
+           !bytes[..bytes.len() - padding].iter().all(|&byte| (0x21..0x7F).contains(&byte)) {
+            return Err(Error::InvalidTag(string.to_owned()));
+        }
+        let mut tag = [b' '; 4];
+        tag[..bytes.len()].copy_from_slice(bytes);
+        Ok(Tag::new(&tag))
+    }
+}
+
+/// Shows the tag's four characters, escaping any byte that isn't printable ASCII.
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &byte in &self.to_bytes() {
+            if (0x20..0x7F).contains(&byte) {
+                write!(f, "{}", byte as char)?;
+            } else {
+                write!(f, "\\x{:02X}", byte)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Tag(\"{}\")", self)
+    }
+}
+
+// The tags Core Text names, which cover the registered OpenType and AAT tables.
+pub const kCTFontTableBASE: Tag = Tag::new(b"BASE");
+pub const kCTFontTableCBDT: Tag = Tag::new(b"CBDT");
+pub const kCTFontTableCBLC: Tag = Tag::new(b"CBLC");
+pub const kCTFontTableCFF: Tag = Tag::new(b"CFF ");
+pub const kCTFontTableCFF2: Tag = Tag::new(b"CFF2");
+pub const kCTFontTableCOLR: Tag = Tag::new(b"COLR");
+pub const kCTFontTableCPAL: Tag = Tag::new(b"CPAL");
+pub const kCTFontTableDSIG: Tag = Tag::new(b"DSIG");
+pub const kCTFontTableEBDT: Tag = Tag::new(b"EBDT");
+pub const kCTFontTableEBLC: Tag = Tag::new(b"EBLC");
+pub const kCTFontTableEBSC: Tag = Tag::new(b"EBSC");
+pub const kCTFontTableGDEF: Tag = Tag::new(b"GDEF");
+pub const kCTFontTableGPOS: Tag = Tag::new(b"GPOS");
+pub const kCTFontTableGSUB: Tag = Tag::new(b"GSUB");
+pub const kCTFontTableHVAR: Tag = Tag::new(b"HVAR");
+pub const kCTFontTableJSTF: Tag = Tag::new(b"JSTF");
+pub const kCTFontTableLTSH: Tag = Tag::new(b"LTSH");
+pub const kCTFontTableMATH: Tag = Tag::new(b"MATH");
+pub const kCTFontTableMERG: Tag = Tag::new(b"MERG");
+pub const kCTFontTableMVAR: Tag = Tag::new(b"MVAR");
+pub const kCTFontTableOS2: Tag = Tag::new(b"OS/2");
+pub const kCTFontTablePCLT: Tag = Tag::new(b"PCLT");
+pub const kCTFontTableSTAT: Tag = Tag::new(b"STAT");
+pub const kCTFontTableSVG: Tag = Tag::new(b"SVG ");
+pub const kCTFontTableVDMX: Tag = Tag::new(b"VDMX");
+pub const kCTFontTableVORG: Tag = Tag::new(b"VORG");
+pub const kCTFontTableVVAR: Tag = Tag::new(b"VVAR");
+pub const kCTFontTableZapf: Tag = Tag::new(b"Zapf");
+pub const kCTFontTableAcnt: Tag = Tag::new(b"acnt");
+pub const kCTFontTableAnkr: Tag = Tag::new(b"ankr");
+pub const kCTFontTableAvar: Tag = Tag::new(b"avar");
+pub const kCTFontTableBdat: Tag = Tag::new(b"bdat");
+pub const kCTFontTableBhed: Tag = Tag::new(b"bhed");
+pub const kCTFontTableBloc: Tag = Tag::new(b"bloc");
+pub const kCTFontTableBsln: Tag = Tag::new(b"bsln");
+pub const kCTFontTableCidg: Tag = Tag::new(b"cidg");
+pub const kCTFontTableCmap: Tag = Tag::new(b"cmap");
+pub const kCTFontTableCvar: Tag = Tag::new(b"cvar");
+pub const kCTFontTableCvt: Tag = Tag::new(b"cvt ");
+pub const kCTFontTableFdsc: Tag = Tag::new(b"fdsc");
+pub const kCTFontTableFeat: Tag = Tag::new(b"feat");
+pub const kCTFontTableFmtx: Tag = Tag::new(b"fmtx");
+pub const kCTFontTableFond: Tag = Tag::new(b"fond");
+pub const kCTFontTableFpgm: Tag = Tag::new(b"fpgm");
+pub const kCTFontTableFvar: Tag = Tag::new(b"fvar");
+pub const kCTFontTableGasp: Tag = Tag::new(b"gasp");
+pub const kCTFontTableGlyf: Tag = Tag::new(b"glyf");
+pub const kCTFontTableGvar: Tag = Tag::new(b"gvar");
+pub const kCTFontTableHdmx: Tag = Tag::new(b"hdmx");
+pub const kCTFontTableHead: Tag = Tag::new(b"head");
+pub const kCTFontTableHhea: Tag = Tag::new(b"hhea");
+pub const kCTFontTableHmtx: Tag = Tag::new(b"hmtx");
+pub const kCTFontTableHsty: Tag = Tag::new(b"hsty");
+pub const kCTFontTableJust: Tag = Tag::new(b"just");
+pub const kCTFontTableKern: Tag = Tag::new(b"kern");
+pub const kCTFontTableKerx: Tag = Tag::new(b"kerx");
+pub const kCTFontTableLcar: Tag = Tag::new(b"lcar");
+pub const kCTFontTableLtag: Tag = Tag::new(b"ltag");
+pub const kCTFontTableLoca: Tag = Tag::new(b"loca");
+pub const kCTFontTableMaxp: Tag = Tag::new(b"maxp");
+pub const kCTFontTableMeta: Tag = Tag::new(b"meta");
+pub const kCTFontTableMort: Tag = Tag::new(b"mort");
+pub const kCTFontTableMorx: Tag = Tag::new(b"morx");
+pub const kCTFontTableName: Tag = Tag::new(b"name");
+pub const kCTFontTableOpbd: Tag = Tag::new(b"opbd");
+pub const kCTFontTablePost: Tag = Tag::new(b"post");
+pub const kCTFontTablePrep: Tag = Tag::new(b"prep");
+pub const kCTFontTableProp: Tag = Tag::new(b"prop");
+pub const kCTFontTableSbit: Tag = Tag::new(b"sbit");
+pub const kCTFontTableSbix: Tag = Tag::new(b"sbix");
+pub const kCTFontTableTrak: Tag = Tag::new(b"trak");
+pub const kCTFontTableVhea: Tag = Tag::new(b"vhea");
+pub const kCTFontTableVmtx: Tag = Tag::new(b"vmtx");
+pub const kCTFontTableXref: Tag = Tag::new(b"xref");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tags_padded_with_trailing_spaces() {
+        assert_eq!("GSUB".parse::<Tag>().unwrap(), Tag::new(b"GSUB"));
+        assert_eq!("OS/2".parse::<Tag>().unwrap(), kCTFontTableOS2);
+        assert_eq!("cvt".parse::<Tag>().unwrap(), kCTFontTableCvt);
+        assert_eq!("cvt ".parse::<Tag>().unwrap(), kCTFontTableCvt);
+        assert_eq!("a".parse::<Tag>().unwrap(), Tag::new(b"a   "));
+    }
+
+    #[test]
+    fn rejects_invalid_tags() {
+        for string in &["", " ", "    ", "GSUBS", " cvt", "c vt", "a  b", "ab\tc", "cv\x7F",
+                        "\u{e9}t\u{e9}"] {
+            match string.parse::<Tag>() {
+                Err(Error::InvalidTag(ref invalid)) => assert_eq!(invalid, string),
+                result => panic!("{:?} parsed as {:?}", string, result),
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The font that unit tests use, a subset of DejaVu Sans described in `tests/fonts/README.md`.
+
+use base::CGGlyph;
+use memory_font::{self, MemoryFont};
+use parser;
+use tag::Tag;
+
+pub const UNITS_PER_EM: f64 = 2048.0;
+
+pub const SPACE: CGGlyph = 3;
+pub const A: CGGlyph = 36;
+pub const T: CGGlyph = 55;
+pub const LOWER_A: CGGlyph = 68;
+pub const LOWER_F: CGGlyph = 73;
+pub const LOWER_I: CGGlyph = 76;
+pub const LOWER_X: CGGlyph = 91;
+pub const GRAVE_COMB: CGGlyph = 689;
+pub const FI: CGGlyph = 5042;
+/// The form of U+0300 that `ccmp` substitutes after capitals.
+pub const GRAVE_COMB_CAP: CGGlyph = 5925;
+pub const GRINNING_FACE: CGGlyph = 5857;
+
+const DATA: &[u8] = include_bytes!("../tests/fonts/DejaVuSans-Subset.ttf");
+
+/// Loads the font at `pt_size`. At `UNITS_PER_EM` points, points are font units.
+pub fn dejavu_sans(pt_size: f64) -> MemoryFont {
+    memory_font::new_from_bytes(DATA.to_vec(), pt_size).unwrap()
+}
+
+/// Loads the font at `pt_size` with `tables` added, replacing any it has with the same tags,
+/// and the tables tagged in `removed` left out.
+pub fn dejavu_sans_with_tables(tables: &[(Tag, Vec<u8>)], removed: &[Tag], pt_size: f64)
+                               -> MemoryFont {
+    let count = parser::read_u16(DATA, 4).unwrap() as usize;
+    let mut records: Vec<(Tag, Vec<u8>)> = (0..count).map(|index| {
+        let record = 12 + index * 16;
+        let tag = Tag(parser::read_u32(DATA, record).unwrap());
+        let offset = parser::read_u32(DATA, record + 8).unwrap() as usize;
+        let length = parser::read_u32(DATA, record + 12).unwrap() as usize;
+        (tag, DATA[offset..offset + length].to_vec())
+    }).filter(|&(tag, _)| {
+        !removed.contains(&tag) && !tables.iter().any(|&(replaced, _)| replaced == tag)
+    }).collect();
+    records.extend(tables.iter().cloned());
+    records.sort_by_key(|&(tag, _)| tag);
+
+    // The search fields and checksums are left as 0, since nothing reads them.
+    let mut data = words(&[1, 0, records.len() as i32, 0, 0, 0]);
+    let mut offset = data.len() + records.len() * 16;
+    for &(tag, ref table) in &records {
+        data.extend_from_slice(&tag.to_bytes());
+        data.extend(words(&[0, 0, (offset >> 16) as i32, offset as i32, 0, table.len() as i32]));
+        offset += (table.len() + 3) & !3;
+    }
+    for (_, table) in &records {
+        data.extend_from_slice(table);
+        data.resize((data.len() + 3) & !3, 0);
+    }
+    memory_font::new_from_bytes(data, pt_size).unwrap()
+}
+
+/// Returns a format 0 `name` table holding `names`, as Windows US English records.
+pub fn name_table(names: &[(u16, &str)]) -> Vec<u8> {
+    let mut table = words(&[0, names.len() as i32, 6 + names.len() as i32 * 12]);
+    let mut storage = vec![];
+    for &(name_id, name) in names {
+        let string: Vec<u8> = name.encode_utf16().flat_map(|unit| unit.to_be_bytes()).collect();
+        table.extend(words(&[3, 1, 0x0409, name_id as i32, string.len() as i32,
+                             storage.len() as i32]));
+        storage.extend(string);
+    }
+    table.extend(storage);
+    table
+}
+
+/// Returns a `STAT` table of the given minor version naming weights and widths: weight 700
+/// Bold (name ID 300), weight 400 Regular (301, elidable), weights 650 to 900 Heavy (302),
+/// width 75 Condensed (303), width 100 Normal (304, elidable), and weight 700 with width 75
+/// Caption (305). Width names go before weight names, and version 1.1 tables give 310 as the
+/// elided fallback name.
+pub fn stat_table(minor_version: u16) -> Vec<u8> {
+    let header_size = if minor_version >= 1 { 20 } else { 18 };
+    let mut table = words(&[1, minor_version as i32, 8, 2, 0, header_size, 6,
+                            0, header_size + 16]);
+    if minor_version >= 1 {
+        table.extend(words(&[310]));
+    }
+    table.extend(words(&[0x7767, 0x6874, 256, 1, 0x7764, 0x7468, 257, 0]));
+    table.extend(words(&[12, 24, 36, 56, 68, 80]));
+    table.extend(words(&[1, 0, 0, 300, 700, 0]));
+    table.extend(words(&[1, 0, 2, 301, 400, 0]));
+    table.extend(words(&[2, 0, 0, 302, 800, 0, 650, 0, 900, 0]));
+    table.extend(words(&[1, 1, 0, 303, 75, 0]));
+    table.extend(words(&[1, 1, 2, 304, 100, 0]));
+    table.extend(words(&[4, 2, 0, 305, 0, 700, 0, 1, 75, 0]));
+    table
+}
+
+/// Returns a version 1.2 `GDEF` table with three carets on the fi ligature, a coordinate at
+/// 600, outline point 5, and a coordinate at 1200 varied by delta set 0, 2, and one mark glyph
+/// set holding the combining grave accent.
+pub fn gdef_table() -> Vec<u8> {
+    let mut table = words(&[1, 2, 0, 0, 14, 0, 54]);
+    table.extend(words(&[6, 1, 12, 1, 1, FI as i32]));
+    table.extend(words(&[3, 8, 12, 16, 1, 600, 2, 5, 3, 1200, 6, 0, 2, 0x8000]));
+    table.extend(words(&[1, 1, 0, 8, 1, 1, GRAVE_COMB as i32]));
+    table
+}
+
+/// Writes `values` as big-endian 16-bit words, for building tables by hand. Negative values
+/// are written in two's complement, and 32-bit fields take two words.
+pub fn words(values: &[i32]) -> Vec<u8> {
+    values.iter().flat_map(|&value| (value as u16).to_be_bytes()).collect()
+}